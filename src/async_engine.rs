@@ -1,126 +1,448 @@
 use crate::board::*;
 use crate::eval::*;
+use crate::log_println;
 use crate::move_gen;
 use crate::search;
 use crate::thread_flag::ThreadFlag;
 use crate::time_manager;
 use crate::transpos;
 use crate::uci;
-use std::sync::Arc;
+use crate::zobrist::Hash;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::Duration;
+
+// The latest completed iteration of a search thread
+#[derive(Debug, Copy, Clone)]
+struct ThreadResult {
+    depth: u8,
+    eval: Value,
+    best_move_idx: u8,
+    ponder_move: Option<Move>, // The reply we expect to the best move, from the PV
+}
+
+// Picks the result to play from all threads, preferring deeper searches and then better evals
+fn pick_best_thread_result(results: &[Option<ThreadResult>]) -> Option<ThreadResult> {
+    let mut best_result: Option<ThreadResult> = None;
+    for result in results.iter().flatten() {
+        let is_better = match best_result {
+            Some(best) => {
+                result.depth > best.depth || (result.depth == best.depth && result.eval > best.eval)
+            }
+            None => true,
+        };
+
+        if is_better {
+            best_result = Some(*result);
+        }
+    }
+
+    best_result
+}
+
+// Time limits of the running search
+// These can change mid-search, when a ponder search turns into a normal one
+#[derive(Debug, Copy, Clone)]
+struct TimeLimits {
+    stop_time: std::time::Instant,
+    max_time_to_use: f64,
+    nodes_per_sec: Option<f64>, // See TimeState::nodes_per_sec, the stop time isn't used if this is set
+}
+
+impl TimeLimits {
+    // If no time limit is needed, returns None
+    fn new(
+        board: &Board,
+        time_state: time_manager::TimeState,
+        start_time: std::time::Instant,
+    ) -> Option<TimeLimits> {
+        let max_time_to_use = time_manager::get_max_time_to_use(board, time_state)?;
+        Some(TimeLimits {
+            stop_time: start_time + std::time::Duration::from_secs_f64(max_time_to_use),
+            max_time_to_use,
+            nodes_per_sec: time_state.nodes_per_sec,
+        })
+    }
+
+    fn get_stop_time(&self) -> Option<std::time::Instant> {
+        if self.nodes_per_sec.is_some() {
+            None
+        } else {
+            Some(self.stop_time)
+        }
+    }
+
+    fn get_max_nodes(&self) -> Option<usize> {
+        self.nodes_per_sec
+            .map(|nodes_per_sec| (self.max_time_to_use * nodes_per_sec) as usize)
+    }
+
+    // In seconds, where the time used so far is measured in nodes if we're counting time in nodes
+    fn get_remaining_time(&self, total_nodes: usize) -> f64 {
+        if let Some(nodes_per_sec) = self.nodes_per_sec {
+            f64::max(
+                0.0,
+                self.max_time_to_use - (total_nodes as f64) / nodes_per_sec,
+            )
+        } else {
+            self.stop_time
+                .saturating_duration_since(std::time::Instant::now())
+                .as_secs_f64()
+        }
+    }
+}
+
+fn print_time_limits_debug(time_limits: Option<TimeLimits>) {
+    if let Some(limits) = time_limits {
+        if let Some(max_nodes) = limits.get_max_nodes() {
+            log_println!("info string Nodes to use: {}", max_nodes);
+        } else {
+            log_println!(
+                "info string Time to use: {:.0}ms",
+                limits.max_time_to_use * 1000.0
+            );
+        }
+    } else {
+        log_println!("info string No time limit");
+    }
+}
+
+// What to search for, from the UCI "go" command
+// The search stops at whichever limit is hit first
+#[derive(Debug, Clone)]
+pub struct SearchParams {
+    pub limits: search::SearchLimits, // The stop flag is replaced with our own, see stop_search()
+    pub time_state: Option<time_manager::TimeState>,
+    pub is_infinite: bool, // Don't stop until we're told to, even if we hit the max depth
+    pub is_pondering: bool,
+    pub root_excluded_moves: Vec<u8>, // Root moves we aren't allowed to play
+    pub contempt: Value,
+}
+
+impl SearchParams {
+    pub fn new() -> SearchParams {
+        SearchParams {
+            limits: search::SearchLimits::new(),
+            time_state: None,
+            is_infinite: false,
+            is_pondering: false,
+            root_excluded_moves: Vec::new(),
+            contempt: 0,
+        }
+    }
+}
+
+impl Default for SearchParams {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 pub struct AsyncEngine {
+    listener: search::SharedSearchListener, // Only the leader thread reports to it
+    is_debug: bool, // Print diagnostics as UCI info strings, see the "debug" command
     board: Board,
+    game_hashes: Vec<Hash>, // Positions played before the current board, for repetition detection
     arc_table: Arc<transpos::Table>,
     stop_flag: ThreadFlag,
     thread_join_handles: Vec<thread::JoinHandle<Option<u8>>>, // Outputs best move idx
+
+    time_limits: Arc<Mutex<Option<TimeLimits>>>,
+
+    // Set while we're searching on the opponent's time, until we get a ponderhit or stop
+    // See: https://www.chessprogramming.org/Pondering
+    ponder_flag: ThreadFlag,
+    ponder_time_state: Option<time_manager::TimeState>,
 }
 
 impl AsyncEngine {
     pub fn new(table_size_mbs: usize) -> AsyncEngine {
         AsyncEngine {
+            listener: Arc::new(Mutex::new(())),
+            is_debug: false,
             board: Board::start_pos(),
+            game_hashes: Vec::new(),
             arc_table: Arc::new(transpos::Table::new(table_size_mbs)),
             stop_flag: ThreadFlag::new(),
             thread_join_handles: Vec::new(),
+            time_limits: Arc::new(Mutex::new(None)),
+            ponder_flag: ThreadFlag::new(),
+            ponder_time_state: None,
         }
     }
 
-    pub fn start_search(
-        &mut self,
-        max_depth: u8,
-        time_state: Option<time_manager::TimeState>,
-        num_threads: usize,
-    ) {
+    pub fn start_search(&mut self, params: SearchParams, num_threads: usize, num_pv_lines: usize) {
         self.stop_search();
 
+        // Fresh flags, so nothing left over from the last search (like a ponderhit timer) can affect this one
+        self.stop_flag = ThreadFlag::new();
+        self.ponder_flag = ThreadFlag::new();
+
+        let mut moves = move_gen::MoveBuffer::new();
+        move_gen::generate_moves(self.get_board(), &mut moves);
+
+        // We can't report more lines than there are moves
+        let num_allowed_moves = moves.len().saturating_sub(params.root_excluded_moves.len());
+        let num_pv_lines = num_pv_lines.clamp(1, usize::max(num_allowed_moves, 1));
+
         let start_time = std::time::Instant::now();
+        let limits = params.limits.start(start_time);
 
-        let mut max_time_to_use: Option<f64> = None;
-        let mut stop_time: Option<std::time::Instant> = None;
-        if time_state.is_some() {
-            max_time_to_use =
-                time_manager::get_max_time_to_use(self.get_board(), time_state.unwrap());
-            if max_time_to_use.is_some() {
-                stop_time =
-                    Some(start_time + std::time::Duration::from_secs_f64(max_time_to_use.unwrap()));
+        self.arc_table.new_search();
+
+        if params.is_pondering {
+            // No time limits until the opponent plays the move we're pondering on
+            self.ponder_flag.trigger();
+            self.ponder_time_state = params.time_state;
+            self.time_limits = Arc::new(Mutex::new(None));
+        } else {
+            self.ponder_time_state = None;
+            let time_limits = params
+                .time_state
+                .and_then(|time_state| TimeLimits::new(&self.board, time_state, start_time));
+            if self.is_debug {
+                print_time_limits_debug(time_limits);
             }
+            self.time_limits = Arc::new(Mutex::new(time_limits));
+        }
+
+        if moves.is_empty() {
+            // Nothing to search, but we still can't finish before we're allowed to, like any other search
+            let listener = Arc::clone(&self.listener);
+            let board = self.board;
+            let stop_flag = self.stop_flag.clone();
+            let ponder_flag = self.ponder_flag.clone();
+            self.thread_join_handles.push(thread::spawn(move || {
+                while (params.is_infinite || ponder_flag.get()) && !stop_flag.get() {
+                    thread::sleep(std::time::Duration::from_millis(1));
+                }
+
+                listener
+                    .lock()
+                    .unwrap()
+                    .on_no_legal_moves(&board, search::get_game_result(&board));
+                None
+            }));
+            return;
         }
 
+        // Lazy SMP: every thread runs its own iterative deepening on the shared table
+        // See: https://www.chessprogramming.org/Lazy_SMP
+        let thread_results = Arc::new(Mutex::new(vec![None; num_threads]));
+        let num_finished_helpers = Arc::new(AtomicUsize::new(0));
+
         for thread_idx in 0..num_threads {
+            let listener = Arc::clone(&self.listener);
+            let is_debug = self.is_debug;
             let board = self.board.clone();
+            let params = params.clone();
+            let limits = limits.clone();
+            let game_hashes = self.game_hashes.clone();
             let stop_flag = self.stop_flag.clone();
+            let ponder_flag = self.ponder_flag.clone();
+            let time_limits = Arc::clone(&self.time_limits);
             let table_ref = Arc::clone(&self.arc_table);
+            let thread_results = Arc::clone(&thread_results);
+            let num_finished_helpers = Arc::clone(&num_finished_helpers);
 
             self.thread_join_handles.push(thread::spawn(move || {
-                // Unsafe deference the table
-                let table_ptr = Arc::as_ptr(&table_ref);
-                let table = unsafe { &mut *(table_ptr as *mut transpos::Table) };
+                let table = table_ref.as_ref();
 
                 let is_leader_thread = thread_idx == 0;
 
+                // Helper threads search one ply deeper on odd indices, so the threads don't all search in lockstep
+                let depth_offset = if is_leader_thread {
+                    0
+                } else {
+                    (thread_idx % 2) as u8
+                };
+
+                // Only the leader reports, so helpers just search the best line
+                let num_lines = if is_leader_thread { num_pv_lines } else { 1 };
+
                 let mut node_counts = Vec::new();
                 let mut best_moves = Vec::new();
-                let mut guessed_next_eval: Option<Value> = None;
-                for depth_minus_one in 0..max_depth {
-                    let depth = depth_minus_one + 1;
+                let mut guessed_next_evals: Vec<Option<Value>> = vec![None; num_lines];
+                let mut total_nodes: usize = 0;
+                let mut root_moves = Vec::new();
+
+                let mut current_move_reporter = if is_leader_thread {
+                    Some(search::CurrentMoveReporter::new(
+                        Arc::clone(&listener),
+                        start_time,
+                    ))
+                } else {
+                    None
+                };
+                let max_depth = limits.get_max_depth();
+                'depth_loop: for depth_minus_one in 0..max_depth {
+                    let depth = depth_minus_one.saturating_add(1 + depth_offset);
+                    if depth > max_depth {
+                        break;
+                    }
 
-                    {
-                        let (search_eval, search_info) = search::search(
+                    let cur_time_limits = *time_limits.lock().unwrap();
+
+                    // Only the leader reports, after all lines of this depth are done
+                    let mut best_line_progress: Option<search::SearchProgress> = None;
+
+                    // Each line excludes the root moves of the lines before it
+                    let mut root_excluded_moves = params.root_excluded_moves.clone();
+                    for (line_idx, guessed_next_eval) in guessed_next_evals.iter_mut().enumerate() {
+                        // The node limit is for the whole search, not each iteration
+                        let time_max_nodes =
+                            cur_time_limits.and_then(|limits| limits.get_max_nodes());
+                        let max_nodes = [limits.max_nodes, time_max_nodes]
+                            .into_iter()
+                            .flatten()
+                            .min()
+                            .map(|max_nodes| max_nodes.saturating_sub(total_nodes));
+                        // Depth 1 is always finished though, so there's a move to play
+                        if max_nodes == Some(0) && depth > 1 {
+                            break 'depth_loop;
+                        }
+
+                        let time_stop_time =
+                            cur_time_limits.and_then(|limits| limits.get_stop_time());
+                        let iteration_limits = search::SearchLimits {
+                            stop_flag: Some(stop_flag.clone()),
+                            stop_time: [limits.stop_time, time_stop_time]
+                                .into_iter()
+                                .flatten()
+                                .min(),
+                            max_nodes,
+                            ..limits.clone()
+                        };
+
+                        let mut root_search_info = search::SearchInfo::new();
+                        root_search_info.root_excluded_moves = root_excluded_moves.clone();
+                        root_search_info.contempt = params.contempt;
+                        root_search_info.game_hashes = game_hashes.clone();
+                        root_search_info.current_move_reporter = current_move_reporter.take();
+                        root_search_info.root_moves = std::mem::take(&mut root_moves);
+
+                        let (search_eval, mut search_info) = search::search_with_info(
                             &board,
                             table,
                             depth,
-                            guessed_next_eval,
-                            Some(&stop_flag),
-                            stop_time,
+                            *guessed_next_eval,
+                            root_search_info,
+                            &iteration_limits,
                         );
 
-                        if search_eval.is_infinite() {
+                        total_nodes += search_info.total_nodes;
+                        current_move_reporter = search_info.current_move_reporter.take();
+                        root_moves = std::mem::take(&mut search_info.root_moves);
+
+                        if is_inf_eval(search_eval) {
                             // Search aborted
-                            break;
+                            break 'depth_loop;
                         }
 
-                        node_counts.push(search_info.total_nodes);
+                        *guessed_next_eval = Some(search_eval);
+                        root_excluded_moves.push(search_info.root_best_move_idx);
 
-                        guessed_next_eval = Some(search_eval);
-                        {
+                        if line_idx == 0 {
+                            node_counts.push(search_info.total_nodes);
                             best_moves.push(search_info.root_best_move_idx);
+                            thread_results.lock().unwrap()[thread_idx] = Some(ThreadResult {
+                                depth,
+                                eval: search_eval,
+                                best_move_idx: search_info.root_best_move_idx,
+                                ponder_move: search_info.get_pv().get(1).copied(),
+                            });
+                        }
 
-                            if is_leader_thread && !search_eval.is_infinite() {
-                                // TODO: Somewhat lame to be calling UCI stuff from async_engine
-                                let elapsed_time = std::time::Instant::now() - start_time;
-                                uci::print_search_results(
+                        if is_leader_thread {
+                            let progress = search::SearchProgress {
+                                multipv: line_idx + 1,
+                                total_nodes,
+                                elapsed_time: start_time.elapsed().as_secs_f64(),
+                                ..search::SearchProgress::from_search_info(
                                     &board,
                                     table,
                                     depth,
                                     search_eval,
                                     &search_info,
-                                    elapsed_time.as_secs_f64(),
-                                );
-                            }
+                                )
+                            };
+                            listener.lock().unwrap().on_new_pv(&board, &progress);
 
-                            if stop_time.is_some() {
-                                let remaining_time = stop_time.unwrap() - std::time::Instant::now();
-                                if time_manager::should_exit_early(
-                                    max_time_to_use.unwrap(),
-                                    remaining_time.as_secs_f64(),
-                                    &best_moves,
-                                ) {
-                                    break;
+                            if line_idx == 0 {
+                                if is_debug {
+                                    uci::print_search_stats(depth, &search_info.stats);
                                 }
+                                best_line_progress = Some(progress);
+                            }
+                        }
+
+                        if line_idx == 0 && limits.is_mate_found(search_eval) {
+                            // Found the mate we were looking for, the other lines don't matter anymore
+                            if let Some(progress) = &best_line_progress {
+                                listener.lock().unwrap().on_depth_complete(&board, progress);
+                            }
+                            break 'depth_loop;
+                        }
+                    }
+
+                    if let Some(progress) = best_line_progress.take() {
+                        listener
+                            .lock()
+                            .unwrap()
+                            .on_depth_complete(&board, &progress);
+                    }
+
+                    if let Some(limits) = cur_time_limits {
+                        let remaining_time = limits.get_remaining_time(total_nodes);
+                        if time_manager::should_exit_early(
+                            limits.max_time_to_use,
+                            remaining_time,
+                            &best_moves,
+                        ) {
+                            if is_debug && is_leader_thread {
+                                log_println!(
+                                    "info string Stopping early after depth {}, {:.0}ms left",
+                                    depth,
+                                    remaining_time * 1000.0
+                                );
                             }
+                            break;
                         }
                     }
                 }
 
                 if is_leader_thread {
-                    if best_moves.len() > 0 {
-                        let mut moves = move_gen::MoveBuffer::new();
-                        move_gen::generate_moves(&board, &mut moves);
-                        uci::print_best_move(moves[*best_moves.last().unwrap() as usize]);
-                    } else {
-                        panic!("No best move found in time")
+                    // We can't play a move on the opponent's time (or during an infinite search), so wait until we're told to
+                    while (params.is_infinite || ponder_flag.get()) && !stop_flag.get() {
+                        thread::sleep(std::time::Duration::from_millis(1));
                     }
+
+                    // We're done, wait for the helpers to stop so we can use their results too
+                    stop_flag.trigger();
+                    while num_finished_helpers.load(Ordering::Acquire) < num_threads - 1 {
+                        thread::yield_now();
+                    }
+
+                    let mut moves = move_gen::MoveBuffer::new();
+                    move_gen::generate_moves(&board, &mut moves);
+
+                    // Without any finished depth (only possible with a max depth of 0), any legal move will do
+                    let best_result = pick_best_thread_result(&thread_results.lock().unwrap());
+                    let best_move_idx = best_result.map_or(0, |result| result.best_move_idx);
+                    let best_move = moves[best_move_idx as usize];
+
+                    // The reply we expect from the opponent is what we'll ponder on next
+                    let ponder_move = best_result.and_then(|result| result.ponder_move);
+                    listener
+                        .lock()
+                        .unwrap()
+                        .on_finish(&board, best_move, ponder_move);
+
+                    // The leader returns the move that was played, instead of just its own best move
+                    return Some(best_move_idx);
+                } else {
+                    num_finished_helpers.fetch_add(1, Ordering::Release);
                 }
 
                 if best_moves.len() > 0 {
@@ -132,28 +454,98 @@ impl AsyncEngine {
         }
     }
 
-    // Returns the best move index
+    // The opponent played the move we were pondering on, so switch to a normal timed search
+    // Returns false if we weren't pondering
+    pub fn ponder_hit(&mut self) -> bool {
+        if !self.ponder_flag.get() || self.thread_join_handles.is_empty() {
+            return false;
+        }
+
+        let start_time = std::time::Instant::now();
+        let new_time_limits = self
+            .ponder_time_state
+            .take()
+            .and_then(|time_state| TimeLimits::new(&self.board, time_state, start_time));
+        if self.is_debug {
+            print_time_limits_debug(new_time_limits);
+        }
+        *self.time_limits.lock().unwrap() = new_time_limits;
+
+        // NOTE: When time is counted in nodes, the running iterations finish first, and nodes searched while pondering count too
+        if let Some(stop_time) = new_time_limits.and_then(|limits| limits.get_stop_time()) {
+            // The iterations already running were started without a stop time, so stop them ourselves
+            let stop_flag = self.stop_flag.clone();
+            thread::spawn(move || {
+                while !stop_flag.get() {
+                    let now = std::time::Instant::now();
+                    if now >= stop_time {
+                        stop_flag.trigger();
+                        break;
+                    }
+
+                    thread::sleep(Duration::min(stop_time - now, Duration::from_millis(5)));
+                }
+            });
+        }
+
+        self.ponder_flag.reset();
+        true
+    }
+
+    // Returns the index of the move the search played, see move_gen::generate_moves()
     pub fn stop_search(&mut self) -> Option<u8> {
         self.stop_flag.trigger();
+        self.join_search_threads()
+    }
+
+    // Like stop_search(), but lets the search finish by itself
+    // NOTE: Never returns if the search is infinite or pondering
+    pub fn wait_for_search(&mut self) -> Option<u8> {
+        self.join_search_threads()
+    }
+
+    fn join_search_threads(&mut self) -> Option<u8> {
         let mut best_move_idx: Option<u8> = None;
-        for handle in self.thread_join_handles.drain(..) {
-            let handle_result = handle.join();
-            if handle_result.is_ok() {
-                best_move_idx = handle_result.unwrap();
-            } else {
+        for (thread_idx, handle) in self.thread_join_handles.drain(..).enumerate() {
+            let Ok(thread_best_move_idx) = handle.join() else {
                 panic!("Search thread crashed");
+            };
+
+            // The leader's result is the move that was played
+            if thread_idx == 0 {
+                best_move_idx = thread_best_move_idx;
             }
         }
         self.stop_flag.reset();
         best_move_idx
     }
 
+    // NOTE: Only used by searches started after this
+    pub fn set_listener(&mut self, listener: search::SharedSearchListener) {
+        self.listener = listener;
+    }
+
+    pub fn set_debug(&mut self, is_debug: bool) {
+        self.is_debug = is_debug;
+    }
+
+    pub fn is_debug(&self) -> bool {
+        self.is_debug
+    }
+
     pub fn get_board(&self) -> &Board {
         &self.board
     }
 
-    pub fn set_board(&mut self, new_board: &Board) {
+    pub fn set_board(&mut self, new_board: &Board, game_hashes: Vec<Hash>) {
         self.board = new_board.clone();
+        self.game_hashes = game_hashes;
+    }
+
+    // Recomputes the board's piece-square and material scores and NNUE accumulator, which are stale after the eval params or network change
+    pub fn refresh_board(&mut self) {
+        self.stop_search();
+        self.board.full_update();
     }
 
     // NOTE: Doesn't reset the table if the size matches
@@ -168,4 +560,31 @@ impl AsyncEngine {
         self.stop_search();
         self.arc_table = Arc::new(transpos::Table::new(self.arc_table.get_size_mbs()));
     }
+
+    pub fn save_table(&mut self, path: &str) -> std::io::Result<()> {
+        self.stop_search();
+        self.arc_table.save(path)
+    }
+
+    pub fn load_table(&mut self, path: &str) -> std::io::Result<()> {
+        self.stop_search();
+        self.arc_table = Arc::new(transpos::Table::load(path)?);
+        Ok(())
+    }
+
+    // See transpos::Table::get_hashfull()
+    pub fn get_hashfull(&self) -> usize {
+        self.arc_table.get_hashfull()
+    }
+
+    pub fn get_table_size_mbs(&self) -> usize {
+        self.arc_table.get_size_mbs()
+    }
+
+    // Forgets everything from the previous game
+    pub fn new_game(&mut self) {
+        self.reset_table();
+        self.set_board(&Board::start_pos(), Vec::new());
+        self.ponder_time_state = None;
+    }
 }
@@ -0,0 +1,72 @@
+use crate::eval::*;
+use crate::fen;
+use crate::log_println;
+use crate::search;
+use crate::transpos;
+
+// Searches a fixed set of positions to a fixed depth
+// The total node count works as a fingerprint of the search, since it only changes when the search's behavior does
+// See: https://www.chessprogramming.org/Engine_Testing
+
+pub const DEFAULT_DEPTH: u8 = 10;
+const TABLE_SIZE_MBS: usize = 16;
+
+// A mix of openings, middlegames and endgames
+const BENCH_FENS: [&str; 16] = [
+    fen::FEN_START_POS,
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 10",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 11",
+    "4rrk1/pp1n3p/3q2pQ/2p1pb2/2PP4/2P3N1/P2B2PP/4RRK1 b - - 7 19",
+    "rq3rk1/ppp2ppp/1bnpb3/3N2B1/3NP3/7P/PPPQ1PP1/2KR3R w - - 7 14",
+    "r1bq1r1k/1pp1n1pp/1p1p4/4p2Q/4Pp2/1BNP4/PPP2PPP/3R1RK1 w - - 2 14",
+    "r3r1k1/2p2ppp/p1p1bn2/8/1q2P3/2NPQN2/PPP3PP/R4RK1 b - - 2 15",
+    "r1bbk1nr/pp3p1p/2n5/1N4p1/2Np1B2/8/PPP2PPP/2KR1B1R w kq - 0 13",
+    "r1bq1rk1/ppp1nppp/4n3/3p3Q/3P4/1BP1B3/PP1N2PP/R4RK1 w - - 1 16",
+    "4r1k1/r1q2ppp/ppp2n2/4P3/5Rb1/1N1BQ3/PPP3PP/R5K1 w - - 1 17",
+    "2rqkb1r/ppp2p2/2npb1p1/1N1Nn2p/2P1PP2/8/PP2B1PP/R1BQK2R b KQ - 0 11",
+    "6k1/6p1/6Pp/ppp5/3pn2P/1P3K2/1PP2P2/3N4 b - - 0 1",
+    "3b4/5kp1/1p1p1p1p/pP1PpP1P/P1P1P3/3KN3/8/8 w - - 0 1",
+    "8/6pk/1p6/8/PP3p1p/5P2/4KP1q/3Q4 w - - 0 1",
+    "7k/3p2pp/4q3/8/4Q3/5Kp1/P6b/8 w - - 0 1",
+    "8/8/8/5k2/3R4/5K2/8/2r5 w - - 0 1",
+];
+
+#[derive(Debug, Copy, Clone, Default)]
+pub struct BenchResults {
+    pub total_nodes: usize,
+    pub total_time: f64, // In seconds
+}
+
+impl BenchResults {
+    pub fn get_nodes_per_sec(&self) -> usize {
+        ((self.total_nodes as f64) / self.total_time.max(0.001)) as usize
+    }
+}
+
+// Each position gets a fresh table, so the results don't depend on the order or anything searched before
+pub fn run(depth: u8, print: bool) -> BenchResults {
+    let mut results = BenchResults::default();
+    for (position_idx, position_fen) in BENCH_FENS.iter().enumerate() {
+        let board = fen::load_fen(position_fen).unwrap();
+        let table = transpos::Table::new(TABLE_SIZE_MBS);
+
+        let start_time = std::time::Instant::now();
+        let limits = search::SearchLimits::new().depth(depth);
+        let search_result = search::search_iterative(&board, &table, &[], &limits).unwrap();
+        results.total_time += start_time.elapsed().as_secs_f64();
+        results.total_nodes += search_result.total_nodes;
+
+        if print {
+            log_println!(
+                "Position {}/{}: {} nodes, best move {}, eval {}",
+                position_idx + 1,
+                BENCH_FENS.len(),
+                search_result.total_nodes,
+                search_result.best_move,
+                eval_to_str(search_result.eval)
+            );
+        }
+    }
+
+    results
+}
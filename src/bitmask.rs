@@ -1,3 +1,5 @@
+use alloc::string::String;
+
 pub type BitMask = u64;
 
 pub const fn bm_to_idx(mask: BitMask) -> usize {
@@ -1,8 +1,13 @@
 use crate::bitmask::*;
+use crate::eval;
+use crate::eval::{IntScore, Value};
 use crate::move_gen;
+use crate::nnue;
 use crate::zobrist;
 use crate::{fen, lookup_gen};
-use std::fmt::Write;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::Write;
 
 pub const PIECE_PAWN: usize = 0;
 pub const PIECE_KNIGHT: usize = 1;
@@ -16,8 +21,25 @@ pub const NUM_PIECES_NO_KING: usize = 5;
 pub const PIECE_CHARS: [char; NUM_PIECES] = ['P', 'N', 'B', 'R', 'Q', 'K'];
 pub const PIECE_NAMES: [&str; NUM_PIECES] = ["Pawn", "Knight", "Bishop", "Rook", "Queen", "King"];
 
+// Columns the king and rook end up on after castling, for each side (these are the same in Chess960)
+// Order is [left,right] (left being queenside)
+pub const CASTLE_KING_TO_COLUMNS: [i64; 2] = [2, 6];
+pub const CASTLE_ROOK_TO_COLUMNS: [i64; 2] = [3, 5];
+
+// Where the castling rooks start in normal chess
+pub const DEFAULT_CASTLE_ROOK_MASKS: [[BitMask; 2]; 2] = [
+    [bm_from_coord("A1"), bm_from_coord("H1")],
+    [bm_from_coord("A8"), bm_from_coord("H8")],
+];
+
 ////////////////////////////////////////////////////////////////////////////
 
+// A move packed into 16 bits, so it can be stored compactly (e.g. in the transposition table)
+// Bits 0-5 are the from square, bits 6-11 are the to square, bits 12-14 are the promotion piece (0 if none)
+// Zero is never a valid move, since the from and to squares can't be the same
+pub type EncodedMove = u16;
+pub const ENCODED_MOVE_NONE: EncodedMove = 0;
+
 #[derive(Debug, Copy, Clone)]
 pub struct Move {
     pub from: BitMask,
@@ -49,24 +71,146 @@ impl Move {
         self.flags & flag != 0
     }
 
+    // Castles are stored as the king taking its own rook, which works for every Chess960 setup
+    // Returns where the king and rook end up
+    pub fn get_castle_tos(&self) -> (BitMask, BitMask) {
+        debug_assert!(self.has_flag(Move::FLAG_CASTLE));
+        let side = (self.to > self.from) as usize;
+        let y = bm_to_xy(self.from).1;
+        (
+            bm_from_xy(CASTLE_KING_TO_COLUMNS[side], y),
+            bm_from_xy(CASTLE_ROOK_TO_COLUMNS[side], y),
+        )
+    }
+
     pub fn is_quiet(&self) -> bool {
         !self.has_flag(Move::FLAG_CAPTURE) && !self.has_flag(Move::FLAG_PROMOTION)
     }
-}
 
-impl std::fmt::Display for Move {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut stream: String = String::new();
-        write!(stream, "{}{}", bm_to_coord(self.from), bm_to_coord(self.to))?;
+    pub fn encode(&self) -> EncodedMove {
+        let promotion_piece_idx = if self.to_piece_idx != self.from_piece_idx {
+            self.to_piece_idx
+        } else {
+            0
+        };
+
+        (bm_to_idx(self.from) as EncodedMove)
+            | ((bm_to_idx(self.to) as EncodedMove) << 6)
+            | ((promotion_piece_idx as EncodedMove) << 12)
+    }
+
+    // Standard algebraic notation (e.g. "Nbd7", "exd5", "e8=Q+", "O-O"), for the board before this move is played
+    // See: https://www.chessprogramming.org/Algebraic_Chess_Notation#Standard_Algebraic_Notation_.28SAN.29
+    pub fn to_san(&self, board: &Board) -> String {
+        let mut san = String::new();
+
+        let mut moves = move_gen::MoveBuffer::new();
+        move_gen::generate_moves(board, &mut moves);
+
+        if self.has_flag(Move::FLAG_CASTLE) {
+            san += if self.to > self.from { "O-O" } else { "O-O-O" };
+        } else {
+            let (from_x, from_y) = bm_to_xy(self.from);
+            let from_coord = bm_to_coord(self.from);
+
+            // Promotions that capture aren't flagged as captures, so check the board too
+            let is_capture = self.has_flag(Move::FLAG_CAPTURE)
+                || (self.to & board.occupancy[1 - board.turn_idx]) != 0;
+
+            if self.from_piece_idx == PIECE_PAWN {
+                if is_capture {
+                    san.push(from_coord.chars().next().unwrap());
+                }
+            } else {
+                san.push(PIECE_CHARS[self.from_piece_idx]);
+
+                // Only say where the piece came from if another piece of the same type could also move there
+                let mut shares_column = false;
+                let mut shares_row = false;
+                let mut is_ambiguous = false;
+                for other_move in moves.iter() {
+                    if other_move.from_piece_idx == self.from_piece_idx
+                        && other_move.to == self.to
+                        && other_move.from != self.from
+                    {
+                        let (other_x, other_y) = bm_to_xy(other_move.from);
+                        is_ambiguous = true;
+                        shares_column |= other_x == from_x;
+                        shares_row |= other_y == from_y;
+                    }
+                }
+
+                if is_ambiguous {
+                    if !shares_column {
+                        san.push(from_coord.chars().next().unwrap());
+                    } else if !shares_row {
+                        san.push(from_coord.chars().nth(1).unwrap());
+                    } else {
+                        san += &from_coord;
+                    }
+                }
+            }
+
+            if is_capture {
+                san.push('x');
+            }
+            san += &bm_to_coord(self.to);
+
+            if self.to_piece_idx != self.from_piece_idx {
+                san.push('=');
+                san.push(PIECE_CHARS[self.to_piece_idx]);
+            }
+        }
+
+        let mut next_board = *board;
+        next_board.do_move(self);
+        if next_board.checkers != 0 {
+            let is_mate = next_board.legal_moves().next().is_none();
+            san.push(if is_mate { '#' } else { '+' });
+        }
+
+        san
+    }
+
+    // Long algebraic notation, as used by UCI (e.g. "e2e4", "e7e8q")
+    // Castles are written as the king's move (e.g. "e1g1"), or as the king taking its rook (e.g. "e1h1") in Chess960
+    pub fn to_uci(&self, is_chess960: bool) -> String {
+        let to = if self.has_flag(Move::FLAG_CASTLE) && !is_chess960 {
+            self.get_castle_tos().0
+        } else {
+            self.to
+        };
+
+        let mut result = bm_to_coord(self.from) + &bm_to_coord(to);
         if self.to_piece_idx != self.from_piece_idx {
             // Promotion
-            write!(
-                stream,
-                "{}",
-                PIECE_CHARS[self.to_piece_idx].to_ascii_lowercase()
-            )?;
+            result.push(PIECE_CHARS[self.to_piece_idx].to_ascii_lowercase());
+        }
+        result
+    }
+}
+
+impl core::fmt::Display for Move {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.to_uci(false))
+    }
+}
+
+// Why a move string couldn't be turned into a move, see Board::parse_uci_move()
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MoveParseError {
+    InvalidFormat(String), // Not written like "e2e4" or "e7e8q"
+    IllegalMove(String),   // Written fine, but not a legal move in the position
+}
+
+impl core::fmt::Display for MoveParseError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            MoveParseError::InvalidFormat(move_str) => {
+                write!(f, "\"{}\" isn't a UCI move", move_str)
+            }
+            MoveParseError::IllegalMove(move_str) => write!(f, "Illegal move \"{}\"", move_str),
         }
-        write!(f, "{}", stream)
     }
 }
 
@@ -98,9 +242,48 @@ pub struct Board {
     // Order is [left,right] (left being queenside)
     pub castle_rights: [[bool; 2]; 2],
 
+    // Where the rook for each castle right starts, which can be any column in Chess960
+    pub castle_rook_masks: [[BitMask; 2]; 2],
+
+    // Castles are written as the king taking its rook in UCI moves, see Move::to_uci()
+    pub is_chess960: bool,
+
     pub half_move_counter: u8,
+    pub full_move_counter: u16, // Starts at 1, and goes up after each of black's moves
 
     pub hash: zobrist::Hash,
+    pub pawn_hash: zobrist::Hash, // Only includes pawns, for caching pawn structure evaluation
+
+    // Sum of the piece-square table scores for each team, updated incrementally
+    pub piece_square_scores: [IntScore; 2],
+
+    // Sum of the piece base values for each team, updated incrementally
+    pub material_scores: [IntScore; 2],
+
+    // Sum of the phase weights of all pieces, updated incrementally (see eval::calc_phase())
+    pub phase_weight: u32,
+
+    // Only kept up to date while an NNUE network is loaded
+    pub accumulator: nnue::Accumulator,
+}
+
+// Everything needed to take back a move, see Board::make_move() and Board::undo_move()
+// Pieces are moved back by undo_move(), the rest is restored from here
+#[derive(Debug, Copy, Clone)]
+pub struct MoveUndo {
+    captured_piece_idx: Option<usize>,
+    castle_rights: [[bool; 2]; 2],
+    en_passant_mask: BitMask,
+    half_move_counter: u8,
+    full_move_counter: u16,
+    hash: zobrist::Hash,
+    pawn_hash: zobrist::Hash,
+    attacks: [BitMask; 2],
+    checkers: BitMask,
+    pinned: [BitMask; 2],
+    piece_square_scores: [IntScore; 2],
+    material_scores: [IntScore; 2],
+    phase_weight: u32,
 }
 
 impl Board {
@@ -115,9 +298,17 @@ impl Board {
             turn_idx: 0,
             en_passant_mask: 0,
             castle_rights: [[false; 2]; 2],
+            castle_rook_masks: DEFAULT_CASTLE_ROOK_MASKS,
+            is_chess960: false,
             half_move_counter: 0,
+            full_move_counter: 1,
 
             hash: 0,
+            pawn_hash: 0,
+            piece_square_scores: [IntScore::ZERO; 2],
+            material_scores: [IntScore::ZERO; 2],
+            phase_weight: 0,
+            accumulator: nnue::Accumulator::new(),
         }
     }
 
@@ -148,12 +339,281 @@ impl Board {
         board
     }
 
+    // Includes the half-move and full-move counters, so loading it back gives the same board
+    pub fn to_fen(&self) -> String {
+        fen::make_fen(self)
+    }
+
     /////////////////////////////////////////////////
 
     pub fn combined_occupancy(&self) -> u64 {
         self.occupancy[0] | self.occupancy[1]
     }
 
+    // Returns the type of the team's piece on the given square, if there is one
+    pub fn get_piece_idx_at(&self, team_idx: usize, pos: BitMask) -> Option<usize> {
+        self.pieces[team_idx]
+            .iter()
+            .position(|&piece_mask| (piece_mask & pos) != 0)
+    }
+
+    // Rebuilds a full move from an encoded one, using this board to determine the pieces and flags
+    // The result is not guaranteed to be legal, see is_pseudo_legal()
+    pub fn decode_move(&self, encoded_move: EncodedMove) -> Option<Move> {
+        if encoded_move == ENCODED_MOVE_NONE {
+            return None;
+        }
+
+        let from = bm_from_idx((encoded_move & 63) as usize);
+        let to = bm_from_idx(((encoded_move >> 6) & 63) as usize);
+        let promotion_piece_idx = ((encoded_move >> 12) & 7) as usize;
+
+        let from_piece_idx = self.get_piece_idx_at(self.turn_idx, from)?;
+        let mut mv = Move {
+            from,
+            to,
+            from_piece_idx,
+            to_piece_idx: from_piece_idx,
+            flags: 0,
+        };
+
+        if (to & self.occupancy[1 - self.turn_idx]) != 0 {
+            mv.flags |= Move::FLAG_CAPTURE;
+        }
+
+        if from_piece_idx == PIECE_PAWN {
+            let from_y = bm_to_xy(from).1;
+            let to_y = bm_to_xy(to).1;
+            if promotion_piece_idx != 0 {
+                mv.to_piece_idx = promotion_piece_idx;
+                mv.flags |= Move::FLAG_PROMOTION;
+            } else if (to & self.en_passant_mask) != 0 {
+                mv.flags |= Move::FLAG_EN_PASSANT | Move::FLAG_CAPTURE;
+            } else if (from_y - to_y).abs() == 2 {
+                mv.flags |= Move::FLAG_DOUBLE_PAWN_MOVE;
+            }
+        } else if from_piece_idx == PIECE_KING && (to & self.pieces[self.turn_idx][PIECE_ROOK]) != 0
+        {
+            // King taking its own rook
+            mv.flags |= Move::FLAG_CASTLE;
+        }
+
+        Some(mv)
+    }
+
+    // Legal moves in this position, generated lazily
+    // Use move_gen::generate_moves() instead when every move is needed anyway, since filling a buffer is faster
+    pub fn legal_moves(&self) -> move_gen::LegalMoves<'_> {
+        move_gen::LegalMoves::new(self)
+    }
+
+    // Finds the legal move matching a move in UCI notation, see Move::to_uci()
+    // Like GUIs do, castles must be written as the king taking its rook in Chess960, and as the king's move otherwise
+    pub fn parse_uci_move(&self, move_str: &str) -> Result<Move, MoveParseError> {
+        let bytes = move_str.as_bytes();
+        let is_coord =
+            |i: usize| (b'a'..=b'h').contains(&bytes[i]) && (b'1'..=b'8').contains(&bytes[i + 1]);
+        let is_valid_format = (bytes.len() == 4 || bytes.len() == 5)
+            && is_coord(0)
+            && is_coord(2)
+            && (bytes.len() == 4 || b"nbrq".contains(&bytes[4]));
+        if !is_valid_format {
+            return Err(MoveParseError::InvalidFormat(move_str.to_string()));
+        }
+
+        self.legal_moves()
+            .find(|mv| mv.to_uci(self.is_chess960) == move_str)
+            .ok_or_else(|| MoveParseError::IllegalMove(move_str.to_string()))
+    }
+
+    // Finds the legal move matching a move in standard algebraic notation, see Move::to_san()
+    // Also accepts some common variations, like missing check marks, "0-0" for castling, and "e8Q" for promotions
+    pub fn parse_san(&self, san: &str) -> Option<Move> {
+        let san = san.trim_end_matches(['+', '#', '!', '?']);
+
+        let mut moves = move_gen::MoveBuffer::new();
+        move_gen::generate_moves(self, &mut moves);
+
+        if san == "O-O" || san == "0-0" || san == "O-O-O" || san == "0-0-0" {
+            let is_right = san.len() == 3;
+            return moves
+                .iter()
+                .find(|mv| mv.has_flag(Move::FLAG_CASTLE) && (mv.to > mv.from) == is_right)
+                .copied();
+        }
+
+        let mut chars: Vec<char> = san.chars().filter(|&ch| ch != 'x' && ch != '=').collect();
+
+        let piece_idx = match chars.first() {
+            Some(&ch) if ch.is_ascii_uppercase() => {
+                chars.remove(0);
+                PIECE_CHARS
+                    .iter()
+                    .position(|&piece_char| piece_char == ch)?
+            }
+            _ => PIECE_PAWN,
+        };
+
+        // Pawn moves end with the destination row, unless they promote
+        let promotion_piece_idx = match chars.last() {
+            Some(&ch) if piece_idx == PIECE_PAWN && ch.is_ascii_alphabetic() => {
+                chars.pop();
+                let ch = ch.to_ascii_uppercase();
+                Some(
+                    PIECE_CHARS
+                        .iter()
+                        .position(|&piece_char| piece_char == ch)?,
+                )
+            }
+            _ => None,
+        };
+
+        if chars.len() < 2 {
+            return None;
+        }
+        let (from_chars, to_chars) = chars.split_at(chars.len() - 2);
+        let to = match to_chars {
+            ['a'..='h', '1'..='8'] => bm_from_xy(
+                (to_chars[0] as u8 - b'a') as i64,
+                (to_chars[1] as u8 - b'1') as i64,
+            ),
+            _ => return None,
+        };
+
+        // Anything before the destination narrows down where the piece came from
+        let mut from_column = None;
+        let mut from_row = None;
+        for &ch in from_chars {
+            match ch {
+                'a'..='h' => from_column = Some((ch as u8 - b'a') as i64),
+                '1'..='8' => from_row = Some((ch as u8 - b'1') as i64),
+                _ => return None,
+            }
+        }
+
+        let to_piece_idx = promotion_piece_idx.unwrap_or(piece_idx);
+        let mut found_move = None;
+        for mv in moves.iter() {
+            let (from_x, from_y) = bm_to_xy(mv.from);
+            if mv.from_piece_idx == piece_idx
+                && mv.to == to
+                && mv.to_piece_idx == to_piece_idx
+                && from_column.is_none_or(|x| x == from_x)
+                && from_row.is_none_or(|y| y == from_y)
+            {
+                if found_move.is_some() {
+                    return None; // Ambiguous
+                }
+                found_move = Some(*mv);
+            }
+        }
+
+        found_move
+    }
+
+    // Checks that a move could be played in this position, ignoring whether it leaves our king in check
+    // Useful for validating moves from untrusted sources (e.g. the transposition table, where hash collisions happen)
+    pub fn is_pseudo_legal(&self, mv: &Move) -> bool {
+        let team_idx = self.turn_idx;
+        let occ_team = self.occupancy[team_idx];
+        let occ_opp = self.occupancy[1 - team_idx];
+        let occ_combined = occ_team | occ_opp;
+
+        if mv.from_piece_idx >= NUM_PIECES
+            || (self.pieces[team_idx][mv.from_piece_idx] & mv.from) == 0
+            || mv.from == mv.to
+        {
+            return false;
+        }
+
+        if mv.has_flag(Move::FLAG_CASTLE) {
+            if mv.from_piece_idx != PIECE_KING
+                || mv.to_piece_idx != PIECE_KING
+                || mv.flags != Move::FLAG_CASTLE
+            {
+                return false;
+            }
+
+            // The king takes the rook of the castle right
+            let castle_side = (0..2).find(|&side| {
+                self.castle_rights[team_idx][side]
+                    && self.castle_rook_masks[team_idx][side] == mv.to
+            });
+            return castle_side.is_some_and(|side| {
+                move_gen::can_castle(side, self, team_idx, self.checkers != 0)
+            });
+        }
+
+        if (mv.to & occ_team) != 0 {
+            return false;
+        }
+
+        if mv.has_flag(Move::FLAG_CAPTURE)
+            != ((mv.to & occ_opp) != 0 || mv.has_flag(Move::FLAG_EN_PASSANT))
+        {
+            // Promotions from move generation don't have the capture flag set
+            if !mv.has_flag(Move::FLAG_PROMOTION) {
+                return false;
+            }
+        }
+
+        if mv.from_piece_idx == PIECE_PAWN {
+            const PROMOTE_MASK: [BitMask; 2] = [bm_make_row(7), bm_make_row(0)];
+            const STARTING_PAWNS_MASK: [BitMask; 2] = [bm_make_row(1), bm_make_row(6)];
+            let pawn_advance_dy = if team_idx == 0 { 1 } else { -1 };
+
+            let is_promotion = (mv.to & PROMOTE_MASK[team_idx]) != 0;
+            if is_promotion != mv.has_flag(Move::FLAG_PROMOTION) {
+                return false;
+            }
+            if is_promotion {
+                if mv.to_piece_idx == PIECE_PAWN || mv.to_piece_idx >= PIECE_KING {
+                    return false;
+                }
+            } else if mv.to_piece_idx != PIECE_PAWN {
+                return false;
+            }
+
+            let single_move = bm_shift(mv.from, 0, pawn_advance_dy) & !occ_combined;
+            if mv.to == single_move {
+                return !mv.has_flag(Move::FLAG_EN_PASSANT | Move::FLAG_DOUBLE_PAWN_MOVE);
+            }
+
+            if (mv.from & STARTING_PAWNS_MASK[team_idx]) != 0
+                && mv.to == (bm_shift(single_move, 0, pawn_advance_dy) & !occ_combined)
+            {
+                return mv.has_flag(Move::FLAG_DOUBLE_PAWN_MOVE);
+            }
+
+            let pawn_attacks = move_gen::generate_attacks(self, team_idx, PIECE_PAWN, mv.from);
+            if (mv.to & pawn_attacks) == 0 {
+                return false;
+            }
+
+            if mv.has_flag(Move::FLAG_EN_PASSANT) {
+                mv.to == self.en_passant_mask
+            } else {
+                (mv.to & occ_opp) != 0
+            }
+        } else {
+            if mv.to_piece_idx != mv.from_piece_idx
+                || mv.has_flag(
+                    Move::FLAG_PROMOTION | Move::FLAG_EN_PASSANT | Move::FLAG_DOUBLE_PAWN_MOVE,
+                )
+            {
+                return false;
+            }
+
+            let tos = lookup_gen::get_piece_tos(
+                mv.from_piece_idx,
+                mv.from,
+                bm_to_idx(mv.from),
+                occ_combined,
+            );
+            (mv.to & tos) != 0
+        }
+    }
+
     // Updates everything persistent, for after you set up the board
     // Only to be used infrequently
     pub fn full_update(&mut self) {
@@ -168,23 +628,41 @@ impl Board {
         {
             // Full-update hash
             self.hash = 0;
+            self.pawn_hash = 0;
 
             for team_idx in 0..2 {
                 for piece_idx in 0..NUM_PIECES {
                     for piece_mask in bm_iter_bits(self.pieces[team_idx][piece_idx]) {
                         let pos_idx = bm_to_idx(piece_mask);
                         self.hash ^= zobrist::hash_piece(team_idx, piece_idx, pos_idx);
+                        if piece_idx == PIECE_PAWN {
+                            self.pawn_hash ^= zobrist::hash_piece(team_idx, piece_idx, pos_idx);
+                        }
                     }
                 }
             }
 
-            self.hash ^= zobrist::hash_castle_rights(self.castle_rights);
+            self.hash ^= zobrist::hash_castle_rights(self.castle_rights, self.castle_rook_masks);
             self.hash ^= zobrist::hash_en_passant(self.en_passant_mask);
             if self.turn_idx == 1 {
                 self.hash ^= zobrist::hash_turn();
             }
         }
 
+        // Full-update piece-square scores
+        self.piece_square_scores = eval::calc_piece_square_scores(self);
+
+        // Full-update material
+        self.material_scores = eval::calc_material_scores(self);
+        self.phase_weight = eval::calc_phase_weight(self);
+
+        // Full-update accumulator
+        if let Some(network) = nnue::get_network() {
+            let mut accumulator = nnue::Accumulator::new();
+            accumulator.refresh(network, self);
+            self.accumulator = accumulator;
+        }
+
         // Full-update attacks
         self.update_attacks(self.turn_idx);
         self.update_attacks(1 - self.turn_idx);
@@ -245,49 +723,101 @@ impl Board {
 
     pub fn do_move(&mut self, mv: &Move) {
         // From: https://github.com/ZealanL/BoardMouse/blob/4d3b6c608a3cb82a1299580a90dcb3c831fc02f8/src/Engine/BoardState/BoardState.cpp
-        // Order: Left/Queen-side, Right/King-side
-        const CASTLING_ROOK_FROM_MASKS: [[BitMask; 2]; 2] = [
-            [
-                // White
-                bm_from_coord("A1"),
-                bm_from_coord("H1"),
-            ],
-            [
-                // Black
-                bm_from_coord("A8"),
-                bm_from_coord("H8"),
-            ],
-        ];
-
-        const CASTLING_ROOK_FROM_COMBINED_MASK: BitMask = CASTLING_ROOK_FROM_MASKS[0][0]
-            | CASTLING_ROOK_FROM_MASKS[0][1]
-            | CASTLING_ROOK_FROM_MASKS[1][0]
-            | CASTLING_ROOK_FROM_MASKS[1][1];
+
+        // Castles are stored as the king taking its rook, so find where the king actually goes
+        let is_castle = mv.has_flag(Move::FLAG_CASTLE);
+        let (to, rook_to) = if is_castle {
+            mv.get_castle_tos()
+        } else {
+            (mv.to, 0)
+        };
 
         let from_idx = bm_to_idx(mv.from);
-        let to_idx = bm_to_idx(mv.to);
+        let to_idx = bm_to_idx(to);
         let inv_from = !mv.from;
-        let inv_to = !mv.to;
+        let inv_to = !to;
+
+        // Accumulators from before the network was loaded have to be remade from scratch afterwards
+        let network = nnue::get_network();
+        let accumulator_network = network.filter(|network| self.accumulator.is_valid_for(network));
 
         // Undo castle and en passant hashes
-        self.hash ^= zobrist::hash_castle_rights(self.castle_rights);
+        self.hash ^= zobrist::hash_castle_rights(self.castle_rights, self.castle_rook_masks);
         self.hash ^= zobrist::hash_en_passant(self.en_passant_mask);
 
+        if is_castle {
+            // Pick up the rook first, since in Chess960 the king can land where it started (or the other way around)
+            let rook_from = mv.to;
+            debug_assert!(self.pieces[self.turn_idx][PIECE_ROOK] & rook_from == rook_from);
+            self.pieces[self.turn_idx][PIECE_ROOK] &= !rook_from;
+            self.occupancy[self.turn_idx] &= !rook_from;
+
+            self.hash ^= zobrist::hash_piece(self.turn_idx, PIECE_ROOK, bm_to_idx(rook_from));
+            self.piece_square_scores[self.turn_idx] -=
+                eval::get_piece_square_score(self.turn_idx, PIECE_ROOK, bm_to_idx(rook_from));
+            if let Some(network) = accumulator_network {
+                self.accumulator.remove_piece(
+                    network,
+                    self.turn_idx,
+                    PIECE_ROOK,
+                    bm_to_idx(rook_from),
+                );
+            }
+        }
+
         // Update pieces
         self.pieces[self.turn_idx][mv.from_piece_idx] &= inv_from;
-        self.pieces[self.turn_idx][mv.to_piece_idx] |= mv.to;
+        self.pieces[self.turn_idx][mv.to_piece_idx] |= to;
         self.hash ^= zobrist::hash_piece(self.turn_idx, mv.from_piece_idx, from_idx);
         self.hash ^= zobrist::hash_piece(self.turn_idx, mv.to_piece_idx, to_idx);
+        self.piece_square_scores[self.turn_idx] +=
+            eval::get_piece_square_score(self.turn_idx, mv.to_piece_idx, to_idx)
+                - eval::get_piece_square_score(self.turn_idx, mv.from_piece_idx, from_idx);
+        if mv.from_piece_idx != mv.to_piece_idx {
+            // Promotion
+            self.material_scores[self.turn_idx] += eval::get_piece_material_score(mv.to_piece_idx)
+                - eval::get_piece_material_score(mv.from_piece_idx);
+            self.phase_weight += eval::get_piece_phase_weight(mv.to_piece_idx);
+            self.phase_weight -= eval::get_piece_phase_weight(mv.from_piece_idx);
+        }
+        if mv.from_piece_idx == PIECE_PAWN {
+            self.pawn_hash ^= zobrist::hash_piece(self.turn_idx, PIECE_PAWN, from_idx);
+        }
+        if mv.to_piece_idx == PIECE_PAWN {
+            self.pawn_hash ^= zobrist::hash_piece(self.turn_idx, PIECE_PAWN, to_idx);
+        }
+        if let Some(network) = accumulator_network {
+            self.accumulator
+                .remove_piece(network, self.turn_idx, mv.from_piece_idx, from_idx);
+            self.accumulator
+                .add_piece(network, self.turn_idx, mv.to_piece_idx, to_idx);
+        }
         for opp_piece_idx in 0..NUM_PIECES {
-            if (self.pieces[1 - self.turn_idx][opp_piece_idx] & mv.to) != 0 {
+            if (self.pieces[1 - self.turn_idx][opp_piece_idx] & to) != 0 {
                 self.hash ^= zobrist::hash_piece(1 - self.turn_idx, opp_piece_idx, to_idx);
+                if opp_piece_idx == PIECE_PAWN {
+                    self.pawn_hash ^= zobrist::hash_piece(1 - self.turn_idx, PIECE_PAWN, to_idx);
+                }
+                self.piece_square_scores[1 - self.turn_idx] -=
+                    eval::get_piece_square_score(1 - self.turn_idx, opp_piece_idx, to_idx);
+                self.material_scores[1 - self.turn_idx] -=
+                    eval::get_piece_material_score(opp_piece_idx);
+                self.phase_weight -= eval::get_piece_phase_weight(opp_piece_idx);
+                if let Some(network) = accumulator_network {
+                    self.accumulator.remove_piece(
+                        network,
+                        1 - self.turn_idx,
+                        opp_piece_idx,
+                        to_idx,
+                    );
+                }
             }
             self.pieces[1 - self.turn_idx][opp_piece_idx] &= inv_to;
         }
 
         // Update occupancy
-        self.occupancy[self.turn_idx] |= mv.to;
         self.occupancy[self.turn_idx] &= inv_from;
+        self.occupancy[self.turn_idx] |= to;
         self.occupancy[1 - self.turn_idx] &= inv_to;
 
         self.en_passant_mask = 0; // Reset en passant mask (we will set it only if it is a double pawn move)
@@ -301,27 +831,35 @@ impl Board {
 
             self.hash ^=
                 zobrist::hash_piece(1 - self.turn_idx, PIECE_PAWN, bm_to_idx(en_passant_pos));
-        } else if mv.has_flag(Move::FLAG_CASTLE) {
-            // We are castling, find and move the rook
-
-            let castle_right: bool = mv.to > mv.from; // This works because we cant castle with a vertical king move
-
-            let rook_from = CASTLING_ROOK_FROM_MASKS[self.turn_idx][castle_right as usize];
-            let rook_to = if castle_right {
-                bm_shift(mv.to, -1, 0)
-            } else {
-                bm_shift(mv.to, 1, 0)
-            };
-
-            debug_assert!(self.pieces[self.turn_idx][PIECE_ROOK] & rook_from == rook_from);
+            self.pawn_hash ^=
+                zobrist::hash_piece(1 - self.turn_idx, PIECE_PAWN, bm_to_idx(en_passant_pos));
+            self.piece_square_scores[1 - self.turn_idx] -= eval::get_piece_square_score(
+                1 - self.turn_idx,
+                PIECE_PAWN,
+                bm_to_idx(en_passant_pos),
+            );
+            self.material_scores[1 - self.turn_idx] -= eval::get_piece_material_score(PIECE_PAWN);
+            if let Some(network) = accumulator_network {
+                self.accumulator.remove_piece(
+                    network,
+                    1 - self.turn_idx,
+                    PIECE_PAWN,
+                    bm_to_idx(en_passant_pos),
+                );
+            }
+        } else if is_castle {
+            // Put the rook down next to the king
             debug_assert!(self.combined_occupancy() & rook_to == 0);
+            self.pieces[self.turn_idx][PIECE_ROOK] |= rook_to;
+            self.occupancy[self.turn_idx] |= rook_to;
 
-            let rook_flip = rook_from | rook_to;
-            self.pieces[self.turn_idx][PIECE_ROOK] ^= rook_flip;
-            self.occupancy[self.turn_idx] ^= rook_flip;
-
-            self.hash ^= zobrist::hash_piece(self.turn_idx, PIECE_ROOK, bm_to_idx(rook_from));
             self.hash ^= zobrist::hash_piece(self.turn_idx, PIECE_ROOK, bm_to_idx(rook_to));
+            self.piece_square_scores[self.turn_idx] +=
+                eval::get_piece_square_score(self.turn_idx, PIECE_ROOK, bm_to_idx(rook_to));
+            if let Some(network) = accumulator_network {
+                self.accumulator
+                    .add_piece(network, self.turn_idx, PIECE_ROOK, bm_to_idx(rook_to));
+            }
 
             // Don't need to update castle rights as the king move clause will handle it after
         }
@@ -331,31 +869,41 @@ impl Board {
             self.castle_rights[self.turn_idx] = [false; 2];
         }
 
-        // Detect move that disables castling
+        // Detect move that disables castling (moving a castling rook, or capturing it)
         let combined_to_from = mv.to | mv.from;
-        if (combined_to_from & CASTLING_ROOK_FROM_COMBINED_MASK) != 0 {
-            for i in 0..2 {
-                for j in 0..2 {
-                    if (combined_to_from & CASTLING_ROOK_FROM_MASKS[i][j]) != 0 {
-                        self.castle_rights[i][j] = false;
-                    }
+        for i in 0..2 {
+            for j in 0..2 {
+                if (combined_to_from & self.castle_rook_masks[i][j]) != 0 {
+                    self.castle_rights[i][j] = false;
                 }
             }
         }
 
         let is_capture_or_pawn_move =
-            (self.occupancy[1 - self.turn_idx] & mv.to != 0) || (mv.from_piece_idx == PIECE_PAWN);
+            mv.has_flag(Move::FLAG_CAPTURE) || (mv.from_piece_idx == PIECE_PAWN);
         if is_capture_or_pawn_move {
             self.half_move_counter = 0;
         } else {
-            self.half_move_counter += 1;
+            self.half_move_counter = self.half_move_counter.saturating_add(1);
+        }
+
+        if self.turn_idx == 1 {
+            self.full_move_counter = self.full_move_counter.saturating_add(1);
+        }
+
+        if let Some(network) = network {
+            if accumulator_network.is_none() {
+                let mut accumulator = nnue::Accumulator::new();
+                accumulator.refresh(network, self);
+                self.accumulator = accumulator;
+            }
         }
 
         self.update_attacks(self.turn_idx);
         self.turn_idx = 1 - self.turn_idx;
 
         // Redo castle and en passant hashes
-        self.hash ^= zobrist::hash_castle_rights(self.castle_rights);
+        self.hash ^= zobrist::hash_castle_rights(self.castle_rights, self.castle_rook_masks);
         self.hash ^= zobrist::hash_en_passant(self.en_passant_mask);
 
         // Flip turn hash
@@ -370,17 +918,142 @@ impl Board {
 
         self.hash ^= zobrist::hash_turn();
     }
+
+    fn save_undo(&self, captured_piece_idx: Option<usize>) -> MoveUndo {
+        MoveUndo {
+            captured_piece_idx,
+            castle_rights: self.castle_rights,
+            en_passant_mask: self.en_passant_mask,
+            half_move_counter: self.half_move_counter,
+            full_move_counter: self.full_move_counter,
+            hash: self.hash,
+            pawn_hash: self.pawn_hash,
+            attacks: self.attacks,
+            checkers: self.checkers,
+            pinned: self.pinned,
+            piece_square_scores: self.piece_square_scores,
+            material_scores: self.material_scores,
+            phase_weight: self.phase_weight,
+        }
+    }
+
+    fn restore_undo(&mut self, undo: &MoveUndo) {
+        self.castle_rights = undo.castle_rights;
+        self.en_passant_mask = undo.en_passant_mask;
+        self.half_move_counter = undo.half_move_counter;
+        self.full_move_counter = undo.full_move_counter;
+        self.hash = undo.hash;
+        self.pawn_hash = undo.pawn_hash;
+        self.attacks = undo.attacks;
+        self.checkers = undo.checkers;
+        self.pinned = undo.pinned;
+        self.piece_square_scores = undo.piece_square_scores;
+        self.material_scores = undo.material_scores;
+        self.phase_weight = undo.phase_weight;
+    }
+
+    // Does the move, returning what undo_move() needs to take it back
+    // Cheaper than copying the whole board (which includes the NNUE accumulator) for every move in the search
+    pub fn make_move(&mut self, mv: &Move) -> MoveUndo {
+        let captured_piece_idx = if (mv.to & self.occupancy[1 - self.turn_idx]) != 0 {
+            self.get_piece_idx_at(1 - self.turn_idx, mv.to)
+        } else {
+            None
+        };
+
+        let undo = self.save_undo(captured_piece_idx);
+        self.do_move(mv);
+        undo
+    }
+
+    // Takes back a move done with make_move(), which must be the last move done on this board
+    pub fn undo_move(&mut self, mv: &Move, undo: &MoveUndo) {
+        self.turn_idx = 1 - self.turn_idx;
+        let team_idx = self.turn_idx;
+
+        let is_castle = mv.has_flag(Move::FLAG_CASTLE);
+        let (to, rook_to) = if is_castle {
+            mv.get_castle_tos()
+        } else {
+            (mv.to, 0)
+        };
+
+        // Pick everything up before putting anything back, since in Chess960 the king and rook can swap squares
+        self.pieces[team_idx][mv.to_piece_idx] &= !to;
+        self.occupancy[team_idx] &= !to;
+        if is_castle {
+            self.pieces[team_idx][PIECE_ROOK] &= !rook_to;
+            self.occupancy[team_idx] &= !rook_to;
+            self.pieces[team_idx][PIECE_ROOK] |= mv.to;
+            self.occupancy[team_idx] |= mv.to;
+        }
+        self.pieces[team_idx][mv.from_piece_idx] |= mv.from;
+        self.occupancy[team_idx] |= mv.from;
+
+        let captured_pos = if mv.has_flag(Move::FLAG_EN_PASSANT) {
+            bm_shift(mv.to, 0, if team_idx == 0 { -1 } else { 1 })
+        } else {
+            mv.to
+        };
+        let captured_piece_idx = if mv.has_flag(Move::FLAG_EN_PASSANT) {
+            Some(PIECE_PAWN)
+        } else {
+            undo.captured_piece_idx
+        };
+        if let Some(captured_piece_idx) = captured_piece_idx {
+            self.pieces[1 - team_idx][captured_piece_idx] |= captured_pos;
+            self.occupancy[1 - team_idx] |= captured_pos;
+        }
+
+        // Accumulators made with an older network will be remade on the next move anyway
+        if let Some(network) =
+            nnue::get_network().filter(|network| self.accumulator.is_valid_for(network))
+        {
+            let to_idx = bm_to_idx(to);
+            self.accumulator
+                .remove_piece(network, team_idx, mv.to_piece_idx, to_idx);
+            self.accumulator
+                .add_piece(network, team_idx, mv.from_piece_idx, bm_to_idx(mv.from));
+            if is_castle {
+                self.accumulator
+                    .remove_piece(network, team_idx, PIECE_ROOK, bm_to_idx(rook_to));
+                self.accumulator
+                    .add_piece(network, team_idx, PIECE_ROOK, bm_to_idx(mv.to));
+            }
+            if let Some(captured_piece_idx) = captured_piece_idx {
+                self.accumulator.add_piece(
+                    network,
+                    1 - team_idx,
+                    captured_piece_idx,
+                    bm_to_idx(captured_pos),
+                );
+            }
+        }
+
+        self.restore_undo(undo);
+    }
+
+    pub fn make_null_move(&mut self) -> MoveUndo {
+        let undo = self.save_undo(None);
+        self.do_null_move();
+        undo
+    }
+
+    pub fn undo_null_move(&mut self, undo: &MoveUndo) {
+        self.turn_idx = 1 - self.turn_idx;
+        self.restore_undo(undo);
+    }
 }
 
-impl std::fmt::Display for Board {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Display for Board {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         // Based on: https://github.com/official-stockfish/Stockfish/blob/d46c0b6f492bc00fa0a91d91f18e474c14541330/src/bitboard.cpp#L58
 
         const DIVIDER: &str = "+---+---+---+---+---+---+---+---+";
 
         let mut stream: String = String::new();
         writeln!(stream, "Board {{")?;
-        writeln!(stream, "\tFEN: {}", fen::make_fen(self))?;
+        writeln!(stream, "\tFEN: {}", self.to_fen())?;
         writeln!(stream, "\tTurn: {}", self.turn_idx)?;
         writeln!(
             stream,
@@ -446,3 +1119,98 @@ impl std::fmt::Display for Board {
         write!(f, "{}", stream)
     }
 }
+
+// Boards are stored as their FEN
+// NOTE: Deserializing needs lookup tables, so crate::init() must have been called first
+#[cfg(feature = "serde")]
+impl serde::Serialize for Board {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_fen())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Board {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let fen_str = String::deserialize(deserializer)?;
+        fen::load_fen(&fen_str).map_err(serde::de::Error::custom)
+    }
+}
+
+// Moves are stored in UCI notation, see Move::to_uci()
+// There's no Deserialize, since a move can't be rebuilt without its position (use Board::parse_uci_move() for that)
+#[cfg(feature = "serde")]
+impl serde::Serialize for Move {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////
+
+// Piece values used for static exchange evaluation
+// The king is worth a huge amount so that it will never be traded into a defended square
+pub const SEE_PIECE_VALUES: [Value; NUM_PIECES] = [100, 300, 300, 500, 900, 10000];
+
+// Static Exchange Evaluation
+// Returns the material won (or lost) after every piece that can recapture on the move's target square does so,
+//  with each side always recapturing using its least valuable attacker, and stopping whenever a recapture would lose
+// See: https://www.chessprogramming.org/SEE_-_The_Swap_Algorithm
+pub fn see(board: &Board, mv: &Move) -> Value {
+    let to_idx = bm_to_idx(mv.to);
+
+    // Gain of each capture in the sequence, from the perspective of the side making it
+    let mut gains: [Value; 32] = [0; 32];
+
+    let mut occupy = board.combined_occupancy();
+    if mv.has_flag(Move::FLAG_EN_PASSANT) {
+        gains[0] = SEE_PIECE_VALUES[PIECE_PAWN];
+        occupy &= !bm_shift(mv.to, 0, if board.turn_idx == 0 { -1 } else { 1 });
+    } else if let Some(piece_idx) = board.get_piece_idx_at(1 - board.turn_idx, mv.to) {
+        gains[0] = SEE_PIECE_VALUES[piece_idx];
+    }
+
+    if mv.has_flag(Move::FLAG_PROMOTION) {
+        gains[0] += SEE_PIECE_VALUES[mv.to_piece_idx] - SEE_PIECE_VALUES[PIECE_PAWN];
+    }
+
+    let mut from = mv.from;
+    let mut attacker_piece_idx = mv.to_piece_idx;
+    let mut team_idx = board.turn_idx;
+    let mut depth = 0;
+    loop {
+        depth += 1;
+        team_idx = 1 - team_idx;
+
+        // Speculative gain if the other side recaptures our attacker
+        gains[depth] = SEE_PIECE_VALUES[attacker_piece_idx] - gains[depth - 1];
+
+        // Remove the attacker, which may reveal x-ray attackers behind it
+        occupy &= !from;
+        let attackers = move_gen::get_attackers_to(board, to_idx, occupy);
+
+        // Find the least valuable attacker for the side to recapture
+        let mut next_from: BitMask = 0;
+        for piece_idx in 0..NUM_PIECES {
+            let piece_attackers = attackers & board.pieces[team_idx][piece_idx];
+            if piece_attackers != 0 {
+                next_from = piece_attackers & piece_attackers.wrapping_neg();
+                attacker_piece_idx = piece_idx;
+                break;
+            }
+        }
+
+        if next_from == 0 || depth + 1 >= gains.len() {
+            break;
+        }
+        from = next_from;
+    }
+
+    // Negamax the gains back down to the first capture
+    while depth > 1 {
+        depth -= 1;
+        gains[depth - 1] = -Value::max(-gains[depth - 1], gains[depth]);
+    }
+
+    gains[0]
+}
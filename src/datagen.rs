@@ -0,0 +1,253 @@
+use crate::board::*;
+use crate::eval::*;
+use crate::fen;
+use crate::move_gen;
+use crate::pgn;
+use crate::pgn::{MoveAnnotation, PgnGame};
+use crate::search;
+use crate::search::GameResult;
+use crate::transpos;
+use crate::zobrist::Hash;
+use rand::Rng;
+use std::io::Write;
+
+// Generates training data by having the engine play against itself
+// Each position is written as "<fen> | <score> | <result>", with the score in centipawns and the result as 1.0/0.5/0.0, both from white's perspective
+// The games themselves can also be written to "<out path>.pgn", with the engine's evals as comments
+
+#[derive(Debug, Copy, Clone)]
+pub struct DatagenSettings {
+    pub num_games: usize,
+    pub nodes_per_move: usize,
+    pub random_plies: usize, // Random moves at the start of each game, so the games don't all play out the same
+    pub max_game_plies: usize, // Games this long are adjudicated as draws
+    pub table_size_mbs: usize,
+    pub write_pgn: bool,
+}
+
+impl DatagenSettings {
+    pub fn new() -> DatagenSettings {
+        DatagenSettings {
+            num_games: 100,
+            nodes_per_move: 5000,
+            random_plies: 8,
+            max_game_plies: 400,
+            table_size_mbs: 16,
+            write_pgn: false,
+        }
+    }
+}
+
+impl Default for DatagenSettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Adjudication, so games don't go on long after the result is clear
+const WIN_ADJUDICATION_EVAL: Value = 1000;
+const WIN_ADJUDICATION_PLIES: usize = 4;
+const DRAW_ADJUDICATION_EVAL: Value = 10;
+const DRAW_ADJUDICATION_PLIES: usize = 10;
+const DRAW_ADJUDICATION_MIN_PLY: usize = 80;
+
+// Returns true if the opening should be thrown away, because the game already ended during it
+fn play_random_opening(
+    board: &mut Board,
+    game_hashes: &mut Vec<Hash>,
+    moves_played: &mut Vec<Move>,
+    num_plies: usize,
+    rng: &mut impl Rng,
+) -> bool {
+    for _ in 0..num_plies {
+        let mut moves = move_gen::MoveBuffer::new();
+        move_gen::generate_moves(board, &mut moves);
+        if moves.is_empty() {
+            return true;
+        }
+
+        let mv = moves[rng.random_range(0..moves.len())];
+        game_hashes.push(board.hash);
+        moves_played.push(mv);
+        board.do_move(&mv);
+    }
+
+    search::get_game_result(board) != GameResult::InProgress
+}
+
+// Plays a single game, and returns the recorded positions and the game itself
+// The game always has a result, see PgnGame::result
+pub fn play_game(
+    settings: &DatagenSettings,
+    table: &transpos::Table,
+    rng: &mut impl Rng,
+) -> (Vec<(String, Value)>, PgnGame) {
+    let mut game = PgnGame::new(Board::start_pos());
+    game.tags
+        .push(("Event".to_string(), "BoardCrab datagen".to_string()));
+    game.tags
+        .push(("White".to_string(), "BoardCrab".to_string()));
+    game.tags
+        .push(("Black".to_string(), "BoardCrab".to_string()));
+
+    let (mut board, mut game_hashes) = loop {
+        let mut board = game.start_board;
+        let mut game_hashes = Vec::new();
+        game.moves.clear();
+        if !play_random_opening(
+            &mut board,
+            &mut game_hashes,
+            &mut game.moves,
+            settings.random_plies,
+            rng,
+        ) {
+            break (board, game_hashes);
+        }
+    };
+    game.annotations = vec![MoveAnnotation::default(); game.moves.len()];
+    game.comment = Some(format!("{} random opening plies", game.moves.len()));
+
+    let mut positions = Vec::new();
+    let result = play_searched_moves(
+        settings,
+        table,
+        &mut board,
+        &mut game_hashes,
+        &mut game,
+        &mut positions,
+    );
+    game.result = Some(result);
+    (positions, game)
+}
+
+// Plays the rest of the game after the opening, and returns the result for white (1.0, 0.5, or 0.0)
+fn play_searched_moves(
+    settings: &DatagenSettings,
+    table: &transpos::Table,
+    board: &mut Board,
+    game_hashes: &mut Vec<Hash>,
+    game: &mut PgnGame,
+    positions: &mut Vec<(String, Value)>,
+) -> f32 {
+    let mut num_win_plies = 0;
+    let mut num_draw_plies = 0;
+    for ply in 0..settings.max_game_plies {
+        // Results are from the perspective of the side to move, until the end
+        let game_result = search::get_game_result(board);
+        let num_repetitions = game_hashes
+            .iter()
+            .filter(|&&hash| hash == board.hash)
+            .count();
+        let turn_result = match game_result {
+            GameResult::Checkmate => Some(0.0),
+            GameResult::InProgress if num_repetitions >= 2 => Some(0.5),
+            GameResult::InProgress => None,
+            _ => Some(0.5),
+        };
+
+        if let Some(turn_result) = turn_result {
+            return get_white_result(board, turn_result);
+        }
+
+        let limits = search::SearchLimits::new().nodes(settings.nodes_per_move);
+        let search_result = search::search_iterative(board, table, game_hashes, &limits).unwrap();
+        let (eval, best_move) = (search_result.eval, search_result.best_move);
+        let white_eval = if board.turn_idx == 0 { eval } else { -eval };
+
+        // Positions in check don't have a meaningful static eval, so they make poor training data
+        if board.checkers == 0 && !is_mate_eval(eval) {
+            positions.push((fen::make_fen(board), white_eval));
+        }
+
+        if eval.abs() >= WIN_ADJUDICATION_EVAL {
+            num_win_plies += 1;
+            if num_win_plies >= WIN_ADJUDICATION_PLIES {
+                let turn_result = if eval > 0 { 1.0 } else { 0.0 };
+                return get_white_result(board, turn_result);
+            }
+        } else {
+            num_win_plies = 0;
+        }
+
+        if ply >= DRAW_ADJUDICATION_MIN_PLY && eval.abs() <= DRAW_ADJUDICATION_EVAL {
+            num_draw_plies += 1;
+            if num_draw_plies >= DRAW_ADJUDICATION_PLIES {
+                return 0.5;
+            }
+        } else {
+            num_draw_plies = 0;
+        }
+
+        game_hashes.push(board.hash);
+        board.do_move(&best_move);
+        game.moves.push(best_move);
+        game.annotations.push(MoveAnnotation {
+            nags: Vec::new(),
+            comment: Some(pgn::make_eval_comment(white_eval, None)),
+        });
+    }
+
+    0.5
+}
+
+fn get_white_result(board: &Board, turn_result: f32) -> f32 {
+    if board.turn_idx == 0 {
+        turn_result
+    } else {
+        1.0 - turn_result
+    }
+}
+
+// Plays all the games and writes their positions to the output file
+// Returns the total amount of positions written
+pub fn run(settings: &DatagenSettings, out_path: &str) -> std::io::Result<usize> {
+    let mut writer = std::io::BufWriter::new(
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(out_path)?,
+    );
+
+    let mut pgn_writer = if settings.write_pgn {
+        Some(std::io::BufWriter::new(
+            std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(format!("{}.pgn", out_path))?,
+        ))
+    } else {
+        None
+    };
+
+    let mut rng = rand::rng();
+    let mut total_positions = 0;
+    for game_idx in 0..settings.num_games {
+        let table = transpos::Table::new(settings.table_size_mbs);
+        let (positions, mut game) = play_game(settings, &table, &mut rng);
+        let result = game.result.unwrap();
+
+        if let Some(pgn_writer) = &mut pgn_writer {
+            game.tags
+                .push(("Round".to_string(), (game_idx + 1).to_string()));
+            writeln!(pgn_writer, "{}", game.to_pgn())?;
+            pgn_writer.flush()?;
+        }
+
+        for (position_fen, score) in &positions {
+            writeln!(writer, "{} | {} | {:.1}", position_fen, score, result)?;
+        }
+        writer.flush()?;
+
+        total_positions += positions.len();
+        println!(
+            "Game {}/{}: result {:.1}, {} positions ({} total)",
+            game_idx + 1,
+            settings.num_games,
+            result,
+            positions.len(),
+            total_positions
+        );
+    }
+
+    Ok(total_positions)
+}
@@ -0,0 +1,59 @@
+use std::io::Write;
+use std::sync::Mutex;
+
+// Records all UCI traffic to a file, for bugs that only show up inside a GUI (see the "Debug Log File" option)
+// Each line gets the Unix time in seconds and which way it went: "<<" was received, ">>" was sent
+// Files are appended to, so logs from several sessions can share one
+
+// Shared with the search threads, which print too
+static LOG_FILE: Mutex<Option<std::fs::File>> = Mutex::new(None);
+
+// Starts logging to the file, or stops logging with None
+pub fn set_path(path: Option<&str>) -> std::io::Result<()> {
+    let file = match path {
+        Some(path) => Some(
+            std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)?,
+        ),
+        None => None,
+    };
+
+    *LOG_FILE.lock().unwrap() = file;
+    Ok(())
+}
+
+fn write(direction: &str, text: &str) {
+    let mut log_file = LOG_FILE.lock().unwrap();
+    let Some(file) = log_file.as_mut() else {
+        return;
+    };
+
+    let time = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64();
+    for line in text.lines() {
+        // Losing a log line isn't worth interrupting the GUI over
+        let _ = writeln!(file, "{:.3} {} {}", time, direction, line);
+    }
+}
+
+pub fn write_received(text: &str) {
+    write("<<", text.trim_end());
+}
+
+pub fn write_sent(text: &str) {
+    write(">>", text);
+}
+
+// Same as println!, but also writes the line to the debug log
+#[macro_export]
+macro_rules! log_println {
+    ($($x:tt)*) => {{
+        let text = format!($($x)*);
+        println!("{}", text);
+        $crate::debug_log::write_sent(&text);
+    }};
+}
@@ -0,0 +1,221 @@
+use crate::board::*;
+use crate::fen;
+use crate::game::Game;
+use crate::search;
+use crate::search::{GameResult, SearchLimits, SharedSearchListener};
+use crate::thread_flag::ThreadFlag;
+use crate::transpos;
+use crate::zobrist::Hash;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+// For using BoardCrab as a library, without having to deal with search threads and stop flags
+// Positions and options work like the UCI commands of the same name:
+//
+//   let mut engine = Engine::new(16);
+//   engine.set_option("Hash", "64")?;
+//   engine.set_position("startpos", &["e2e4", "e7e5"])?;
+//   let best_move = engine.go(SearchLimits::new().depth(10)).get_best_move();
+//
+// Searches can also run on their own thread, and be controlled through the SearchHandle they return:
+//
+//   let handle = engine.start_search(SearchLimits::new().move_time(Duration::from_secs(5)));
+//   ...
+//   handle.stop();
+//   let best_move = handle.join().get_best_move();
+//
+// NOTE: Searches are single-threaded, see async_engine.rs for Lazy SMP
+
+// How a search ended
+#[derive(Debug, Copy, Clone)]
+pub enum SearchOutcome {
+    Finished(search::IterativeResult),
+    NoLegalMoves(GameResult), // Checkmate or stalemate, so there was nothing to search
+}
+
+impl SearchOutcome {
+    pub fn get_best_move(&self) -> Option<Move> {
+        match self {
+            SearchOutcome::Finished(result) => Some(result.best_move),
+            SearchOutcome::NoLegalMoves(_) => None,
+        }
+    }
+}
+
+// A running (or finished) search, see Engine::start_search()
+// Dropping the handle stops the search, without waiting for it
+pub struct SearchHandle {
+    stop_flag: ThreadFlag,
+    join_handle: Option<thread::JoinHandle<SearchOutcome>>, // Only None once joined
+}
+
+impl SearchHandle {
+    // Tells the search to stop as soon as it can, use join() to wait for it and get the result
+    pub fn stop(&self) {
+        self.stop_flag.trigger();
+    }
+
+    // False once the search has stopped, either by itself or because of stop()
+    pub fn is_running(&self) -> bool {
+        self.join_handle
+            .as_ref()
+            .is_some_and(|join_handle| !join_handle.is_finished())
+    }
+
+    // Waits for the search to finish
+    // NOTE: Never returns if the search has no limits and isn't stopped
+    pub fn join(mut self) -> SearchOutcome {
+        let join_handle = self.join_handle.take().unwrap();
+        join_handle.join().expect("Search thread crashed")
+    }
+}
+
+impl Drop for SearchHandle {
+    fn drop(&mut self) {
+        if self.join_handle.is_some() {
+            self.stop();
+        }
+    }
+}
+
+// Why the engine couldn't do what it was told
+#[derive(Debug, Clone)]
+pub enum EngineError {
+    InvalidFen(fen::FenError),
+    InvalidMove(MoveParseError),
+    UnknownOption(String),
+    InvalidOptionValue(String, String), // Name and value
+}
+
+impl core::fmt::Display for EngineError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            EngineError::InvalidFen(err) => write!(f, "{}", err),
+            EngineError::InvalidMove(err) => write!(f, "{}", err),
+            EngineError::UnknownOption(name) => write!(f, "Unknown option \"{}\"", name),
+            EngineError::InvalidOptionValue(name, value) => {
+                write!(f, "Invalid value \"{}\" for option \"{}\"", value, name)
+            }
+        }
+    }
+}
+
+// Same range as the UCI option
+const MAX_TABLE_SIZE_MBS: usize = 65536;
+
+pub struct Engine {
+    board: Board,
+    game_hashes: Vec<Hash>, // Positions played before the current board, for repetition detection
+    table: Arc<transpos::Table>,
+    listener: SharedSearchListener,
+}
+
+impl Engine {
+    pub fn new(table_size_mbs: usize) -> Engine {
+        crate::init();
+        Engine {
+            board: Board::start_pos(),
+            game_hashes: Vec::new(),
+            table: Arc::new(transpos::Table::new(table_size_mbs)),
+            listener: Arc::new(Mutex::new(())),
+        }
+    }
+
+    pub fn get_board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn set_board(&mut self, board: &Board, game_hashes: Vec<Hash>) {
+        self.board = *board;
+        self.game_hashes = game_hashes;
+    }
+
+    // Like the UCI "position" command, the FEN can also be "startpos", and moves are in UCI notation
+    // If anything is invalid, the current position is kept
+    pub fn set_position(&mut self, fen_str: &str, moves: &[&str]) -> Result<(), EngineError> {
+        let board = if fen_str == "startpos" {
+            Board::start_pos()
+        } else {
+            fen::load_fen(fen_str).map_err(EngineError::InvalidFen)?
+        };
+
+        let mut game = Game::new(board);
+        for move_str in moves {
+            game.push_uci(move_str).map_err(EngineError::InvalidMove)?;
+        }
+
+        self.set_board(game.get_board(), game.get_hashes().to_vec());
+        Ok(())
+    }
+
+    // Like the UCI "setoption" command, names are case-insensitive
+    // Only the options that make sense outside of UCI are supported:
+    // - "Hash": Table size in megabytes, changing it clears the table
+    // - "Clear Hash": The value is ignored
+    // NOTE: Searches that are already running keep the table they started with
+    pub fn set_option(&mut self, name: &str, value: &str) -> Result<(), EngineError> {
+        let invalid_value = || EngineError::InvalidOptionValue(name.to_string(), value.to_string());
+        if name.eq_ignore_ascii_case("Hash") {
+            let table_size_mbs: usize = value.trim().parse().map_err(|_| invalid_value())?;
+            if !(1..=MAX_TABLE_SIZE_MBS).contains(&table_size_mbs) {
+                return Err(invalid_value());
+            }
+
+            if table_size_mbs != self.table.get_size_mbs() {
+                self.table = Arc::new(transpos::Table::new(table_size_mbs));
+            }
+            Ok(())
+        } else if name.eq_ignore_ascii_case("Clear Hash") {
+            self.table = Arc::new(transpos::Table::new(self.table.get_size_mbs()));
+            Ok(())
+        } else {
+            Err(EngineError::UnknownOption(name.to_string()))
+        }
+    }
+
+    // Forgets everything from the last game, and goes back to the start position
+    pub fn new_game(&mut self) {
+        self.table = Arc::new(transpos::Table::new(self.table.get_size_mbs()));
+        self.set_board(&Board::start_pos(), Vec::new());
+    }
+
+    // Gets told about the progress of searches started after this
+    pub fn set_listener(&mut self, listener: SharedSearchListener) {
+        self.listener = listener;
+    }
+
+    // Searches the current board on a new thread, until the max depth or a limit is hit, or the handle is stopped
+    // If the limits already have a stop flag, stopping the handle triggers it
+    pub fn start_search(&self, mut limits: SearchLimits) -> SearchHandle {
+        let stop_flag = limits.stop_flag.get_or_insert_with(ThreadFlag::new).clone();
+
+        let board = self.board;
+        let game_hashes = self.game_hashes.clone();
+        let table = Arc::clone(&self.table);
+        let mut listener = Arc::clone(&self.listener);
+        let join_handle = thread::spawn(move || {
+            let result = search::search_iterative_with_listener(
+                &board,
+                &table,
+                &game_hashes,
+                &limits,
+                &mut listener,
+            );
+            match result {
+                Some(result) => SearchOutcome::Finished(result),
+                None => SearchOutcome::NoLegalMoves(search::get_game_result(&board)),
+            }
+        });
+
+        SearchHandle {
+            stop_flag,
+            join_handle: Some(join_handle),
+        }
+    }
+
+    // Like start_search(), but waits for the search to finish
+    // NOTE: Never returns if the limits can't be hit, use start_search() to stop it yourself
+    pub fn go(&self, limits: SearchLimits) -> SearchOutcome {
+        self.start_search(limits).join()
+    }
+}
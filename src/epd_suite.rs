@@ -0,0 +1,123 @@
+use crate::board::*;
+use crate::eval::*;
+use crate::fen::EpdRecord;
+use crate::search;
+use crate::transpos;
+
+// Runs test suites like WAC or STS, where each EPD record has the best moves ("bm") or moves to avoid ("am")
+// See: https://www.chessprogramming.org/Test-Positions
+
+#[derive(Debug, Copy, Clone)]
+pub struct SuiteSettings {
+    pub time_per_position: Option<f64>, // In seconds
+    pub nodes_per_position: Option<usize>,
+    pub max_depth: u8,
+    pub table_size_mbs: usize,
+}
+
+impl SuiteSettings {
+    pub fn new() -> SuiteSettings {
+        SuiteSettings {
+            time_per_position: Some(1.0),
+            nodes_per_position: None,
+            max_depth: u8::MAX,
+            table_size_mbs: 16,
+        }
+    }
+}
+
+impl Default for SuiteSettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Returns whether the move solves the position, or None if the record has no (valid) "bm" or "am" moves to check against
+pub fn is_solved(record: &EpdRecord, mv: &Move) -> Option<bool> {
+    let best_moves = record.get_moves("bm");
+    let avoid_moves = record.get_moves("am");
+    if best_moves.is_none() && avoid_moves.is_none() {
+        return None;
+    }
+
+    let matches = |moves: &[Move]| moves.iter().any(|other| other.encode() == mv.encode());
+    Some(
+        best_moves.is_none_or(|moves| matches(&moves))
+            && avoid_moves.is_none_or(|moves| !matches(&moves)),
+    )
+}
+
+#[derive(Debug, Copy, Clone, Default)]
+pub struct SuiteResults {
+    pub num_solved: usize,
+    pub num_checked: usize, // Positions that had moves to check against
+    pub total_nodes: usize,
+    pub total_time: f64,
+}
+
+// Searches every position, printing how each went, and returns the totals
+pub fn run(records: &[EpdRecord], settings: &SuiteSettings) -> SuiteResults {
+    let mut results = SuiteResults::default();
+    for (record_idx, record) in records.iter().enumerate() {
+        let name = record
+            .get_id()
+            .map_or_else(|| format!("#{}", record_idx + 1), |id| id.to_string());
+
+        // Positions are independent, so nothing should carry over
+        let table = transpos::Table::new(settings.table_size_mbs);
+
+        let start_time = std::time::Instant::now();
+        let limits = search::SearchLimits {
+            max_depth: Some(settings.max_depth),
+            move_time: settings
+                .time_per_position
+                .map(std::time::Duration::from_secs_f64),
+            max_nodes: settings.nodes_per_position,
+            ..search::SearchLimits::new()
+        };
+        let Some(search_result) = search::search_iterative(&record.board, &table, &[], &limits)
+        else {
+            println!("{}: skipped, no legal moves", name);
+            continue;
+        };
+        results.total_nodes += search_result.total_nodes;
+        results.total_time += start_time.elapsed().as_secs_f64();
+
+        let mut expected_strs = Vec::new();
+        for opcode in ["bm", "am"] {
+            if let Some(operands) = record.get_opcode(opcode) {
+                expected_strs.push(format!("{} {}", opcode, operands.join(" ")));
+            }
+        }
+        let expected_str = if expected_strs.is_empty() {
+            String::new()
+        } else {
+            format!(" (expected {})", expected_strs.join(", "))
+        };
+
+        let status = match is_solved(record, &search_result.best_move) {
+            Some(true) => {
+                results.num_solved += 1;
+                results.num_checked += 1;
+                "ok"
+            }
+            Some(false) => {
+                results.num_checked += 1;
+                "FAIL"
+            }
+            None => "unchecked",
+        };
+
+        println!(
+            "{}: {} {}{} depth {} eval {}",
+            name,
+            status,
+            search_result.best_move.to_san(&record.board),
+            expected_str,
+            search_result.depth,
+            eval_to_str(search_result.eval)
+        );
+    }
+
+    results
+}
@@ -1,31 +1,227 @@
 use crate::bitmask::*;
 use crate::board::*;
 use crate::eval_lookup;
+use crate::eval_params;
+use crate::kpk;
 use crate::lookup_gen;
+use crate::move_gen;
+use crate::nnue;
+#[cfg(feature = "std")]
+use crate::zobrist::Hash;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use core::cell::RefCell;
 
-pub type Value = f32; // Note: MUST be a float type
-pub const VALUE_INF: Value = Value::INFINITY;
-pub const VALUE_CHECKMATE: Value = 1_000.0;
-pub const VALUE_CHECKMATE_MIN: Value = 500.0;
+pub type Value = i32; // In centipawns
+pub const VALUE_INF: Value = 32_000; // Also returned by aborted searches
+pub const VALUE_CHECKMATE: Value = 30_000;
+pub const VALUE_CHECKMATE_MIN: Value = VALUE_CHECKMATE - 1_000; // Mates further than this many plies away aren't representable
 
-pub fn eval_to_str(eval: Value) -> String {
-    if eval.abs() >= VALUE_CHECKMATE_MIN {
-        let mate_move_count = VALUE_CHECKMATE - eval.abs();
-        let ply_till_mate = (mate_move_count * eval.signum()) as i64;
-        format!("#{}", (ply_till_mate + 1) / 2)
+// Eval weights are kept in pawns, and only rounded to a Value once the eval is finished
+pub type Weight = f32;
+
+pub fn weight_to_value(weight: Weight) -> Value {
+    #[cfg(feature = "std")]
+    let value = (weight * 100.0).round() as Value;
+
+    // f32::round() needs std, so round half away from zero by hand (casting truncates towards zero)
+    #[cfg(not(feature = "std"))]
+    let value = (weight * 100.0 + Weight::copysign(0.5, weight)) as Value;
+
+    value
+}
+
+// Mate evals are stored as VALUE_CHECKMATE minus the amount of plies from the root until mate
+pub fn is_mate_eval(eval: Value) -> bool {
+    eval.abs() >= VALUE_CHECKMATE_MIN
+}
+
+pub fn is_inf_eval(eval: Value) -> bool {
+    eval.abs() >= VALUE_INF
+}
+
+// Returns the amount of full moves until mate, negative if we are the ones getting mated
+pub fn get_mate_moves(eval: Value) -> Option<i64> {
+    if is_mate_eval(eval) {
+        let plies_till_mate = (VALUE_CHECKMATE - eval.abs()) as i64;
+        let moves_till_mate = (plies_till_mate + 1) / 2;
+        Some(moves_till_mate * (eval.signum() as i64))
     } else {
-        eval.to_string()
+        None
     }
 }
 
-pub fn decay_eval(eval: Value) -> Value {
-    if eval.abs() >= VALUE_CHECKMATE_MIN {
-        eval - eval.signum()
+pub fn eval_to_str(eval: Value) -> String {
+    if let Some(mate_moves) = get_mate_moves(eval) {
+        format!("#{}", mate_moves)
     } else {
-        eval
+        format!("{:.2}", (eval as f32) / 100.0)
+    }
+}
+
+//////////////////////////////////////////////////////////
+
+// A pair of middlegame and endgame values, blended together by the game phase
+// See: https://www.chessprogramming.org/Tapered_Eval
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Score {
+    pub mg: Weight,
+    pub eg: Weight,
+}
+
+impl Score {
+    pub const ZERO: Score = Score { mg: 0.0, eg: 0.0 };
+
+    pub const fn new(mg: Weight, eg: Weight) -> Score {
+        Score { mg, eg }
+    }
+
+    // Tuned eval weights are stored as [mg, eg]
+    pub const fn from_weights(weights: [Weight; 2]) -> Score {
+        Score::new(weights[0], weights[1])
+    }
+
+    // Phase is from 0-1, where 1 is the full middlegame and 0 is a bare endgame
+    pub fn blend(&self, phase: Weight) -> Weight {
+        self.mg * phase + self.eg * (1.0 - phase)
+    }
+}
+
+impl core::ops::Add for Score {
+    type Output = Score;
+    fn add(self, other: Score) -> Score {
+        Score::new(self.mg + other.mg, self.eg + other.eg)
+    }
+}
+
+impl core::ops::AddAssign for Score {
+    fn add_assign(&mut self, other: Score) {
+        *self = *self + other;
+    }
+}
+
+impl core::ops::Sub for Score {
+    type Output = Score;
+    fn sub(self, other: Score) -> Score {
+        Score::new(self.mg - other.mg, self.eg - other.eg)
+    }
+}
+
+impl core::ops::SubAssign for Score {
+    fn sub_assign(&mut self, other: Score) {
+        *self = *self - other;
+    }
+}
+
+impl core::ops::Neg for Score {
+    type Output = Score;
+    fn neg(self) -> Score {
+        Score::new(-self.mg, -self.eg)
+    }
+}
+
+impl core::ops::Mul<Weight> for Score {
+    type Output = Score;
+    fn mul(self, scale: Weight) -> Score {
+        Score::new(self.mg * scale, self.eg * scale)
+    }
+}
+
+// A Score in fixed-point integer units, for sums that are kept up to date incrementally
+// Floats would drift away from the sum calculated from scratch as pieces are added and removed
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct IntScore {
+    pub mg: i32,
+    pub eg: i32,
+}
+
+impl IntScore {
+    pub const ZERO: IntScore = IntScore { mg: 0, eg: 0 };
+
+    // Units per pawn
+    const SCALE: Weight = 10000.0;
+
+    pub const fn new(mg: i32, eg: i32) -> IntScore {
+        IntScore { mg, eg }
+    }
+
+    pub fn from_score(score: Score) -> IntScore {
+        // Rounds half away from zero, like weight_to_value()
+        let round = |weight: Weight| {
+            let scaled = weight * Self::SCALE;
+            (scaled + Weight::copysign(0.5, scaled)) as i32
+        };
+        IntScore::new(round(score.mg), round(score.eg))
+    }
+
+    pub fn to_score(&self) -> Score {
+        Score::new(
+            self.mg as Weight / Self::SCALE,
+            self.eg as Weight / Self::SCALE,
+        )
+    }
+}
+
+impl core::ops::Add for IntScore {
+    type Output = IntScore;
+    fn add(self, other: IntScore) -> IntScore {
+        IntScore::new(self.mg + other.mg, self.eg + other.eg)
     }
 }
 
+impl core::ops::AddAssign for IntScore {
+    fn add_assign(&mut self, other: IntScore) {
+        *self = *self + other;
+    }
+}
+
+impl core::ops::Sub for IntScore {
+    type Output = IntScore;
+    fn sub(self, other: IntScore) -> IntScore {
+        IntScore::new(self.mg - other.mg, self.eg - other.eg)
+    }
+}
+
+impl core::ops::SubAssign for IntScore {
+    fn sub_assign(&mut self, other: IntScore) {
+        *self = *self - other;
+    }
+}
+
+impl core::ops::Mul<i32> for IntScore {
+    type Output = IntScore;
+    fn mul(self, scale: i32) -> IntScore {
+        IntScore::new(self.mg * scale, self.eg * scale)
+    }
+}
+
+// How much each piece type contributes to the game phase
+const PHASE_WEIGHTS: [u32; NUM_PIECES] = [0, 1, 1, 2, 4, 0];
+const PHASE_WEIGHT_TOTAL: u32 = 24; // Both teams' pieces in the starting position
+
+pub fn get_piece_phase_weight(piece_idx: usize) -> u32 {
+    PHASE_WEIGHTS[piece_idx]
+}
+
+// Phase weight of all pieces from scratch, Board keeps this updated incrementally
+pub fn calc_phase_weight(board: &Board) -> u32 {
+    let mut phase_weight = 0;
+    for (piece_idx, piece_phase_weight) in PHASE_WEIGHTS.iter().enumerate() {
+        let piece_count = (board.pieces[0][piece_idx] | board.pieces[1][piece_idx]).count_ones();
+        phase_weight += piece_count * piece_phase_weight;
+    }
+
+    phase_weight
+}
+
+// Returns the game phase from 0-1 based on the remaining material, where 1 is the full middlegame
+pub fn calc_phase(board: &Board) -> Weight {
+    (u32::min(board.phase_weight, PHASE_WEIGHT_TOTAL) as Weight) / (PHASE_WEIGHT_TOTAL as Weight)
+}
+
 //////////////////////////////////////////////////////////
 
 const LIGHT_SQUARES: BitMask = 0x55aa55aa55aa55aa;
@@ -33,16 +229,16 @@ const DARK_SQUARES: BitMask = !LIGHT_SQUARES;
 
 // Returns the "attacking power" of a team from 0-1
 // This is meant to represent how capable the player is of making a deadly attack on the king
-pub fn calc_attacking_power(board: &Board, team_idx: usize) -> Value {
+pub fn calc_attacking_power(board: &Board, team_idx: usize) -> Weight {
     let rook_count = board.pieces[team_idx][PIECE_ROOK].count_ones();
     if board.pieces[team_idx][PIECE_QUEEN] != 0 {
         let bishop_count = board.pieces[team_idx][PIECE_BISHOP].count_ones();
         let knight_count = board.pieces[team_idx][PIECE_BISHOP].count_ones();
-        Value::min(
+        Weight::min(
             1.0,
-            0.8 + (rook_count as Value) * 0.15
-                + (bishop_count as Value) * 0.05
-                + (knight_count as Value) * 0.03,
+            0.8 + (rook_count as Weight) * 0.15
+                + (bishop_count as Weight) * 0.05
+                + (knight_count as Weight) * 0.03,
         )
     } else {
         if rook_count >= 2 {
@@ -54,10 +250,6 @@ pub fn calc_attacking_power(board: &Board, team_idx: usize) -> Value {
     }
 }
 
-fn dual_weight(weights: [Value; 2], scale: Value) -> Value {
-    weights[0] * scale + weights[1] * (1.0 - scale)
-}
-
 fn get_pawn_attack_mask(board: &Board, team_idx: usize) -> BitMask {
     let pawns = board.pieces[team_idx][PIECE_PAWN];
 
@@ -73,106 +265,397 @@ fn get_pawn_attack_mask(board: &Board, team_idx: usize) -> BitMask {
     capture_mask
 }
 
-pub fn eval_material(board: &Board, team_idx: usize, opp_attack_power: Value) -> Value {
-    let mut value: Value = 0.0;
-    for piece_idx in 0..NUM_PIECES_NO_KING {
-        value += (board.pieces[team_idx][piece_idx].count_ones() as Value)
-            * dual_weight(eval_lookup::PIECE_BASE_VALUE[piece_idx], opp_attack_power);
+// Tables are from white's perspective, so black's squares are flipped vertically
+// See: https://www.chessprogramming.org/Piece-Square_Tables
+pub fn get_piece_square_score(team_idx: usize, piece_idx: usize, pos_idx: usize) -> IntScore {
+    let rel_pos_idx = if team_idx == 0 { pos_idx } else { pos_idx ^ 56 };
+    IntScore::from_score(Score::from_weights(
+        eval_params::get().piece_tb[piece_idx][rel_pos_idx],
+    ))
+}
+
+// Piece-square scores of both teams from scratch, Board keeps these updated incrementally
+pub fn calc_piece_square_scores(board: &Board) -> [IntScore; 2] {
+    let mut scores = [IntScore::ZERO; 2];
+    for (team_idx, score) in scores.iter_mut().enumerate() {
+        for piece_idx in 0..NUM_PIECES {
+            for piece_mask in bm_iter_bits(board.pieces[team_idx][piece_idx]) {
+                *score += get_piece_square_score(team_idx, piece_idx, bm_to_idx(piece_mask));
+            }
+        }
     }
 
-    value
+    scores
+}
+
+// The king isn't counted, since it can never be taken
+pub fn get_piece_material_score(piece_idx: usize) -> IntScore {
+    if piece_idx == PIECE_KING {
+        IntScore::ZERO
+    } else {
+        IntScore::from_score(Score::from_weights(
+            eval_params::get().piece_base_value[piece_idx],
+        ))
+    }
+}
+
+// Material scores of both teams from scratch, Board keeps these updated incrementally
+pub fn calc_material_scores(board: &Board) -> [IntScore; 2] {
+    let mut scores = [IntScore::ZERO; 2];
+    for (team_idx, score) in scores.iter_mut().enumerate() {
+        for piece_idx in 0..NUM_PIECES_NO_KING {
+            *score += get_piece_material_score(piece_idx)
+                * (board.pieces[team_idx][piece_idx].count_ones() as i32);
+        }
+    }
+
+    scores
 }
 
+// Adjusts piece values based on how many of our own pawns are left
+// Knights like closed positions, while rooks and the bishop pair need open lines
+// See: https://www.chessprogramming.org/Material#Imbalances
+pub fn eval_imbalance(board: &Board, team_idx: usize) -> Score {
+    const BASE_PAWN_COUNT: Weight = 5.0;
+
+    let pieces = &board.pieces[team_idx];
+    let extra_pawns = (pieces[PIECE_PAWN].count_ones() as Weight) - BASE_PAWN_COUNT;
+
+    let mut score = Score::from_weights(eval_params::get().knight_pawn_adjust)
+        * (pieces[PIECE_KNIGHT].count_ones() as Weight * extra_pawns)
+        + Score::from_weights(eval_params::get().rook_pawn_adjust)
+            * (pieces[PIECE_ROOK].count_ones() as Weight * extra_pawns);
+    if pieces[PIECE_BISHOP].count_ones() >= 2 {
+        score += Score::from_weights(eval_params::get().bishop_pair_pawn_adjust) * extra_pawns;
+    }
+
+    score
+}
+
+// Rows where a knight is far enough forward to be an outpost, for each team
+const OUTPOST_ROWS: [BitMask; 2] = [
+    bm_make_row(3) | bm_make_row(4) | bm_make_row(5),
+    bm_make_row(2) | bm_make_row(3) | bm_make_row(4),
+];
+
 pub fn eval_piece_type(
     board: &Board,
     team_idx: usize,
     piece_idx: usize,
     piece_mask: BitMask,
-    opp_attack_power: Value,
-) -> Value {
-    let mut value: Value = 0.0;
+) -> Score {
+    let mut score = Score::ZERO;
 
+    let pawns = board.pieces[team_idx][PIECE_PAWN];
     let opp_pawns = board.pieces[1 - team_idx][PIECE_PAWN];
-    let pawn_attacks = get_pawn_attack_mask(board, team_idx);
+
+    if piece_idx == PIECE_BISHOP && piece_mask.count_ones() >= 2 {
+        // See: https://www.chessprogramming.org/Bishop_Pair
+        score += Score::from_weights(eval_params::get().bishop_pair);
+    }
 
     for pos_mask in bm_iter_bits(piece_mask) {
-        let (x, y) = bm_to_xy(pos_mask);
-        let rel_y = [y, 7 - y][team_idx];
-        let rel_pos_idx = x + rel_y * 8;
-        value += dual_weight(
-            eval_lookup::PIECE_TB[piece_idx][rel_pos_idx as usize],
-            opp_attack_power,
-        );
+        let (x, _y) = bm_to_xy(pos_mask);
 
         if piece_idx == PIECE_PAWN {
-            let (pawn_x, pawn_y) = bm_to_xy(pos_mask);
-            let pawn_rel_y = if team_idx == 0 { pawn_y } else { 7 - pawn_y };
-
-            let mut behind_rows: BitMask = (1u64 << (8 * (pawn_y + 1))) - 1;
-            if team_idx == 1 {
-                behind_rows = bm_flip_vertical(behind_rows);
+            let color_mask = if (pos_mask & LIGHT_SQUARES) != 0 {
+                LIGHT_SQUARES
+            } else {
+                DARK_SQUARES
+            };
+            if (board.pieces[team_idx][PIECE_BISHOP] & color_mask) != 0 {
+                score += Score::from_weights(eval_params::get().blocking_pawns);
             }
+        } else if piece_idx == PIECE_KNIGHT {
+            // A knight defended by a pawn that enemy pawns can never chase away
+            // See: https://www.chessprogramming.org/Outposts
+            let is_outpost = (pos_mask & OUTPOST_ROWS[team_idx]) != 0
+                && (get_pawn_attack_mask(board, team_idx) & pos_mask) != 0
+                && (lookup_gen::get_attack_span_mask(team_idx, bm_to_idx(pos_mask)) & opp_pawns)
+                    == 0;
+            if is_outpost {
+                score += Score::from_weights(eval_params::get().knight_outpost);
+            }
+        } else if piece_idx == PIECE_ROOK {
+            let column = bm_make_column(x);
+            if (column & (pawns | opp_pawns)) == 0 {
+                score += Score::from_weights(eval_params::get().open_rooks);
+            } else if (column & pawns) == 0 {
+                score += Score::from_weights(eval_params::get().semi_open_rooks);
+            }
+        }
+    }
 
-            let column: BitMask = bm_make_column(pawn_x);
-            let columns = column
-                | bm_shift(column & !bm_make_column(7), 1, 0)
-                | bm_shift(column & !bm_make_column(0), -1, 0);
+    score
+}
 
-            let pass_prev = columns & !behind_rows;
+// Pawns with another one of our pawns in front of them
+pub fn get_doubled_pawns(board: &Board, team_idx: usize) -> BitMask {
+    let pawns = board.pieces[team_idx][PIECE_PAWN];
+    let mut doubled_pawns = 0;
+    for pawn in bm_iter_bits(pawns) {
+        if (lookup_gen::get_front_span_mask(team_idx, bm_to_idx(pawn)) & pawns) != 0 {
+            doubled_pawns |= pawn;
+        }
+    }
 
-            let is_passed = (pass_prev & opp_pawns) == 0;
-            if is_passed {
-                let rel_pos_idx = pawn_x + pawn_rel_y * 8;
-                value += dual_weight(
-                    eval_lookup::PASSED_PAWN_TB[rel_pos_idx as usize],
-                    opp_attack_power,
-                );
-            }
+    doubled_pawns
+}
 
-            // TODO: Scale with distance between the pawns
-            let pawns_in_file = (piece_mask & column).count_ones();
-            if pawns_in_file > 1 {
-                value += dual_weight(eval_lookup::DOUBLED_PAWNS, opp_attack_power);
-            }
+// Pawns with none of our pawns on the neighboring columns
+pub fn get_isolated_pawns(board: &Board, team_idx: usize) -> BitMask {
+    let pawns = board.pieces[team_idx][PIECE_PAWN];
+    let mut isolated_pawns = 0;
+    for pawn in bm_iter_bits(pawns) {
+        let x = bm_to_xy(pawn).0 as usize;
+        if (lookup_gen::get_adjacent_columns_mask(x) & pawns) == 0 {
+            isolated_pawns |= pawn;
+        }
+    }
 
-            if (pawn_attacks & pos_mask) != 0 {
-                value += dual_weight(eval_lookup::CONNECTED_PAWNS, opp_attack_power);
-            }
+    isolated_pawns
+}
 
-            let color_mask = if (pos_mask & LIGHT_SQUARES) != 0 {
-                LIGHT_SQUARES
+// Pawns that have fallen behind their neighbors and can't safely advance
+// See: https://www.chessprogramming.org/Backward_Pawn
+pub fn get_backward_pawns(board: &Board, team_idx: usize) -> BitMask {
+    let pawns = board.pieces[team_idx][PIECE_PAWN];
+    let opp_pawn_attacks = get_pawn_attack_mask(board, 1 - team_idx);
+    let mut backward_pawns = 0;
+    for pawn in bm_iter_bits(pawns) {
+        let pos_idx = bm_to_idx(pawn);
+        let x = bm_to_xy(pawn).0 as usize;
+
+        // Neighboring pawns on the same row or further back could still come up to support this pawn
+        let support_mask = lookup_gen::get_adjacent_columns_mask(x)
+            & !lookup_gen::get_passed_pawn_mask(team_idx, pos_idx);
+        if (support_mask & pawns) != 0 {
+            continue;
+        }
+
+        let has_neighbors = (lookup_gen::get_adjacent_columns_mask(x) & pawns) != 0;
+        let stop_square = bm_shift(pawn, 0, if team_idx == 0 { 1 } else { -1 });
+        if has_neighbors && (stop_square & opp_pawn_attacks) != 0 {
+            backward_pawns |= pawn;
+        }
+    }
+
+    backward_pawns
+}
+
+// Pawns that are defended by another pawn, or have a pawn right next to them
+pub fn get_connected_pawns(board: &Board, team_idx: usize) -> BitMask {
+    let pawns = board.pieces[team_idx][PIECE_PAWN];
+    let phalanx_pawns = (bm_shift(pawns & !bm_make_column(7), 1, 0)
+        | bm_shift(pawns & !bm_make_column(0), -1, 0))
+        & pawns;
+
+    (get_pawn_attack_mask(board, team_idx) & pawns) | phalanx_pawns
+}
+
+// Pawns with no enemy pawns in front of them or on the neighboring columns in front of them
+// See: https://www.chessprogramming.org/Passed_Pawn
+pub fn get_passed_pawns(board: &Board, team_idx: usize) -> BitMask {
+    let pawns = board.pieces[team_idx][PIECE_PAWN];
+    let opp_pawns = board.pieces[1 - team_idx][PIECE_PAWN];
+    let mut passed_pawns = 0;
+    for pawn in bm_iter_bits(pawns) {
+        let pos_idx = bm_to_idx(pawn);
+
+        // Only the front pawn of a doubled pair counts as passed
+        let blockers = lookup_gen::get_passed_pawn_mask(team_idx, pos_idx) & opp_pawns
+            | lookup_gen::get_front_span_mask(team_idx, pos_idx) & pawns;
+        if blockers == 0 {
+            passed_pawns |= pawn;
+        }
+    }
+
+    passed_pawns
+}
+
+// See: https://www.chessprogramming.org/Pawn_Structure
+pub fn eval_pawn_structure(board: &Board, team_idx: usize) -> Score {
+    let mut score = Score::ZERO;
+
+    for pawn in bm_iter_bits(get_passed_pawns(board, team_idx)) {
+        let rel_pos_idx = if team_idx == 0 {
+            bm_to_idx(pawn)
+        } else {
+            bm_to_idx(pawn) ^ 56
+        };
+        score += Score::from_weights(eval_params::get().passed_pawn_tb[rel_pos_idx]);
+    }
+
+    let count_score = |mask: BitMask, weights: [Weight; 2]| {
+        Score::from_weights(weights) * (mask.count_ones() as Weight)
+    };
+
+    score
+        + count_score(
+            get_doubled_pawns(board, team_idx),
+            eval_params::get().doubled_pawns,
+        )
+        + count_score(
+            get_isolated_pawns(board, team_idx),
+            eval_params::get().isolated_pawns,
+        )
+        + count_score(
+            get_backward_pawns(board, team_idx),
+            eval_params::get().backward_pawns,
+        )
+        + count_score(
+            get_connected_pawns(board, team_idx),
+            eval_params::get().connected_pawns,
+        )
+}
+
+// Scores passed pawns on what the pieces around them are doing, which the cached pawn structure can't know about
+// See: https://www.chessprogramming.org/Passed_Pawn
+pub fn eval_passed_pawns(board: &Board, team_idx: usize) -> Score {
+    let passed_pawns = get_passed_pawns(board, team_idx);
+    if passed_pawns == 0 {
+        return Score::ZERO;
+    }
+
+    let king_idx = bm_to_idx(board.pieces[team_idx][PIECE_KING]);
+    let opp_king_idx = bm_to_idx(board.pieces[1 - team_idx][PIECE_KING]);
+    let pawn_attacks = get_pawn_attack_mask(board, team_idx);
+
+    // In pawn endgames, the enemy king is the only thing that can stop a passed pawn
+    let opp_non_pawns = board.occupancy[1 - team_idx]
+        & !board.pieces[1 - team_idx][PIECE_PAWN]
+        & !board.pieces[1 - team_idx][PIECE_KING];
+    let is_opp_pawns_only = opp_non_pawns == 0;
+
+    let mut score = Score::ZERO;
+    let mut has_unstoppable = false;
+    for pawn in bm_iter_bits(passed_pawns) {
+        let pos_idx = bm_to_idx(pawn);
+        let rel_y = if team_idx == 0 {
+            bm_to_xy(pawn).1
+        } else {
+            7 - bm_to_xy(pawn).1
+        };
+        let stop_square = bm_shift(pawn, 0, if team_idx == 0 { 1 } else { -1 });
+        let stop_idx = bm_to_idx(stop_square);
+
+        if (pawn & pawn_attacks) != 0 {
+            score += Score::from_weights(eval_params::get().passed_pawn_supported);
+        }
+        if (stop_square & board.occupancy[1 - team_idx]) != 0 {
+            score += Score::from_weights(eval_params::get().passed_pawn_blocked);
+        }
+
+        // Kings matter more the further the pawn has gotten
+        let king_distance_diff = lookup_gen::get_distance(opp_king_idx, stop_idx) as Weight
+            - lookup_gen::get_distance(king_idx, stop_idx) as Weight;
+        score += Score::from_weights(eval_params::get().passed_pawn_king_distance)
+            * (king_distance_diff * rel_y as Weight);
+
+        // Rule of the square, the enemy king can't catch up with a pawn that has a free path
+        if is_opp_pawns_only
+            && (lookup_gen::get_front_span_mask(team_idx, pos_idx) & board.combined_occupancy())
+                == 0
+        {
+            let square_idx = if board.turn_idx == team_idx {
+                // We get to move first, so it's as if the pawn had already moved
+                let move_dist = if rel_y == 1 { 2 } else { 1 };
+                bm_to_idx(bm_shift(pawn, 0, [move_dist, -move_dist][team_idx]))
             } else {
-                DARK_SQUARES
+                pos_idx
             };
-            if (board.pieces[team_idx][PIECE_BISHOP] & color_mask) != 0 {
-                value += dual_weight(eval_lookup::BLOCKING_PAWNS, opp_attack_power);
-            }
-        } else if piece_idx == PIECE_ROOK {
-            let is_open_file = (bm_make_column(x)
-                & (board.pieces[0][PIECE_PAWN] | board.pieces[1][PIECE_PAWN]))
-                == 0;
-            if is_open_file {
-                value += dual_weight(eval_lookup::OPEN_ROOKS, opp_attack_power);
+            let square_mask = lookup_gen::get_pawn_square_mask(team_idx, square_idx);
+            if (square_mask & board.pieces[1 - team_idx][PIECE_KING]) == 0 {
+                has_unstoppable = true;
             }
         }
     }
 
-    value
+    if has_unstoppable {
+        score += Score::from_weights(eval_params::get().unstoppable_passer);
+    }
+
+    score
+}
+
+// Pawn structure only depends on where the pawns are, so it's cached by the board's pawn hash
+// Each thread gets its own cache, so search threads never have to wait on each other
+// See: https://www.chessprogramming.org/Pawn_Hash_Table
+#[cfg(feature = "std")]
+const PAWN_CACHE_SIZE: usize = 1 << 14;
+
+#[cfg(feature = "std")]
+#[derive(Debug, Copy, Clone)]
+struct PawnCacheEntry {
+    pawn_hash: Hash,
+    params_version: u32, // Scores from older eval params are out of date
+    scores: [Score; 2],
+}
+
+#[cfg(feature = "std")]
+thread_local! {
+    // NOTE: Boards without pawns have a pawn hash of 0, which correctly matches the empty entries
+    static PAWN_CACHE: RefCell<Vec<PawnCacheEntry>> = RefCell::new(vec![
+        PawnCacheEntry {
+            pawn_hash: 0,
+            params_version: 0,
+            scores: [Score::ZERO; 2],
+        };
+        PAWN_CACHE_SIZE
+    ]);
+}
+
+// Returns the pawn structure score of both teams
+#[cfg(feature = "std")]
+pub fn eval_pawn_structures_cached(board: &Board) -> [Score; 2] {
+    PAWN_CACHE.with_borrow_mut(|pawn_cache| {
+        let entry = &mut pawn_cache[(board.pawn_hash as usize) % PAWN_CACHE_SIZE];
+        let params_version = eval_params::get_version();
+        if entry.pawn_hash != board.pawn_hash || entry.params_version != params_version {
+            entry.pawn_hash = board.pawn_hash;
+            entry.params_version = params_version;
+            entry.scores = [eval_pawn_structure(board, 0), eval_pawn_structure(board, 1)];
+        }
+
+        entry.scores
+    })
 }
 
-pub fn eval_mobility(board: &Board, team_idx: usize) -> Value {
-    let attacks = board.attacks[team_idx];
-    (attacks.count_ones() as Value) * 0.02 // Per square-attacked
+// There's no thread local storage without std, so there's no cache either
+#[cfg(not(feature = "std"))]
+pub fn eval_pawn_structures_cached(board: &Board) -> [Score; 2] {
+    [eval_pawn_structure(board, 0), eval_pawn_structure(board, 1)]
+}
+
+// Rewards pieces for the amount of safe squares they can move to
+// Squares with our own pieces or attacked by enemy pawns aren't counted
+// See: https://www.chessprogramming.org/Mobility
+pub fn eval_mobility(board: &Board, team_idx: usize) -> Score {
+    let safe_squares = !board.occupancy[team_idx] & !get_pawn_attack_mask(board, 1 - team_idx);
+
+    let mut score = Score::ZERO;
+    for piece_idx in PIECE_KNIGHT..PIECE_KING {
+        let weights = Score::from_weights(eval_params::get().mobility_weights[piece_idx]);
+        for from in bm_iter_bits(board.pieces[team_idx][piece_idx]) {
+            let attacks = move_gen::generate_attacks(board, team_idx, piece_idx, from);
+            let mobility = (attacks & safe_squares).count_ones() as Weight;
+            score += weights * (mobility - eval_lookup::MOBILITY_AVERAGES[piece_idx]);
+        }
+    }
+
+    score
 }
 
-pub fn eval_king_safety(board: &Board, team_idx: usize, opp_attack_power: Value) -> Value {
+pub fn eval_king_safety(board: &Board, team_idx: usize) -> Score {
+    // King safety only matters if the opponent can actually attack
+    let opp_attack_power = calc_attacking_power(board, 1 - team_idx);
     if opp_attack_power <= 0.0 {
-        return 0.0;
+        return Score::ZERO;
     }
 
     let king = board.pieces[team_idx][PIECE_KING];
     let king_pos_idx = bm_to_idx(king);
-    let (_king_x, king_y) = bm_to_xy(king);
+    let (king_x, king_y) = bm_to_xy(king);
 
     let top_rank_y: i64 = [7, 0][team_idx];
     let up_dir: i64 = [1, -1][team_idx];
@@ -190,29 +673,191 @@ pub fn eval_king_safety(board: &Board, team_idx: usize, opp_attack_power: Value)
         lookup_gen::get_piece_tos(PIECE_QUEEN, king, king_pos_idx, board.occupancy[team_idx])
             .count_ones();
 
-    dual_weight(eval_lookup::KING_PAWN_COVER, opp_attack_power) * (covering_pawns as Value)
-        + dual_weight(eval_lookup::KING_ACCESSIBILITY, opp_attack_power) * (accessibility as Value)
+    // Open and semi-open columns around the king give enemy rooks and queens a way in
+    let all_pawns = pawns | board.pieces[1 - team_idx][PIECE_PAWN];
+    let mut num_open_columns = 0;
+    let mut num_semi_open_columns = 0;
+    for x in i64::max(king_x - 1, 0)..=i64::min(king_x + 1, 7) {
+        let column_mask = bm_make_column(x);
+        if (column_mask & all_pawns) == 0 {
+            num_open_columns += 1;
+        } else if (column_mask & pawns) == 0 {
+            num_semi_open_columns += 1;
+        }
+    }
+
+    let shelter_score = Score::from_weights(eval_params::get().king_pawn_cover)
+        * (covering_pawns as Weight)
+        + Score::from_weights(eval_params::get().king_accessibility) * (accessibility as Weight)
+        + Score::from_weights(eval_params::get().king_open_column) * (num_open_columns as Weight)
+        + Score::from_weights(eval_params::get().king_semi_open_column)
+            * (num_semi_open_columns as Weight);
+
+    (shelter_score * opp_attack_power) + eval_king_attackers(board, team_idx)
 }
 
-fn eval_team(board: &Board, team_idx: usize) -> Value {
-    let opp_attack_power = calc_attacking_power(board, 1 - team_idx);
+// Counts the enemy pieces attacking the squares around our king, weighted by how dangerous they are
+// A single attacker is rarely a threat, but the danger grows quickly as more pieces join in
+// See: https://www.chessprogramming.org/King_Safety#Attacking_King_Zone
+pub fn eval_king_attackers(board: &Board, team_idx: usize) -> Score {
+    let king_zone =
+        lookup_gen::get_king_zone_mask(team_idx, bm_to_idx(board.pieces[team_idx][PIECE_KING]));
+
+    let mut num_attackers = 0;
+    let mut attack_units = 0;
+    for piece_idx in PIECE_KNIGHT..PIECE_KING {
+        for from in bm_iter_bits(board.pieces[1 - team_idx][piece_idx]) {
+            let attacks = move_gen::generate_attacks(board, 1 - team_idx, piece_idx, from);
+            let num_zone_attacks = (attacks & king_zone).count_ones();
+            if num_zone_attacks > 0 {
+                num_attackers += 1;
+                attack_units += num_zone_attacks * eval_lookup::KING_ATTACK_UNITS[piece_idx];
+            }
+        }
+    }
+
+    if num_attackers < 2 {
+        return Score::ZERO;
+    }
+
+    let capped_attack_units = u32::min(attack_units, eval_lookup::KING_ATTACK_UNITS_MAX) as Weight;
+    Score::from_weights(eval_params::get().king_attack_danger)
+        * (capped_attack_units * capped_attack_units)
+}
+
+// Squares attacked by each kind of piece of a team
+// Board::attacks isn't used since it's only kept up to date for the team that just moved
+fn get_attack_maps(board: &Board, team_idx: usize) -> [BitMask; NUM_PIECES] {
+    let mut attack_maps = [0; NUM_PIECES];
+    for (piece_idx, attack_map) in attack_maps.iter_mut().enumerate() {
+        for from in bm_iter_bits(board.pieces[team_idx][piece_idx]) {
+            *attack_map |= move_gen::generate_attacks(board, team_idx, piece_idx, from);
+        }
+    }
+
+    attack_maps
+}
+
+// Rewards attacks on enemy pieces that are worth more than the attacker, or aren't defended at all
+// See: https://www.chessprogramming.org/Evaluation_of_Pieces#Threats
+pub fn eval_threats(board: &Board, team_idx: usize) -> Score {
+    let opp_pieces = &board.pieces[1 - team_idx];
+    let opp_non_pawns =
+        board.occupancy[1 - team_idx] & !opp_pieces[PIECE_PAWN] & !opp_pieces[PIECE_KING];
+
+    let attack_maps = get_attack_maps(board, team_idx);
+    let all_attacks = attack_maps.iter().fold(0, |acc, attacks| acc | attacks);
+    let minor_attacks = attack_maps[PIECE_KNIGHT] | attack_maps[PIECE_BISHOP];
+    let opp_defended = get_attack_maps(board, 1 - team_idx)
+        .iter()
+        .fold(0, |acc, attacks| acc | attacks);
+
+    let mut score = Score::from_weights(eval_params::get().threat_by_pawn)
+        * ((attack_maps[PIECE_PAWN] & opp_non_pawns).count_ones() as Weight);
+    for piece_idx in [PIECE_ROOK, PIECE_QUEEN] {
+        let minor_threats = (opp_pieces[piece_idx] & minor_attacks).count_ones() as Weight;
+        score += Score::from_weights(eval_params::get().threat_by_minor[piece_idx]) * minor_threats;
+
+        let rook_threats = (opp_pieces[piece_idx] & attack_maps[PIECE_ROOK]).count_ones() as Weight;
+        score += Score::from_weights(eval_params::get().threat_by_rook[piece_idx]) * rook_threats;
+    }
+
+    let hanging = opp_non_pawns & all_attacks & !opp_defended;
+    score
+        + Score::from_weights(eval_params::get().hanging_pieces) * (hanging.count_ones() as Weight)
+}
+
+// Corners where a bishop can deliver mate, for each square color
+const LIGHT_CORNERS: [usize; 2] = [7, 56]; // h1, a8
+const DARK_CORNERS: [usize; 2] = [0, 63]; // a1, h8
+
+// Helps finish off a lone king, by driving it to the edge and bringing our king closer
+// With only a bishop and knight, mate is only possible in the corners of the bishop's color
+// See: https://www.chessprogramming.org/Mop-up_Evaluation
+pub fn eval_mop_up(board: &Board, team_idx: usize) -> Score {
+    let opp_king = board.pieces[1 - team_idx][PIECE_KING];
+    if board.occupancy[1 - team_idx] != opp_king || !is_checkmate_possible(board, team_idx) {
+        return Score::ZERO;
+    }
+
+    let king_idx = bm_to_idx(board.pieces[team_idx][PIECE_KING]);
+    let opp_king_idx = bm_to_idx(opp_king);
 
-    let mut value: Value = eval_material(board, team_idx, opp_attack_power);
+    let pieces = &board.pieces[team_idx];
+    let is_bishop_and_knight = board.occupancy[team_idx]
+        == (pieces[PIECE_KING] | pieces[PIECE_BISHOP] | pieces[PIECE_KNIGHT])
+        && pieces[PIECE_BISHOP].count_ones() == 1
+        && pieces[PIECE_KNIGHT].count_ones() == 1;
+    let edge_score = if is_bishop_and_knight {
+        let corners = if (pieces[PIECE_BISHOP] & LIGHT_SQUARES) != 0 {
+            LIGHT_CORNERS
+        } else {
+            DARK_CORNERS
+        };
+        let corner_distance = corners
+            .iter()
+            .map(|&corner_idx| lookup_gen::get_distance(opp_king_idx, corner_idx))
+            .min()
+            .unwrap();
+        (7 - corner_distance) as Weight
+    } else {
+        lookup_gen::get_center_distance(opp_king_idx) as Weight
+    };
+    let king_closeness = (7 - lookup_gen::get_distance(king_idx, opp_king_idx)) as Weight;
+
+    Score::from_weights(eval_params::get().mop_up_edge) * edge_score
+        + Score::from_weights(eval_params::get().mop_up_king_closeness) * king_closeness
+}
+
+// Bonus for the side to move, since having the next move is usually worth something
+// Not given in pawn endgames, where zugzwang often makes the move a burden instead
+// See: https://www.chessprogramming.org/Tempo
+pub fn eval_tempo(board: &Board, team_idx: usize) -> Score {
+    if board.turn_idx != team_idx {
+        return Score::ZERO;
+    }
+
+    let pawns_and_kings = board.pieces[0][PIECE_PAWN]
+        | board.pieces[1][PIECE_PAWN]
+        | board.pieces[0][PIECE_KING]
+        | board.pieces[1][PIECE_KING];
+    if board.combined_occupancy() == pawns_and_kings {
+        return Score::ZERO;
+    }
+
+    Score::from_weights(eval_params::get().turn_bonus)
+}
+
+// The cheap part of the eval, mostly kept up to date incrementally by the board
+fn eval_team_lazy(board: &Board, team_idx: usize) -> Score {
+    (board.material_scores[team_idx] + board.piece_square_scores[team_idx]).to_score()
+        + eval_imbalance(board, team_idx)
+        + eval_tempo(board, team_idx)
+}
+
+// The expensive part of the eval, which can be skipped if the lazy part is far enough outside the window
+fn eval_team_full(board: &Board, team_idx: usize) -> Score {
+    let mut score = Score::ZERO;
     for piece_idx in 0..NUM_PIECES {
-        value += eval_piece_type(
+        score += eval_piece_type(
             board,
             team_idx,
             piece_idx,
             board.pieces[team_idx][piece_idx],
-            opp_attack_power,
         );
     }
 
-    if board.turn_idx == team_idx {
-        value += dual_weight(eval_lookup::TURN_BONUS, opp_attack_power);
-    }
+    score
+        + eval_pawn_structures_cached(board)[team_idx]
+        + eval_passed_pawns(board, team_idx)
+        + eval_mobility(board, team_idx)
+        + eval_king_safety(board, team_idx)
+        + eval_threats(board, team_idx)
+        + eval_mop_up(board, team_idx)
+}
 
-    value + eval_mobility(board, team_idx) + eval_king_safety(board, team_idx, opp_attack_power)
+fn eval_team(board: &Board, team_idx: usize) -> Score {
+    eval_team_lazy(board, team_idx) + eval_team_full(board, team_idx)
 }
 
 // Returns true if the player can possibly checkmate the other
@@ -238,74 +883,222 @@ fn is_checkmate_possible(board: &Board, team_idx: usize) -> bool {
     }
 }
 
+// Neither team has enough material to checkmate
+pub fn is_insufficient_material(board: &Board) -> bool {
+    !is_checkmate_possible(board, 0) && !is_checkmate_possible(board, 1)
+}
+
+const KPK_WIN_BONUS: Weight = 5.0;
+
+// Endgame scale factors, see get_endgame_scale()
+const SCALE_OPPOSITE_BISHOPS: Weight = 0.5;
+const SCALE_ROOK_ENDING_PAWN_UP: Weight = 0.75;
+const SCALE_NO_PAWNS_MINOR_UP: Weight = 0.1;
+
+fn get_non_pawn_material(board: &Board, team_idx: usize) -> Weight {
+    let mut material = 0.0;
+    for piece_idx in PIECE_KNIGHT..PIECE_KING {
+        material += eval_params::get().piece_base_value[piece_idx][1]
+            * (board.pieces[team_idx][piece_idx].count_ones() as Weight);
+    }
+
+    material
+}
+
+// Classifies the material of the position, and returns how much the endgame score of the team that's ahead should be kept (from 0-1)
+// Some endings are very hard to win even with more material, so the eval is pulled towards a draw
+// See: https://www.chessprogramming.org/Draw_Evaluation
+pub fn get_endgame_scale(board: &Board, strong_team_idx: usize) -> Weight {
+    let weak_team_idx = 1 - strong_team_idx;
+    let strong_pieces = &board.pieces[strong_team_idx];
+    let weak_pieces = &board.pieces[weak_team_idx];
+
+    // Up at most a minor piece, but no pawns to make it count
+    let material_diff =
+        get_non_pawn_material(board, strong_team_idx) - get_non_pawn_material(board, weak_team_idx);
+    if strong_pieces[PIECE_PAWN] == 0
+        && material_diff <= eval_params::get().piece_base_value[PIECE_BISHOP][1]
+    {
+        return SCALE_NO_PAWNS_MINOR_UP;
+    }
+
+    let pawn_diff =
+        strong_pieces[PIECE_PAWN].count_ones() as i64 - weak_pieces[PIECE_PAWN].count_ones() as i64;
+    let has_only = |team_idx: usize, piece_idx: usize| {
+        let pieces = &board.pieces[team_idx];
+        let non_pawns = board.occupancy[team_idx] & !pieces[PIECE_PAWN] & !pieces[PIECE_KING];
+        non_pawns == pieces[piece_idx] && non_pawns.count_ones() == 1
+    };
+
+    if has_only(strong_team_idx, PIECE_BISHOP) && has_only(weak_team_idx, PIECE_BISHOP) {
+        let strong_bishop_light = (strong_pieces[PIECE_BISHOP] & LIGHT_SQUARES) != 0;
+        let weak_bishop_light = (weak_pieces[PIECE_BISHOP] & LIGHT_SQUARES) != 0;
+        if strong_bishop_light != weak_bishop_light {
+            return SCALE_OPPOSITE_BISHOPS;
+        }
+    }
+
+    if has_only(strong_team_idx, PIECE_ROOK)
+        && has_only(weak_team_idx, PIECE_ROOK)
+        && pawn_diff <= 1
+    {
+        return SCALE_ROOK_ENDING_PAWN_UP;
+    }
+
+    1.0
+}
+
 // Evaluates the position from the perspective of the current turn
 pub fn eval_board(board: &Board) -> Value {
-    let self_eval = eval_team(board, board.turn_idx);
-    let opp_eval = eval_team(board, 1 - board.turn_idx);
+    eval_board_in_window(board, -VALUE_INF, VALUE_INF).0
+}
 
-    if (self_eval + opp_eval) < 15.0 {
-        // Check for insufficient material draw
+// How far outside the window the lazy eval has to be to skip the rest of the eval
+// Not tuned yet
+const LAZY_EVAL_MARGIN: Value = 400;
 
-        let mut checkmate_possible: bool = false;
-        for team_idx in 0..2 {
-            if is_checkmate_possible(board, team_idx) {
-                checkmate_possible = true;
-                break;
-            }
-        }
+// Evaluates the position from the perspective of the current turn, returning the eval and whether it's exact
+// If the cheap part of the eval is already far outside of the window, the expensive part is skipped
+// See: https://www.chessprogramming.org/Lazy_Evaluation
+pub fn eval_board_in_window(
+    board: &Board,
+    lower_bound: Value,
+    upper_bound: Value,
+) -> (Value, bool) {
+    if is_insufficient_material(board) {
+        return (0, true);
+    }
+
+    if let Some(network) = nnue::get_network() {
+        return (nnue::evaluate(network, board), true);
+    }
+
+    let lazy_score =
+        eval_team_lazy(board, board.turn_idx) - eval_team_lazy(board, 1 - board.turn_idx);
+    let lazy_eval = finish_eval(board, lazy_score);
+    if lazy_eval - LAZY_EVAL_MARGIN >= upper_bound || lazy_eval + LAZY_EVAL_MARGIN <= lower_bound {
+        return (lazy_eval, false);
+    }
 
-        if !checkmate_possible {
-            return 0.0;
+    let score = lazy_score + eval_team_full(board, board.turn_idx)
+        - eval_team_full(board, 1 - board.turn_idx);
+    (finish_eval(board, score), true)
+}
+
+// Applies the endgame adjustments and blends the score by the game phase
+fn finish_eval(board: &Board, mut score: Score) -> Value {
+    // King and pawn vs king is solved, so we know exactly whether it's a win
+    if let Some(is_win) = kpk::probe(board) {
+        if !is_win {
+            return 0;
         }
+
+        let pawn_turn_dir = if board.pieces[board.turn_idx][PIECE_PAWN] != 0 {
+            1.0
+        } else {
+            -1.0
+        };
+        score.eg += KPK_WIN_BONUS * pawn_turn_dir;
     }
 
-    self_eval - opp_eval
+    // Only the endgame part is scaled, since these are all endgames anyway
+    let strong_team_idx = if score.eg >= 0.0 {
+        board.turn_idx
+    } else {
+        1 - board.turn_idx
+    };
+    score.eg *= get_endgame_scale(board, strong_team_idx);
+
+    weight_to_value(score.blend(calc_phase(board)))
 }
 
-pub fn print_eval(board: &Board) {
-    // Prints a Stockfish-inspired eval table
+// One term of the eval, scored for both teams
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EvalTraceTerm {
+    pub name: String,
+    pub scores: [Score; 2],
+}
 
-    let attack_power = [
-        calc_attacking_power(board, 0),
-        calc_attacking_power(board, 1),
-    ];
-    println!("{:<14}   {:<6}   {:<6}", "", "White", "Black");
+// A breakdown of how the eval of a position was reached
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EvalTrace {
+    pub phase: Weight,
+    pub terms: Vec<EvalTraceTerm>,
+    pub team_totals: [Score; 2], // Sum of the terms for each team
+    pub endgame_scale: Weight,   // Applied to the endgame score of the team that's ahead
+    pub eval: Value,             // From the perspective of the current turn, same as eval_board()
+    pub is_nnue: bool,           // If true, the eval comes from the network instead of the terms
+}
 
-    let team_vals = [eval_team(board, 0), eval_team(board, 1)];
-    let mut entries = [Vec::new(), Vec::new()];
-    for team_idx in 0..2 {
-        entries[team_idx].push((
-            "Material".to_string(),
-            eval_material(board, team_idx, attack_power[1 - team_idx]),
-        ));
-        for piece_idx in 0..NUM_PIECES {
-            let piece_type_eval = eval_piece_type(
+pub fn explain(board: &Board) -> EvalTrace {
+    let mut terms = Vec::new();
+    let mut add_term = |name: &str, eval_fn: &dyn Fn(usize) -> Score| {
+        terms.push(EvalTraceTerm {
+            name: name.to_string(),
+            scores: [eval_fn(0), eval_fn(1)],
+        });
+    };
+
+    add_term("Material", &|team_idx| {
+        board.material_scores[team_idx].to_score()
+    });
+    add_term("Imbalance", &|team_idx| eval_imbalance(board, team_idx));
+    add_term("Piece Squares", &|team_idx| {
+        board.piece_square_scores[team_idx].to_score()
+    });
+    for piece_idx in 0..NUM_PIECES {
+        add_term(&(PIECE_NAMES[piece_idx].to_string() + "s"), &|team_idx| {
+            eval_piece_type(
                 board,
                 team_idx,
                 piece_idx,
                 board.pieces[team_idx][piece_idx],
-                attack_power[1 - team_idx],
-            );
-            entries[team_idx].push((PIECE_NAMES[piece_idx].to_string() + "s", piece_type_eval));
-        }
-        entries[team_idx].push(("Mobility".to_string(), eval_mobility(board, team_idx)));
-        entries[team_idx].push((
-            "King Safety".to_string(),
-            eval_king_safety(board, team_idx, attack_power[1 - team_idx]),
-        ));
+            )
+        });
+    }
+    add_term("Pawn Structure", &|team_idx| {
+        eval_pawn_structure(board, team_idx)
+    });
+    add_term("Passed Pawns", &|team_idx| {
+        eval_passed_pawns(board, team_idx)
+    });
+    add_term("Mobility", &|team_idx| eval_mobility(board, team_idx));
+    add_term("King Safety", &|team_idx| eval_king_safety(board, team_idx));
+    add_term("Threats", &|team_idx| eval_threats(board, team_idx));
+    add_term("Tempo", &|team_idx| eval_tempo(board, team_idx));
+    add_term("Mop-up", &|team_idx| eval_mop_up(board, team_idx));
 
-        entries[team_idx].push(("TOTAL".to_string(), team_vals[team_idx]));
+    let team_totals = [eval_team(board, 0), eval_team(board, 1)];
+    let strong_team_idx = if (team_totals[0] - team_totals[1]).eg >= 0.0 {
+        0
+    } else {
+        1
+    };
+
+    EvalTrace {
+        phase: calc_phase(board),
+        terms,
+        team_totals,
+        endgame_scale: get_endgame_scale(board, strong_team_idx),
+        eval: eval_board(board),
+        is_nnue: nnue::get_network().is_some(),
     }
+}
 
-    assert_eq!(entries[0].len(), entries[1].len());
-    let num_entries = entries[0].len();
-    for i in 0..num_entries {
-        if i == (num_entries - 1) {
-            println!("{}", "-".to_string().repeat(33));
-        }
+#[cfg(feature = "std")]
+pub fn print_eval(board: &Board) {
+    // Prints a Stockfish-inspired eval table
+
+    let trace = explain(board);
+    let phase = trace.phase;
+    println!("Phase: {:.2}", phase);
+    println!("Endgame Scale: {:.2}", trace.endgame_scale);
+    println!("{:<14}   {:<6}   {:<6}", "", "White", "Black");
 
-        let name = &entries[0][i].0;
-        let vals = [entries[0][i].1, entries[1][i].1];
+    let print_row = |name: &str, scores: [Score; 2]| {
+        let vals = [scores[0].blend(phase), scores[1].blend(phase)];
         println!(
             "{:>14} | {:>+0width$.prec$} | {:>+0width$.prec$} | {:>+0width$.prec$}",
             name,
@@ -315,44 +1108,47 @@ pub fn print_eval(board: &Board) {
             width = 6,
             prec = 2
         );
+    };
+
+    for term in &trace.terms {
+        print_row(&term.name, term.scores);
     }
+    println!("{}", "-".to_string().repeat(33));
+    print_row("TOTAL", trace.team_totals);
+
+    // The handcrafted eval above isn't used while a network is loaded
+    println!(
+        "{} eval (side to move): {:+.2}",
+        if trace.is_nnue { "NNUE" } else { "Final" },
+        (trace.eval as Weight) / 100.0
+    );
 }
 
 // Evaluates a move
 pub fn eval_move(board: &Board, mv: &Move) -> Value {
-    const CAPTURE_BASE_BONUS: Value = 1.0;
-    const CHECK_BONUS: Value = 1.0;
-    const PIN_BONUS: Value = 0.0;
-    const TURN_BONUS: Value = 0.1;
+    const CAPTURE_BASE_BONUS: Value = 100;
+    const CHECK_BONUS: Value = 100;
+    const PIN_BONUS: Value = 0;
+    const TURN_BONUS: Value = 10;
 
-    let mut eval: Value = 0.0;
+    let mut eval: Value = 0;
 
     let to_idx = bm_to_idx(mv.to);
     let to_defended = (board.attacks[1 - board.turn_idx] & mv.to) != 0;
 
     if mv.has_flag(Move::FLAG_PROMOTION) {
         if mv.to_piece_idx == PIECE_QUEEN {
-            eval += 50.0; // Very important move to look at
+            eval += 5000; // Very important move to look at
         } else {
-            eval -= 10.0; // Very rarely do we want to promote to something other than a queen
+            eval -= 1000; // Very rarely do we want to promote to something other than a queen
         }
     }
 
     if mv.has_flag(Move::FLAG_CAPTURE) {
-        let mut capture_val: Value = CAPTURE_BASE_BONUS;
-
-        if mv.has_flag(Move::FLAG_EN_PASSANT) {
-            capture_val += eval_lookup::PIECE_BASE_VALUE[PIECE_PAWN][0];
-        } else {
-            for piece_idx in 0..NUM_PIECES_NO_KING {
-                if (board.pieces[1 - board.turn_idx][piece_idx] & mv.to) != 0 {
-                    capture_val += eval_lookup::PIECE_BASE_VALUE[piece_idx][0];
-                    break;
-                }
-            }
-        }
-
-        eval += capture_val;
+        // Static exchange evaluation already accounts for the attacker being recaptured
+        eval += CAPTURE_BASE_BONUS + see(board, mv);
+    } else if to_defended {
+        eval -= weight_to_value(eval_params::get().piece_base_value[mv.from_piece_idx][0]);
     }
 
     // Determine if the move is a check or pin
@@ -382,15 +1178,12 @@ pub fn eval_move(board: &Board, mv: &Move) -> Value {
         }
     }
 
-    if to_defended {
-        eval -= eval_lookup::PIECE_BASE_VALUE[mv.from_piece_idx][0];
-    }
-
     eval += TURN_BONUS;
 
     eval
 }
 
-pub fn to_centipawns(value: Value) -> i64 {
-    (value * 100.0).round() as i64
+// Returns true if the capture loses material after all recaptures are resolved
+pub fn is_losing_capture(board: &Board, mv: &Move) -> bool {
+    mv.has_flag(Move::FLAG_CAPTURE) && see(board, mv) < 0
 }
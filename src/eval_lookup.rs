@@ -481,7 +481,67 @@ pub const PASSED_PAWN_TB: [[f32; 2]; 64] = [
 pub const BLOCKING_PAWNS: [f32; 2] = [-0.070, -0.077];
 pub const DOUBLED_PAWNS: [f32; 2] = [-0.052, -0.137];
 pub const CONNECTED_PAWNS: [f32; 2] = [0.149, 0.101];
+pub const ISOLATED_PAWNS: [f32; 2] = [-0.100, -0.150]; // Not tuned yet
+pub const BACKWARD_PAWNS: [f32; 2] = [-0.080, -0.100]; // Not tuned yet
 pub const OPEN_ROOKS: [f32; 2] = [0.347, 0.047];
+pub const SEMI_OPEN_ROOKS: [f32; 2] = [0.150, 0.050]; // Not tuned yet
+pub const BISHOP_PAIR: [f32; 2] = [0.300, 0.500]; // Not tuned yet
+pub const KNIGHT_OUTPOST: [f32; 2] = [0.300, 0.150]; // Not tuned yet
 pub const KING_PAWN_COVER: [f32; 2] = [0.140, -0.064];
 pub const KING_ACCESSIBILITY: [f32; 2] = [-0.056, 0.013];
+
+// Not tuned yet
+pub const KING_OPEN_COLUMN: [f32; 2] = [-0.250, -0.050];
+pub const KING_SEMI_OPEN_COLUMN: [f32; 2] = [-0.120, -0.020];
+pub const KING_ATTACK_UNITS: [u32; 6] = [0, 2, 2, 3, 5, 0]; // Per attacked king zone square, indexed by piece
+pub const KING_ATTACK_UNITS_MAX: u32 = 40;
+pub const KING_ATTACK_DANGER: [f32; 2] = [-0.0020, -0.0005]; // Scales with the squared attack units
+
+// Not tuned yet
+// Mobility is scored relative to a typical amount of safe squares for each piece, so it's around zero on average
+pub const MOBILITY_WEIGHTS: [[f32; 2]; 6] = [
+    [0.0, 0.0],
+    [0.040, 0.030],
+    [0.045, 0.045],
+    [0.020, 0.045],
+    [0.010, 0.020],
+    [0.0, 0.0],
+];
+pub const MOBILITY_AVERAGES: [f32; 6] = [0.0, 4.0, 6.0, 6.0, 12.0, 0.0];
 pub const TURN_BONUS: [f32; 2] = [0.242, 0.176];
+
+// Not tuned yet
+pub const THREAT_BY_PAWN: [f32; 2] = [0.400, 0.300]; // Per attacked enemy piece (not pawns)
+pub const THREAT_BY_MINOR: [[f32; 2]; 6] = [
+    [0.0, 0.0],
+    [0.0, 0.0],
+    [0.0, 0.0],
+    [0.350, 0.300],
+    [0.400, 0.350],
+    [0.0, 0.0],
+];
+pub const THREAT_BY_ROOK: [[f32; 2]; 6] = [
+    [0.0, 0.0],
+    [0.0, 0.0],
+    [0.0, 0.0],
+    [0.0, 0.0],
+    [0.350, 0.300],
+    [0.0, 0.0],
+];
+pub const HANGING_PIECES: [f32; 2] = [0.250, 0.150];
+
+// Per own pawn above five, see eval::eval_imbalance()
+// Based on Larry Kaufman's "The Evaluation of Material Imbalances"
+pub const KNIGHT_PAWN_ADJUST: [f32; 2] = [0.0625, 0.0625];
+pub const ROOK_PAWN_ADJUST: [f32; 2] = [-0.125, -0.125];
+pub const BISHOP_PAIR_PAWN_ADJUST: [f32; 2] = [-0.025, -0.025]; // Not tuned yet
+
+// Not tuned yet
+pub const PASSED_PAWN_SUPPORTED: [f32; 2] = [0.100, 0.200];
+pub const PASSED_PAWN_BLOCKED: [f32; 2] = [-0.100, -0.250];
+pub const PASSED_PAWN_KING_DISTANCE: [f32; 2] = [0.0, 0.015]; // Per square of king distance to the stop square, scaled by the pawn's row
+pub const UNSTOPPABLE_PASSER: [f32; 2] = [0.0, 6.000];
+
+// Not tuned yet
+pub const MOP_UP_EDGE: [f32; 2] = [0.100, 0.100]; // Per step the enemy king is away from the center
+pub const MOP_UP_KING_CLOSENESS: [f32; 2] = [0.040, 0.040];
@@ -0,0 +1,246 @@
+use crate::eval::Weight;
+use crate::eval_lookup;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+// The weights used by the handcrafted eval, so they can be changed without recompiling (e.g. for tuning)
+// Defaults are the constants in eval_lookup.rs
+// Params files are TOML, with each weight as a (nested) array of numbers, like "bishop_pair = [0.3, 0.5]"
+// Weights missing from the file keep their default values
+
+// Anything made up of eval weights, so every weight can be visited in a fixed order
+pub trait Weights {
+    fn for_each_weight(&mut self, f: &mut dyn FnMut(&mut Weight));
+
+    // Formats the weights like a Rust array literal
+    fn to_source(&self) -> String;
+}
+
+impl Weights for Weight {
+    fn for_each_weight(&mut self, f: &mut dyn FnMut(&mut Weight)) {
+        f(self);
+    }
+
+    fn to_source(&self) -> String {
+        format!("{:?}", self) // Shortest form that reads back as the exact same value
+    }
+}
+
+impl<T: Weights, const N: usize> Weights for [T; N] {
+    fn for_each_weight(&mut self, f: &mut dyn FnMut(&mut Weight)) {
+        for weight in self.iter_mut() {
+            weight.for_each_weight(f);
+        }
+    }
+
+    fn to_source(&self) -> String {
+        let items: Vec<String> = self.iter().map(|weight| weight.to_source()).collect();
+        format!("[{}]", items.join(", "))
+    }
+}
+
+macro_rules! define_eval_params {
+    ($($name:ident: $type:ty = $default:expr,)*) => {
+        #[derive(Debug, Clone, PartialEq)]
+        pub struct EvalParams {
+            $(pub $name: $type,)*
+        }
+
+        impl EvalParams {
+            pub const DEFAULT: EvalParams = EvalParams {
+                $($name: $default,)*
+            };
+
+            pub fn for_each_weight(&mut self, f: &mut dyn FnMut(&mut Weight)) {
+                $(self.$name.for_each_weight(f);)*
+            }
+
+            // Visits every weight with a name made from the param and its flattened index, like "bishop_pair_1"
+            pub fn for_each_named_weight(&mut self, f: &mut dyn FnMut(&str, &mut Weight)) {
+                $({
+                    let mut idx = 0;
+                    self.$name.for_each_weight(&mut |weight| {
+                        f(&format!("{}_{}", stringify!($name), idx), weight);
+                        idx += 1;
+                    });
+                })*
+            }
+
+            // Formats the params as constants that can be pasted into eval_lookup.rs
+            pub fn to_source(&self) -> String {
+                let mut source = String::new();
+                $(source += &format!(
+                    "pub const {}: {} = {};\n",
+                    stringify!($name).to_uppercase(),
+                    stringify!($type).replace("Weight", "f32"),
+                    self.$name.to_source()
+                );)*
+                source
+            }
+
+            pub fn to_toml(&self) -> String {
+                let mut toml = String::new();
+                $(toml += &format!("{} = {}\n", stringify!($name), self.$name.to_source());)*
+                toml
+            }
+
+            // Sets a single weight from its flattened numbers
+            fn set_weight(&mut self, name: &str, values: &[Weight]) -> Result<(), String> {
+                let weights: &mut dyn Weights = match name {
+                    $(stringify!($name) => &mut self.$name,)*
+                    _ => return Err(format!("unknown weight \"{}\"", name)),
+                };
+
+                let mut num_weights = 0;
+                weights.for_each_weight(&mut |_| num_weights += 1);
+                if values.len() != num_weights {
+                    return Err(format!(
+                        "\"{}\" should have {} values, got {}",
+                        name,
+                        num_weights,
+                        values.len()
+                    ));
+                }
+
+                let mut values_iter = values.iter();
+                weights.for_each_weight(&mut |weight| *weight = *values_iter.next().unwrap());
+                Ok(())
+            }
+        }
+    };
+}
+
+define_eval_params! {
+    piece_base_value: [[Weight; 2]; 6] = eval_lookup::PIECE_BASE_VALUE,
+    piece_tb: [[[Weight; 2]; 64]; 6] = eval_lookup::PIECE_TB,
+    passed_pawn_tb: [[Weight; 2]; 64] = eval_lookup::PASSED_PAWN_TB,
+    blocking_pawns: [Weight; 2] = eval_lookup::BLOCKING_PAWNS,
+    doubled_pawns: [Weight; 2] = eval_lookup::DOUBLED_PAWNS,
+    connected_pawns: [Weight; 2] = eval_lookup::CONNECTED_PAWNS,
+    isolated_pawns: [Weight; 2] = eval_lookup::ISOLATED_PAWNS,
+    backward_pawns: [Weight; 2] = eval_lookup::BACKWARD_PAWNS,
+    open_rooks: [Weight; 2] = eval_lookup::OPEN_ROOKS,
+    semi_open_rooks: [Weight; 2] = eval_lookup::SEMI_OPEN_ROOKS,
+    bishop_pair: [Weight; 2] = eval_lookup::BISHOP_PAIR,
+    knight_outpost: [Weight; 2] = eval_lookup::KNIGHT_OUTPOST,
+    king_pawn_cover: [Weight; 2] = eval_lookup::KING_PAWN_COVER,
+    king_accessibility: [Weight; 2] = eval_lookup::KING_ACCESSIBILITY,
+    king_open_column: [Weight; 2] = eval_lookup::KING_OPEN_COLUMN,
+    king_semi_open_column: [Weight; 2] = eval_lookup::KING_SEMI_OPEN_COLUMN,
+    king_attack_danger: [Weight; 2] = eval_lookup::KING_ATTACK_DANGER,
+    mobility_weights: [[Weight; 2]; 6] = eval_lookup::MOBILITY_WEIGHTS,
+    turn_bonus: [Weight; 2] = eval_lookup::TURN_BONUS,
+    threat_by_pawn: [Weight; 2] = eval_lookup::THREAT_BY_PAWN,
+    threat_by_minor: [[Weight; 2]; 6] = eval_lookup::THREAT_BY_MINOR,
+    threat_by_rook: [[Weight; 2]; 6] = eval_lookup::THREAT_BY_ROOK,
+    hanging_pieces: [Weight; 2] = eval_lookup::HANGING_PIECES,
+    knight_pawn_adjust: [Weight; 2] = eval_lookup::KNIGHT_PAWN_ADJUST,
+    rook_pawn_adjust: [Weight; 2] = eval_lookup::ROOK_PAWN_ADJUST,
+    bishop_pair_pawn_adjust: [Weight; 2] = eval_lookup::BISHOP_PAIR_PAWN_ADJUST,
+    passed_pawn_supported: [Weight; 2] = eval_lookup::PASSED_PAWN_SUPPORTED,
+    passed_pawn_blocked: [Weight; 2] = eval_lookup::PASSED_PAWN_BLOCKED,
+    passed_pawn_king_distance: [Weight; 2] = eval_lookup::PASSED_PAWN_KING_DISTANCE,
+    unstoppable_passer: [Weight; 2] = eval_lookup::UNSTOPPABLE_PASSER,
+    mop_up_edge: [Weight; 2] = eval_lookup::MOP_UP_EDGE,
+    mop_up_king_closeness: [Weight; 2] = eval_lookup::MOP_UP_KING_CLOSENESS,
+}
+
+impl Default for EvalParams {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+impl EvalParams {
+    // Only supports the subset of TOML that to_toml() writes: comments, and keys set to numbers or arrays of numbers
+    pub fn from_toml(toml: &str) -> Result<EvalParams, String> {
+        let mut params = EvalParams::DEFAULT;
+
+        let without_comments: String = toml
+            .lines()
+            .map(|line| line.split('#').next().unwrap())
+            .collect::<Vec<&str>>()
+            .join("\n");
+
+        let mut rest = without_comments.trim_start();
+        while !rest.is_empty() {
+            let Some((name, after_name)) = rest.split_once('=') else {
+                return Err(format!("expected \"=\" after \"{}\"", rest.trim()));
+            };
+            let name = name.trim();
+            let after_name = after_name.trim_start();
+
+            // Arrays can span multiple lines, so read until the brackets are closed
+            let mut depth = 0;
+            let mut value_len = after_name.len();
+            for (i, ch) in after_name.char_indices() {
+                match ch {
+                    '[' => depth += 1,
+                    ']' => depth -= 1,
+                    '\n' if depth == 0 => {
+                        value_len = i;
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+            if depth != 0 {
+                return Err(format!("unclosed brackets in \"{}\"", name));
+            }
+
+            let value_str = &after_name[..value_len];
+            let mut values = Vec::new();
+            for value in value_str
+                .split(|ch: char| ch == '[' || ch == ']' || ch == ',' || ch.is_whitespace())
+                .filter(|value| !value.is_empty())
+            {
+                values.push(
+                    value
+                        .parse::<Weight>()
+                        .map_err(|_| format!("invalid number \"{}\" in \"{}\"", value, name))?,
+                );
+            }
+
+            params.set_weight(name, &values)?;
+            rest = after_name[value_len..].trim_start();
+        }
+
+        Ok(params)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn load(path: &str) -> std::io::Result<EvalParams> {
+        let toml = std::fs::read_to_string(path)?;
+        EvalParams::from_toml(&toml)
+            .map_err(|msg| std::io::Error::new(std::io::ErrorKind::InvalidData, msg))
+    }
+
+    #[cfg(feature = "std")]
+    pub fn save(&self, path: &str) -> std::io::Result<()> {
+        std::fs::write(path, self.to_toml())
+    }
+}
+
+static mut EVAL_PARAMS: EvalParams = EvalParams::DEFAULT;
+
+// Increased every time the params change, so anything cached from the old params can be thrown out
+static PARAMS_VERSION: AtomicU32 = AtomicU32::new(0);
+
+pub fn get() -> &'static EvalParams {
+    unsafe { &*core::ptr::addr_of!(EVAL_PARAMS) }
+}
+
+pub fn get_version() -> u32 {
+    PARAMS_VERSION.load(Ordering::Acquire)
+}
+
+// NOTE: Must not be called during a search
+// Boards made before this still have piece-square and material scores from the old params, see Board::full_update()
+pub fn set(params: EvalParams) {
+    unsafe {
+        EVAL_PARAMS = params;
+    }
+    PARAMS_VERSION.fetch_add(1, Ordering::AcqRel);
+}
@@ -1,13 +1,17 @@
-use crate::bitmask::{bm_from_coord, bm_from_xy, bm_get, bm_to_coord};
+use crate::bitmask::{bm_from_coord, bm_from_xy, bm_get, bm_to_coord, bm_to_xy};
 use crate::board::*;
+use crate::eval::Value;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
 
-type Result<T> = std::result::Result<T, FenError>;
+type Result<T> = core::result::Result<T, FenError>;
 
 #[derive(Debug, Clone)]
 pub struct FenError(String);
 
-impl std::fmt::Display for FenError {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+impl core::fmt::Display for FenError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         write!(f, "FenError: {}", self.0)
     }
 }
@@ -113,6 +117,9 @@ pub fn load_fen_from_parts(fen_parts: &Vec<String>) -> Result<Board> {
     }
 
     // Read castle rights
+    // Chess960 positions can use X-FEN, where "K" and "Q" mean the outermost rook on that side,
+    //  or Shredder-FEN, where the rook's column is given instead (e.g. "HAha")
+    // See: https://www.chessprogramming.org/Forsyth-Edwards_Notation#Shredder-FEN
     if fen_parts.len() >= 3 {
         let castle_str = &fen_parts[2];
         if castle_str == "-" {
@@ -124,16 +131,45 @@ pub fn load_fen_from_parts(fen_parts: &Vec<String>) -> Result<Board> {
 
             for ch in castle_str.chars() {
                 let team_idx = if ch.is_ascii_uppercase() { 0 } else { 1 };
-                if ch.eq_ignore_ascii_case(&'K') {
-                    board.castle_rights[team_idx][1] = true;
-                } else if ch.eq_ignore_ascii_case(&'Q') {
-                    board.castle_rights[team_idx][0] = true;
-                } else {
+                let back_row = if team_idx == 0 { 0 } else { 7 };
+                let (king_x, king_y) = bm_to_xy(board.pieces[team_idx][PIECE_KING]);
+                if king_y != back_row {
                     throw_err(
-                        format!("invalid castle string \"{castle_str}\", bad char \'{ch}\'")
-                            .as_str(),
+                        format!(
+                            "invalid castle string \"{castle_str}\", king isn't on its back row"
+                        )
+                        .as_str(),
                     )?;
                 }
+
+                let is_rook_at = |x: i64| bm_get(board.pieces[team_idx][PIECE_ROOK], x, back_row);
+                let rook_x = match ch.to_ascii_uppercase() {
+                    'K' => ((king_x + 1)..8).rev().find(|&x| is_rook_at(x)),
+                    'Q' => (0..king_x).find(|&x| is_rook_at(x)),
+                    column_ch @ 'A'..='H' => {
+                        Some((column_ch as u8 - b'A') as i64).filter(|&x| is_rook_at(x))
+                    }
+                    _ => {
+                        return throw_err(
+                            format!("invalid castle string \"{castle_str}\", bad char \'{ch}\'")
+                                .as_str(),
+                        );
+                    }
+                };
+
+                let Some(rook_x) = rook_x else {
+                    return throw_err(
+                        format!("invalid castle string \"{castle_str}\", no rook for \'{ch}\'")
+                            .as_str(),
+                    );
+                };
+
+                let side = (rook_x > king_x) as usize;
+                board.castle_rights[team_idx][side] = true;
+                board.castle_rook_masks[team_idx][side] = bm_from_xy(rook_x, back_row);
+                if king_x != 4 || rook_x != [0, 7][side] {
+                    board.is_chess960 = true;
+                }
             }
         }
     }
@@ -170,7 +206,16 @@ pub fn load_fen_from_parts(fen_parts: &Vec<String>) -> Result<Board> {
         }
     }
 
-    // We don't need the move number
+    // Read full-move counter
+    if fen_parts.len() >= 6 {
+        let full_move_counter = &fen_parts[5];
+        match full_move_counter.parse::<u16>() {
+            Ok(x) if x >= 1 => board.full_move_counter = x,
+            _ => {
+                throw_err(format!("invalid full-move counter \"{full_move_counter}\"").as_str())?;
+            }
+        }
+    }
 
     // Full update again
     board.full_update();
@@ -189,7 +234,7 @@ pub fn load_fen(fen: &str) -> Result<Board> {
 pub fn make_fen(board: &Board) -> String {
     // TODO: This code is pretty messy and generally lame
 
-    use std::fmt::Write;
+    use core::fmt::Write;
     let mut position_stream: String = String::new();
 
     // Write position
@@ -242,11 +287,28 @@ pub fn make_fen(board: &Board) -> String {
     }
 
     // Write castle rights
+    // Uses X-FEN for Chess960, so the rook's column is only given when it isn't the outermost rook on its side
     let mut castle_rights_stream: String = String::new();
     for team_idx in 0..2 {
         for side in (0..2).rev() {
             if board.castle_rights[team_idx][side] {
-                let side_char = if side == 0 { 'Q' } else { 'K' };
+                let back_row = if team_idx == 0 { 0 } else { 7 };
+                let rook_x = bm_to_xy(board.castle_rook_masks[team_idx][side]).0;
+                let mut outer_columns = if side == 0 {
+                    0..rook_x
+                } else {
+                    (rook_x + 1)..8
+                };
+                let is_outermost =
+                    !outer_columns.any(|x| bm_get(board.pieces[team_idx][PIECE_ROOK], x, back_row));
+
+                let side_char = if !is_outermost {
+                    (b'A' + rook_x as u8) as char
+                } else if side == 0 {
+                    'Q'
+                } else {
+                    'K'
+                };
                 write!(
                     castle_rights_stream,
                     "{}",
@@ -279,10 +341,177 @@ pub fn make_fen(board: &Board) -> String {
         }
     }
 
-    write!(result, " {} {}", board.half_move_counter, 1).unwrap(); // Write half move and normal move counter
-                                                                   // TODO: Add actual move counter
+    write!(
+        result,
+        " {} {}",
+        board.half_move_counter, board.full_move_counter
+    )
+    .unwrap(); // Write half move and full move counter
 
     result
 }
 
 pub const FEN_START_POS: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+// A position from an EPD file, with its opcodes (like "bm Nf3;" or "id \"WAC.001\";")
+// See: https://www.chessprogramming.org/Extended_Position_Description
+#[derive(Debug, Clone)]
+pub struct EpdRecord {
+    pub board: Board,
+    pub opcodes: Vec<(String, Vec<String>)>, // Opcode names and their operands, in order
+}
+
+impl EpdRecord {
+    pub fn get_opcode(&self, name: &str) -> Option<&[String]> {
+        self.opcodes
+            .iter()
+            .find(|(opcode_name, _)| opcode_name == name)
+            .map(|(_, operands)| operands.as_slice())
+    }
+
+    pub fn get_id(&self) -> Option<&str> {
+        self.get_opcode("id")?.first().map(|id| id.as_str())
+    }
+
+    // Centipawn evaluation from the side to move's perspective
+    pub fn get_centipawn_eval(&self) -> Option<Value> {
+        self.get_opcode("ce")?.first()?.parse().ok()
+    }
+
+    // Parses the SAN moves of an opcode like "bm" (best moves) or "am" (avoid moves)
+    // Returns None if the opcode is missing, or if any of its moves aren't legal here
+    pub fn get_moves(&self, name: &str) -> Option<Vec<Move>> {
+        self.get_opcode(name)?
+            .iter()
+            .map(|san| self.board.parse_san(san))
+            .collect()
+    }
+
+    // Parses the "pv" opcode, where each move is played after the last
+    pub fn get_pv(&self) -> Option<Vec<Move>> {
+        let mut board = self.board;
+        let mut pv = Vec::new();
+        for san in self.get_opcode("pv")? {
+            let mv = board.parse_san(san)?;
+            board.do_move(&mv);
+            pv.push(mv);
+        }
+        Some(pv)
+    }
+}
+
+// Splits the opcodes after the position, where operands in quotes can contain spaces and semicolons
+fn parse_epd_opcodes(opcodes_str: &str) -> Result<Vec<(String, Vec<String>)>> {
+    let mut opcodes = Vec::new();
+    let mut tokens: Vec<String> = Vec::new();
+    let mut chars = opcodes_str.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            ';' => {
+                if !tokens.is_empty() {
+                    let name = tokens.remove(0);
+                    opcodes.push((name, core::mem::take(&mut tokens)));
+                }
+            }
+            '"' => {
+                let mut token = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(ch) => token.push(ch),
+                        None => {
+                            return Err(FenError(format!(
+                                "Invalid epd opcodes: \"{}\", unclosed string",
+                                opcodes_str
+                            )))
+                        }
+                    }
+                }
+                tokens.push(token);
+            }
+            _ if ch.is_whitespace() => {}
+            _ => {
+                let mut token = ch.to_string();
+                while let Some(&next_ch) = chars.peek() {
+                    if next_ch.is_whitespace() || next_ch == ';' || next_ch == '"' {
+                        break;
+                    }
+                    token.push(next_ch);
+                    chars.next();
+                }
+                tokens.push(token);
+            }
+        }
+    }
+
+    // The last opcode should end with a semicolon, but it's often left out
+    if !tokens.is_empty() {
+        let name = tokens.remove(0);
+        opcodes.push((name, tokens));
+    }
+
+    Ok(opcodes)
+}
+
+// EPD records are the first 4 FEN fields followed by opcodes
+// Some files also have the FEN move counters before the opcodes, so those are read too
+// The "hmvc" and "fmvn" opcodes also set the counters
+pub fn load_epd(epd: &str) -> Result<EpdRecord> {
+    let mut rest = epd.trim();
+    let mut fen_parts = Vec::new();
+    while fen_parts.len() < 6 {
+        let (part, after_part) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        let is_counter = !part.is_empty() && part.chars().all(|ch| ch.is_ascii_digit());
+        if part.is_empty() || (fen_parts.len() >= 4 && !is_counter) {
+            break;
+        }
+        fen_parts.push(part.to_string());
+        rest = after_part.trim_start();
+    }
+
+    if fen_parts.len() < 4 {
+        return Err(FenError(format!(
+            "Invalid epd: \"{}\", needs at least 4 position fields",
+            epd
+        )));
+    }
+
+    let opcodes = parse_epd_opcodes(rest)?;
+    for (name, idx) in [("hmvc", 4), ("fmvn", 5)] {
+        if let Some((_, operands)) = opcodes.iter().find(|(opcode_name, _)| opcode_name == name) {
+            fen_parts.resize(6, "0".to_string());
+            fen_parts[idx] = operands.first().cloned().unwrap_or_default();
+        }
+    }
+    if fen_parts.len() == 6 && fen_parts[5] == "0" {
+        fen_parts[5] = "1".to_string(); // hmvc without fmvn
+    }
+
+    let board = load_fen_from_parts(&fen_parts)?;
+    Ok(EpdRecord { board, opcodes })
+}
+
+// Parses every record in an EPD file, skipping empty lines
+#[cfg(feature = "std")]
+pub fn load_epd_file(path: &str) -> std::io::Result<Vec<EpdRecord>> {
+    let epd_file = std::fs::read_to_string(path)?;
+
+    let mut records = Vec::new();
+    for (line_idx, line) in epd_file.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        match load_epd(line) {
+            Ok(record) => records.push(record),
+            Err(err) => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("line {}: {}", line_idx + 1, err),
+                ))
+            }
+        }
+    }
+
+    Ok(records)
+}
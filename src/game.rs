@@ -0,0 +1,93 @@
+use crate::board::*;
+use crate::move_gen;
+use crate::search;
+use crate::search::GameResult;
+use crate::zobrist::Hash;
+
+// A game being played, which keeps the moves so they can be taken back and repetitions can be detected
+// See: https://www.chessprogramming.org/Repetitions
+#[derive(Debug, Clone)]
+pub struct Game {
+    start_board: Board,
+    board: Board,
+    moves: Vec<Move>,
+    undos: Vec<MoveUndo>, // One for each move
+    hashes: Vec<Hash>,    // Hash of the position before each move
+}
+
+impl Game {
+    pub fn new(start_board: Board) -> Game {
+        Game {
+            start_board,
+            board: start_board,
+            moves: Vec::new(),
+            undos: Vec::new(),
+            hashes: Vec::new(),
+        }
+    }
+
+    pub fn get_start_board(&self) -> &Board {
+        &self.start_board
+    }
+
+    // The current position
+    pub fn get_board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn get_moves(&self) -> &[Move] {
+        &self.moves
+    }
+
+    // Positions played before the current one, like search::search_iterative() takes
+    pub fn get_hashes(&self) -> &[Hash] {
+        &self.hashes
+    }
+
+    // Plays a move, which must be legal in the current position
+    pub fn push(&mut self, mv: Move) {
+        debug_assert!(self.board.is_pseudo_legal(&mv) && move_gen::is_legal(&self.board, &mv));
+
+        self.hashes.push(self.board.hash);
+        self.undos.push(self.board.make_move(&mv));
+        self.moves.push(mv);
+    }
+
+    // Plays a move in UCI notation, see Board::parse_uci_move()
+    pub fn push_uci(&mut self, move_str: &str) -> Result<Move, MoveParseError> {
+        let mv = self.board.parse_uci_move(move_str)?;
+        self.push(mv);
+        Ok(mv)
+    }
+
+    // Takes back the last move, and returns it (or None if no moves were played)
+    pub fn undo(&mut self) -> Option<Move> {
+        let mv = self.moves.pop()?;
+        let undo = self.undos.pop().unwrap();
+        self.hashes.pop();
+        self.board.undo_move(&mv, &undo);
+        Some(mv)
+    }
+
+    // Checks if the current position occurred at least twice before
+    pub fn is_threefold_repetition(&self) -> bool {
+        // Positions from before the last capture or pawn move can't be repeated, and only the same side can be to move
+        let max_plies_back = (self.board.half_move_counter as usize).min(self.hashes.len());
+        let num_repetitions = (2..=max_plies_back)
+            .step_by(2)
+            .filter(|&plies_back| self.hashes[self.hashes.len() - plies_back] == self.board.hash)
+            .count();
+        num_repetitions >= 2
+    }
+
+    // How the game ended, or None if it's still going
+    pub fn result(&self) -> Option<GameResult> {
+        match search::get_game_result(&self.board) {
+            GameResult::InProgress if self.is_threefold_repetition() => {
+                Some(GameResult::Repetition)
+            }
+            GameResult::InProgress => None,
+            result => Some(result),
+        }
+    }
+}
@@ -0,0 +1,203 @@
+use crate::bitmask::*;
+use crate::board::*;
+use crate::lookup_gen;
+use alloc::vec::Vec;
+
+// King and pawn vs king bitbase, generated with retrograde analysis
+// Positions are normalized so the pawn is white's and on the a-d columns, then stored as one bit which is set if white wins
+// See: https://www.chessprogramming.org/KPK
+
+const NUM_PAWN_SQUARES: usize = 24; // Columns a-d, rows 2-7
+const NUM_POSITIONS: usize = NUM_PAWN_SQUARES * 2 * 64 * 64;
+
+static mut KPK_BITBASE: [u64; NUM_POSITIONS / 64] = [0; NUM_POSITIONS / 64];
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum KpkResult {
+    Invalid,
+    Unknown,
+    Draw,
+    Win,
+}
+
+fn get_index(turn_idx: usize, king_idx: usize, opp_king_idx: usize, pawn_idx: usize) -> usize {
+    let pawn_square = (pawn_idx / 8 - 1) * 4 + (pawn_idx % 8);
+    ((pawn_square * 2 + turn_idx) * 64 + king_idx) * 64 + opp_king_idx
+}
+
+// Returns (turn_idx, king_idx, opp_king_idx, pawn_idx)
+fn decode_index(idx: usize) -> (usize, usize, usize, usize) {
+    let opp_king_idx = idx % 64;
+    let king_idx = (idx / 64) % 64;
+    let turn_idx = (idx / (64 * 64)) % 2;
+    let pawn_square = idx / (64 * 64 * 2);
+    let pawn_idx = (pawn_square / 4 + 1) * 8 + (pawn_square % 4);
+    (turn_idx, king_idx, opp_king_idx, pawn_idx)
+}
+
+fn get_pawn_attacks(pawn_idx: usize) -> BitMask {
+    let pawn = bm_from_idx(pawn_idx);
+    bm_shift(pawn & !bm_make_column(7), 1, 1) | bm_shift(pawn & !bm_make_column(0), -1, 1)
+}
+
+// Classifies the positions that can be decided without looking at the moves after them
+fn classify_initial(idx: usize) -> KpkResult {
+    let (turn_idx, king_idx, opp_king_idx, pawn_idx) = decode_index(idx);
+    let king_tos = lookup_gen::get_piece_base_tos(PIECE_KING, king_idx);
+    let opp_king_tos = lookup_gen::get_piece_base_tos(PIECE_KING, opp_king_idx);
+    let pawn_attacks = get_pawn_attacks(pawn_idx);
+
+    if king_idx == opp_king_idx
+        || lookup_gen::get_distance(king_idx, opp_king_idx) <= 1
+        || king_idx == pawn_idx
+        || opp_king_idx == pawn_idx
+    {
+        return KpkResult::Invalid;
+    }
+
+    if turn_idx == 0 {
+        if (pawn_attacks & bm_from_idx(opp_king_idx)) != 0 {
+            return KpkResult::Invalid; // Black would have been in check on white's turn
+        }
+
+        // Promoting on a square the enemy king can't take
+        let promotion_idx = pawn_idx + 8;
+        if pawn_idx / 8 == 6
+            && promotion_idx != king_idx
+            && promotion_idx != opp_king_idx
+            && (lookup_gen::get_distance(opp_king_idx, promotion_idx) > 1
+                || lookup_gen::get_distance(king_idx, promotion_idx) == 1)
+        {
+            return KpkResult::Win;
+        }
+    } else {
+        // Stalemate, or the pawn can be taken
+        let opp_king_moves = opp_king_tos & !king_tos & !pawn_attacks;
+        if opp_king_moves == 0 || (opp_king_moves & bm_from_idx(pawn_idx)) != 0 {
+            return KpkResult::Draw;
+        }
+    }
+
+    KpkResult::Unknown
+}
+
+// Classifies a position from the results of the positions after each move
+fn classify(results: &[KpkResult], idx: usize) -> KpkResult {
+    let (turn_idx, king_idx, opp_king_idx, pawn_idx) = decode_index(idx);
+    let king_tos = lookup_gen::get_piece_base_tos(PIECE_KING, king_idx);
+    let opp_king_tos = lookup_gen::get_piece_base_tos(PIECE_KING, opp_king_idx);
+    let pawn = bm_from_idx(pawn_idx);
+
+    let mut next_idxs = Vec::with_capacity(10);
+    if turn_idx == 0 {
+        for to in bm_iter_bits(king_tos & !opp_king_tos & !pawn) {
+            next_idxs.push(get_index(1, bm_to_idx(to), opp_king_idx, pawn_idx));
+        }
+
+        // Promotions are already handled by classify_initial()
+        let kings = bm_from_idx(king_idx) | bm_from_idx(opp_king_idx);
+        let push_idx = pawn_idx + 8;
+        if pawn_idx / 8 < 6 && (bm_from_idx(push_idx) & kings) == 0 {
+            next_idxs.push(get_index(1, king_idx, opp_king_idx, push_idx));
+
+            let double_push_idx = pawn_idx + 16;
+            if pawn_idx / 8 == 1 && (bm_from_idx(double_push_idx) & kings) == 0 {
+                next_idxs.push(get_index(1, king_idx, opp_king_idx, double_push_idx));
+            }
+        }
+    } else {
+        // Taking the pawn is already handled by classify_initial()
+        let opp_king_moves = opp_king_tos & !king_tos & !get_pawn_attacks(pawn_idx) & !pawn;
+        for to in bm_iter_bits(opp_king_moves) {
+            next_idxs.push(get_index(0, king_idx, bm_to_idx(to), pawn_idx));
+        }
+    }
+
+    // White wins if any move wins, black draws if any move draws
+    let (good_result, bad_result) = if turn_idx == 0 {
+        (KpkResult::Win, KpkResult::Draw)
+    } else {
+        (KpkResult::Draw, KpkResult::Win)
+    };
+
+    let mut all_bad = true;
+    for next_idx in next_idxs {
+        let next_result = results[next_idx];
+        if next_result == good_result {
+            return good_result;
+        } else if next_result != bad_result {
+            all_bad = false;
+        }
+    }
+
+    if all_bad {
+        bad_result
+    } else {
+        KpkResult::Unknown
+    }
+}
+
+pub fn init() {
+    #[cfg(feature = "std")]
+    println!("Generating KPK bitbase...");
+
+    let mut results: Vec<KpkResult> = (0..NUM_POSITIONS).map(classify_initial).collect();
+
+    // Keep going until nothing new can be decided
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for idx in 0..NUM_POSITIONS {
+            if results[idx] == KpkResult::Unknown {
+                let result = classify(&results, idx);
+                if result != KpkResult::Unknown {
+                    results[idx] = result;
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    // Anything left undecided can't be forced into a win
+    for (idx, result) in results.iter().enumerate() {
+        if *result == KpkResult::Win {
+            unsafe {
+                KPK_BITBASE[idx / 64] |= 1 << (idx % 64);
+            }
+        }
+    }
+}
+
+// Returns whether the team with the pawn wins, if the board only has two kings and a single pawn
+pub fn probe(board: &Board) -> Option<bool> {
+    if board.combined_occupancy().count_ones() != 3 {
+        return None;
+    }
+
+    let strong_team_idx = if board.pieces[0][PIECE_PAWN] != 0 {
+        0
+    } else if board.pieces[1][PIECE_PAWN] != 0 {
+        1
+    } else {
+        return None;
+    };
+
+    // Flip the board so that the pawn is white's and on the left half of the board
+    let mut flip = if strong_team_idx == 0 { 0 } else { 56 };
+    let mut pawn_idx = bm_to_idx(board.pieces[strong_team_idx][PIECE_PAWN]) ^ flip;
+    if pawn_idx % 8 >= 4 {
+        flip ^= 7;
+        pawn_idx ^= 7;
+    }
+
+    let king_idx = bm_to_idx(board.pieces[strong_team_idx][PIECE_KING]) ^ flip;
+    let opp_king_idx = bm_to_idx(board.pieces[1 - strong_team_idx][PIECE_KING]) ^ flip;
+    let turn_idx = if board.turn_idx == strong_team_idx {
+        0
+    } else {
+        1
+    };
+
+    let idx = get_index(turn_idx, king_idx, opp_king_idx, pawn_idx);
+    Some(unsafe { (KPK_BITBASE[idx / 64] >> (idx % 64)) & 1 != 0 })
+}
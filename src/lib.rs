@@ -1,28 +1,96 @@
+// Only the core library is built without the std feature, see Cargo.toml
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "std")]
 pub mod async_engine;
+#[cfg(feature = "std")]
+pub mod bench;
 pub mod bitmask;
 pub mod board;
+#[cfg(feature = "std")]
+pub mod book;
+#[cfg(feature = "std")]
+pub mod datagen;
+#[cfg(feature = "std")]
+pub mod debug_log;
+#[cfg(feature = "std")]
+pub mod engine;
+#[cfg(feature = "std")]
+pub mod epd_suite;
 pub mod eval;
 mod eval_lookup;
+pub mod eval_params;
 pub mod fen;
+#[cfg(feature = "std")]
+pub mod game;
+pub mod kpk;
 pub mod lookup_gen;
 pub mod lookup_gen_magic;
+#[cfg(feature = "std")]
+pub mod match_runner;
 pub mod move_gen;
+#[cfg(feature = "std")]
+pub mod move_picker;
+pub mod nnue;
+#[cfg(feature = "std")]
+pub mod perft;
+#[cfg(feature = "std")]
+pub mod pgn;
+#[cfg(feature = "std")]
 pub mod search;
+#[cfg(feature = "std")]
+pub mod search_params;
+#[cfg(feature = "std")]
 pub mod thread_flag;
+#[cfg(feature = "std")]
 pub mod time_manager;
+#[cfg(feature = "std")]
 pub mod transpos;
+#[cfg(feature = "std")]
+pub mod tune;
+#[cfg(feature = "std")]
 pub mod uci;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "std")]
+pub mod xboard;
 pub mod zobrist;
 
-static INIT_ONCE: std::sync::Once = std::sync::Once::new();
-
 fn _init() {
     lookup_gen::init();
     #[cfg(not(debug_assertions))]
     lookup_gen_magic::init();
     zobrist::init();
+    kpk::init();
 }
 
+#[cfg(feature = "std")]
 pub fn init() {
+    static INIT_ONCE: std::sync::Once = std::sync::Once::new();
     INIT_ONCE.call_once(_init);
 }
+
+// There's no Once without std, so anyone calling this while another caller is initializing waits for them
+#[cfg(not(feature = "std"))]
+pub fn init() {
+    use core::sync::atomic::{AtomicU8, Ordering};
+
+    const NOT_STARTED: u8 = 0;
+    const RUNNING: u8 = 1;
+    const DONE: u8 = 2;
+    static INIT_STATE: AtomicU8 = AtomicU8::new(NOT_STARTED);
+
+    if INIT_STATE
+        .compare_exchange(NOT_STARTED, RUNNING, Ordering::Acquire, Ordering::Acquire)
+        .is_ok()
+    {
+        _init();
+        INIT_STATE.store(DONE, Ordering::Release);
+    } else {
+        while INIT_STATE.load(Ordering::Acquire) != DONE {
+            core::hint::spin_loop();
+        }
+    }
+}
@@ -22,6 +22,19 @@ static mut LT_BETWEEN_EXCLUSIVE: [[BitMask; 64]; 64] = [[0; 64]; 64]; // The pat
 
 static mut LT_RAY: [[BitMask; 64]; 64] = [[0; 64]; 64]; // A full ray that starts at the first pos and continues until the edge of the board
 
+// Pawn structure masks
+static mut LT_ADJACENT_COLUMNS: [BitMask; 8] = [0; 8]; // The columns to the left and right of a column
+static mut LT_FRONT_SPAN: [[BitMask; 64]; 2] = [[0; 64]; 2]; // Every square in front of a pawn on its column, for each team
+static mut LT_PASSED_PAWN: [[BitMask; 64]; 2] = [[0; 64]; 2]; // Squares that must have no enemy pawns for a pawn to be passed, for each team
+static mut LT_ATTACK_SPAN: [[BitMask; 64]; 2] = [[0; 64]; 2]; // Squares a pawn could ever attack as it advances, for each team
+static mut LT_PAWN_SQUARE: [[BitMask; 64]; 2] = [[0; 64]; 2]; // Where the enemy king must be to catch a pawn on its own, if it's the enemy's turn, for each team
+
+static mut LT_DISTANCE: [[u8; 64]; 64] = [[0; 64]; 64]; // The amount of king moves between two points
+static mut LT_CENTER_DISTANCE: [u8; 64] = [0; 64]; // Manhattan distance to the closest of the four center squares
+
+// Squares around the king that attackers are counted on, extended a row towards the enemy, for each team
+static mut LT_KING_ZONE: [[BitMask; 64]; 2] = [[0; 64]; 2];
+
 fn is_inside_board(x: i64, y: i64) -> bool {
     (x >= 0) && (y >= 0) && (x < 8) && (y < 8)
 }
@@ -100,6 +113,65 @@ fn init_at_pos(x: i64, y: i64) {
         }
     }
 
+    // Make pawn structure masks
+    let adjacent_columns = bm_shift(bm_make_column(x) & !bm_make_column(7), 1, 0)
+        | bm_shift(bm_make_column(x) & !bm_make_column(0), -1, 0);
+    for team_idx in 0..2 {
+        let dy = if team_idx == 0 { 1 } else { -1 };
+        let front_span = make_ray(x, y + dy, 0, dy, 0);
+        let rows_in_front = front_span | bm_shift(front_span, 1, 0) | bm_shift(front_span, -1, 0);
+        let attack_span = adjacent_columns & rows_in_front;
+        unsafe {
+            LT_FRONT_SPAN[team_idx][idx] = front_span;
+            LT_ATTACK_SPAN[team_idx][idx] = attack_span;
+            LT_PASSED_PAWN[team_idx][idx] = front_span | attack_span;
+        }
+
+        // See: https://www.chessprogramming.org/Rule_of_the_Square
+        let promotion_y = if team_idx == 0 { 7 } else { 0 };
+        let start_y = if team_idx == 0 { 1 } else { 6 };
+        let mut moves_to_promote = (promotion_y - y).abs();
+        if y == start_y {
+            moves_to_promote -= 1; // Can skip a square with the double move
+        }
+        for sx in 0..8 {
+            for sy in 0..8 {
+                // The king can still take the new queen if it's right next to the promotion square
+                if i64::max((sx - x).abs(), (sy - promotion_y).abs()) <= moves_to_promote + 1 {
+                    unsafe {
+                        bm_set(&mut LT_PAWN_SQUARE[team_idx][idx], sx, sy, true);
+                    }
+                }
+            }
+        }
+    }
+    unsafe {
+        LT_ADJACENT_COLUMNS[x as usize] = adjacent_columns;
+    }
+
+    // Make king zones
+    let king_area = unsafe { LT_KING_MOVE[idx] } | bm_from_xy(x, y);
+    unsafe {
+        LT_KING_ZONE[0][idx] = king_area | (bm_shift(king_area, 0, 1));
+        LT_KING_ZONE[1][idx] = king_area | (bm_shift(king_area, 0, -1));
+    }
+
+    // Make distances
+    for ex in 0..8 {
+        for ey in 0..8 {
+            let eidx = (ex + ey * 8) as usize;
+            unsafe {
+                LT_DISTANCE[idx][eidx] = i64::max((ex - x).abs(), (ey - y).abs()) as u8;
+            }
+        }
+    }
+
+    let center_dist_x = if x < 4 { 3 - x } else { x - 4 };
+    let center_dist_y = if y < 4 { 3 - y } else { y - 4 };
+    unsafe {
+        LT_CENTER_DISTANCE[idx] = (center_dist_x + center_dist_y) as u8;
+    }
+
     /////////////////
 
     // Make between paths
@@ -264,7 +336,40 @@ pub fn get_ray_mask(idx_from: usize, idx_towards: usize) -> BitMask {
     unsafe { LT_RAY[idx_from][idx_towards] }
 }
 
+pub fn get_adjacent_columns_mask(x: usize) -> BitMask {
+    unsafe { LT_ADJACENT_COLUMNS[x] }
+}
+
+pub fn get_front_span_mask(team_idx: usize, pos_idx: usize) -> BitMask {
+    unsafe { LT_FRONT_SPAN[team_idx][pos_idx] }
+}
+
+pub fn get_passed_pawn_mask(team_idx: usize, pos_idx: usize) -> BitMask {
+    unsafe { LT_PASSED_PAWN[team_idx][pos_idx] }
+}
+
+pub fn get_attack_span_mask(team_idx: usize, pos_idx: usize) -> BitMask {
+    unsafe { LT_ATTACK_SPAN[team_idx][pos_idx] }
+}
+
+pub fn get_pawn_square_mask(team_idx: usize, pos_idx: usize) -> BitMask {
+    unsafe { LT_PAWN_SQUARE[team_idx][pos_idx] }
+}
+
+pub fn get_distance(idx_a: usize, idx_b: usize) -> u8 {
+    unsafe { LT_DISTANCE[idx_a][idx_b] }
+}
+
+pub fn get_center_distance(pos_idx: usize) -> u8 {
+    unsafe { LT_CENTER_DISTANCE[pos_idx] }
+}
+
+pub fn get_king_zone_mask(team_idx: usize, pos_idx: usize) -> BitMask {
+    unsafe { LT_KING_ZONE[team_idx][pos_idx] }
+}
+
 pub fn init() {
+    #[cfg(feature = "std")]
     println!("Initializing move lookup tables...");
     for x in 0..8 {
         for y in 0..8 {
@@ -272,6 +377,9 @@ pub fn init() {
         }
     }
 
+    #[cfg(feature = "std")]
+    #[cfg(feature = "std")]
     println!("Initializing lookup tables...");
+    #[cfg(feature = "std")]
     println!(" > Done!");
 }
@@ -1,4 +1,17 @@
+use board_crab_lib::bench;
+use board_crab_lib::board::Board;
+use board_crab_lib::book;
+use board_crab_lib::datagen;
+use board_crab_lib::epd_suite;
+use board_crab_lib::eval_params;
+use board_crab_lib::fen;
+use board_crab_lib::match_runner;
+use board_crab_lib::perft;
+use board_crab_lib::pgn;
+use board_crab_lib::tune;
 use board_crab_lib::uci;
+use board_crab_lib::xboard;
+use std::sync::mpsc;
 
 fn main() {
     board_crab_lib::init();
@@ -13,10 +26,439 @@ fn main() {
         }
     }));
 
+    // Usage: [--eval-params <params path>] [command]
+    // Commands: datagen <out path> [games] [nodes per move] [--pgn]
+    //           tune <dataset path> <out path> [passes]
+    //           book <pgn path> <out path> [max plies] [min games]
+    //           epd <suite path> [ms per position] [nodes per position]
+    //           bench [depth]
+    //           perft <depth> [fen]
+    //           match <engine 1> <engine 2> [--games <n>] [--tc <secs>+<inc secs>] [--nodes <n>]
+    //                 [--openings <epd/pgn path>] [--option1 <name>=<value>] [--option2 <name>=<value>]
+    //                 [--sprt elo0=<elo>,elo1=<elo>,alpha=<chance>,beta=<chance>]
+    let mut args: Vec<String> = std::env::args().collect();
+    if let Some(flag_idx) = args.iter().position(|arg| arg == "--eval-params") {
+        let Some(params_path) = args.get(flag_idx + 1).cloned() else {
+            eprintln!("Missing path after --eval-params");
+            std::process::exit(1);
+        };
+        match eval_params::EvalParams::load(&params_path) {
+            Ok(params) => eval_params::set(params),
+            Err(err) => {
+                eprintln!("Failed to load eval params from {}: {}", params_path, err);
+                std::process::exit(1);
+            }
+        }
+        args.drain(flag_idx..flag_idx + 2);
+    }
+
+    if args.len() >= 2 {
+        match args[1].as_str() {
+            "datagen" => return run_datagen(&args[2..]),
+            "tune" => return run_tune(&args[2..]),
+            "book" => return run_book(&args[2..]),
+            "epd" => return run_epd(&args[2..]),
+            "bench" => return run_bench(&args[2..]),
+            "perft" => return run_perft(&args[2..]),
+            "match" => return run_match(&args[2..]),
+            _ => {}
+        }
+    }
+
+    let inputs = spawn_input_threads();
+
+    // XBoard GUIs always start with "xboard", anything else is UCI
+    let Ok(Some(first_input)) = inputs.recv() else {
+        return;
+    };
+    if first_input.trim() == "xboard" {
+        let mut state = xboard::XBoardState::new();
+        while let Ok(Some(input)) = inputs.recv() {
+            xboard::process_cmd(input, &mut state);
+        }
+        xboard::shutdown(&mut state);
+        return;
+    }
+
     let mut state = uci::UCIState::new();
-    loop {
-        let mut input = String::new();
-        std::io::stdin().read_line(&mut input).unwrap();
+    uci::process_cmd(first_input, &mut state);
+    while let Ok(Some(input)) = inputs.recv() {
         uci::process_cmd(input, &mut state);
     }
+    uci::shutdown(&mut state);
+}
+
+// Reads stdin on its own thread, so the end of input (or a read error) and Ctrl-C can all be handled between commands
+// Sends each line, then None once we should shut down
+fn spawn_input_threads() -> mpsc::Receiver<Option<String>> {
+    let (sender, receiver) = mpsc::channel();
+
+    #[cfg(unix)]
+    {
+        use signal_hook::consts::{SIGINT, SIGTERM};
+
+        let sender = sender.clone();
+        let mut signals = signal_hook::iterator::Signals::new([SIGINT, SIGTERM])
+            .expect("Failed to set up signal handling");
+        std::thread::spawn(move || {
+            for (signal_idx, _) in signals.forever().enumerate() {
+                if signal_idx == 0 {
+                    let _ = sender.send(None);
+                } else {
+                    // Stuck in a long command (like perft), so give up on shutting down cleanly
+                    std::process::exit(130);
+                }
+            }
+        });
+    }
+
+    std::thread::spawn(move || loop {
+        let mut input = String::new();
+        match std::io::stdin().read_line(&mut input) {
+            Ok(0) | Err(_) => {
+                let _ = sender.send(None);
+                break;
+            }
+            Ok(_) => {
+                if sender.send(Some(input)).is_err() {
+                    break;
+                }
+            }
+        }
+    });
+
+    receiver
+}
+
+fn run_datagen(args: &[String]) {
+    // Also writes the games to "<out path>.pgn"
+    let mut settings = datagen::DatagenSettings::new();
+    let mut args = args.to_vec();
+    if let Some(flag_idx) = args.iter().position(|arg| arg == "--pgn") {
+        settings.write_pgn = true;
+        args.remove(flag_idx);
+    }
+
+    let Some(out_path) = args.first() else {
+        eprintln!("Usage: datagen <out path> [games] [nodes per move] [--pgn]");
+        std::process::exit(1);
+    };
+
+    let parse_arg = |idx: usize, default: usize| match args.get(idx) {
+        Some(arg) => arg.parse().unwrap_or_else(|_| {
+            eprintln!("Invalid number \"{}\"", arg);
+            std::process::exit(1);
+        }),
+        None => default,
+    };
+    settings.num_games = parse_arg(1, settings.num_games);
+    settings.nodes_per_move = parse_arg(2, settings.nodes_per_move);
+
+    match datagen::run(&settings, out_path) {
+        Ok(total_positions) => println!("Wrote {} positions to {}", total_positions, out_path),
+        Err(err) => {
+            eprintln!("Failed to write to {}: {}", out_path, err);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn run_tune(args: &[String]) {
+    let (Some(dataset_path), Some(out_path)) = (args.first(), args.get(1)) else {
+        eprintln!("Usage: tune <dataset path> <out path> [passes]");
+        std::process::exit(1);
+    };
+
+    let mut settings = tune::TuneSettings::new();
+    if let Some(arg) = args.get(2) {
+        settings.max_passes = arg.parse().unwrap_or_else(|_| {
+            eprintln!("Invalid number \"{}\"", arg);
+            std::process::exit(1);
+        });
+    }
+
+    let entries = tune::load_dataset(dataset_path).unwrap_or_else(|err| {
+        eprintln!("Failed to load {}: {}", dataset_path, err);
+        std::process::exit(1);
+    });
+    println!("Loaded {} positions", entries.len());
+
+    // The tuned params are saved after every pass, so tuning can be stopped at any time
+    // They can be loaded with --eval-params, or the EvalParamsFile option
+    tune::tune(
+        &entries,
+        eval_params::get().clone(),
+        &settings,
+        |pass_idx, params, error| {
+            println!("Pass {}: error {:.8}", pass_idx + 1, error);
+            if let Err(err) = params.save(out_path) {
+                eprintln!("Failed to write to {}: {}", out_path, err);
+            }
+        },
+    );
+}
+
+fn run_book(args: &[String]) {
+    let (Some(pgn_path), Some(out_path)) = (args.first(), args.get(1)) else {
+        eprintln!("Usage: book <pgn path> <out path> [max plies] [min games]");
+        std::process::exit(1);
+    };
+
+    let mut settings = book::BookBuildSettings::new();
+    let parse_arg = |idx: usize, default: usize| match args.get(idx) {
+        Some(arg) => arg.parse().unwrap_or_else(|_| {
+            eprintln!("Invalid number \"{}\"", arg);
+            std::process::exit(1);
+        }),
+        None => default,
+    };
+    settings.max_plies = parse_arg(2, settings.max_plies);
+    settings.min_games = parse_arg(3, settings.min_games as usize) as u32;
+
+    let games = pgn::load(pgn_path).unwrap_or_else(|err| {
+        eprintln!("Failed to load {}: {}", pgn_path, err);
+        std::process::exit(1);
+    });
+
+    // Unfinished games don't tell us anything about the moves
+    let mut builder = book::BookBuilder::new(settings);
+    let mut num_used_games = 0;
+    for game in &games {
+        if let Some(result) = game.result {
+            builder.add_game(&game.start_board, &game.moves, result);
+            num_used_games += 1;
+        }
+    }
+
+    let book = builder.build();
+    match book.save(out_path) {
+        Ok(()) => println!(
+            "Wrote {} entries from {}/{} games to {}",
+            book.len(),
+            num_used_games,
+            games.len(),
+            out_path
+        ),
+        Err(err) => {
+            eprintln!("Failed to write to {}: {}", out_path, err);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn run_epd(args: &[String]) {
+    let Some(suite_path) = args.first() else {
+        eprintln!("Usage: epd <suite path> [ms per position] [nodes per position]");
+        std::process::exit(1);
+    };
+
+    let parse_arg = |idx: usize| {
+        args.get(idx).map(|arg| {
+            arg.parse::<usize>().unwrap_or_else(|_| {
+                eprintln!("Invalid number \"{}\"", arg);
+                std::process::exit(1);
+            })
+        })
+    };
+
+    // With only a node limit, there's no time limit
+    let mut settings = epd_suite::SuiteSettings::new();
+    let time_ms = parse_arg(1);
+    settings.nodes_per_position = parse_arg(2);
+    if time_ms.is_some() || settings.nodes_per_position.is_some() {
+        settings.time_per_position = time_ms
+            .filter(|&time_ms| time_ms > 0)
+            .map(|time_ms| (time_ms as f64) / 1000.0);
+    }
+
+    let records = fen::load_epd_file(suite_path).unwrap_or_else(|err| {
+        eprintln!("Failed to load {}: {}", suite_path, err);
+        std::process::exit(1);
+    });
+
+    let results = epd_suite::run(&records, &settings);
+    let nodes_per_sec = ((results.total_nodes as f64) / results.total_time.max(0.001)) as usize;
+    println!(
+        "Solved {}/{} ({:.1}%), {} nodes, {} nps",
+        results.num_solved,
+        results.num_checked,
+        100.0 * (results.num_solved as f64) / (results.num_checked.max(1) as f64),
+        results.total_nodes,
+        nodes_per_sec
+    );
+}
+
+fn run_bench(args: &[String]) {
+    let depth = match args.first() {
+        Some(arg) => arg.parse().unwrap_or_else(|_| {
+            eprintln!("Invalid depth \"{}\"", arg);
+            std::process::exit(1);
+        }),
+        None => bench::DEFAULT_DEPTH,
+    };
+
+    // The last line is what testing frameworks (like OpenBench) read
+    let results = bench::run(depth, true);
+    println!(
+        "{} nodes {} nps",
+        results.total_nodes,
+        results.get_nodes_per_sec()
+    );
+}
+
+fn run_perft(args: &[String]) {
+    let Some(depth) = args.first().and_then(|arg| arg.parse::<u8>().ok()) else {
+        eprintln!("Usage: perft <depth> [fen]");
+        std::process::exit(1);
+    };
+
+    // The FEN can be passed as one argument, or split over several
+    let board = if args.len() > 1 {
+        fen::load_fen(&args[1..].join(" ")).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        })
+    } else {
+        Board::start_pos()
+    };
+
+    let settings = perft::PerftSettings::new();
+    uci::print_perft(&board, depth, settings.num_threads, settings.table_size_mbs);
+}
+
+fn run_match(args: &[String]) {
+    const USAGE: &str =
+        "Usage: match <engine 1> <engine 2> [--games <n>] [--tc <secs>+<inc secs>] [--nodes <n>] \
+        [--openings <epd/pgn path>] [--option1 <name>=<value>] [--option2 <name>=<value>] \
+        [--sprt elo0=<elo>,elo1=<elo>,alpha=<chance>,beta=<chance>]";
+    let exit_with = |msg: &str| -> ! {
+        eprintln!("{}", msg);
+        std::process::exit(1);
+    };
+
+    if args.len() < 2 || args[0].starts_with("--") || args[1].starts_with("--") {
+        exit_with(USAGE);
+    }
+
+    // "self" is this executable, so two configurations of BoardCrab can play each other
+    let mut configs = [&args[0], &args[1]].map(|engine| {
+        if engine == "self" {
+            let exe_path = std::env::current_exe().unwrap_or_else(|err| {
+                exit_with(&format!("Failed to find this executable: {}", err))
+            });
+            match_runner::EngineConfig::new(&exe_path.to_string_lossy())
+        } else {
+            match_runner::EngineConfig::new(engine)
+        }
+    });
+
+    let mut settings = match_runner::MatchSettings::new();
+    let mut openings = Vec::new();
+    let mut has_num_games = false;
+    let parse_num = |arg: &str| {
+        arg.parse::<f64>()
+            .ok()
+            .filter(|num| *num >= 0.0)
+            .unwrap_or_else(|| exit_with(&format!("Invalid number \"{}\"", arg)))
+    };
+    for flag_args in args[2..].chunks(2) {
+        let [flag, value] = flag_args else {
+            exit_with(USAGE);
+        };
+        match flag.as_str() {
+            "--games" => {
+                settings.num_games = parse_num(value) as usize;
+                has_num_games = true;
+            }
+            "--tc" => {
+                let (base_time, time_inc) = value.split_once('+').unwrap_or((value, "0"));
+                settings.base_time = parse_num(base_time);
+                settings.time_inc = parse_num(time_inc);
+            }
+            "--nodes" => settings.nodes_per_move = Some(parse_num(value) as usize),
+            "--openings" => {
+                let loaded = if value.ends_with(".pgn") {
+                    pgn::load(value)
+                        .map(|games| games.iter().map(|game| game.get_final_board()).collect())
+                } else {
+                    fen::load_epd_file(value)
+                        .map(|records| records.iter().map(|record| record.board).collect())
+                };
+                openings = loaded
+                    .unwrap_or_else(|err| exit_with(&format!("Failed to load {}: {}", value, err)));
+            }
+            "--option1" | "--option2" => {
+                let Some((name, option_value)) = value.split_once('=') else {
+                    exit_with(&format!("Expected <name>=<value>, got \"{}\"", value));
+                };
+                let config_idx = if flag == "--option1" { 0 } else { 1 };
+                configs[config_idx]
+                    .options
+                    .push((name.to_string(), option_value.to_string()));
+            }
+            "--sprt" => {
+                // Anything left out keeps its default
+                let mut sprt = match_runner::SprtSettings::new();
+                for pair in value.split(',') {
+                    let parsed = pair
+                        .split_once('=')
+                        .and_then(|(name, num)| Some((name, num.parse::<f64>().ok()?)));
+                    match parsed {
+                        Some(("elo0", num)) => sprt.elo0 = num,
+                        Some(("elo1", num)) => sprt.elo1 = num,
+                        Some(("alpha", num)) if num > 0.0 && num < 1.0 => sprt.alpha = num,
+                        Some(("beta", num)) if num > 0.0 && num < 1.0 => sprt.beta = num,
+                        _ => exit_with(&format!("Invalid SPRT setting \"{}\"", pair)),
+                    }
+                }
+                if sprt.elo1 <= sprt.elo0 {
+                    exit_with("SPRT elo1 must be above elo0");
+                }
+                settings.sprt = Some(sprt);
+            }
+            _ => exit_with(USAGE),
+        }
+    }
+
+    // The SPRT stops the match by itself, so the game count is only a safety limit
+    if settings.sprt.is_some() && !has_num_games {
+        settings.num_games = 100_000;
+    }
+
+    let results = match_runner::run(&configs, &openings, &settings)
+        .unwrap_or_else(|err| exit_with(&format!("Match failed: {}", err)));
+    let elo_str = match results.get_elo() {
+        Some((elo, elo_margin)) => format!("{:.1} +/- {:.1}", elo, elo_margin),
+        None => "unknown".to_string(),
+    };
+    println!(
+        "Score of engine 1 vs engine 2: W/D/L: {}/{}/{} ({:.1}%), Elo: {}",
+        results.wins,
+        results.draws,
+        results.losses,
+        results.get_score() * 100.0,
+        elo_str
+    );
+
+    if let Some(sprt) = &settings.sprt {
+        let llr = results.llrs.last().copied().unwrap_or_default();
+        let status_str = match sprt.get_status(llr) {
+            match_runner::SprtStatus::AcceptH1 => "H1 accepted, engine 1 is stronger",
+            match_runner::SprtStatus::AcceptH0 => "H0 accepted, engine 1 isn't stronger",
+            match_runner::SprtStatus::Continue => "inconclusive, ran out of games",
+        };
+        let (lower_bound, upper_bound) = sprt.get_bounds();
+        println!(
+            "SPRT [{}, {}] (alpha {}, beta {}): {}, LLR {:.2} ({:.2}, {:.2}) after {} games",
+            sprt.elo0,
+            sprt.elo1,
+            sprt.alpha,
+            sprt.beta,
+            status_str,
+            llr,
+            lower_bound,
+            upper_bound,
+            results.get_num_games()
+        );
+    }
 }
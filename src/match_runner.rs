@@ -0,0 +1,592 @@
+use crate::board::*;
+use crate::eval::*;
+use crate::fen;
+use crate::game::Game;
+use crate::search::GameResult;
+use std::io::{BufRead, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+// Plays games between two UCI engines, so a change can be sanity-checked without needing cutechess
+// Every opening is played twice, once with each engine as white, so neither engine gets the better side of an opening
+// All results are from the perspective of the first engine
+
+#[derive(Debug, Clone)]
+pub struct EngineConfig {
+    pub command: String, // Path to the engine's executable
+    pub args: Vec<String>,
+    pub options: Vec<(String, String)>, // Sent as "setoption" once the engine starts
+}
+
+impl EngineConfig {
+    pub fn new(command: &str) -> EngineConfig {
+        EngineConfig {
+            command: command.to_string(),
+            args: Vec::new(),
+            options: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct MatchSettings {
+    pub num_games: usize,
+    pub base_time: f64,                // In seconds
+    pub time_inc: f64,                 // In seconds
+    pub nodes_per_move: Option<usize>, // Replaces the clock when set, which makes games reproducible
+    pub time_margin: f64, // Engines only lose on time once they're this far over (in seconds), to allow for lag
+    pub max_game_plies: usize, // Games this long are adjudicated as draws
+    pub sprt: Option<SprtSettings>, // Stops the match early once the test has a result, num_games is then the most to play
+}
+
+impl MatchSettings {
+    pub fn new() -> MatchSettings {
+        MatchSettings {
+            num_games: 100,
+            base_time: 10.0,
+            time_inc: 0.1,
+            nodes_per_move: None,
+            time_margin: 0.1,
+            max_game_plies: 600,
+            sprt: None,
+        }
+    }
+}
+
+impl Default for MatchSettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Sequential probability ratio test, which keeps playing until it can tell whether the first engine is elo1 stronger (H1) or only elo0 (H0)
+// Alpha and beta are the chances of wrongly accepting H1 and H0, lower values need more games
+// See: https://www.chessprogramming.org/Sequential_Probability_Ratio_Test
+#[derive(Debug, Copy, Clone)]
+pub struct SprtSettings {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+impl SprtSettings {
+    pub fn new() -> SprtSettings {
+        SprtSettings {
+            elo0: 0.0,
+            elo1: 5.0,
+            alpha: 0.05,
+            beta: 0.05,
+        }
+    }
+
+    // The LLR bounds, H0 is accepted below the lower one and H1 above the upper one
+    pub fn get_bounds(&self) -> (f64, f64) {
+        (
+            (self.beta / (1.0 - self.alpha)).ln(),
+            ((1.0 - self.beta) / self.alpha).ln(),
+        )
+    }
+
+    // Log-likelihood ratio of H1 over H0, using the normal approximation of the score
+    // Zero until the results have any variance
+    pub fn get_llr(&self, results: &MatchResults) -> f64 {
+        let variance = results.get_score_variance();
+        if variance <= 0.0 {
+            return 0.0;
+        }
+
+        let score0 = elo_to_score(self.elo0);
+        let score1 = elo_to_score(self.elo1);
+        let num_games = results.get_num_games() as f64;
+        num_games * (score1 - score0) * (2.0 * results.get_score() - score0 - score1)
+            / (2.0 * variance)
+    }
+
+    pub fn get_status(&self, llr: f64) -> SprtStatus {
+        let (lower_bound, upper_bound) = self.get_bounds();
+        if llr >= upper_bound {
+            SprtStatus::AcceptH1
+        } else if llr <= lower_bound {
+            SprtStatus::AcceptH0
+        } else {
+            SprtStatus::Continue
+        }
+    }
+}
+
+impl Default for SprtSettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SprtStatus {
+    Continue,
+    AcceptH0, // Closer to elo0, so a patch fails
+    AcceptH1, // Closer to elo1, so a patch passes
+}
+
+// Adjudication, so games don't go on long after the result is clear
+// Both engines have to agree, so these count plies with the same verdict in a row
+const WIN_ADJUDICATION_EVAL: Value = 1000;
+const WIN_ADJUDICATION_PLIES: usize = 6;
+const DRAW_ADJUDICATION_EVAL: Value = 10;
+const DRAW_ADJUDICATION_PLIES: usize = 12;
+const DRAW_ADJUDICATION_MIN_PLY: usize = 80;
+
+// How long engines get to answer anything but "go"
+const ENGINE_REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+
+// With a node limit there's no clock, but engines that never answer still have to lose eventually
+const NODES_MOVE_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Default)]
+pub struct MatchResults {
+    pub wins: usize,
+    pub draws: usize,
+    pub losses: usize,
+    pub llrs: Vec<f64>, // The SPRT's LLR after every game, empty without SPRT
+}
+
+impl MatchResults {
+    pub fn get_num_games(&self) -> usize {
+        self.wins + self.draws + self.losses
+    }
+
+    // From 0.0 (lost every game) to 1.0 (won every game)
+    pub fn get_score(&self) -> f64 {
+        (self.wins as f64 + self.draws as f64 * 0.5) / (self.get_num_games().max(1) as f64)
+    }
+
+    // Variance of a single game's score
+    fn get_score_variance(&self) -> f64 {
+        let score = self.get_score();
+        (self.wins as f64 * (1.0 - score).powi(2)
+            + self.draws as f64 * (0.5 - score).powi(2)
+            + self.losses as f64 * score.powi(2))
+            / (self.get_num_games().max(1) as f64)
+    }
+
+    // The Elo difference, and the margin of its 95% confidence interval
+    // None if there are no games yet, or only wins or only losses (then the difference is infinite)
+    // See: https://www.chessprogramming.org/Match_Statistics
+    pub fn get_elo(&self) -> Option<(f64, f64)> {
+        let num_games = self.get_num_games() as f64;
+        let score = self.get_score();
+        if num_games == 0.0 || score <= 0.0 || score >= 1.0 {
+            return None;
+        }
+
+        // With few games the interval can reach past a score of 0.0 or 1.0, then the margin is infinite
+        let score_margin = 1.96 * (self.get_score_variance() / num_games).sqrt();
+        let max_elo = score_to_elo((score + score_margin).min(1.0));
+        let min_elo = score_to_elo((score - score_margin).max(0.0));
+        let elo_margin = (max_elo - min_elo) / 2.0;
+        Some((score_to_elo(score), elo_margin))
+    }
+}
+
+// Infinite at a score of 0.0 or 1.0
+pub fn score_to_elo(score: f64) -> f64 {
+    400.0 * (score / (1.0 - score)).log10()
+}
+
+// The expected score of an engine that's this much stronger
+pub fn elo_to_score(elo: f64) -> f64 {
+    1.0 / (1.0 + 10.0f64.powf(-elo / 400.0))
+}
+
+// An engine running in its own process, which we talk to over stdin/stdout
+struct UciEngine {
+    name: String,
+    process: Child,
+    stdin: ChildStdin,
+    lines: mpsc::Receiver<String>, // Filled by a thread reading the engine's stdout
+}
+
+impl UciEngine {
+    fn start(config: &EngineConfig) -> std::io::Result<UciEngine> {
+        let mut process = Command::new(&config.command)
+            .args(&config.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let stdin = process.stdin.take().unwrap();
+        let stdout = process.stdout.take().unwrap();
+
+        // The sender is dropped once the engine's output ends, which is how a crash shows up
+        let (sender, lines) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in std::io::BufReader::new(stdout).lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut engine = UciEngine {
+            name: config.command.clone(),
+            process,
+            stdin,
+            lines,
+        };
+
+        engine.send("uci")?;
+        let mut id_name = None;
+        engine.wait_for("uciok", ENGINE_REPLY_TIMEOUT, |line| {
+            if let Some(name) = line.strip_prefix("id name ") {
+                id_name = Some(name.trim().to_string());
+            }
+        })?;
+        if let Some(id_name) = id_name {
+            engine.name = id_name;
+        }
+
+        for (name, value) in &config.options {
+            engine.send(&format!("setoption name {} value {}", name, value))?;
+        }
+        engine.wait_until_ready()?;
+        Ok(engine)
+    }
+
+    fn send(&mut self, cmd: &str) -> std::io::Result<()> {
+        writeln!(self.stdin, "{}", cmd)?;
+        self.stdin.flush()
+    }
+
+    // Waits for a line starting with the token, and passes every line before it to on_line
+    fn wait_for(
+        &self,
+        token: &str,
+        timeout: Duration,
+        mut on_line: impl FnMut(&str),
+    ) -> std::io::Result<String> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match self.lines.recv_timeout(remaining) {
+                Ok(line) => {
+                    if line.split_whitespace().next() == Some(token) {
+                        return Ok(line);
+                    }
+                    on_line(&line);
+                }
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::TimedOut,
+                        format!("{} didn't send \"{}\" in time", self.name, token),
+                    ));
+                }
+                Err(mpsc::RecvTimeoutError::Disconnected) => {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::UnexpectedEof,
+                        format!("{} exited", self.name),
+                    ));
+                }
+            }
+        }
+    }
+
+    fn wait_until_ready(&mut self) -> std::io::Result<()> {
+        self.send("isready")?;
+        self.wait_for("readyok", ENGINE_REPLY_TIMEOUT, |_| {})?;
+        Ok(())
+    }
+
+    // Returns the best move, and the last score the engine gave for it (from its own perspective)
+    fn go(
+        &mut self,
+        position_cmd: &str,
+        go_cmd: &str,
+        timeout: Duration,
+    ) -> std::io::Result<(String, Option<Value>)> {
+        self.send(position_cmd)?;
+        self.send(go_cmd)?;
+
+        let mut score = None;
+        let line = self.wait_for("bestmove", timeout, |line| {
+            if line.starts_with("info") {
+                score = parse_info_score(line).or(score);
+            }
+        })?;
+        let best_move = line.split_whitespace().nth(1).unwrap_or_default();
+        Ok((best_move.to_string(), score))
+    }
+}
+
+impl Drop for UciEngine {
+    fn drop(&mut self) {
+        // Give the engine a moment to quit by itself
+        let _ = self.send("quit");
+        let quit_deadline = Instant::now() + Duration::from_secs(1);
+        while Instant::now() < quit_deadline {
+            if let Ok(Some(_)) = self.process.try_wait() {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+    }
+}
+
+// Reads the score from an "info" line, with mate scores as mate evals
+fn parse_info_score(line: &str) -> Option<Value> {
+    let parts: Vec<&str> = line.split_whitespace().collect();
+    let score_idx = parts.iter().position(|&part| part == "score")?;
+    let value = parts.get(score_idx + 2)?.parse::<Value>().ok()?;
+    match *parts.get(score_idx + 1)? {
+        "cp" => Some(value),
+        "mate" if value > 0 => Some(VALUE_CHECKMATE - (value * 2 - 1)),
+        "mate" => Some(-VALUE_CHECKMATE - value * 2),
+        _ => None,
+    }
+}
+
+// How a game ended, with the result for white
+struct GameOutcome {
+    white_result: f32,
+    reason: String,
+}
+
+impl GameOutcome {
+    fn new(white_result: f32, reason: &str) -> GameOutcome {
+        GameOutcome {
+            white_result,
+            reason: reason.to_string(),
+        }
+    }
+
+    // The side to move lost
+    fn turn_lost(turn_idx: usize, reason: String) -> GameOutcome {
+        GameOutcome {
+            white_result: if turn_idx == 0 { 0.0 } else { 1.0 },
+            reason,
+        }
+    }
+}
+
+// Engines are indexed by color, so engines[0] is white
+// An engine that errors (e.g. by crashing) loses, and is set to None so it gets restarted
+fn play_game(
+    engines: &mut [Option<UciEngine>; 2],
+    start_board: &Board,
+    settings: &MatchSettings,
+) -> GameOutcome {
+    let start_fen = fen::make_fen(start_board);
+    let mut game = Game::new(*start_board);
+    let mut clocks = [settings.base_time; 2];
+    let mut num_win_plies = 0;
+    let mut num_draw_plies = 0;
+    let mut last_white_score: Option<Value> = None;
+
+    for (color_idx, engine) in engines.iter_mut().enumerate() {
+        let engine_ref = engine.as_mut().unwrap();
+        if let Err(err) = engine_ref
+            .send("ucinewgame")
+            .and_then(|_| engine_ref.wait_until_ready())
+        {
+            *engine = None;
+            return GameOutcome::turn_lost(color_idx, err.to_string());
+        }
+    }
+
+    for ply in 0..settings.max_game_plies {
+        match game.result() {
+            Some(GameResult::Checkmate) => {
+                return GameOutcome::turn_lost(game.get_board().turn_idx, "checkmate".to_string())
+            }
+            Some(GameResult::Stalemate) => return GameOutcome::new(0.5, "stalemate"),
+            Some(GameResult::FiftyMoveRule) => return GameOutcome::new(0.5, "fifty move rule"),
+            Some(GameResult::InsufficientMaterial) => {
+                return GameOutcome::new(0.5, "insufficient material")
+            }
+            Some(GameResult::Repetition) => return GameOutcome::new(0.5, "repetition"),
+            Some(GameResult::InProgress) | None => {}
+        }
+
+        let turn_idx = game.get_board().turn_idx;
+        let mut position_cmd = format!("position fen {}", start_fen);
+        if !game.get_moves().is_empty() {
+            position_cmd += " moves";
+            for mv in game.get_moves() {
+                position_cmd += &format!(" {}", mv.to_uci(start_board.is_chess960));
+            }
+        }
+        let (go_cmd, timeout) = match settings.nodes_per_move {
+            Some(nodes) => (format!("go nodes {}", nodes), NODES_MOVE_TIMEOUT),
+            None => (
+                format!(
+                    "go wtime {} btime {} winc {} binc {}",
+                    (clocks[0] * 1000.0) as i64,
+                    (clocks[1] * 1000.0) as i64,
+                    (settings.time_inc * 1000.0) as i64,
+                    (settings.time_inc * 1000.0) as i64
+                ),
+                Duration::from_secs_f64(clocks[turn_idx] + settings.time_margin),
+            ),
+        };
+
+        let engine = engines[turn_idx].as_mut().unwrap();
+        let start_time = Instant::now();
+        let (move_str, score) = match engine.go(&position_cmd, &go_cmd, timeout) {
+            Ok(reply) => reply,
+            Err(err) => {
+                let reason = if err.kind() == std::io::ErrorKind::TimedOut {
+                    format!("{} lost on time", engine.name)
+                } else {
+                    err.to_string()
+                };
+
+                // An engine that's still thinking could send its move during the next game, so it's restarted
+                engines[turn_idx] = None;
+                return GameOutcome::turn_lost(turn_idx, reason);
+            }
+        };
+
+        if settings.nodes_per_move.is_none() {
+            clocks[turn_idx] -= start_time.elapsed().as_secs_f64();
+            if clocks[turn_idx] < -settings.time_margin {
+                return GameOutcome::turn_lost(turn_idx, format!("{} lost on time", engine.name));
+            }
+            clocks[turn_idx] = clocks[turn_idx].max(0.0) + settings.time_inc;
+        }
+
+        if game.push_uci(&move_str).is_err() {
+            return GameOutcome::turn_lost(
+                turn_idx,
+                format!("{} played illegal move \"{}\"", engine.name, move_str),
+            );
+        }
+
+        // Adjudication needs both engines to agree, so a missing score breaks the streak
+        let white_score = score.map(|score| if turn_idx == 0 { score } else { -score });
+        let agree = |min: Value, max: Value| {
+            [white_score, last_white_score]
+                .iter()
+                .all(|score| score.is_some_and(|score| score >= min && score <= max))
+        };
+
+        if agree(WIN_ADJUDICATION_EVAL, VALUE_INF) || agree(-VALUE_INF, -WIN_ADJUDICATION_EVAL) {
+            num_win_plies += 1;
+            if num_win_plies >= WIN_ADJUDICATION_PLIES {
+                let white_result = if white_score.unwrap() > 0 { 1.0 } else { 0.0 };
+                return GameOutcome::new(white_result, "adjudicated win");
+            }
+        } else {
+            num_win_plies = 0;
+        }
+
+        if ply >= DRAW_ADJUDICATION_MIN_PLY
+            && agree(-DRAW_ADJUDICATION_EVAL, DRAW_ADJUDICATION_EVAL)
+        {
+            num_draw_plies += 1;
+            if num_draw_plies >= DRAW_ADJUDICATION_PLIES {
+                return GameOutcome::new(0.5, "adjudicated draw");
+            }
+        } else {
+            num_draw_plies = 0;
+        }
+
+        last_white_score = white_score;
+    }
+
+    GameOutcome::new(0.5, "max game length")
+}
+
+// Plays all the games, printing each result as it comes in, and returns the totals
+// With SPRT, the match stops as soon as the test accepts either hypothesis
+// Without openings, every game starts from the start position
+// Fails if an engine can't be (re)started
+pub fn run(
+    configs: &[EngineConfig; 2],
+    openings: &[Board],
+    settings: &MatchSettings,
+) -> std::io::Result<MatchResults> {
+    let start_pos = [Board::start_pos()];
+    let openings = if openings.is_empty() {
+        &start_pos[..]
+    } else {
+        openings
+    };
+
+    let mut engines: [Option<UciEngine>; 2] = [None, None];
+    let mut results = MatchResults::default();
+    for game_idx in 0..settings.num_games {
+        for (engine_idx, (engine, config)) in engines.iter_mut().zip(configs).enumerate() {
+            if engine.is_none() {
+                let new_engine = UciEngine::start(config)?;
+                if game_idx == 0 {
+                    println!("Engine {}: {}", engine_idx + 1, new_engine.name);
+                }
+                *engine = Some(new_engine);
+            }
+        }
+
+        // The first engine is white in even games, and the engines swap sides on the same opening in odd games
+        let opening = &openings[(game_idx / 2) % openings.len()];
+        let is_swapped = game_idx % 2 == 1;
+        if is_swapped {
+            engines.swap(0, 1);
+        }
+        let outcome = play_game(&mut engines, opening, settings);
+        if is_swapped {
+            engines.swap(0, 1);
+        }
+
+        let result = if is_swapped {
+            1.0 - outcome.white_result
+        } else {
+            outcome.white_result
+        };
+        if result > 0.5 {
+            results.wins += 1;
+        } else if result < 0.5 {
+            results.losses += 1;
+        } else {
+            results.draws += 1;
+        }
+
+        let result_str = match outcome.white_result {
+            result if result > 0.5 => "1-0",
+            result if result < 0.5 => "0-1",
+            _ => "1/2-1/2",
+        };
+        let mut llr_str = String::new();
+        let mut sprt_status = SprtStatus::Continue;
+        if let Some(sprt) = &settings.sprt {
+            let llr = sprt.get_llr(&results);
+            let (lower_bound, upper_bound) = sprt.get_bounds();
+            llr_str = format!(", LLR: {:.2} ({:.2}, {:.2})", llr, lower_bound, upper_bound);
+            results.llrs.push(llr);
+            sprt_status = sprt.get_status(llr);
+        }
+
+        println!(
+            "Game {}/{} (engine {} as white): {} ({}), W/D/L: {}/{}/{}{}",
+            game_idx + 1,
+            settings.num_games,
+            if is_swapped { 2 } else { 1 },
+            result_str,
+            outcome.reason,
+            results.wins,
+            results.draws,
+            results.losses,
+            llr_str
+        );
+
+        if sprt_status != SprtStatus::Continue {
+            break;
+        }
+    }
+
+    Ok(results)
+}
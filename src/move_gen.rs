@@ -13,7 +13,7 @@ impl MoveBuffer {
     pub fn new() -> MoveBuffer {
         #![allow(invalid_value)]
         MoveBuffer {
-            data: unsafe { std::mem::MaybeUninit::uninit().assume_init() }, // Uninitialized
+            data: unsafe { core::mem::MaybeUninit::uninit().assume_init() }, // Uninitialized
             size: 0,
         }
     }
@@ -49,14 +49,14 @@ impl MoveBuffer {
     }
 }
 
-impl std::ops::Index<usize> for MoveBuffer {
+impl core::ops::Index<usize> for MoveBuffer {
     type Output = Move;
     fn index(&self, i: usize) -> &Move {
         &self.data[i]
     }
 }
 
-impl std::ops::IndexMut<usize> for MoveBuffer {
+impl core::ops::IndexMut<usize> for MoveBuffer {
     fn index_mut(&mut self, i: usize) -> &mut Move {
         &mut self.data[i]
     }
@@ -161,194 +161,480 @@ pub fn generate_attacks(
     attacks
 }
 
+// Finds every piece (from both teams) attacking a square, using a custom occupancy
+// Pieces missing from the occupancy are ignored, so sliders will x-ray through them
+pub fn get_attackers_to(board: &Board, pos_idx: usize, occupy: BitMask) -> BitMask {
+    let pos = bm_from_idx(pos_idx);
+
+    let mut attackers: BitMask = 0;
+    for team_idx in 0..2 {
+        // Pawns that attack this square are where an opposing pawn on this square would attack
+        let pawn_advance_dy = if team_idx == 0 { -1 } else { 1 };
+        let pawn_froms = generate_pawn_attacks_side::<0>(pos, pawn_advance_dy)
+            | generate_pawn_attacks_side::<1>(pos, pawn_advance_dy);
+        attackers |= pawn_froms & board.pieces[team_idx][PIECE_PAWN];
+    }
+
+    let both_pieces = |piece_idx: usize| board.pieces[0][piece_idx] | board.pieces[1][piece_idx];
+    let queens = both_pieces(PIECE_QUEEN);
+
+    attackers |= lookup_gen::get_piece_base_tos(PIECE_KNIGHT, pos_idx) & both_pieces(PIECE_KNIGHT);
+    attackers |= lookup_gen::get_piece_base_tos(PIECE_KING, pos_idx) & both_pieces(PIECE_KING);
+    attackers |= lookup_gen::get_piece_tos(PIECE_BISHOP, pos, pos_idx, occupy)
+        & (both_pieces(PIECE_BISHOP) | queens);
+    attackers |= lookup_gen::get_piece_tos(PIECE_ROOK, pos, pos_idx, occupy)
+        & (both_pieces(PIECE_ROOK) | queens);
+
+    attackers & occupy
+}
+
 pub fn can_castle(side: usize, board: &Board, team_idx: usize, is_in_check: bool) -> bool {
-    // From: https://github.com/ZealanL/BoardMouse/blob/4d3b6c608a3cb82a1299580a90dcb3c831fc02f8/src/Engine/MoveGen/MoveGen.cpp#L13
     // Ordering is [Left/Queen-side, Right/King-side]
-    const CASTLE_EMPTY_MASKS: [[BitMask; 2]; 2] = [
-        [
-            // White
-            bm_from_coord("B1") | bm_from_coord("C1") | bm_from_coord("D1"),
-            bm_from_coord("F1") | bm_from_coord("G1"),
-        ],
-        [
-            // Black
-            bm_from_coord("B8") | bm_from_coord("C8") | bm_from_coord("D8"),
-            bm_from_coord("F8") | bm_from_coord("G8"),
-        ],
-    ];
-
-    // These squares cannot be in attack from the enemy in order to castle
-    const CASTLE_SAFETY_MASKS: [[BitMask; 2]; 2] = [
-        [
-            // White
-            bm_from_coord("C1") | bm_from_coord("D1"),
-            bm_from_coord("F1") | bm_from_coord("G1"),
-        ],
-        [
-            // Black
-            bm_from_coord("C8") | bm_from_coord("D8"), // Far
-            bm_from_coord("F8") | bm_from_coord("G8"), // Near
-        ],
-    ];
-
+    // Works for Chess960 too, where the king and rook can start anywhere on the back row
+    // See: https://www.chessprogramming.org/Chess960#Castling
     if !board.castle_rights[team_idx][side] {
         return false;
     }
     if is_in_check {
         return false;
     }
-    if (board.combined_occupancy() & CASTLE_EMPTY_MASKS[team_idx][side]) != 0 {
+
+    let king = board.pieces[team_idx][PIECE_KING];
+    let rook = board.castle_rook_masks[team_idx][side];
+    let king_idx = bm_to_idx(king);
+    let rook_idx = bm_to_idx(rook);
+    let row = (king_idx / 8) as i64;
+    let king_to_idx = bm_to_idx(bm_from_xy(CASTLE_KING_TO_COLUMNS[side], row));
+    let rook_to_idx = bm_to_idx(bm_from_xy(CASTLE_ROOK_TO_COLUMNS[side], row));
+
+    // Everything the king and rook pass over or land on must be empty, other than themselves
+    let king_path = lookup_gen::get_between_mask_inclusive(king_idx, king_to_idx);
+    let rook_path = lookup_gen::get_between_mask_inclusive(rook_idx, rook_to_idx);
+    let occ_others = board.combined_occupancy() & !king & !rook;
+    if (occ_others & (king_path | rook_path)) != 0 {
+        return false;
+    }
+
+    // The king can't pass over or land on an attacked square
+    if (board.attacks[1 - team_idx] & king_path) != 0 {
         return false;
     }
-    if (board.attacks[1 - team_idx] & CASTLE_SAFETY_MASKS[team_idx][side]) != 0 {
+
+    // In Chess960, our rook could be what's blocking an attack along the row on where the king lands
+    let row_attackers = lookup_gen::get_piece_tos(
+        PIECE_ROOK,
+        bm_from_idx(king_to_idx),
+        king_to_idx,
+        occ_others,
+    ) & (board.pieces[1 - team_idx][PIECE_ROOK]
+        | board.pieces[1 - team_idx][PIECE_QUEEN]);
+    if row_attackers != 0 {
         return false;
     }
 
     true
 }
 
-pub fn generate_moves(board: &Board, out_move_set: &mut MoveBuffer) {
+// Squares that our non-king pieces have to move to when in check, to block the check or capture the checker
+// Double checks aren't handled, since only the king can move then
+#[inline(always)]
+fn get_check_move_mask(board: &Board) -> BitMask {
+    if board.checkers.count_ones() == 1 {
+        let king = board.pieces[board.turn_idx][PIECE_KING];
+        lookup_gen::get_between_mask_inclusive(bm_to_idx(king), bm_to_idx(board.checkers))
+    } else {
+        !0
+    }
+}
+
+// Legal destination squares of one of our pieces, not including castling
+#[inline(always)]
+fn get_legal_tos(board: &Board, piece_idx: usize, from: BitMask, move_mask: BitMask) -> BitMask {
     let occ_team = board.occupancy[board.turn_idx];
     let occ_opp = board.occupancy[1 - board.turn_idx];
     let occ_combined = occ_team | occ_opp;
     let king = board.pieces[board.turn_idx][PIECE_KING];
-    let num_checkers = board.checkers.count_ones(); // TODO: Don't need a full popcount, just >1 check
+    let pawn_advance_dy = if board.turn_idx == 0 { 1 } else { -1 };
+
+    let idx = bm_to_idx(from);
+    let mut tos: BitMask;
+    if piece_idx == PIECE_PAWN {
+        // Single-move
+        tos = bm_shift(from, 0, pawn_advance_dy) & !occ_combined;
+
+        // Double-move
+        const STARTING_PAWNS_MASK: [BitMask; 2] = [bm_make_row(1), bm_make_row(6)];
+        if (from & STARTING_PAWNS_MASK[board.turn_idx]) != 0 {
+            tos |= bm_shift(tos, 0, pawn_advance_dy) & !occ_combined;
+        }
+
+        // Pawn attacks
+        let attack_tos = (generate_pawn_attacks_side::<0>(from, pawn_advance_dy)
+            | generate_pawn_attacks_side::<1>(from, pawn_advance_dy))
+            & (occ_opp | board.en_passant_mask);
+
+        tos |= attack_tos;
 
-    let move_mask: BitMask;
-    if num_checkers == 1 {
-        // Must block the check or capture the checker
-        move_mask =
-            lookup_gen::get_between_mask_inclusive(bm_to_idx(king), bm_to_idx(board.checkers));
+        if attack_tos & board.en_passant_mask != 0 {
+            // Check annoying edge case to make sure en passant is actually legal
+            if is_en_passant_pinned_horizontal(from, board, board.turn_idx, pawn_advance_dy) {
+                // En passant isn't legal!
+                tos &= !board.en_passant_mask;
+            }
+        }
     } else {
-        // No restrictions
-        // (Double checks will be handled separately)
-        move_mask = !0;
+        // Use lookup
+        tos = lookup_gen::get_piece_tos(piece_idx, from, idx, occ_combined);
     }
 
-    let pawn_advance_dy = if board.turn_idx == 0 { 1 } else { -1 };
+    // Ban capturing our own pieces
+    tos &= !occ_team;
+
+    if piece_idx == PIECE_KING {
+        // King cannot move into attacked areas
+        tos &= !board.attacks[1 - board.turn_idx];
+    } else {
+        if (board.pinned[board.turn_idx] & from) != 0 {
+            // Restrict to the path following the inverse direction of the pin from the king
+            // Thankfully no piece can jump over a square without leaving a pin, otherwise this would break
+            tos &= lookup_gen::get_ray_mask(bm_to_idx(king), idx);
+        }
+
+        if board.en_passant_mask != 0
+            && piece_idx == PIECE_PAWN
+            && board.checkers != 0
+            && (board.checkers == bm_shift(board.en_passant_mask, 0, -pawn_advance_dy))
+        {
+            // Check for the very special case where we can capture on en passant to stop check
+            // (See: "8/6k1/6p1/4NpPp/3PK2P/1r2P3/1br5/4RR2 w - f6 0 33")
+            tos &= move_mask | board.en_passant_mask;
+        } else {
+            // Crop tos to the move mask
+            // E.g. will require pieces to block checks/capture checkers
+            tos &= move_mask;
+        }
+    }
+
+    tos
+}
+
+// The rooks we can castle with right now, as a mask
+#[inline(always)]
+fn get_castle_rooks(board: &Board) -> BitMask {
+    let mut rooks = 0;
+    for castle_side in 0..2 {
+        if can_castle(castle_side, board, board.turn_idx, board.checkers != 0) {
+            rooks |= board.castle_rook_masks[board.turn_idx][castle_side];
+        }
+    }
+    rooks
+}
+
+// Finds the legal destination squares of each of our pieces, and calls on_tos(piece_idx, from, tos, is_castle) with them
+// Each castle is passed separately, as the king taking its rook, right before the king's other moves
+#[inline(always)]
+fn for_each_legal_tos(board: &Board, mut on_tos: impl FnMut(usize, BitMask, BitMask, bool)) {
+    let is_double_check = board.checkers.count_ones() > 1; // TODO: Don't need a full popcount, just >1 check
+    let move_mask = get_check_move_mask(board);
 
     for piece_idx in 0..NUM_PIECES {
-        if (num_checkers > 1) && (piece_idx != PIECE_KING) {
+        if is_double_check && (piece_idx != PIECE_KING) {
             // Multiple checks, king must move
             continue;
         }
 
         for from in bm_iter_bits(board.pieces[board.turn_idx][piece_idx]) {
-            let idx = bm_to_idx(from);
-            let mut tos: BitMask;
-            if piece_idx == PIECE_PAWN {
-                // Single-move
-                tos = bm_shift(from, 0, pawn_advance_dy) & !occ_combined;
-
-                // Double-move
-                const STARTING_PAWNS_MASK: [BitMask; 2] = [bm_make_row(1), bm_make_row(6)];
-                if (from & STARTING_PAWNS_MASK[board.turn_idx]) != 0 {
-                    tos |= bm_shift(tos, 0, pawn_advance_dy) & !occ_combined;
+            if piece_idx == PIECE_KING {
+                for rook in bm_iter_bits(get_castle_rooks(board)) {
+                    on_tos(PIECE_KING, from, rook, true);
                 }
+            }
 
-                // Pawn attacks
-                let attack_tos = (generate_pawn_attacks_side::<0>(from, pawn_advance_dy)
-                    | generate_pawn_attacks_side::<1>(from, pawn_advance_dy))
-                    & (occ_opp | board.en_passant_mask);
+            on_tos(
+                piece_idx,
+                from,
+                get_legal_tos(board, piece_idx, from, move_mask),
+                false,
+            );
+        }
+    }
+}
 
-                tos |= attack_tos;
+const PROMOTE_MASK: [BitMask; 2] = [bm_make_row(7), bm_make_row(0)];
 
-                if attack_tos & board.en_passant_mask != 0 {
-                    // Check annoying edge case to make sure en passant is actually legal
-                    if is_en_passant_pinned_horizontal(from, board, board.turn_idx, pawn_advance_dy)
-                    {
-                        // En passant isn't legal!
-                        tos &= !board.en_passant_mask;
-                    }
+// Flags of a move that isn't a castle or promotion
+#[inline(always)]
+fn get_move_flags(board: &Board, piece_idx: usize, from: BitMask, to: BitMask) -> u8 {
+    let mut flags: u8 = 0;
+    if piece_idx == PIECE_PAWN {
+        let pawn_advance_dy = if board.turn_idx == 0 { 1 } else { -1 };
+        if (to & board.en_passant_mask) != 0 {
+            flags = Move::FLAG_EN_PASSANT | Move::FLAG_CAPTURE
+        } else if to == bm_shift(from, 0, pawn_advance_dy * 2) {
+            flags = Move::FLAG_DOUBLE_PAWN_MOVE
+        }
+    }
+
+    if (to & board.occupancy[1 - board.turn_idx]) != 0 {
+        flags |= Move::FLAG_CAPTURE;
+    }
+    flags
+}
+
+// Adds the moves of a piece to each of the destination squares, which can't be castles
+#[inline(always)]
+fn push_moves(
+    board: &Board,
+    piece_idx: usize,
+    from: BitMask,
+    tos: BitMask,
+    out_move_set: &mut MoveBuffer,
+) {
+    for to in bm_iter_bits(tos) {
+        if piece_idx == PIECE_PAWN && (to & PROMOTE_MASK[board.turn_idx]) != 0 {
+            // Promotion
+            for to_piece_idx in 1..NUM_PIECES {
+                if to_piece_idx == PIECE_KING {
+                    continue; // Can't promote to king lol
                 }
-            } else {
-                // Use lookup
-                tos = lookup_gen::get_piece_tos(piece_idx, from, idx, occ_combined);
+
+                out_move_set.push(Move {
+                    from,
+                    to,
+                    from_piece_idx: PIECE_PAWN,
+                    to_piece_idx,
+                    flags: Move::FLAG_PROMOTION,
+                });
             }
+            continue;
+        }
 
-            // Ban capturing our own pieces
-            tos &= !occ_team;
+        out_move_set.push(Move {
+            from,
+            to,
+            from_piece_idx: piece_idx,
+            to_piece_idx: piece_idx,
+            flags: get_move_flags(board, piece_idx, from, to),
+        });
+    }
+}
 
-            if piece_idx == PIECE_KING {
-                // King cannot move into attacked areas
-                tos &= !board.attacks[1 - board.turn_idx];
-
-                for castle_side in 0..2 {
-                    if can_castle(castle_side, board, board.turn_idx, num_checkers != 0) {
-                        out_move_set.push(Move {
-                            from: king,
-                            to: if castle_side == 0 {
-                                bm_shift(king, -2, 0)
-                            } else {
-                                bm_shift(king, 2, 0)
-                            },
-                            from_piece_idx: PIECE_KING,
-                            to_piece_idx: PIECE_KING,
-                            flags: Move::FLAG_CASTLE,
-                        });
-                    }
-                }
-            } else {
-                if (board.pinned[board.turn_idx] & from) != 0 {
-                    // Restrict to the path following the inverse direction of the pin from the king
-                    // Thankfully no piece can jump over a square without leaving a pin, otherwise this would break
-                    tos &= lookup_gen::get_ray_mask(bm_to_idx(king), idx);
-                }
+// Generates the legal moves that are loud (captures and promotions) if LOUD, and/or quiet if QUIET
+#[inline(always)]
+fn generate_moves_of_kind<const LOUD: bool, const QUIET: bool>(
+    board: &Board,
+    out_move_set: &mut MoveBuffer,
+) {
+    let loud_mask = board.occupancy[1 - board.turn_idx];
+    let pawn_loud_mask = loud_mask | board.en_passant_mask | PROMOTE_MASK[board.turn_idx];
+
+    for_each_legal_tos(board, |piece_idx, from, tos, is_castle| {
+        if is_castle {
+            if !QUIET {
+                return;
+            }
+            out_move_set.push(Move {
+                from,
+                to: tos,
+                from_piece_idx: PIECE_KING,
+                to_piece_idx: PIECE_KING,
+                flags: Move::FLAG_CASTLE,
+            });
+            return;
+        }
+
+        let kind_mask = if piece_idx == PIECE_PAWN {
+            pawn_loud_mask
+        } else {
+            loud_mask
+        };
+        let tos = match (LOUD, QUIET) {
+            (true, true) => tos,
+            (true, false) => tos & kind_mask,
+            (false, true) => tos & !kind_mask,
+            (false, false) => 0,
+        };
+
+        push_moves(board, piece_idx, from, tos, out_move_set);
+    });
+}
 
-                if board.en_passant_mask != 0
-                    && piece_idx == PIECE_PAWN
-                    && board.checkers != 0
-                    && (board.checkers == bm_shift(board.en_passant_mask, 0, -pawn_advance_dy))
+pub fn generate_moves(board: &Board, out_move_set: &mut MoveBuffer) {
+    generate_moves_of_kind::<true, true>(board, out_move_set);
+}
+
+// Only the moves that aren't quiet (see Move::is_quiet()), so captures and promotions
+pub fn generate_captures(board: &Board, out_move_set: &mut MoveBuffer) {
+    generate_moves_of_kind::<true, false>(board, out_move_set);
+}
+
+// Only the quiet moves (see Move::is_quiet()), including castles
+pub fn generate_quiets(board: &Board, out_move_set: &mut MoveBuffer) {
+    generate_moves_of_kind::<false, true>(board, out_move_set);
+}
+
+// Every legal move when in check, in the same order as generate_moves()
+// Faster since pinned pieces are skipped (they can never stop a check), as are pieces that can't reach the check's path
+pub fn generate_evasions(board: &Board, out_move_set: &mut MoveBuffer) {
+    debug_assert!(board.checkers != 0);
+
+    let move_mask = get_check_move_mask(board);
+    if board.checkers.count_ones() == 1 {
+        let movable = board.occupancy[board.turn_idx] & !board.pinned[board.turn_idx];
+        for piece_idx in 0..PIECE_KING {
+            for from in bm_iter_bits(board.pieces[board.turn_idx][piece_idx] & movable) {
+                if piece_idx != PIECE_PAWN
+                    && (lookup_gen::get_piece_base_tos(piece_idx, bm_to_idx(from)) & move_mask) == 0
                 {
-                    // Check for the very special case where we can capture on en passant to stop check
-                    // (See: "8/6k1/6p1/4NpPp/3PK2P/1r2P3/1br5/4RR2 w - f6 0 33")
-                    tos &= move_mask | board.en_passant_mask;
-                } else {
-                    // Crop tos to the move mask
-                    // E.g. will require pieces to block checks/capture checkers
-                    tos &= move_mask;
+                    continue;
                 }
+
+                let tos = get_legal_tos(board, piece_idx, from, move_mask);
+                push_moves(board, piece_idx, from, tos, out_move_set);
             }
+        }
+    }
 
-            for to in bm_iter_bits(tos) {
-                let mut flags: u8 = 0;
-                if piece_idx == PIECE_PAWN {
-                    const PROMOTE_MASK: [BitMask; 2] = [bm_make_row(7), bm_make_row(0)];
-                    if (to & PROMOTE_MASK[board.turn_idx]) != 0 {
-                        // Promotion
-                        for to_piece_idx in 1..NUM_PIECES {
-                            if to_piece_idx == PIECE_KING {
-                                continue; // Can't promote to king lol
-                            }
-
-                            out_move_set.push(Move {
-                                from,
-                                to,
-                                from_piece_idx: PIECE_PAWN,
-                                to_piece_idx,
-                                flags: Move::FLAG_PROMOTION,
-                            });
-                        }
-                        continue;
-                    } else if (to & board.en_passant_mask) != 0 {
-                        flags = Move::FLAG_EN_PASSANT | Move::FLAG_CAPTURE
-                    } else if to == bm_shift(from, 0, pawn_advance_dy * 2) {
-                        flags = Move::FLAG_DOUBLE_PAWN_MOVE
-                    }
-                }
+    // Castling out of check isn't allowed, so these are all of the king's moves
+    let king = board.pieces[board.turn_idx][PIECE_KING];
+    let tos = get_legal_tos(board, PIECE_KING, king, move_mask);
+    push_moves(board, PIECE_KING, king, tos, out_move_set);
+}
+
+// Whether a pseudo-legal move (see Board::is_pseudo_legal()) is legal, without generating every move
+pub fn is_legal(board: &Board, mv: &Move) -> bool {
+    if mv.has_flag(Move::FLAG_CASTLE) {
+        return (get_castle_rooks(board) & mv.to) != 0;
+    }
+
+    if board.checkers.count_ones() > 1 && mv.from_piece_idx != PIECE_KING {
+        // Multiple checks, king must move
+        return false;
+    }
+
+    let tos = get_legal_tos(
+        board,
+        mv.from_piece_idx,
+        mv.from,
+        get_check_move_mask(board),
+    );
+    (tos & mv.to) != 0
+}
+
+// Same as generate_moves().len(), but faster since no moves are made
+// See: https://www.chessprogramming.org/Perft#Bulk-counting
+pub fn count_moves(board: &Board) -> usize {
+    let mut num_moves = 0;
+    for_each_legal_tos(board, |piece_idx, _from, tos, _is_castle| {
+        num_moves += tos.count_ones() as usize;
+        if piece_idx == PIECE_PAWN {
+            // Each promotion square is 4 moves, one for each piece
+            num_moves += ((tos & PROMOTE_MASK[board.turn_idx]).count_ones() as usize) * 3;
+        }
+    });
+    num_moves
+}
+
+// Legal moves generated one at a time, in the same order as generate_moves()
+// Each piece's moves are only found once the previous piece's moves have been used up, so stopping early skips the rest
+pub struct LegalMoves<'a> {
+    board: &'a Board,
+    move_mask: BitMask,
+    piece_idx: usize,
+    froms: ItrBits, // Pieces of piece_idx we haven't gotten to yet
+    from: BitMask,
+    castle_rooks: ItrBits, // Castles left for the current piece (only ever the king)
+    tos: ItrBits,          // Destinations left for the current piece
+    promotion_to: BitMask, // Promotion square we're part way through
+    next_promotion_piece_idx: usize,
+}
+
+impl<'a> LegalMoves<'a> {
+    pub fn new(board: &'a Board) -> LegalMoves<'a> {
+        LegalMoves {
+            board,
+            move_mask: get_check_move_mask(board),
+            piece_idx: PIECE_PAWN,
+            froms: bm_iter_bits(if board.checkers.count_ones() > 1 {
+                0 // Multiple checks, king must move
+            } else {
+                board.pieces[board.turn_idx][PIECE_PAWN]
+            }),
+            from: 0,
+            castle_rooks: bm_iter_bits(0),
+            tos: bm_iter_bits(0),
+            promotion_to: 0,
+            next_promotion_piece_idx: 0,
+        }
+    }
+}
 
-                if (to & board.occupancy[1 - board.turn_idx]) != 0 {
-                    flags |= Move::FLAG_CAPTURE;
+impl Iterator for LegalMoves<'_> {
+    type Item = Move;
+    fn next(&mut self) -> Option<Move> {
+        let board = self.board;
+        loop {
+            if self.promotion_to != 0 {
+                let to = self.promotion_to;
+                let to_piece_idx = self.next_promotion_piece_idx;
+                self.next_promotion_piece_idx += 1;
+                if self.next_promotion_piece_idx == PIECE_KING {
+                    // Can't promote to king
+                    self.promotion_to = 0;
                 }
+                return Some(Move {
+                    from: self.from,
+                    to,
+                    from_piece_idx: PIECE_PAWN,
+                    to_piece_idx,
+                    flags: Move::FLAG_PROMOTION,
+                });
+            }
 
-                out_move_set.push(Move {
-                    from,
+            if let Some(to) = self.castle_rooks.next() {
+                return Some(Move {
+                    from: self.from,
                     to,
-                    from_piece_idx: piece_idx,
-                    to_piece_idx: piece_idx,
-                    flags,
+                    from_piece_idx: PIECE_KING,
+                    to_piece_idx: PIECE_KING,
+                    flags: Move::FLAG_CASTLE,
                 });
             }
+
+            if let Some(to) = self.tos.next() {
+                if self.piece_idx == PIECE_PAWN && (to & PROMOTE_MASK[board.turn_idx]) != 0 {
+                    self.promotion_to = to;
+                    self.next_promotion_piece_idx = PIECE_KNIGHT;
+                    continue;
+                }
+                return Some(Move {
+                    from: self.from,
+                    to,
+                    from_piece_idx: self.piece_idx,
+                    to_piece_idx: self.piece_idx,
+                    flags: get_move_flags(board, self.piece_idx, self.from, to),
+                });
+            }
+
+            if let Some(from) = self.froms.next() {
+                self.from = from;
+                if self.piece_idx == PIECE_KING {
+                    self.castle_rooks = bm_iter_bits(get_castle_rooks(board));
+                }
+                self.tos = bm_iter_bits(get_legal_tos(board, self.piece_idx, from, self.move_mask));
+                continue;
+            }
+
+            // On to the next piece type
+            if self.piece_idx == PIECE_KING {
+                return None;
+            }
+            self.piece_idx += 1;
+            if board.checkers.count_ones() > 1 {
+                // Multiple checks, king must move
+                self.piece_idx = PIECE_KING;
+            }
+            self.froms = bm_iter_bits(board.pieces[board.turn_idx][self.piece_idx]);
         }
     }
 }
@@ -0,0 +1,190 @@
+use crate::bitmask::*;
+use crate::board::*;
+use crate::eval::*;
+use crate::move_gen;
+use crate::search::SearchInfo;
+
+// Hands out the legal moves of a position from most to least promising, generating them in stages
+// Most cutoffs happen on one of the first few moves, so the quiet moves often never need to be generated
+// The order is: table move, good loud moves, killer moves, quiet moves, bad loud moves
+// See: https://www.chessprogramming.org/Move_Generation#Staged_Move_Generation
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Stage {
+    TableMove,
+    GenerateLoud,
+    GoodLoud,
+    Killers,
+    GenerateQuiet,
+    Quiet,
+    BadLoud,
+    Done,
+}
+
+pub struct MovePicker {
+    stage: Stage,
+    table_move: Option<Move>, // Must already be checked to be legal
+    killers: [EncodedMove; 2],
+    picked_killers: [EncodedMove; 2], // So the quiet stage doesn't hand them out twice
+    next_killer_idx: usize,
+
+    // Loud moves are at the start of the buffer, then the quiet moves
+    moves: move_gen::MoveBuffer,
+    evals: [Value; move_gen::MAX_MOVES],
+    next_idx: usize,
+    loud_end: usize,
+    bad_loud_idx: usize, // Where the loud moves we skipped for being bad start
+}
+
+impl MovePicker {
+    pub fn new(table_move: Option<Move>, killers: [EncodedMove; 2]) -> MovePicker {
+        MovePicker {
+            stage: Stage::TableMove,
+            table_move,
+            killers,
+            picked_killers: [ENCODED_MOVE_NONE; 2],
+            next_killer_idx: 0,
+            moves: move_gen::MoveBuffer::new(),
+            evals: [0; move_gen::MAX_MOVES],
+            next_idx: 0,
+            loud_end: 0,
+            bad_loud_idx: 0,
+        }
+    }
+
+    // Goes back to the first move, keeping the same table move and killers
+    pub fn reset(&mut self) {
+        self.stage = Stage::TableMove;
+        self.picked_killers = [ENCODED_MOVE_NONE; 2];
+        self.next_killer_idx = 0;
+        self.moves.clear();
+        self.next_idx = 0;
+        self.loud_end = 0;
+        self.bad_loud_idx = 0;
+    }
+
+    fn is_table_move(&self, mv: &Move) -> bool {
+        self.table_move
+            .is_some_and(|table_move| table_move.encode() == mv.encode())
+    }
+
+    // Swaps the best move left in the range to the front of it, and returns its index
+    fn select_best(&mut self, start: usize, end: usize) -> usize {
+        let mut best_idx = start;
+        for i in (start + 1)..end {
+            if self.evals[i] > self.evals[best_idx] {
+                best_idx = i;
+            }
+        }
+
+        let best_move = self.moves[best_idx];
+        self.moves[best_idx] = self.moves[start];
+        self.moves[start] = best_move;
+        self.evals.swap(best_idx, start);
+        start
+    }
+
+    pub fn next(&mut self, board: &Board, search_info: &SearchInfo) -> Option<Move> {
+        loop {
+            match self.stage {
+                Stage::TableMove => {
+                    self.stage = Stage::GenerateLoud;
+                    if self.table_move.is_some() {
+                        return self.table_move;
+                    }
+                }
+                Stage::GenerateLoud => {
+                    move_gen::generate_captures(board, &mut self.moves);
+                    self.loud_end = self.moves.len();
+                    for i in 0..self.loud_end {
+                        self.evals[i] = eval_move(board, &self.moves[i]);
+                    }
+                    self.stage = Stage::GoodLoud;
+                }
+                Stage::GoodLoud => {
+                    if self.next_idx >= self.loud_end {
+                        self.bad_loud_idx = self.loud_end;
+                        self.stage = Stage::Killers;
+                        continue;
+                    }
+
+                    let idx = self.select_best(self.next_idx, self.loud_end);
+                    if self.evals[idx] < 0 {
+                        // Everything left is a losing capture or an underpromotion, so try those last
+                        self.bad_loud_idx = idx;
+                        self.stage = Stage::Killers;
+                        continue;
+                    }
+
+                    self.next_idx += 1;
+                    let mv = self.moves[idx];
+                    if !self.is_table_move(&mv) {
+                        return Some(mv);
+                    }
+                }
+                Stage::Killers => {
+                    if self.next_killer_idx >= self.killers.len() {
+                        self.stage = Stage::GenerateQuiet;
+                        continue;
+                    }
+
+                    let killer = self.killers[self.next_killer_idx];
+                    self.next_killer_idx += 1;
+
+                    // Killers come from other positions, so they might not be playable here
+                    let Some(mv) = board.decode_move(killer) else {
+                        continue;
+                    };
+                    if mv.is_quiet()
+                        && !self.is_table_move(&mv)
+                        && !self.picked_killers.contains(&killer)
+                        && board.is_pseudo_legal(&mv)
+                        && move_gen::is_legal(board, &mv)
+                    {
+                        self.picked_killers[self.next_killer_idx - 1] = killer;
+                        return Some(mv);
+                    }
+                }
+                Stage::GenerateQuiet => {
+                    move_gen::generate_quiets(board, &mut self.moves);
+                    for i in self.loud_end..self.moves.len() {
+                        let mv = &self.moves[i];
+                        let history_value = search_info.history_values[board.turn_idx]
+                            [mv.from_piece_idx][bm_to_idx(mv.to)];
+                        self.evals[i] = eval_move(board, mv) + (history_value * 2.0) as Value;
+                    }
+                    self.next_idx = self.loud_end;
+                    self.stage = Stage::Quiet;
+                }
+                Stage::Quiet => {
+                    if self.next_idx >= self.moves.len() {
+                        self.next_idx = self.bad_loud_idx;
+                        self.stage = Stage::BadLoud;
+                        continue;
+                    }
+
+                    let idx = self.select_best(self.next_idx, self.moves.len());
+                    self.next_idx += 1;
+                    let mv = self.moves[idx];
+                    if !self.is_table_move(&mv) && !self.picked_killers.contains(&mv.encode()) {
+                        return Some(mv);
+                    }
+                }
+                Stage::BadLoud => {
+                    if self.next_idx >= self.loud_end {
+                        self.stage = Stage::Done;
+                        continue;
+                    }
+
+                    let idx = self.select_best(self.next_idx, self.loud_end);
+                    self.next_idx += 1;
+                    let mv = self.moves[idx];
+                    if !self.is_table_move(&mv) {
+                        return Some(mv);
+                    }
+                }
+                Stage::Done => return None,
+            }
+        }
+    }
+}
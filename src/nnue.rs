@@ -0,0 +1,220 @@
+use crate::bitmask::*;
+use crate::board::*;
+use crate::eval::{Value, VALUE_CHECKMATE_MIN};
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicPtr, AtomicU32, Ordering};
+
+// Efficiently updatable neural network evaluation
+// Architecture is (768 -> HIDDEN_SIZE)x2 -> 1, with an accumulator for each team's perspective
+// Network files are raw little-endian i16s: feature weights, feature biases, output weights, output bias
+// See: https://www.chessprogramming.org/NNUE
+
+pub const NUM_FEATURES: usize = 2 * NUM_PIECES * 64; // Team, piece, position
+pub const HIDDEN_SIZE: usize = 128;
+
+// Quantization
+const QA: i32 = 255;
+const QB: i32 = 64;
+const EVAL_SCALE: i32 = 400; // Output is in centipawns
+
+pub struct Network {
+    id: u32, // Unique for every loaded network, so accumulators know which network they were made with
+    feature_weights: Vec<[i16; HIDDEN_SIZE]>,
+    feature_biases: [i16; HIDDEN_SIZE],
+    output_weights: [[i16; HIDDEN_SIZE]; 2], // Side to move, then the other side
+    output_bias: i16,
+}
+
+static NEXT_NETWORK_ID: AtomicU32 = AtomicU32::new(1);
+
+impl Network {
+    #[cfg(feature = "std")]
+    pub fn load(path: &str) -> std::io::Result<Network> {
+        use std::io::Read;
+
+        let mut bytes = Vec::new();
+        std::fs::File::open(path)?.read_to_end(&mut bytes)?;
+        Network::from_bytes(&bytes)
+            .map_err(|msg| std::io::Error::new(std::io::ErrorKind::InvalidData, msg))
+    }
+
+    // Reads a network from the contents of a network file, see load()
+    pub fn from_bytes(bytes: &[u8]) -> Result<Network, String> {
+        // Trainers often pad the file to a multiple of 64 bytes
+        const NUM_VALUES: usize = NUM_FEATURES * HIDDEN_SIZE + HIDDEN_SIZE + 2 * HIDDEN_SIZE + 1;
+        if bytes.len() < NUM_VALUES * 2 || bytes.len() >= NUM_VALUES * 2 + 64 {
+            return Err(format!(
+                "expected a {}-byte network with {} hidden neurons, got {} bytes",
+                NUM_VALUES * 2,
+                HIDDEN_SIZE,
+                bytes.len()
+            ));
+        }
+
+        let mut values = bytes
+            .chunks_exact(2)
+            .map(|chunk| i16::from_le_bytes([chunk[0], chunk[1]]));
+        let mut read_neurons = || {
+            let mut neurons = [0; HIDDEN_SIZE];
+            for neuron in neurons.iter_mut() {
+                *neuron = values.next().unwrap();
+            }
+            neurons
+        };
+
+        let feature_weights = (0..NUM_FEATURES).map(|_| read_neurons()).collect();
+        let feature_biases = read_neurons();
+        let output_weights = [read_neurons(), read_neurons()];
+        let output_bias = values.next().unwrap();
+
+        Ok(Network {
+            id: NEXT_NETWORK_ID.fetch_add(1, Ordering::Relaxed),
+            feature_weights,
+            feature_biases,
+            output_weights,
+            output_bias,
+        })
+    }
+}
+
+// The network used for evaluation, or null if we're using the handcrafted eval
+static NETWORK: AtomicPtr<Network> = AtomicPtr::new(core::ptr::null_mut());
+
+pub fn get_network() -> Option<&'static Network> {
+    unsafe { NETWORK.load(Ordering::Acquire).as_ref() }
+}
+
+// NOTE: Must not be called during a search
+// The previous network is leaked, since boards may still be referencing it
+pub fn set_network(network: Option<Network>) {
+    let network_ptr = match network {
+        Some(network) => Box::into_raw(Box::new(network)),
+        None => core::ptr::null_mut(),
+    };
+    NETWORK.store(network_ptr, Ordering::Release);
+}
+
+// Each team sees the board from their own side, with their own pieces first
+fn get_feature_idx(
+    perspective_idx: usize,
+    team_idx: usize,
+    piece_idx: usize,
+    pos_idx: usize,
+) -> usize {
+    let (rel_team_idx, rel_pos_idx) = if perspective_idx == 0 {
+        (team_idx, pos_idx)
+    } else {
+        (1 - team_idx, pos_idx ^ 56)
+    };
+    (rel_team_idx * NUM_PIECES + piece_idx) * 64 + rel_pos_idx
+}
+
+// Sums of the first layer for both perspectives, updated with every move
+#[derive(Debug, Copy, Clone)]
+pub struct Accumulator {
+    pub values: [[i16; HIDDEN_SIZE]; 2],
+    pub network_id: u32, // 0 if no network was used
+}
+
+impl Accumulator {
+    pub const fn new() -> Accumulator {
+        Accumulator {
+            values: [[0; HIDDEN_SIZE]; 2],
+            network_id: 0,
+        }
+    }
+
+    pub fn is_valid_for(&self, network: &Network) -> bool {
+        self.network_id == network.id
+    }
+
+    pub fn refresh(&mut self, network: &Network, board: &Board) {
+        self.values = [network.feature_biases; 2];
+        self.network_id = network.id;
+
+        for team_idx in 0..2 {
+            for piece_idx in 0..NUM_PIECES {
+                for piece_mask in bm_iter_bits(board.pieces[team_idx][piece_idx]) {
+                    self.add_piece(network, team_idx, piece_idx, bm_to_idx(piece_mask));
+                }
+            }
+        }
+    }
+
+    pub fn add_piece(
+        &mut self,
+        network: &Network,
+        team_idx: usize,
+        piece_idx: usize,
+        pos_idx: usize,
+    ) {
+        for perspective_idx in 0..2 {
+            let weights = &network.feature_weights
+                [get_feature_idx(perspective_idx, team_idx, piece_idx, pos_idx)];
+            for (value, weight) in self.values[perspective_idx].iter_mut().zip(weights) {
+                *value = value.wrapping_add(*weight);
+            }
+        }
+    }
+
+    pub fn remove_piece(
+        &mut self,
+        network: &Network,
+        team_idx: usize,
+        piece_idx: usize,
+        pos_idx: usize,
+    ) {
+        for perspective_idx in 0..2 {
+            let weights = &network.feature_weights
+                [get_feature_idx(perspective_idx, team_idx, piece_idx, pos_idx)];
+            for (value, weight) in self.values[perspective_idx].iter_mut().zip(weights) {
+                *value = value.wrapping_sub(*weight);
+            }
+        }
+    }
+}
+
+impl Default for Accumulator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Squared clipped ReLU
+fn screlu(value: i16) -> i32 {
+    let clamped = (value as i32).clamp(0, QA);
+    clamped * clamped
+}
+
+// Evaluates the position from the perspective of the current turn
+pub fn evaluate(network: &Network, board: &Board) -> Value {
+    // The board's accumulator could be from before this network was loaded
+    let mut fresh_accumulator = Accumulator::new();
+    let accumulator = if board.accumulator.is_valid_for(network) {
+        &board.accumulator
+    } else {
+        fresh_accumulator.refresh(network, board);
+        &fresh_accumulator
+    };
+
+    let perspectives = [board.turn_idx, 1 - board.turn_idx];
+    let mut output: i64 = 0;
+    for (perspective_idx, output_weights) in perspectives.iter().zip(&network.output_weights) {
+        for (value, weight) in accumulator.values[*perspective_idx]
+            .iter()
+            .zip(output_weights)
+        {
+            output += (screlu(*value) as i64) * (*weight as i64);
+        }
+    }
+
+    let output = output / (QA as i64) + (network.output_bias as i64);
+    let centipawns = output * (EVAL_SCALE as i64) / ((QA * QB) as i64);
+
+    // A bad network shouldn't be able to produce mate evals
+    let max_eval = (VALUE_CHECKMATE_MIN - 1) as i64;
+    centipawns.clamp(-max_eval, max_eval) as Value
+}
@@ -0,0 +1,184 @@
+use crate::board::*;
+use crate::log_println;
+use crate::move_gen;
+use crate::zobrist::Hash;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+// Counts the leaf nodes of the move tree to a fixed depth, to check that move generation is correct
+// See: https://www.chessprogramming.org/Perft
+
+#[derive(Debug, Copy, Clone)]
+pub struct PerftSettings {
+    pub num_threads: usize,
+    pub print: bool, // Print the node count after each root move ("divide"), and the total
+    pub table_size_mbs: usize, // 0 to not use a table
+}
+
+impl PerftSettings {
+    pub fn new() -> PerftSettings {
+        PerftSettings {
+            num_threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            print: false,
+            table_size_mbs: 64,
+        }
+    }
+}
+
+impl Default for PerftSettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Node counts of positions at a certain depth, so subtrees reached through transpositions are only counted once
+// Shared between threads without locks, the key is XORed with the data so entries torn by another thread's write don't match
+// See: https://www.chessprogramming.org/Shared_Hash_Table#Lockless
+struct PerftEntry {
+    key: AtomicU64,
+    data: AtomicU64, // Node count in the upper 56 bits, depth in the lower 8
+}
+
+struct PerftTable {
+    entries: Vec<PerftEntry>,
+}
+
+impl PerftTable {
+    fn new(size_mbs: usize) -> PerftTable {
+        let num_entries = usize::max((size_mbs * 1024 * 1024) / size_of::<PerftEntry>(), 1);
+        let entries = (0..num_entries)
+            .map(|_| PerftEntry {
+                key: AtomicU64::new(0),
+                data: AtomicU64::new(0),
+            })
+            .collect();
+        PerftTable { entries }
+    }
+
+    fn get_entry(&self, hash: Hash) -> &PerftEntry {
+        &self.entries[(hash % (self.entries.len() as u64)) as usize]
+    }
+
+    fn get(&self, hash: Hash, depth: u8) -> Option<usize> {
+        let entry = self.get_entry(hash);
+        let data = entry.data.load(Ordering::Relaxed);
+        if (entry.key.load(Ordering::Relaxed) ^ data) == hash && (data as u8) == depth {
+            Some((data >> 8) as usize)
+        } else {
+            None
+        }
+    }
+
+    // Always replaces, deep entries are rare enough that they aren't worth protecting
+    fn set(&self, hash: Hash, depth: u8, count: usize) {
+        let entry = self.get_entry(hash);
+        let data = ((count as u64) << 8) | (depth as u64);
+        entry.key.store(hash ^ data, Ordering::Relaxed);
+        entry.data.store(data, Ordering::Relaxed);
+    }
+}
+
+fn _perft(board: &Board, depth: u8, table: Option<&PerftTable>) -> usize {
+    if depth == 0 {
+        return 1;
+    } else if depth == 1 {
+        // Bulk counting, the leaf moves are never made
+        return move_gen::count_moves(board);
+    }
+
+    if let Some(count) = table.and_then(|table| table.get(board.hash, depth)) {
+        return count;
+    }
+
+    let mut moves = move_gen::MoveBuffer::new();
+    move_gen::generate_moves(board, &mut moves);
+
+    let mut total = 0;
+    for mv in moves.iter() {
+        let mut next_board = *board;
+        next_board.do_move(mv);
+        total += _perft(&next_board, depth - 1, table);
+    }
+
+    if let Some(table) = table {
+        table.set(board.hash, depth, total);
+    }
+    total
+}
+
+// Single-threaded
+pub fn perft(board: &Board, depth: u8, print: bool) -> usize {
+    let settings = PerftSettings {
+        num_threads: 1,
+        print,
+        table_size_mbs: 0,
+    };
+    perft_with_settings(board, depth, &settings)
+}
+
+pub fn perft_with_settings(board: &Board, depth: u8, settings: &PerftSettings) -> usize {
+    let table = if settings.table_size_mbs > 0 && depth > 2 {
+        Some(PerftTable::new(settings.table_size_mbs))
+    } else {
+        None
+    };
+
+    // Without divide output or threads, there's no need to keep track of each root move
+    if !settings.print && settings.num_threads <= 1 {
+        return _perft(board, depth, table.as_ref());
+    }
+
+    let mut moves = move_gen::MoveBuffer::new();
+    move_gen::generate_moves(board, &mut moves);
+
+    let mut move_counts = vec![0; moves.len()];
+    if depth > 0 {
+        // Root moves are handed out one at a time, so threads that get small subtrees just take more of them
+        let next_move_idx = AtomicUsize::new(0);
+        let num_threads = settings.num_threads.clamp(1, usize::max(moves.len(), 1));
+        let thread_counts: Vec<Vec<(usize, usize)>> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..num_threads)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut counts = Vec::new();
+                        loop {
+                            let move_idx = next_move_idx.fetch_add(1, Ordering::Relaxed);
+                            if move_idx >= moves.len() {
+                                break counts;
+                            }
+
+                            let mut next_board = *board;
+                            next_board.do_move(&moves[move_idx]);
+                            let count = _perft(&next_board, depth - 1, table.as_ref());
+                            counts.push((move_idx, count));
+                        }
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect()
+        });
+
+        for (move_idx, count) in thread_counts.into_iter().flatten() {
+            move_counts[move_idx] = count;
+        }
+    }
+
+    let total = if depth > 0 {
+        move_counts.iter().sum()
+    } else {
+        1
+    };
+
+    if settings.print {
+        if depth > 0 {
+            for (mv, count) in moves.iter().zip(&move_counts) {
+                log_println!("{}: {}", mv.to_uci(board.is_chess960), count);
+            }
+        }
+        log_println!("\nNodes Searched: {}", total);
+    }
+
+    total
+}
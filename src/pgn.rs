@@ -0,0 +1,355 @@
+use crate::board::*;
+use crate::eval::*;
+use crate::fen;
+
+// Reads and writes games in PGN files
+// Only the main line is kept when reading, variations are skipped
+// See: https://www.chessprogramming.org/Portable_Game_Notation
+
+// Comments and NAGs (numeric annotation glyphs, like $1 for "!") that follow a move
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MoveAnnotation {
+    pub nags: Vec<u8>,
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    pub start_board: Board,
+    pub moves: Vec<Move>,
+    pub annotations: Vec<MoveAnnotation>, // One for each move
+    pub comment: Option<String>,          // Comment before the first move
+    pub result: Option<f32>, // For white, 1.0 for a win, 0.5 for a draw, 0.0 for a loss, None if unfinished
+}
+
+impl PgnGame {
+    pub fn new(start_board: Board) -> PgnGame {
+        PgnGame {
+            tags: Vec::new(),
+            start_board,
+            moves: Vec::new(),
+            annotations: Vec::new(),
+            comment: None,
+            result: None,
+        }
+    }
+
+    pub fn get_tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag_name, _)| tag_name == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    // Every position in the game, starting with the start board and ending with the position after the last move
+    pub fn get_boards(&self) -> Vec<Board> {
+        let mut boards = Vec::with_capacity(self.moves.len() + 1);
+        let mut board = self.start_board;
+        boards.push(board);
+        for mv in &self.moves {
+            board.do_move(mv);
+            boards.push(board);
+        }
+        boards
+    }
+
+    pub fn get_final_board(&self) -> Board {
+        let mut board = self.start_board;
+        for mv in &self.moves {
+            board.do_move(mv);
+        }
+        board
+    }
+
+    // Comments either go after the last move, or before the first if there are no moves yet
+    fn add_comment(&mut self, comment: &str) {
+        let target = match self.annotations.last_mut() {
+            Some(annotation) => &mut annotation.comment,
+            None => &mut self.comment,
+        };
+        match target {
+            Some(existing) => {
+                existing.push(' ');
+                existing.push_str(comment);
+            }
+            None => *target = Some(comment.to_string()),
+        }
+    }
+}
+
+// Move suffixes, which are short for the first 6 NAGs
+// See: https://en.wikipedia.org/wiki/Numeric_Annotation_Glyphs
+const SUFFIX_NAGS: [(&str, u8); 6] = [
+    ("!!", 3),
+    ("??", 4),
+    ("!?", 5),
+    ("?!", 6),
+    ("!", 1),
+    ("?", 2),
+];
+
+// The tags every PGN game should have, in order, with the value used when they're missing
+// See: https://en.wikipedia.org/wiki/Portable_Game_Notation#Tag_pairs
+const SEVEN_TAG_ROSTER: [(&str, &str); 7] = [
+    ("Event", "?"),
+    ("Site", "?"),
+    ("Date", "????.??.??"),
+    ("Round", "?"),
+    ("White", "?"),
+    ("Black", "?"),
+    ("Result", "*"),
+];
+
+// Lines in the movetext are wrapped at this length, like the PGN standard recommends
+const MAX_LINE_LEN: usize = 80;
+
+fn make_result_str(result: Option<f32>) -> &'static str {
+    match result {
+        Some(result) if result > 0.5 => "1-0",
+        Some(result) if result < 0.5 => "0-1",
+        Some(_) => "1/2-1/2",
+        None => "*",
+    }
+}
+
+// Makes an engine evaluation comment like "[%eval 0.35,12]" or "[%eval #-3]", which GUIs can show or graph
+// The eval should be from white's perspective
+pub fn make_eval_comment(white_eval: Value, depth: Option<u8>) -> String {
+    match depth {
+        Some(depth) => format!("[%eval {},{}]", eval_to_str(white_eval), depth),
+        None => format!("[%eval {}]", eval_to_str(white_eval)),
+    }
+}
+
+impl PgnGame {
+    // Writes the game as PGN, starting with the seven tag roster
+    // Games that don't start from the normal starting position also get a FEN tag
+    pub fn to_pgn(&self) -> String {
+        let mut tags: Vec<(String, String)> = Vec::new();
+        for (name, default_value) in SEVEN_TAG_ROSTER {
+            let value = if name == "Result" {
+                make_result_str(self.result)
+            } else {
+                self.get_tag(name).unwrap_or(default_value)
+            };
+            tags.push((name.to_string(), value.to_string()));
+        }
+
+        let start_fen = fen::make_fen(&self.start_board);
+        if start_fen != fen::make_fen(&Board::start_pos()) {
+            tags.push(("SetUp".to_string(), "1".to_string()));
+            tags.push(("FEN".to_string(), start_fen));
+        }
+
+        for (name, value) in &self.tags {
+            if !tags.iter().any(|(other_name, _)| other_name == name) {
+                tags.push((name.clone(), value.clone()));
+            }
+        }
+
+        let mut pgn = String::new();
+        for (name, value) in &tags {
+            let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+            pgn += &format!("[{} \"{}\"]\n", name, value);
+        }
+        pgn.push('\n');
+
+        let mut tokens = Vec::new();
+        if let Some(comment) = &self.comment {
+            tokens.push(format!("{{{}}}", comment));
+        }
+
+        let mut board = self.start_board;
+        let mut move_number = board.full_move_counter;
+        for (i, mv) in self.moves.iter().enumerate() {
+            if board.turn_idx == 0 {
+                tokens.push(format!("{}.", move_number));
+            } else if i == 0
+                || self
+                    .annotations
+                    .get(i - 1)
+                    .is_some_and(|a| a.comment.is_some())
+            {
+                // Black's moves need their number when it isn't right after white's move
+                tokens.push(format!("{}...", move_number));
+            }
+
+            tokens.push(mv.to_san(&board));
+            if let Some(annotation) = self.annotations.get(i) {
+                for nag in &annotation.nags {
+                    tokens.push(format!("${}", nag));
+                }
+                if let Some(comment) = &annotation.comment {
+                    tokens.push(format!("{{{}}}", comment));
+                }
+            }
+
+            if board.turn_idx == 1 {
+                move_number += 1;
+            }
+            board.do_move(mv);
+        }
+        tokens.push(make_result_str(self.result).to_string());
+
+        let mut line_len = 0;
+        for token in tokens {
+            if line_len > 0 && line_len + 1 + token.len() > MAX_LINE_LEN {
+                pgn.push('\n');
+                line_len = 0;
+            } else if line_len > 0 {
+                pgn.push(' ');
+                line_len += 1;
+            }
+            line_len += token.len();
+            pgn += &token;
+        }
+        pgn.push('\n');
+
+        pgn
+    }
+}
+
+fn parse_result(token: &str) -> Option<Option<f32>> {
+    match token {
+        "1-0" => Some(Some(1.0)),
+        "0-1" => Some(Some(0.0)),
+        "1/2-1/2" => Some(Some(0.5)),
+        "*" => Some(None),
+        _ => None,
+    }
+}
+
+// Parses every game in a PGN file
+pub fn parse_games(pgn: &str) -> Result<Vec<PgnGame>, String> {
+    let mut games = Vec::new();
+    let mut game = PgnGame::new(Board::start_pos());
+    let mut board = game.start_board;
+    let mut has_movetext = false;
+    let mut variation_depth = 0;
+
+    let mut chars = pgn.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '[' if variation_depth == 0 => {
+                // A tag after the moves means the last game had no result
+                if has_movetext {
+                    games.push(game);
+                    game = PgnGame::new(Board::start_pos());
+                    board = game.start_board;
+                    has_movetext = false;
+                }
+
+                let tag: String = chars.by_ref().take_while(|&ch| ch != ']').collect();
+                let Some((name, value)) = tag.split_once(' ') else {
+                    return Err(format!("invalid tag \"[{}]\"", tag));
+                };
+                let value = value.trim();
+                let value = value.strip_prefix('"').unwrap_or(value);
+                let value = value.strip_suffix('"').unwrap_or(value);
+                let value = value.replace("\\\"", "\"").replace("\\\\", "\\");
+
+                if name == "FEN" {
+                    board = fen::load_fen(&value).map_err(|err| err.to_string())?;
+                    game.start_board = board;
+                }
+                game.tags.push((name.to_string(), value));
+            }
+            '{' => {
+                let comment: String = chars.by_ref().take_while(|&ch| ch != '}').collect();
+                let comment = comment.split_whitespace().collect::<Vec<&str>>().join(" ");
+                if variation_depth == 0 && !comment.is_empty() {
+                    game.add_comment(&comment);
+                }
+            }
+            ';' => {
+                let comment: String = chars.by_ref().take_while(|&ch| ch != '\n').collect();
+                if variation_depth == 0 && !comment.trim().is_empty() {
+                    game.add_comment(comment.trim());
+                }
+            }
+            '(' => variation_depth += 1,
+            ')' => variation_depth = usize::saturating_sub(variation_depth, 1),
+            _ if ch.is_whitespace() => {}
+            _ => {
+                let mut token = ch.to_string();
+                while let Some(&next_ch) = chars.peek() {
+                    if next_ch.is_whitespace() || "{}();[".contains(next_ch) {
+                        break;
+                    }
+                    token.push(next_ch);
+                    chars.next();
+                }
+
+                if variation_depth > 0 {
+                    continue;
+                }
+
+                if let Some(nag_str) = token.strip_prefix('$') {
+                    let Ok(nag) = nag_str.parse::<u8>() else {
+                        return Err(format!("invalid NAG \"{}\"", token));
+                    };
+                    if let Some(annotation) = game.annotations.last_mut() {
+                        annotation.nags.push(nag);
+                    }
+                    continue;
+                }
+
+                if let Some(result) = parse_result(&token) {
+                    game.result = result;
+                    games.push(game);
+                    game = PgnGame::new(Board::start_pos());
+                    board = game.start_board;
+                    has_movetext = false;
+                    continue;
+                }
+
+                // Move numbers can be stuck to the move, like "1.e4"
+                let san = match token.rfind('.') {
+                    Some(dot_idx) => &token[dot_idx + 1..],
+                    None => &token,
+                };
+                if san.is_empty() {
+                    continue;
+                }
+
+                let Some(mv) = board.parse_san(san) else {
+                    return Err(format!(
+                        "invalid move \"{}\" in game {} for position \"{}\"",
+                        san,
+                        games.len() + 1,
+                        fen::make_fen(&board)
+                    ));
+                };
+                board.do_move(&mv);
+                game.moves.push(mv);
+
+                let mut annotation = MoveAnnotation::default();
+                if let Some(&(_, nag)) = SUFFIX_NAGS
+                    .iter()
+                    .find(|(suffix, _)| san.trim_end_matches(['+', '#']).ends_with(suffix))
+                {
+                    annotation.nags.push(nag);
+                }
+                game.annotations.push(annotation);
+                has_movetext = true;
+            }
+        }
+    }
+
+    if has_movetext {
+        games.push(game);
+    }
+
+    Ok(games)
+}
+
+pub fn load(path: &str) -> std::io::Result<Vec<PgnGame>> {
+    let pgn = std::fs::read_to_string(path)?;
+    parse_games(&pgn).map_err(|msg| std::io::Error::new(std::io::ErrorKind::InvalidData, msg))
+}
+
+pub fn save(games: &[PgnGame], path: &str) -> std::io::Result<()> {
+    let pgns: Vec<String> = games.iter().map(|game| game.to_pgn()).collect();
+    std::fs::write(path, pgns.join("\n"))
+}
@@ -2,159 +2,822 @@ use crate::bitmask::*;
 use crate::board::*;
 use crate::eval::*;
 use crate::move_gen;
-use crate::thread_flag::ThreadFlag;
+use crate::move_picker::MovePicker;
+use crate::search_params;
+pub use crate::thread_flag::ThreadFlag;
 use crate::transpos;
 use crate::zobrist::Hash;
 use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
 
-fn _perft(board: &Board, depth: u8, depth_elapsed: usize, print: bool) -> usize {
-    let mut moves = move_gen::MoveBuffer::new();
-    move_gen::generate_moves(&board, &mut moves);
-    if depth > 1 {
-        let mut total: usize = 0;
-        for mv in moves.iter() {
-            let mut next_board: Board = *board;
-            next_board.do_move(&mv);
-            let sub_total = _perft(&next_board, depth - 1, depth_elapsed + 1, print);
-            if depth_elapsed == 0 && print {
-                println!("{}: {}", mv, sub_total);
-            }
-            total += sub_total;
-        }
-        if depth_elapsed == 0 && print {
-            println!("\nNodes Searched: {}", total);
+// Contempt makes a draw look bad for the side to move at the root, and good for their opponent
+// See: https://www.chessprogramming.org/Contempt_Factor
+fn get_draw_eval(search_info: &SearchInfo, depth_elapsed: i64) -> Value {
+    if depth_elapsed % 2 == 0 {
+        -search_info.contempt
+    } else {
+        search_info.contempt
+    }
+}
+
+// Plies without a capture or pawn move until the game is drawn
+// See: https://www.chessprogramming.org/Fifty-move_Rule
+pub const FIFTY_MOVE_RULE_PLIES: u8 = 100;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GameResult {
+    InProgress,
+    Checkmate, // The side to move lost
+    Stalemate,
+    FiftyMoveRule,
+    InsufficientMaterial,
+    Repetition, // Only from Game::result(), since it needs the game history
+}
+
+// Determines if the game is over in this position
+// NOTE: Repetitions aren't included, since they depend on the game history (see Game::result())
+pub fn get_game_result(board: &Board) -> GameResult {
+    if board.legal_moves().next().is_none() {
+        if board.checkers != 0 {
+            GameResult::Checkmate
+        } else {
+            GameResult::Stalemate
         }
-        total
-    } else if depth == 1 {
-        if depth_elapsed == 0 && print {
-            for mv in moves.iter() {
-                println!("{}: {}", mv, 1);
+    } else if board.half_move_counter >= FIFTY_MOVE_RULE_PLIES {
+        // Checkmate takes priority over the fifty-move rule, which is why we check this after
+        GameResult::FiftyMoveRule
+    } else if is_insufficient_material(board) {
+        GameResult::InsufficientMaterial
+    } else {
+        GameResult::InProgress
+    }
+}
+
+// Checks if this position already occurred, either earlier in the search or earlier in the game
+// A repetition inside the search counts as a draw, since whoever allowed it could just repeat it again
+// A repetition of positions from before the root has to be an actual threefold repetition
+fn is_repetition(board: &Board, search_info: &SearchInfo, depth_elapsed: i64) -> bool {
+    let mut num_game_repetitions = 0;
+
+    // Positions from before the last capture or pawn move can't be repeated
+    let max_plies_back = board.half_move_counter as i64;
+
+    // Only positions with the same side to move can match, and it takes at least 4 plies to get back to one
+    let mut plies_back = 4;
+    while plies_back <= max_plies_back {
+        let prev_hash = if plies_back <= depth_elapsed {
+            search_info.depth_hashes[(depth_elapsed - plies_back) as usize]
+        } else {
+            let history_idx = (search_info.game_hashes.len() as i64) - (plies_back - depth_elapsed);
+            if history_idx < 0 {
+                break;
+            }
+            search_info.game_hashes[history_idx as usize]
+        };
+
+        if prev_hash == board.hash {
+            if plies_back <= depth_elapsed {
+                return true;
+            }
+
+            num_game_repetitions += 1;
+            if num_game_repetitions >= 2 {
+                return true;
             }
-            println!("\nNodes Searched: {}", moves.len());
         }
-        moves.len()
+
+        plies_back += 2;
+    }
+
+    false
+}
+
+fn get_no_moves_eval(board: &Board, search_info: &SearchInfo, depth_elapsed: i64) -> Value {
+    if board.checkers != 0 {
+        // Checkmated, mates further from the root are less bad
+        -VALUE_CHECKMATE + (depth_elapsed as Value)
     } else {
-        if depth_elapsed == 0 && print {
-            println!("\nNodes Searched: 1");
+        get_draw_eval(search_info, depth_elapsed)
+    }
+}
+
+// The expected type of a node, before it's searched
+// See: https://www.chessprogramming.org/Node_Types
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum NodeType {
+    PV,
+    Cut,
+    All,
+}
+
+// Counts of what the search did, only used for diagnostics (like the UCI "debug" command)
+#[derive(Debug, Copy, Clone, Default)]
+pub struct SearchStats {
+    pub table_probes: usize,
+    pub table_hits: usize,    // Probes that found a valid entry
+    pub table_cutoffs: usize, // Hits that ended the search of the node
+
+    // Nodes that were cut off without searching their moves
+    pub reverse_futility_prunes: usize,
+    pub razor_prunes: usize,
+    pub null_move_prunes: usize,
+    pub probcut_prunes: usize,
+    pub multi_cut_prunes: usize,
+
+    // Moves that were skipped
+    pub futility_prunes: usize,
+    pub late_move_prunes: usize,
+}
+
+// A finished search of one line at one depth
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SearchProgress {
+    pub depth: u8,
+    pub sel_depth: usize,
+    pub multipv: usize, // Which line this is with MultiPV, starting at 1
+    pub eval: Value,
+    pub pv: Vec<Move>,
+    pub total_nodes: usize, // Over the whole search so far, not just this depth
+    pub elapsed_time: f64,  // In seconds
+    pub hashfull: usize,    // Permill of the table that's used, see transpos::Table::get_hashfull()
+}
+
+impl SearchProgress {
+    // The line number, total nodes, and elapsed time only cover this one search, so callers should fix them up
+    pub fn from_search_info(
+        board: &Board,
+        table: &transpos::Table,
+        depth: u8,
+        eval: Value,
+        search_info: &SearchInfo,
+    ) -> SearchProgress {
+        let mut moves = move_gen::MoveBuffer::new();
+        move_gen::generate_moves(board, &mut moves);
+        let root_move = moves[search_info.root_best_move_idx as usize];
+
+        // The PV always starts with the best move, but play it safe if the search didn't finish
+        let pv = search_info.get_pv();
+        let pv = if pv
+            .first()
+            .is_some_and(|mv| mv.encode() == root_move.encode())
+        {
+            pv.to_vec()
+        } else {
+            vec![root_move]
+        };
+
+        SearchProgress {
+            depth,
+            sel_depth: search_info.sel_depth,
+            multipv: 1,
+            eval,
+            pv,
+            total_nodes: search_info.total_nodes,
+            elapsed_time: 0.0,
+            hashfull: table.get_hashfull(),
         }
-        return 1;
     }
 }
 
-pub fn perft(board: &Board, depth: u8, print: bool) -> usize {
-    _perft(board, depth, 0, print)
+// Gets told how a search is going, so library users don't have to parse UCI output
+// The UCI and XBoard modules are just listeners that print what they get
+// Every method does nothing by default, so only the needed ones have to be implemented
+// The board is always the root board of the search
+pub trait SearchListener {
+    // Every line of every finished depth, so this is called more than once per depth with MultiPV
+    fn on_new_pv(&mut self, _board: &Board, _progress: &SearchProgress) {}
+
+    // Once all lines of a depth are finished, with the best line
+    fn on_depth_complete(&mut self, _board: &Board, _progress: &SearchProgress) {}
+
+    // The root move that's about to be searched, at most once every CurrentMoveReporter::REPORT_INTERVAL
+    // The move number is its place in the search order, starting at 1
+    fn on_current_move(&mut self, _board: &Board, _mv: &Move, _move_number: usize, _depth: u8) {}
+
+    // The move to play, and the reply we expect (for pondering) if there is one
+    fn on_finish(&mut self, _board: &Board, _best_move: Move, _ponder_move: Option<Move>) {}
+
+    // Instead of on_finish(), if the game is already over by checkmate or stalemate
+    fn on_no_legal_moves(&mut self, _board: &Board, _game_result: GameResult) {}
 }
 
-//////////////////////////////////////////////////////////////////////////
+// For when nobody is listening
+impl SearchListener for () {}
 
-fn get_no_moves_eval(board: &Board) -> Value {
-    if board.checkers != 0 {
-        -VALUE_CHECKMATE
-    } else {
-        0.0
+// A listener that can be handed to search threads
+pub type SharedSearchListener = Arc<Mutex<dyn SearchListener + Send>>;
+
+// Only locks for each call, so the listener can still be looked at during the search
+impl SearchListener for SharedSearchListener {
+    fn on_new_pv(&mut self, board: &Board, progress: &SearchProgress) {
+        self.lock().unwrap().on_new_pv(board, progress);
+    }
+
+    fn on_depth_complete(&mut self, board: &Board, progress: &SearchProgress) {
+        self.lock().unwrap().on_depth_complete(board, progress);
+    }
+
+    fn on_current_move(&mut self, board: &Board, mv: &Move, move_number: usize, depth: u8) {
+        self.lock()
+            .unwrap()
+            .on_current_move(board, mv, move_number, depth);
+    }
+
+    fn on_finish(&mut self, board: &Board, best_move: Move, ponder_move: Option<Move>) {
+        self.lock()
+            .unwrap()
+            .on_finish(board, best_move, ponder_move);
+    }
+
+    fn on_no_legal_moves(&mut self, board: &Board, game_result: GameResult) {
+        self.lock().unwrap().on_no_legal_moves(board, game_result);
+    }
+}
+
+// Reports which root move is being searched, so long iterations still show some progress
+#[derive(Clone)]
+pub struct CurrentMoveReporter {
+    pub listener: SharedSearchListener,
+    pub next_report_time: std::time::Instant,
+}
+
+impl CurrentMoveReporter {
+    pub const REPORT_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+    // The first report is one interval after the start, since short searches don't need any
+    pub fn new(
+        listener: SharedSearchListener,
+        start_time: std::time::Instant,
+    ) -> CurrentMoveReporter {
+        CurrentMoveReporter {
+            listener,
+            next_report_time: start_time + Self::REPORT_INTERVAL,
+        }
+    }
+
+    fn maybe_report(&mut self, board: &Board, mv: &Move, move_number: usize, depth: u8) {
+        let now = std::time::Instant::now();
+        if now >= self.next_report_time {
+            self.listener
+                .lock()
+                .unwrap()
+                .on_current_move(board, mv, move_number, depth);
+            self.next_report_time = now + Self::REPORT_INTERVAL;
+        }
     }
 }
 
 pub struct SearchInfo {
     pub total_nodes: usize,
+    pub sel_depth: usize, // Highest ply reached, including extension search
     pub depth_hashes: [Hash; 256], // For repetition detection
 
     // See https://www.chessprogramming.org/History_Heuristic
-    pub history_values: [[[Value; 64]; NUM_PIECES]; 2],
+    pub history_values: [[[f32; 64]; NUM_PIECES]; 2],
     pub root_best_move_idx: u8,
+
+    // Number of extensions applied along the line currently being searched
+    pub extension_count: usize,
+
+    // Move to skip at each ply, used to verify singular moves
+    pub excluded_moves: [Option<EncodedMove>; 256],
+
+    // Quiet moves that caused a cutoff at each ply, which are tried early in sibling nodes
+    // See: https://www.chessprogramming.org/Killer_Heuristic
+    pub killer_moves: [[EncodedMove; 2]; 256],
+
+    // Static eval at each ply of the line currently being searched, to tell if we're improving
+    pub static_evals: [Value; 256],
+
+    // Expected node type at each ply of the line currently being searched
+    pub node_types: [NodeType; 256],
+
+    // Root move indices to skip, so MultiPV lines can find the next best move
+    pub root_excluded_moves: Vec<u8>,
+
+    // Filled in by the first search, carry it over between iterations so the root is ordered by the last results
+    pub root_moves: Vec<RootMove>,
+
+    // How much the side to move at the root wants to avoid draws
+    pub contempt: Value,
+
+    // Hashes of the positions played in the game before the root, oldest first
+    pub game_hashes: Vec<Hash>,
+
+    pub stats: SearchStats,
+
+    // Checked before each root move, carry it over between iterations to keep the interval
+    pub current_move_reporter: Option<CurrentMoveReporter>,
+
+    // The best line found from each ply, built up from the line after it as the search returns
+    // The table can't be used for this, since its entries get overwritten
+    // See: https://www.chessprogramming.org/Triangular_PV-Table
+    pub pv_lines: Vec<Vec<Move>>,
 }
 
 impl SearchInfo {
     pub fn new() -> SearchInfo {
         SearchInfo {
             total_nodes: 0,
+            sel_depth: 0,
             depth_hashes: [0; 256],
             history_values: [[[0.0; 64]; NUM_PIECES]; 2],
             root_best_move_idx: 0,
+            extension_count: 0,
+            excluded_moves: [None; 256],
+            killer_moves: [[ENCODED_MOVE_NONE; 2]; 256],
+            static_evals: [0; 256],
+            node_types: [NodeType::PV; 256],
+            root_excluded_moves: Vec::new(),
+            root_moves: Vec::new(),
+            contempt: 0,
+            game_hashes: Vec::new(),
+            stats: SearchStats::default(),
+            current_move_reporter: None,
+            pv_lines: vec![Vec::new(); 256],
+        }
+    }
+
+    // The PV of the last search
+    pub fn get_pv(&self) -> &[Move] {
+        &self.pv_lines[0]
+    }
+
+    // A new best move at this ply, followed by the best line after it
+    fn update_pv(&mut self, depth_elapsed: usize, mv: Move) {
+        let (lines, next_lines) = self.pv_lines.split_at_mut(depth_elapsed + 1);
+        let line = &mut lines[depth_elapsed];
+        line.clear();
+        line.push(mv);
+        if let Some(next_line) = next_lines.first() {
+            line.extend_from_slice(next_line);
         }
     }
 }
 
-fn _search(
-    board: &Board,
-    table: &mut transpos::Table,
+// How many nodes to search between checking the clock and the stop flag
+// Instant::now() is too slow to call at every node, but this is still frequent enough to stop within a millisecond or so
+const STOP_CHECK_INTERVAL: usize = 1024;
+
+// Limits on a search, it's aborted as soon as any of them is hit
+// Outside this crate, build these with the methods below, so more kinds of limits can be added without breaking anyone:
+//
+//   let limits = SearchLimits::new().depth(12).move_time(Duration::from_secs(1));
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct SearchLimits {
+    pub max_depth: Option<u8>, // Only for iterative searches, a single search is given its depth
+    pub mate_moves: Option<u8>, // Stop once we find a mate within this many moves, only for iterative searches
+    pub move_time: Option<std::time::Duration>, // Counted from the start of the search
+    pub stop_flag: Option<ThreadFlag>,
+    pub stop_time: Option<std::time::Instant>,
+    pub max_nodes: Option<usize>,
+}
+
+impl SearchLimits {
+    pub fn new() -> SearchLimits {
+        SearchLimits {
+            max_depth: None,
+            mate_moves: None,
+            move_time: None,
+            stop_flag: None,
+            stop_time: None,
+            max_nodes: None,
+        }
+    }
+
+    pub fn depth(mut self, max_depth: u8) -> SearchLimits {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    pub fn mate(mut self, mate_moves: u8) -> SearchLimits {
+        self.mate_moves = Some(mate_moves);
+        self
+    }
+
+    pub fn move_time(mut self, move_time: std::time::Duration) -> SearchLimits {
+        self.move_time = Some(move_time);
+        self
+    }
+
+    // See ThreadFlag for how cancelling works
+    pub fn stop_flag(mut self, stop_flag: ThreadFlag) -> SearchLimits {
+        self.stop_flag = Some(stop_flag);
+        self
+    }
+
+    pub fn stop_time(mut self, stop_time: std::time::Instant) -> SearchLimits {
+        self.stop_time = Some(stop_time);
+        self
+    }
+
+    // For the whole search, not each iteration
+    pub fn nodes(mut self, max_nodes: usize) -> SearchLimits {
+        self.max_nodes = Some(max_nodes);
+        self
+    }
+
+    pub fn get_max_depth(&self) -> u8 {
+        self.max_depth.unwrap_or(u8::MAX)
+    }
+
+    // If we're looking for a mate, whether this eval is one that's short enough
+    pub fn is_mate_found(&self, eval: Value) -> bool {
+        self.mate_moves.is_some_and(|mate_moves| {
+            let max_mate_plies = (mate_moves as Value) * 2 - 1;
+            eval >= VALUE_CHECKMATE - max_mate_plies
+        })
+    }
+
+    // Turns the move time into a stop time, for a search starting now
+    // The earlier stop time wins if both are set
+    pub fn start(&self, start_time: std::time::Instant) -> SearchLimits {
+        let mut limits = self.clone();
+        if let Some(move_time) = limits.move_time.take() {
+            let move_stop_time = start_time + move_time;
+            limits.stop_time = Some(
+                limits
+                    .stop_time
+                    .map_or(move_stop_time, |stop_time| stop_time.min(move_stop_time)),
+            );
+        }
+        limits
+    }
+
+    // Called at every node, so the slower checks (the clock and the stop flag) are only done every STOP_CHECK_INTERVAL nodes
+    // The node limit is still checked every time, so it stays exact
+    fn poll(&self, total_nodes: usize) -> bool {
+        if self
+            .max_nodes
+            .is_some_and(|max_nodes| total_nodes >= max_nodes)
+        {
+            return true;
+        }
+
+        total_nodes.is_multiple_of(STOP_CHECK_INTERVAL) && self.is_hit(total_nodes)
+    }
+
+    pub fn is_hit(&self, total_nodes: usize) -> bool {
+        if self.stop_flag.as_ref().is_some_and(|flag| flag.get()) {
+            return true;
+        }
+
+        if self
+            .max_nodes
+            .is_some_and(|max_nodes| total_nodes >= max_nodes)
+        {
+            return true;
+        }
+
+        self.stop_time
+            .is_some_and(|stop_time| std::time::Instant::now() >= stop_time)
+    }
+}
+
+// Maximum amount of extensions along a single line
+// Without this, long sequences of checks can explode the search
+const MAX_EXTENSIONS: usize = 16;
+
+// Extension (quiescence) search, only looks at loud moves until the position is quiet
+// See: https://www.chessprogramming.org/Quiescence_Search
+fn extension_search(
+    board: &mut Board,
+    table: &transpos::Table,
     search_info: &mut SearchInfo,
     mut lower_bound: Value,
     upper_bound: Value,
-    depth_remaining: u8,
     depth_elapsed: i64,
-    stop_flag: Option<&ThreadFlag>,
-    stop_time: Option<std::time::Instant>,
+    limits: &SearchLimits,
 ) -> Value {
     search_info.total_nodes += 1;
+    search_info.sel_depth = usize::max(search_info.sel_depth, depth_elapsed as usize);
 
-    let in_extension = depth_remaining == 0;
+    // Huge capture sequences can take a while, so this is checked here too
+    if limits.poll(search_info.total_nodes) {
+        return VALUE_INF;
+    }
 
-    // Check draw by repetition
-    for i in (4..12).step_by(2) {
-        if (depth_elapsed >= i)
-            && search_info.depth_hashes[(depth_elapsed - i) as usize] == board.hash
-        {
-            // Loop detected
-            return 0.0;
-        } else {
-            break;
+    if is_insufficient_material(board) {
+        return get_draw_eval(search_info, depth_elapsed);
+    }
+
+    // Any entry is deep enough to use here, since extension search entries are stored at depth 0
+    let mut table_entry = table.get_fast(board.hash);
+    table_entry.eval = transpos::eval_from_table(table_entry.eval, depth_elapsed);
+    search_info.stats.table_probes += 1;
+    if table_entry.is_valid() {
+        search_info.stats.table_hits += 1;
+        let is_cutoff = match table_entry.entry_type {
+            transpos::EntryType::FailLow => table_entry.eval <= lower_bound,
+            transpos::EntryType::FailHigh => table_entry.eval >= upper_bound,
+            transpos::EntryType::Exact => true,
+            _ => false,
+        };
+        if is_cutoff {
+            search_info.stats.table_cutoffs += 1;
+            return table_entry.eval;
+        }
+    }
+
+    let original_lower_bound = lower_bound;
+
+    let in_check = board.checkers != 0;
+
+    // Standing pat
+    // When in check we can't just stay put, so every evasion has to be searched instead
+    // Outside of check, only the window matters, so the eval can be lazy
+    let (stand_pat_eval, is_stand_pat_exact) = if table_entry.is_valid() {
+        (table_entry.static_eval, true)
+    } else if in_check {
+        (eval_board(board), true)
+    } else {
+        eval_board_in_window(board, lower_bound, upper_bound)
+    };
+    let mut best_eval = -VALUE_INF;
+    if !in_check {
+        best_eval = stand_pat_eval;
+        if best_eval >= upper_bound {
+            return best_eval;
+        } else if best_eval > lower_bound {
+            lower_bound = best_eval;
         }
     }
-    search_info.depth_hashes[depth_elapsed as usize] = board.hash;
 
-    if depth_remaining >= 3 {
-        // No point in checking at a super low depth
-        let mut stop = false;
+    // Only loud moves are allowed in extensions, unless we have to get out of check
+    let mut moves = move_gen::MoveBuffer::new();
+    if in_check {
+        move_gen::generate_evasions(board, &mut moves);
+    } else {
+        move_gen::generate_captures(board, &mut moves);
+    }
+    if moves.is_empty() && (in_check || board.legal_moves().next().is_none()) {
+        return get_no_moves_eval(board, search_info, depth_elapsed);
+    }
+
+    let mut rated_moves: Vec<(usize, Value)> = Vec::with_capacity(moves.len());
+    for i in 0..moves.len() {
+        let mv = &moves[i];
+        if !in_check && mv.has_flag(Move::FLAG_CAPTURE) {
+            if !mv.has_flag(Move::FLAG_PROMOTION) {
+                // Delta pruning, skip captures that can't possibly bring us up to the lower bound
+                // Checking captures are kept, since they could be mate
+                let captured_piece_idx = if mv.has_flag(Move::FLAG_EN_PASSANT) {
+                    PIECE_PAWN
+                } else {
+                    board
+                        .get_piece_idx_at(1 - board.turn_idx, mv.to)
+                        .unwrap_or(PIECE_PAWN)
+                };
+                let max_gain = SEE_PIECE_VALUES[captured_piece_idx]
+                    + search_params::get().delta_pruning_margin;
+                if stand_pat_eval + max_gain < lower_bound {
+                    let undo = board.make_move(mv);
+                    let gives_check = board.checkers != 0;
+                    board.undo_move(mv, &undo);
+                    if !gives_check {
+                        continue;
+                    }
+                }
+            }
 
-        if stop_flag.is_some() && stop_flag.unwrap().get() {
-            stop = true;
-        } else if stop_time.is_some() {
-            if std::time::Instant::now() >= stop_time.unwrap() {
-                stop = true
+            if see(board, mv) < -search_params::get().extension_see_margin {
+                continue; // Losing capture, not worth looking at
             }
         }
 
-        if stop {
+        let move_eval = if mv.encode() == table_entry.best_move {
+            VALUE_INF // Try the table's move first
+        } else {
+            eval_move(board, mv)
+        };
+        rated_moves.push((i, move_eval));
+    }
+    rated_moves.sort_by_key(|m| core::cmp::Reverse(m.1));
+
+    let mut best_move = ENCODED_MOVE_NONE;
+    for (move_idx, _) in rated_moves {
+        let mv = &moves[move_idx];
+        let undo = board.make_move(mv);
+        let next_eval = -extension_search(
+            board,
+            table,
+            search_info,
+            -upper_bound,
+            -lower_bound,
+            depth_elapsed + 1,
+            limits,
+        );
+        board.undo_move(mv, &undo);
+
+        if is_inf_eval(next_eval) {
             return VALUE_INF;
         }
+
+        if next_eval > best_eval {
+            best_eval = next_eval;
+            best_move = moves[move_idx].encode();
+            if next_eval > lower_bound {
+                lower_bound = next_eval;
+            }
+
+            if next_eval >= upper_bound {
+                // Failed high, beta cut-off
+                break;
+            }
+        }
     }
 
-    let mut best_eval = -VALUE_INF;
-    let cur_eval = eval_board(board);
-    if in_extension {
-        // Standing pat eval in extension search
-        best_eval = cur_eval;
+    // Don't overwrite results from the main search, they're much more valuable
+    // Lazy evals can't be cached as the static eval, since they're only rough
+    if is_stand_pat_exact && (!table_entry.is_valid() || table_entry.depth_remaining == 0) {
+        table.set(
+            board.hash,
+            transpos::eval_to_table(best_eval, depth_elapsed),
+            stand_pat_eval,
+            best_move,
+            0,
+            if best_eval >= upper_bound {
+                transpos::EntryType::FailHigh
+            } else if best_eval <= original_lower_bound {
+                transpos::EntryType::FailLow
+            } else {
+                transpos::EntryType::Exact
+            },
+        );
+    }
 
-        if best_eval >= upper_bound {
-            return best_eval;
-        } else if best_eval > lower_bound {
-            lower_bound = best_eval;
+    best_eval
+}
+
+// A legal move at the root, and how it did the last time it was searched
+// Only the best move's eval is exact, the others are just upper bounds (except where they raised the lower bound)
+// See: https://www.chessprogramming.org/Root
+#[derive(Debug, Copy, Clone)]
+pub struct RootMove {
+    pub mv: Move,
+    pub eval: Value,  // -VALUE_INF if it hasn't been searched yet
+    pub nodes: usize, // Searched below this move, the last time it was searched
+}
+
+// The order to try root moves in: the table move, then the best evals of the last search, then the most nodes
+// The first search has nothing to go on, so it uses the normal move order
+fn get_root_move_order(
+    board: &Board,
+    search_info: &mut SearchInfo,
+    table_move: Option<Move>,
+) -> Vec<Move> {
+    if search_info.root_moves.is_empty() {
+        let mut move_picker = MovePicker::new(table_move, search_info.killer_moves[0]);
+        while let Some(mv) = move_picker.next(board, search_info) {
+            search_info.root_moves.push(RootMove {
+                mv,
+                eval: -VALUE_INF,
+                nodes: 0,
+            });
         }
     }
 
-    let table_entry = table.get_fast(board.hash);
+    // Stable, so moves that tie keep their order from before
+    search_info
+        .root_moves
+        .sort_by_key(|root_move| core::cmp::Reverse((root_move.eval, root_move.nodes)));
+
+    let mut order: Vec<Move> = search_info
+        .root_moves
+        .iter()
+        .map(|root_move| root_move.mv)
+        .collect();
+    if let Some(table_move) = table_move {
+        if let Some(idx) = order
+            .iter()
+            .position(|mv| mv.encode() == table_move.encode())
+        {
+            order[..=idx].rotate_right(1);
+        }
+    }
+    order
+}
+
+// Root moves are referred to by their index in the generate_moves() list
+// Only used at the root, so the extra generation doesn't matter
+#[inline(never)]
+fn get_root_move_idx(board: &Board, mv: &Move) -> u8 {
+    let mut moves = move_gen::MoveBuffer::new();
+    move_gen::generate_moves(board, &mut moves);
+    find_move_idx(&moves, mv.encode()).expect("Root move should be legal") as u8
+}
+
+fn _search(
+    board: &mut Board,
+    table: &transpos::Table,
+    search_info: &mut SearchInfo,
+    mut lower_bound: Value,
+    mut upper_bound: Value,
+    depth_remaining: u8,
+    depth_elapsed: i64,
+    limits: &SearchLimits,
+) -> Value {
+    let params = search_params::get();
+
+    // Nodes that return early don't have a line
+    search_info.pv_lines[depth_elapsed as usize].clear();
+
+    if depth_elapsed > 0 && is_repetition(board, search_info, depth_elapsed) {
+        return get_draw_eval(search_info, depth_elapsed);
+    }
+    search_info.depth_hashes[depth_elapsed as usize] = board.hash;
+
+    if depth_elapsed > 0 && is_insufficient_material(board) {
+        return get_draw_eval(search_info, depth_elapsed);
+    }
+
+    if depth_elapsed > 0
+        && board.half_move_counter >= FIFTY_MOVE_RULE_PLIES
+        && get_game_result(board) == GameResult::FiftyMoveRule
+    {
+        return get_draw_eval(search_info, depth_elapsed);
+    }
+
+    // Mate distance pruning
+    // Even mating on the very next move can't beat a shorter mate that was already found
+    // See: https://www.chessprogramming.org/Mate_Distance_Pruning
+    if depth_elapsed > 0 {
+        lower_bound = Value::max(lower_bound, -VALUE_CHECKMATE + (depth_elapsed as Value));
+        upper_bound = Value::min(
+            upper_bound,
+            VALUE_CHECKMATE - ((depth_elapsed + 1) as Value),
+        );
+        if lower_bound >= upper_bound {
+            return lower_bound;
+        }
+    }
+
+    if depth_remaining == 0 {
+        // Reached the horizon, resolve the remaining captures
+        return extension_search(
+            board,
+            table,
+            search_info,
+            lower_bound,
+            upper_bound,
+            depth_elapsed,
+            limits,
+        );
+    }
+
+    search_info.total_nodes += 1;
+    search_info.sel_depth = usize::max(search_info.sel_depth, depth_elapsed as usize);
+
+    if limits.poll(search_info.total_nodes) {
+        return VALUE_INF;
+    }
+
+    let mut best_eval = -VALUE_INF;
+
+    let node_type = search_info.node_types[depth_elapsed as usize];
+
+    // If we're verifying a singular move, this node's result doesn't include every move
+    let excluded_move = search_info.excluded_moves[depth_elapsed as usize];
+
+    // At the root, some moves may be excluded for MultiPV
+    let has_root_exclusions = depth_elapsed == 0 && !search_info.root_excluded_moves.is_empty();
+
+    // Anything wider than a null window is a PV node
+    let is_pv_node = (upper_bound - lower_bound) > 1;
+
+    let mut table_entry = table.get_fast(board.hash);
+    table_entry.eval = transpos::eval_from_table(table_entry.eval, depth_elapsed);
 
     // Table lookup
-    let mut table_best_move: Option<u8> = None;
+    let mut table_best_move: Option<EncodedMove> = None;
+    search_info.stats.table_probes += 1;
     if table_entry.is_valid() {
-        // NOTE: In extensions, this depth check won't work because depth_remaining is stuck at 0
-        if table_entry.depth_remaining >= depth_remaining && !in_extension {
+        search_info.stats.table_hits += 1;
+        if table_entry.depth_remaining >= depth_remaining
+            && excluded_move.is_none()
+            && !has_root_exclusions
+        {
             match table_entry.entry_type {
                 transpos::EntryType::FailLow => {
                     // Exceeds our lower bound, do a cutoff
                     if table_entry.eval <= lower_bound {
+                        search_info.stats.table_cutoffs += 1;
                         return table_entry.eval;
                     }
                 }
                 transpos::EntryType::FailHigh => {
                     if table_entry.eval >= upper_bound {
                         // Exceeds our upper bound, do a cutoff
+                        search_info.stats.table_cutoffs += 1;
                         return table_entry.eval;
                     }
                 }
                 transpos::EntryType::Exact => {
                     // Exact node, no further searching is needed
+                    search_info.stats.table_cutoffs += 1;
                     return table_entry.eval;
                 }
                 _ => {
@@ -169,160 +832,419 @@ fn _search(
         }
 
         // If we didn't hit a quick return, we can still use the best move from this entry
-        table_best_move = Some(table_entry.best_move_idx);
+        // (Extension search entries may not have one)
+        if table_entry.best_move != ENCODED_MOVE_NONE {
+            table_best_move = Some(table_entry.best_move);
+        }
+    }
+
+    // The table caches the static eval, so we don't need to evaluate the same position twice
+    let cur_eval = if table_entry.is_valid() {
+        table_entry.static_eval
+    } else {
+        eval_board(board)
+    };
+    search_info.static_evals[depth_elapsed as usize] = cur_eval;
+
+    // Whether our position has gotten better since our last move
+    let is_improving = depth_elapsed >= 2
+        && board.checkers == 0
+        && cur_eval > search_info.static_evals[(depth_elapsed - 2) as usize];
+
+    // Internal iterative reductions
+    // Without a table move our move ordering will be poor, so spend less effort here
+    let depth_remaining = if table_best_move.is_none()
+        && depth_remaining >= params.internal_iterative_reduction_min_depth
+        && depth_elapsed > 0
+        && excluded_move.is_none()
+    {
+        depth_remaining - 1
+    } else {
+        depth_remaining
+    };
+
+    // Reverse futility pruning
+    // If we're so far ahead that even a big margin doesn't bring us below the upper bound, assume we will fail high
+    if depth_remaining <= params.reverse_futility_max_depth
+        && depth_elapsed > 0
+        && board.checkers == 0
+        && excluded_move.is_none()
+        && cur_eval.abs() < VALUE_CHECKMATE_MIN
+        && cur_eval - params.reverse_futility_margin * (depth_remaining as Value) >= upper_bound
+    {
+        search_info.stats.reverse_futility_prunes += 1;
+        return cur_eval;
+    }
+
+    // Razoring
+    // If we're far below the lower bound near the horizon, check if captures can save us before doing a full search
+    if (depth_remaining as usize) < params.razoring_margins.len()
+        && depth_elapsed > 0
+        && board.checkers == 0
+        && excluded_move.is_none()
+        && cur_eval + params.razoring_margins[depth_remaining as usize] <= lower_bound
+    {
+        let razor_eval = extension_search(
+            board,
+            table,
+            search_info,
+            lower_bound,
+            lower_bound + 1,
+            depth_elapsed,
+            limits,
+        );
+        if is_inf_eval(razor_eval) {
+            return VALUE_INF;
+        }
+
+        if razor_eval <= lower_bound {
+            // Confirmed fail-low
+            search_info.stats.razor_prunes += 1;
+            return razor_eval;
+        }
     }
 
     // Null move pruning
-    if cur_eval >= upper_bound && board.checkers == 0 && depth_remaining >= 1 && depth_elapsed >= 2
+    if cur_eval >= upper_bound
+        && board.checkers == 0
+        && depth_remaining >= 1
+        && depth_elapsed >= 2
+        && excluded_move.is_none()
     {
         let king_and_pawn =
             board.pieces[board.turn_idx][PIECE_PAWN] | board.pieces[board.turn_idx][PIECE_KING];
         let is_king_and_pawn = board.occupancy[board.turn_idx] == king_and_pawn;
         if !is_king_and_pawn {
-            let mut next_board = board.clone();
-            next_board.do_null_move();
+            let undo = board.make_null_move();
+            table.prefetch(board.hash);
 
             let next_depth = depth_remaining / 2;
+            search_info.node_types[(depth_elapsed + 1) as usize] = NodeType::All;
             let next_result = _search(
-                &next_board,
+                board,
                 table,
                 search_info,
                 -upper_bound,
-                -upper_bound + 0.01,
+                -upper_bound + 1,
                 next_depth,
                 depth_elapsed + 1,
-                stop_flag,
-                stop_time,
+                limits,
             );
+            board.undo_null_move(&undo);
+
+            if is_inf_eval(next_result) {
+                return VALUE_INF;
+            }
 
-            let next_eval = decay_eval(-next_result);
+            let next_eval = -next_result;
             if next_eval >= upper_bound {
+                search_info.stats.null_move_prunes += 1;
                 return next_eval;
             }
         }
     }
 
-    let mut moves = move_gen::MoveBuffer::new();
-    move_gen::generate_moves(&board, &mut moves);
-    if moves.is_empty() {
-        return get_no_moves_eval(board);
-    }
+    // ProbCut
+    // If a good capture beats the upper bound by a margin in a shallow search, the full search is very likely to fail high too
+    if !is_pv_node
+        && depth_remaining >= params.probcut_min_depth
+        && depth_elapsed > 0
+        && board.checkers == 0
+        && excluded_move.is_none()
+        && upper_bound.abs() < VALUE_CHECKMATE_MIN
+    {
+        let probcut_bound = upper_bound + params.probcut_margin;
+        let probcut_depth = (depth_remaining - 1).saturating_sub(params.probcut_reduction);
+
+        let mut captures = move_gen::MoveBuffer::new();
+        move_gen::generate_captures(board, &mut captures);
+        for i in 0..captures.len() {
+            let mv = &captures[i];
+            if see(board, mv) < params.probcut_margin {
+                continue; // Not winning enough material to be worth checking
+            }
 
-    #[derive(Copy, Clone)]
-    struct RatedMove {
-        idx: usize,
-        eval: Value,
-    }
+            let undo = board.make_move(mv);
+            table.prefetch(board.hash);
 
-    let table_best_move_idx;
-    if table_best_move.is_some() {
-        if (table_best_move.unwrap() as usize) < moves.len() {
-            table_best_move_idx = table_best_move.unwrap() as usize;
-        } else {
-            debug_assert!(false);
-            table_best_move_idx = usize::MAX;
-        }
-    } else {
-        table_best_move_idx = usize::MAX;
-    }
+            // Resolving the captures is cheap, so only do the shallow search if that beats the bound too
+            let mut next_eval = extension_search(
+                board,
+                table,
+                search_info,
+                -probcut_bound,
+                -probcut_bound + 1,
+                depth_elapsed + 1,
+                limits,
+            );
+            if !is_inf_eval(next_eval) && -next_eval >= probcut_bound && probcut_depth > 0 {
+                // The child is only expected to fail low here, so don't change what the main search expects of it
+                let next_node_type = search_info.node_types[(depth_elapsed + 1) as usize];
+                search_info.node_types[(depth_elapsed + 1) as usize] = NodeType::All;
+                next_eval = _search(
+                    board,
+                    table,
+                    search_info,
+                    -probcut_bound,
+                    -probcut_bound + 1,
+                    probcut_depth,
+                    depth_elapsed + 1,
+                    limits,
+                );
+                search_info.node_types[(depth_elapsed + 1) as usize] = next_node_type;
+            }
+            board.undo_move(mv, &undo);
 
-    let mut rated_moves: Vec<RatedMove> = Vec::with_capacity(moves.len());
-    for i in 0..moves.len() {
-        let mv = moves[i];
-        let is_quiet = mv.is_quiet();
+            if is_inf_eval(next_eval) {
+                return VALUE_INF;
+            }
 
-        if in_extension && is_quiet {
-            continue; // Only loud moves allowed in extensions
+            if -next_eval >= probcut_bound {
+                search_info.stats.probcut_prunes += 1;
+                return -next_eval;
+            }
         }
+    }
 
-        let mut move_eval = eval_move(board, &mv);
+    // The table's move could be from a different position with a colliding hash, so make sure it's playable here
+    let table_move = table_best_move
+        .and_then(|encoded_move| board.decode_move(encoded_move))
+        .filter(|mv| board.is_pseudo_legal(mv) && move_gen::is_legal(board, mv));
+
+    // Singular extensions
+    // If the table's best move is much better than all alternatives, search it a ply deeper
+    let mut is_singular = false;
+    if table_move.is_some()
+        && depth_remaining >= params.singular_extension_min_depth
+        && depth_elapsed > 0
+        && excluded_move.is_none()
+        && table_entry.entry_type != transpos::EntryType::FailLow
+        && table_entry.depth_remaining + 3 >= depth_remaining
+        && table_entry.eval.abs() < VALUE_CHECKMATE_MIN
+    {
+        let singular_bound =
+            table_entry.eval - params.singular_extension_margin * (depth_remaining as Value);
 
-        if is_quiet {
-            let history_value =
-                search_info.history_values[board.turn_idx][mv.from_piece_idx][bm_to_idx(mv.to)];
-            move_eval += history_value * 0.02;
-        }
+        // Search every other move at a reduced depth
+        search_info.excluded_moves[depth_elapsed as usize] = table_move.map(|mv| mv.encode());
+        search_info.node_types[depth_elapsed as usize] = NodeType::All;
+        let excluded_eval = _search(
+            board,
+            table,
+            search_info,
+            singular_bound - 1,
+            singular_bound,
+            depth_remaining / 2,
+            depth_elapsed,
+            limits,
+        );
+        search_info.excluded_moves[depth_elapsed as usize] = None;
 
-        if i == table_best_move_idx {
-            move_eval = Value::MAX;
+        if is_inf_eval(excluded_eval) {
+            return VALUE_INF;
         }
 
-        rated_moves.push(RatedMove {
-            idx: i,
-            eval: move_eval,
-        })
+        // Nothing else came close
+        is_singular = excluded_eval < singular_bound;
     }
 
-    // Insertion sort
-    for i in 1..rated_moves.len() {
-        let mut j = i;
-        while j > 0 {
-            let prev = rated_moves[j - 1];
-            let cur = rated_moves[j];
-
-            if cur.eval > prev.eval {
-                // Swap
-                rated_moves[j - 1] = cur;
-                rated_moves[j] = prev;
-            } else {
+    let killers = search_info.killer_moves[depth_elapsed as usize];
+    let mut move_picker = MovePicker::new(table_move, killers);
+
+    // Multi-cut
+    // At an expected cut node, if several of the first moves fail high with a reduced search, assume this node will too
+    if node_type == NodeType::Cut
+        && depth_remaining >= params.multi_cut_min_depth
+        && depth_elapsed > 0
+        && board.checkers == 0
+        && excluded_move.is_none()
+        && upper_bound.abs() < VALUE_CHECKMATE_MIN
+    {
+        let mut num_fail_highs = 0;
+        for _ in 0..params.multi_cut_moves {
+            let Some(mv) = move_picker.next(board, search_info) else {
                 break;
+            };
+            let undo = board.make_move(&mv);
+
+            search_info.node_types[(depth_elapsed + 1) as usize] = NodeType::All;
+            let next_eval = _search(
+                board,
+                table,
+                search_info,
+                -upper_bound,
+                -upper_bound + 1,
+                (depth_remaining - 1).saturating_sub(params.multi_cut_reduction),
+                depth_elapsed + 1,
+                limits,
+            );
+            board.undo_move(&mv, &undo);
+
+            if is_inf_eval(next_eval) {
+                return VALUE_INF;
             }
 
-            j -= 1;
+            if -next_eval >= upper_bound {
+                num_fail_highs += 1;
+                if num_fail_highs >= params.multi_cut_required {
+                    search_info.stats.multi_cut_prunes += 1;
+                    return upper_bound;
+                }
+            }
         }
+
+        // The full search starts over from the first move
+        move_picker.reset();
     }
 
-    let mut best_move_idx: usize = 0;
-    for i in 0..rated_moves.len() {
-        let move_idx = rated_moves[i].idx;
-        let mv = &moves[move_idx];
+    // Futility pruning is only safe near the horizon when we aren't in check
+    let can_futility_prune = (depth_remaining as usize) < params.futility_margins.len()
+        && board.checkers == 0
+        && cur_eval.abs() < VALUE_CHECKMATE_MIN;
+
+    // Late move pruning
+    // Near the horizon, we only look at the first few quiet moves (even fewer if we aren't improving)
+    let late_move_count = if !is_pv_node
+        && depth_remaining <= params.late_move_pruning_max_depth
+        && depth_elapsed > 0
+        && board.checkers == 0
+    {
+        let base_count =
+            params.late_move_pruning_base + (depth_remaining as usize) * (depth_remaining as usize);
+        if is_improving {
+            base_count
+        } else {
+            base_count / 2
+        }
+    } else {
+        usize::MAX
+    };
 
-        let mut next_board: Board = board.clone();
-        next_board.do_move(mv);
+    // The root uses its own order, see get_root_move_order()
+    let mut root_move_order = if depth_elapsed == 0 {
+        get_root_move_order(board, search_info, table_move).into_iter()
+    } else {
+        Vec::new().into_iter()
+    };
+
+    // Searches at this same ply (like the singular extension search) may have left a line here
+    search_info.pv_lines[depth_elapsed as usize].clear();
+
+    let mut searched_moves: Vec<Move> = Vec::new(); // Including the ones that were pruned
+    let mut has_excluded_moves = false;
+    let mut best_move: Option<Move> = None;
+    while let Some(next_move) = if depth_elapsed == 0 {
+        root_move_order.next()
+    } else {
+        move_picker.next(board, search_info)
+    } {
+        if excluded_move == Some(next_move.encode())
+            || (has_root_exclusions
+                && search_info
+                    .root_excluded_moves
+                    .contains(&get_root_move_idx(board, &next_move)))
+        {
+            has_excluded_moves = true;
+            continue;
+        }
+
+        let i = searched_moves.len();
+        searched_moves.push(next_move);
+        let mv = &next_move;
+
+        if depth_elapsed == 0 {
+            if let Some(reporter) = &mut search_info.current_move_reporter {
+                reporter.maybe_report(board, mv, i + 1, depth_remaining);
+            }
+        }
+
+        let nodes_before = search_info.total_nodes;
+        let undo = board.make_move(mv);
+
+        // Start loading the child's table entry now, it will likely be ready by the time we look it up
+        table.prefetch(board.hash);
+
+        let gives_check = board.checkers != 0;
+
+        if can_futility_prune
+            && i > 0
+            && mv.is_quiet()
+            && !gives_check
+            && cur_eval + params.futility_margins[depth_remaining as usize] <= lower_bound
+        {
+            // This quiet move is very unlikely to raise the eval enough to matter
+            board.undo_move(mv, &undo);
+            search_info.stats.futility_prunes += 1;
+            continue;
+        }
+
+        if i >= late_move_count && mv.is_quiet() && !gives_check {
+            board.undo_move(mv, &undo);
+            search_info.stats.late_move_prunes += 1;
+            continue;
+        }
 
-        let gives_check = next_board.checkers != 0;
         let mut depth_reduction: u8 = 1;
 
-        if gives_check {
-            depth_reduction = 0; // Extend after a check
+        if is_singular && table_move.is_some_and(|table_move| table_move.encode() == mv.encode()) {
+            if search_info.extension_count < MAX_EXTENSIONS {
+                depth_reduction = 0;
+            }
+        } else if gives_check {
+            if search_info.extension_count < MAX_EXTENSIONS {
+                // The opponent will be in check, extend so we don't stop searching in the middle of an attack
+                depth_reduction = 0;
+            }
         } else {
             // Late move reductions
             if i >= 1 && depth_elapsed >= 2 {
-                let reduction_amount = (i as f32) * 0.1 + (depth_remaining as f32) * 0.2;
-                depth_reduction += reduction_amount.round() as u8;
+                let reduction_amount = (i as Value) * params.late_move_reduction_move_factor
+                    + (depth_remaining as Value) * params.late_move_reduction_depth_factor;
+                depth_reduction += (reduction_amount as f32 / 1000.0).round() as u8;
             }
         }
 
         // Prevent depth reduction overflow
         depth_reduction = u8::min(depth_reduction, depth_remaining);
 
+        let is_extended = depth_reduction == 0;
+        if is_extended {
+            search_info.extension_count += 1;
+        }
+
         let mut next_eval: Value;
         loop {
             let next_lower_bound;
             if depth_reduction > 1 {
                 // Search with a null window
-                next_lower_bound = -lower_bound - 0.01;
+                next_lower_bound = -lower_bound - 1;
             } else {
                 next_lower_bound = -upper_bound;
             }
 
+            search_info.node_types[(depth_elapsed + 1) as usize] = match node_type {
+                NodeType::PV if depth_reduction <= 1 => NodeType::PV,
+                NodeType::Cut => NodeType::All,
+                _ => NodeType::Cut,
+            };
+
             next_eval = _search(
-                &next_board,
+                board,
                 table,
                 search_info,
                 next_lower_bound,
                 -lower_bound,
                 depth_remaining - depth_reduction,
                 depth_elapsed + 1,
-                stop_flag,
-                stop_time,
+                limits,
             );
 
-            if next_eval.is_infinite() {
+            if is_inf_eval(next_eval) {
+                board.undo_move(mv, &undo);
                 return VALUE_INF;
             }
 
-            next_eval = decay_eval(-next_eval);
+            next_eval = -next_eval;
 
             if depth_reduction > 1 && next_eval > lower_bound {
                 // Exceeded lower bound, we need to do a full search
@@ -332,27 +1254,51 @@ fn _search(
 
             break;
         }
+        board.undo_move(mv, &undo);
+
+        if is_extended {
+            search_info.extension_count -= 1;
+        }
+
+        if depth_elapsed == 0 {
+            let nodes = search_info.total_nodes - nodes_before;
+            if let Some(root_move) = search_info
+                .root_moves
+                .iter_mut()
+                .find(|root_move| root_move.mv.encode() == mv.encode())
+            {
+                root_move.eval = next_eval;
+                root_move.nodes = nodes;
+            }
+        }
 
         if next_eval > best_eval {
             best_eval = next_eval;
-            best_move_idx = move_idx;
+            best_move = Some(*mv);
             if next_eval > lower_bound {
                 lower_bound = next_eval;
+                search_info.update_pv(depth_elapsed as usize, *mv);
             }
 
             if next_eval >= upper_bound {
                 // Failed high, beta cut-off
                 if mv.is_quiet() {
                     // Higher depth means better search and thus better quality info on how good this move is
-                    let history_weight = 1.0 / (depth_elapsed as Value);
+                    let history_weight = 1.0 / (depth_elapsed as f32);
                     search_info.history_values[board.turn_idx][mv.from_piece_idx]
                         [bm_to_idx(mv.to)] += history_weight;
 
                     // Penalize all the moves we already searched
-                    for j in 0..i {
-                        let omv = &moves[rated_moves[j].idx];
+                    for omv in &searched_moves[..i] {
                         search_info.history_values[board.turn_idx][omv.from_piece_idx]
-                            [bm_to_idx(omv.to)] -= history_weight / (i as Value);
+                            [bm_to_idx(omv.to)] -= history_weight / (i as f32);
+                    }
+
+                    // See: https://www.chessprogramming.org/Killer_Heuristic
+                    let killers = &mut search_info.killer_moves[depth_elapsed as usize];
+                    if killers[0] != mv.encode() {
+                        killers[1] = killers[0];
+                        killers[0] = mv.encode();
                     }
                 }
                 break;
@@ -360,43 +1306,75 @@ fn _search(
         }
     }
 
-    table.set(
-        board.hash,
-        best_eval,
-        best_move_idx as u8,
-        depth_remaining,
-        {
-            if best_eval >= upper_bound {
-                transpos::EntryType::FailHigh
-            } else if best_eval <= lower_bound {
-                transpos::EntryType::FailLow
-            } else {
-                transpos::EntryType::Exact
-            }
-        },
-    );
+    let Some(best_move) = best_move else {
+        if has_excluded_moves {
+            // The only moves were excluded
+            return lower_bound;
+        }
+        return get_no_moves_eval(board, search_info, depth_elapsed);
+    };
+
+    if excluded_move.is_none() && !has_root_exclusions {
+        table.set(
+            board.hash,
+            transpos::eval_to_table(best_eval, depth_elapsed),
+            cur_eval,
+            best_move.encode(),
+            depth_remaining,
+            {
+                if best_eval >= upper_bound {
+                    transpos::EntryType::FailHigh
+                } else if best_eval <= lower_bound {
+                    transpos::EntryType::FailLow
+                } else {
+                    transpos::EntryType::Exact
+                }
+            },
+        );
+    }
 
     if depth_elapsed == 0 {
-        search_info.root_best_move_idx = best_move_idx as u8;
+        search_info.root_best_move_idx = get_root_move_idx(board, &best_move);
     }
 
     best_eval
 }
 
+// Returns an inf eval if any of the limits were hit, to cancel from another thread see ThreadFlag
 pub fn search(
     board: &Board,
-    table: &mut transpos::Table,
+    table: &transpos::Table,
     depth: u8,
     guessed_eval: Option<Value>,
-    stop_flag: Option<&ThreadFlag>,
-    stop_time: Option<std::time::Instant>,
+    limits: &SearchLimits,
 ) -> (Value, SearchInfo) {
-    let mut search_info = SearchInfo::new();
+    search_with_info(board, table, depth, guessed_eval, SearchInfo::new(), limits)
+}
+
+// Searches using root settings from the given search info (excluded root moves, contempt, game history)
+// NOTE: If root moves are excluded, the root result isn't stored in the table, since it doesn't include every move
+pub fn search_with_info(
+    board: &Board,
+    table: &transpos::Table,
+    depth: u8,
+    guessed_eval: Option<Value>,
+    mut search_info: SearchInfo,
+    limits: &SearchLimits,
+) -> (Value, SearchInfo) {
+    // The search makes and unmakes moves on its own copy, so the caller's board is left alone
+    let board = &mut board.clone();
+
+    // Depth 1 is quick, and always finishing it means there's always a move to play
+    let limits = if depth <= 1 {
+        SearchLimits::new()
+    } else {
+        limits.start(std::time::Instant::now())
+    };
+    let limits = &limits;
 
     if depth >= 4 {
         // Use an aspiration window
-        const WINDOW_RANGE_GUESS: Value = 0.3; // Range of the window if there is a guessed eval
-        const WINDOW_RANGE_NO_GUESS: Value = 1.0; // Range of the window if there isn't guessed eval
+        let params = search_params::get();
         let window_start_center = if guessed_eval.is_some() {
             guessed_eval.unwrap()
         } else {
@@ -407,11 +1385,11 @@ pub fn search(
         let mut window_max = window_start_center;
 
         if guessed_eval.is_some() {
-            window_min -= WINDOW_RANGE_GUESS / 2.0;
-            window_max += WINDOW_RANGE_GUESS / 2.0;
+            window_min -= params.aspiration_window_guess / 2;
+            window_max += params.aspiration_window_guess / 2;
         } else {
-            window_min -= WINDOW_RANGE_NO_GUESS / 2.0;
-            window_max += WINDOW_RANGE_NO_GUESS / 2.0;
+            window_min -= params.aspiration_window_no_guess / 2;
+            window_max += params.aspiration_window_no_guess / 2;
         }
 
         let eval = _search(
@@ -422,12 +1400,11 @@ pub fn search(
             window_max,
             depth,
             0,
-            stop_flag,
-            stop_time,
+            limits,
         );
 
-        if eval >= window_min && eval < window_max {
-            // Window was sufficient
+        if is_inf_eval(eval) || (eval > window_min && eval < window_max) {
+            // Window was sufficient (an eval on its edge is only a bound), or the search was aborted
             return (eval, search_info);
         }
     }
@@ -440,56 +1417,186 @@ pub fn search(
         VALUE_CHECKMATE,
         depth,
         0,
-        stop_flag,
-        stop_time,
+        limits,
     );
 
     (search_result, search_info)
 }
 
-pub fn determine_pv(mut board: Board, table: &transpos::Table) -> Vec<Move> {
-    let mut result = Vec::new();
-    let mut found_hashes = HashSet::<Hash>::new();
+// Result of the last finished iteration of search_iterative()
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct IterativeResult {
+    pub eval: Value,
+    pub best_move: Move,
+    pub depth: u8, // 0 if not even depth 1 finished, then the move is just the first legal one
+    pub total_nodes: usize, // Over all iterations
+    pub ponder_move: Option<Move>, // The reply we expect to the best move, from the PV
+}
 
-    loop {
-        let entry = table.get_fast(board.hash);
+// Single-threaded iterative deepening, until the max depth, a mate, or another limit is hit
+// The node limit is for all iterations together, not each one
+// Returns None if there are no legal moves
+pub fn search_iterative(
+    board: &Board,
+    table: &transpos::Table,
+    game_hashes: &[Hash],
+    limits: &SearchLimits,
+) -> Option<IterativeResult> {
+    search_iterative_with_listener(board, table, game_hashes, limits, &mut ())
+}
+
+// Like search_iterative(), but tells the listener about every finished depth
+pub fn search_iterative_with_listener(
+    board: &Board,
+    table: &transpos::Table,
+    game_hashes: &[Hash],
+    limits: &SearchLimits,
+    listener: &mut dyn SearchListener,
+) -> Option<IterativeResult> {
+    let mut moves = move_gen::MoveBuffer::new();
+    move_gen::generate_moves(board, &mut moves);
+    if moves.is_empty() {
+        listener.on_no_legal_moves(board, get_game_result(board));
+        return None;
+    }
 
-        let valid;
-        if result.is_empty() {
-            // No PV yet, allow a locked entry
-            valid = entry.is_set();
+    let start_time = std::time::Instant::now();
+    let limits = &limits.start(start_time);
+    table.new_search();
+
+    let mut result = IterativeResult {
+        eval: 0,
+        best_move: moves[0],
+        depth: 0,
+        total_nodes: 0,
+        ponder_move: None,
+    };
+    let mut root_moves = Vec::new();
+    for depth in 1..=limits.get_max_depth() {
+        let mut iteration_limits = limits.clone();
+        iteration_limits.max_nodes = limits
+            .max_nodes
+            .map(|max_nodes| max_nodes.saturating_sub(result.total_nodes));
+
+        let mut search_info = SearchInfo::new();
+        search_info.game_hashes = game_hashes.to_vec();
+        search_info.root_moves = std::mem::take(&mut root_moves);
+        let guessed_eval = if result.depth > 0 {
+            Some(result.eval)
         } else {
-            // Require full validity
-            valid = entry.is_valid();
-        }
+            None
+        };
+        let (eval, mut search_info) = search_with_info(
+            board,
+            table,
+            depth,
+            guessed_eval,
+            search_info,
+            &iteration_limits,
+        );
 
-        if valid {
-            if found_hashes.contains(&board.hash) {
-                // Looped position
-                break;
-            } else {
-                found_hashes.insert(board.hash);
-            }
+        result.total_nodes += search_info.total_nodes;
+        root_moves = std::mem::take(&mut search_info.root_moves);
+        if is_inf_eval(eval) {
+            break; // Hit a limit
+        }
 
-            let mut moves = move_gen::MoveBuffer::new();
-            move_gen::generate_moves(&board, &mut moves);
+        result.eval = eval;
+        result.best_move = moves[search_info.root_best_move_idx as usize];
+        result.depth = depth;
+        result.ponder_move = search_info.get_pv().get(1).copied();
 
-            let best_move_idx = entry.best_move_idx as usize;
-            if best_move_idx >= moves.len() {
-                panic!("Failed to generate PV, bad move count (hash collision?)");
-            }
+        let progress = SearchProgress {
+            total_nodes: result.total_nodes,
+            elapsed_time: start_time.elapsed().as_secs_f64(),
+            ..SearchProgress::from_search_info(board, table, depth, eval, &search_info)
+        };
+        listener.on_new_pv(board, &progress);
+        listener.on_depth_complete(board, &progress);
 
-            let best_move = moves[best_move_idx];
-            result.push(best_move);
-            board.do_move(&best_move);
-        } else {
+        if limits.is_hit(result.total_nodes) || is_mate_eval(eval) {
             break;
         }
     }
 
+    listener.on_finish(board, result.best_move, result.ponder_move);
+
+    Some(result)
+}
+
+// Why determine_pv() couldn't find a PV
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PvError {
+    NotInTable,  // The position has no table entry with a move
+    IllegalMove, // The table's move isn't legal here, so the entry is from a different position with a colliding hash
+}
+
+impl core::fmt::Display for PvError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            PvError::NotInTable => write!(f, "Position isn't in the table"),
+            PvError::IllegalMove => write!(f, "Table move isn't legal in the position"),
+        }
+    }
+}
+
+// Follows the table's best moves from this position
+// Prefer SearchInfo::get_pv() after a search, since table entries can be overwritten
+pub fn determine_pv(board: Board, table: &transpos::Table) -> Result<Vec<Move>, PvError> {
+    let mut result = Vec::new();
+    extend_pv(board, table, &mut result);
+
     if result.is_empty() {
-        panic!("Failed to generate PV, first table entry never found");
+        let entry = table.get_fast(board.hash);
+        return if entry.is_valid() && entry.best_move != ENCODED_MOVE_NONE {
+            Err(PvError::IllegalMove)
+        } else {
+            Err(PvError::NotInTable)
+        };
     }
 
+    Ok(result)
+}
+
+// Determines the PV of a line starting with a specific root move
+// Used for MultiPV, where only the first line's root move is in the table
+pub fn determine_pv_from_move(
+    mut board: Board,
+    root_move: Move,
+    table: &transpos::Table,
+) -> Vec<Move> {
+    let mut result = vec![root_move];
+    board.do_move(&root_move);
+    extend_pv(board, table, &mut result);
     result
 }
+
+// Finds a move in the move list from its encoded form
+fn find_move_idx(moves: &move_gen::MoveBuffer, encoded_move: EncodedMove) -> Option<usize> {
+    moves.iter().position(|mv| mv.encode() == encoded_move)
+}
+
+// Follows the table's best moves from this position, adding them to the PV
+// Stops at the first move that isn't legal, since a hash collision can give us any entry
+fn extend_pv(mut board: Board, table: &transpos::Table, result: &mut Vec<Move>) {
+    let mut found_hashes = HashSet::<Hash>::new();
+
+    loop {
+        let entry = table.get_fast(board.hash);
+        if !entry.is_valid() || !found_hashes.insert(board.hash) {
+            // End of the line, or a looped position
+            break;
+        }
+
+        let Some(best_move) = board
+            .decode_move(entry.best_move)
+            .filter(|mv| board.is_pseudo_legal(mv) && move_gen::is_legal(&board, mv))
+        else {
+            break;
+        };
+
+        result.push(best_move);
+        board.do_move(&best_move);
+    }
+}
@@ -0,0 +1,146 @@
+use crate::eval::Value;
+
+// The pruning margins and reduction constants used by the search, kept in one place so they can be tuned
+// Without the tune-options feature these are always the defaults, so the compiler sees them as constants
+// With it, every value is also a UCI spin option (see uci.rs), so tuners like SPSA can change them at runtime
+
+// Anything made up of tunable integers, so every value can be visited in a fixed order
+pub trait TuneValues {
+    fn count(&self) -> usize;
+
+    fn for_each_value(&mut self, f: &mut dyn FnMut(&mut i64));
+}
+
+macro_rules! impl_tune_values {
+    ($($type:ty),*) => {
+        $(impl TuneValues for $type {
+            fn count(&self) -> usize {
+                1
+            }
+
+            fn for_each_value(&mut self, f: &mut dyn FnMut(&mut i64)) {
+                let mut value = *self as i64;
+                f(&mut value);
+                *self = value as $type;
+            }
+        })*
+    };
+}
+
+impl_tune_values!(u8, usize, Value);
+
+impl<T: TuneValues, const N: usize> TuneValues for [T; N] {
+    fn count(&self) -> usize {
+        self.iter().map(|value| value.count()).sum()
+    }
+
+    fn for_each_value(&mut self, f: &mut dyn FnMut(&mut i64)) {
+        for value in self.iter_mut() {
+            value.for_each_value(f);
+        }
+    }
+}
+
+macro_rules! define_search_params {
+    ($($name:ident: $type:ty = $default:expr, [$min:expr, $max:expr],)*) => {
+        #[derive(Debug, Clone, PartialEq)]
+        pub struct SearchTuneParams {
+            $(pub $name: $type,)*
+        }
+
+        impl SearchTuneParams {
+            pub const DEFAULT: SearchTuneParams = SearchTuneParams {
+                $($name: $default,)*
+            };
+
+            // Visits every value with its option name and valid range
+            // Values in arrays get their index added to the name, like "futility_margins_1"
+            pub fn for_each_option(&mut self, f: &mut dyn FnMut(&str, &mut i64, i64, i64)) {
+                $({
+                    let is_array = self.$name.count() > 1;
+                    let mut idx = 0;
+                    self.$name.for_each_value(&mut |value| {
+                        if is_array {
+                            f(&format!("{}_{}", stringify!($name), idx), value, $min, $max);
+                        } else {
+                            f(stringify!($name), value, $min, $max);
+                        }
+                        idx += 1;
+                    });
+                })*
+            }
+        }
+    };
+}
+
+define_search_params! {
+    // See: https://www.chessprogramming.org/Singular_Extensions
+    singular_extension_min_depth: u8 = 7, [1, 32],
+    singular_extension_margin: Value = 2, [0, 50], // Per depth remaining
+
+    // See: https://www.chessprogramming.org/Internal_Iterative_Reductions
+    internal_iterative_reduction_min_depth: u8 = 4, [1, 32],
+
+    // See: https://www.chessprogramming.org/Reverse_Futility_Pruning
+    reverse_futility_max_depth: u8 = 6, [0, 32],
+    reverse_futility_margin: Value = 75, [0, 500], // Per depth remaining
+
+    // See: https://www.chessprogramming.org/Razoring
+    razoring_margins: [Value; 4] = [0, 200, 300, 400], [0, 2000], // Indexed by depth remaining
+
+    // See: https://www.chessprogramming.org/ProbCut
+    probcut_min_depth: u8 = 6, [2, 32],
+    probcut_reduction: u8 = 4, [1, 8],
+    probcut_margin: Value = 100, [0, 1000],
+
+    // See: https://www.chessprogramming.org/Futility_Pruning
+    futility_margins: [Value; 3] = [0, 100, 250], [0, 2000], // Indexed by depth remaining
+
+    // See: https://www.chessprogramming.org/Futility_Pruning#MoveCountBasedPruning
+    late_move_pruning_max_depth: u8 = 4, [0, 32],
+    late_move_pruning_base: usize = 3, [0, 64], // Quiet moves searched before depth remaining squared is added
+
+    // See: https://www.chessprogramming.org/Late_Move_Reductions
+    // Reductions are (move index * move factor + depth remaining * depth factor) / 1000, rounded
+    late_move_reduction_move_factor: Value = 100, [0, 1000],
+    late_move_reduction_depth_factor: Value = 200, [0, 1000],
+
+    // See: https://www.chessprogramming.org/Multi-Cut
+    multi_cut_min_depth: u8 = 6, [4, 32],
+    multi_cut_reduction: u8 = 3, [0, 3],
+    multi_cut_moves: usize = 6, [1, 32], // Amount of moves to try
+    multi_cut_required: usize = 3, [1, 32], // Amount of those moves that must fail high
+
+    // Captures that lose more than this according to SEE are skipped in extension search
+    extension_see_margin: Value = 50, [0, 1000],
+
+    // Safety margin added on top of the captured piece's value for delta pruning
+    // See: https://www.chessprogramming.org/Delta_Pruning
+    delta_pruning_margin: Value = 200, [0, 2000],
+
+    // See: https://www.chessprogramming.org/Aspiration_Windows
+    aspiration_window_guess: Value = 30, [2, 1000], // Range of the window if there is a guessed eval
+    aspiration_window_no_guess: Value = 100, [2, 1000], // Range of the window if there isn't guessed eval
+}
+
+#[cfg(feature = "tune-options")]
+static mut SEARCH_PARAMS: SearchTuneParams = SearchTuneParams::DEFAULT;
+
+#[cfg(feature = "tune-options")]
+pub fn get() -> &'static SearchTuneParams {
+    unsafe { &*core::ptr::addr_of!(SEARCH_PARAMS) }
+}
+
+#[cfg(not(feature = "tune-options"))]
+#[inline(always)]
+pub fn get() -> &'static SearchTuneParams {
+    &SearchTuneParams::DEFAULT
+}
+
+// NOTE: Must not be called during a search
+#[cfg(feature = "tune-options")]
+pub fn set(params: SearchTuneParams) {
+    unsafe {
+        SEARCH_PARAMS = params;
+    }
+}
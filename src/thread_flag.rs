@@ -2,8 +2,21 @@ use std::sync::atomic;
 use std::sync::Arc;
 
 // A flag that can be triggered from a thread to notify another thread
-// The bool starts as false
-#[derive(Debug, Clone)]
+// This is also how searches are cancelled from outside, by passing one in SearchLimits::stop_flag:
+//
+//   let stop_flag = ThreadFlag::new();
+//   let limits = SearchLimits::new().stop_flag(stop_flag.clone());
+//   // Then from any other thread, while search::search(..., &limits) runs:
+//   stop_flag.trigger();
+//
+// The contract:
+// - The bool starts as false, and clones share it, so triggering any clone triggers all of them
+// - A search checks the flag every few thousand nodes, so it stops soon after the trigger, but not instantly
+// - An aborted search::search returns an inf eval (see eval::is_inf_eval), which must be thrown away
+// - Iterative searches return the result of the last finished depth instead, and always finish depth 1
+// - The flag stays triggered until reset, so a search started with a triggered flag stops right away
+// - Relaxed ordering is used, so the flag itself doesn't make other writes visible to the other thread
+#[derive(Debug, Clone, Default)]
 pub struct ThreadFlag {
     atomic_bool: Arc<atomic::AtomicBool>,
 }
@@ -19,11 +32,11 @@ impl ThreadFlag {
         self.atomic_bool.load(atomic::Ordering::Relaxed)
     }
 
-    pub fn trigger(&mut self) {
+    pub fn trigger(&self) {
         self.atomic_bool.store(true, atomic::Ordering::Relaxed);
     }
 
-    pub fn reset(&mut self) {
+    pub fn reset(&self) {
         self.atomic_bool.store(false, atomic::Ordering::Relaxed);
     }
 }
@@ -1,10 +1,16 @@
 use crate::board::*;
 
+#[derive(Debug, Copy, Clone)]
 pub struct TimeState {
     pub max_time: Option<f64>,                // Hard maximum time
     pub remaining_time: Option<f64>,          // Remaining time on our clock
     pub time_inc: Option<f64>,                // Time given per ply
     pub moves_till_time_control: Option<u64>, // Plies remaining until the next time control
+    pub move_overhead: f64, // Time lost to GUI or network lag each move, taken off every time budget
+
+    // If set, time is measured in nodes searched instead of on the clock, so searches are reproducible
+    // All times above are then just converted to nodes at this rate
+    pub nodes_per_sec: Option<f64>,
 }
 
 impl TimeState {
@@ -14,6 +20,8 @@ impl TimeState {
             remaining_time: None,
             time_inc: None,
             moves_till_time_control: None,
+            move_overhead: 0.0,
+            nodes_per_sec: None,
         }
     }
 }
@@ -22,13 +30,10 @@ impl TimeState {
 // If no time limit is needed, returns None
 pub fn get_max_time_to_use(board: &Board, time_state: TimeState) -> Option<f64> {
     if time_state.remaining_time.is_none() {
-        return if time_state.max_time.is_some() {
-            // Just return the max time
-            time_state.max_time
-        } else {
-            // No time limits
-            None
-        };
+        // Just use the max time, if there is one
+        return time_state
+            .max_time
+            .map(|max_time| f64::max(0.0, max_time - time_state.move_overhead));
     }
 
     let num_pieces = board.combined_occupancy().count_ones();
@@ -60,7 +65,10 @@ pub fn get_max_time_to_use(board: &Board, time_state: TimeState) -> Option<f64>
 
     // Always leave a little buffer so we don't run out of time
     const TIME_BUFFER: f64 = 0.05;
-    max_time_to_use = f64::max(0.0, max_time_to_use - TIME_BUFFER);
+    max_time_to_use = f64::max(
+        0.0,
+        max_time_to_use - TIME_BUFFER - time_state.move_overhead,
+    );
 
     Some(max_time_to_use)
 }
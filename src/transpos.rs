@@ -1,5 +1,30 @@
-use crate::eval::Value;
+use crate::board::{EncodedMove, ENCODED_MOVE_NONE};
+use crate::eval::*;
 use crate::zobrist::*;
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+// Mate evals are relative to the root, but an entry can be reached from a different root or ply
+// So, we store mates relative to the entry's position instead
+pub fn eval_to_table(eval: Value, depth_elapsed: i64) -> Value {
+    if eval >= VALUE_CHECKMATE_MIN {
+        eval + (depth_elapsed as Value)
+    } else if eval <= -VALUE_CHECKMATE_MIN {
+        eval - (depth_elapsed as Value)
+    } else {
+        eval
+    }
+}
+
+pub fn eval_from_table(eval: Value, depth_elapsed: i64) -> Value {
+    if eval >= VALUE_CHECKMATE_MIN {
+        eval - (depth_elapsed as Value)
+    } else if eval <= -VALUE_CHECKMATE_MIN {
+        eval + (depth_elapsed as Value)
+    } else {
+        eval
+    }
+}
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum EntryType {
@@ -9,96 +34,184 @@ pub enum EntryType {
     FailHigh,
 }
 
+impl EntryType {
+    fn from_u8(val: u8) -> EntryType {
+        match val {
+            1 => EntryType::Exact,
+            2 => EntryType::FailLow,
+            3 => EntryType::FailHigh,
+            _ => EntryType::Invalid,
+        }
+    }
+}
+
+// Generations are stored in 6 bits, so they wrap around every 64 searches
+const GENERATION_MASK: u8 = (1 << 6) - 1;
+
+// Only the upper half of the hash is stored in entries, the lower bits are mostly implied by the bucket index
+fn get_hash_key(hash: Hash) -> u32 {
+    (hash >> 32) as u32
+}
+
 #[derive(Debug, Copy, Clone)]
 pub struct Entry {
-    pub hash: Hash,
+    pub hash_key: u32,
     pub eval: Value,
-    pub best_move_idx: u8,
+    pub static_eval: Value, // Cached result of eval_board() for this position
+    pub best_move: EncodedMove, // Must be validated before use, since hash collisions can happen
     pub depth_remaining: u8,
     pub entry_type: EntryType,
-    pub age_count: u64,
-    pub checksum: u64,
+    pub generation: u8, // The table's generation when this entry was written
 }
 
 impl Entry {
     pub fn new() -> Entry {
         Entry {
-            hash: 0,
-            eval: 0.0,
-            best_move_idx: 0,
+            hash_key: 0,
+            eval: 0,
+            static_eval: 0,
+            best_move: ENCODED_MOVE_NONE,
             depth_remaining: 0,
             entry_type: EntryType::Invalid,
-            age_count: 0,
-            checksum: 0,
+            generation: 0,
         }
     }
 
-    pub fn update_checksum(&mut self) {
-        self.checksum = self.calc_checksum();
+    // Packs everything but the hash and static eval into a single 64-bit value
+    // The entry type only needs 2 bits, leaving the rest of the top byte for the generation
+    fn pack_data(&self) -> u64 {
+        (self.eval as u32 as u64)
+            | ((self.best_move as u64) << 32)
+            | ((self.depth_remaining as u64) << 48)
+            | ((self.entry_type as u64) << 56)
+            | (((self.generation & GENERATION_MASK) as u64) << 58)
     }
 
-    fn calc_checksum(&self) -> u64 {
-        let mut cur_checksum = 0;
-
-        cur_checksum += self.hash;
-        unsafe {
-            cur_checksum += (std::mem::transmute::<Value, i32>(self.eval) as u64) ^ cur_checksum;
+    fn unpack_data(data: u64) -> Entry {
+        Entry {
+            hash_key: 0,
+            eval: data as u32 as Value,
+            static_eval: 0,
+            best_move: (data >> 32) as EncodedMove,
+            depth_remaining: (data >> 48) as u8,
+            entry_type: EntryType::from_u8(((data >> 56) & 3) as u8),
+            generation: (data >> 58) as u8,
         }
-        cur_checksum += self.best_move_idx as u64 ^ cur_checksum;
-        cur_checksum += self.depth_remaining as u64 ^ cur_checksum;
-        cur_checksum += self.entry_type as u64 ^ cur_checksum;
-
-        // NOTE: We don't care about the age count, it's not that important
-
-        cur_checksum
     }
 
-    pub fn is_set(&self) -> bool {
+    pub fn is_valid(&self) -> bool {
         self.entry_type != EntryType::Invalid
     }
 
-    pub fn is_valid(&self) -> bool {
-        if self.entry_type == EntryType::Invalid {
-            return false;
+    // How much we want to keep this entry, entries from older searches are worth much less
+    fn get_replace_value(&self, cur_generation: u8) -> i64 {
+        if !self.is_valid() {
+            return i64::MIN;
         }
 
-        self.checksum == self.calc_checksum()
+        let age = (cur_generation.wrapping_sub(self.generation) & GENERATION_MASK) as i64;
+        (self.depth_remaining as i64) - age * 8
     }
 }
 
 ///////////////////////////////////////////
 
+// Entry storage that can be shared between threads without locking
+// The key holds the hash key and static eval, with the hash key XOR'd with everything else
+// If two threads write at once and the key and data end up from different writes,
+//  the hash key won't match and the entry is simply treated as missing
+// See: https://www.chessprogramming.org/Shared_Hash_Table#Lock-less
+struct AtomicEntry {
+    key: AtomicU64,
+    data: AtomicU64,
+}
+
+impl AtomicEntry {
+    fn new() -> AtomicEntry {
+        AtomicEntry {
+            key: AtomicU64::new(0),
+            data: AtomicU64::new(0),
+        }
+    }
+
+    // Mixes everything besides the hash key into 32 bits
+    fn get_checksum(static_eval_bits: u32, data: u64) -> u32 {
+        static_eval_bits ^ (data as u32) ^ ((data >> 32) as u32)
+    }
+
+    fn load(&self) -> Entry {
+        let key = self.key.load(Ordering::Relaxed);
+        let data = self.data.load(Ordering::Relaxed);
+        let static_eval_bits = key as u32;
+
+        let mut entry = Entry::unpack_data(data);
+        entry.hash_key = ((key >> 32) as u32) ^ Self::get_checksum(static_eval_bits, data);
+        entry.static_eval = static_eval_bits as Value;
+        entry
+    }
+
+    fn store(&self, entry: &Entry) {
+        let data = entry.pack_data();
+        let static_eval_bits = entry.static_eval as u32;
+        let checked_hash_key = entry.hash_key ^ Self::get_checksum(static_eval_bits, data);
+        self.key.store(
+            ((checked_hash_key as u64) << 32) | (static_eval_bits as u64),
+            Ordering::Relaxed,
+        );
+        self.data.store(data, Ordering::Relaxed);
+    }
+}
+
+// The first entries of each bucket only get replaced by entries that are at least as valuable
+// The last entry is always replaced, so new positions can still be stored when the rest of the bucket is full of deep entries
+// See: https://www.chessprogramming.org/Transposition_Table#Replacement_Strategies
 const ENTRIES_PER_BUCKET: usize = 4;
+const ALWAYS_REPLACE_ENTRY_IDX: usize = ENTRIES_PER_BUCKET - 1;
 
-#[derive(Debug, Copy, Clone)]
+// Aligned so that each bucket fits in a single cache line
+#[repr(align(64))]
 struct Bucket {
-    entries: [Entry; ENTRIES_PER_BUCKET],
+    entries: [AtomicEntry; ENTRIES_PER_BUCKET],
 }
 
 impl Bucket {
     pub fn new() -> Bucket {
         Bucket {
-            entries: [Entry::new(); ENTRIES_PER_BUCKET],
+            entries: std::array::from_fn(|_| AtomicEntry::new()),
         }
     }
 }
 
 ///////////////////////////////////////////
 
+fn read_u64(reader: &mut impl Read) -> std::io::Result<u64> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+// Header of saved table files, followed by the version
+const FILE_MAGIC: &[u8; 4] = b"BCTT";
+const FILE_VERSION: u32 = 4;
+
 pub struct Table {
     buckets: Vec<Bucket>,
-    age_count: u64,
+    bucket_idx_mask: usize, // The bucket count is a power of two, so we can mask instead of using modulo
+    generation: AtomicU8,   // Bumped every search, so entries from old searches get replaced first
     size_mbs: usize,
 }
 
 impl Table {
     pub fn new(size_mbs: usize) -> Table {
-        let num_buckets = (size_mbs * 1_000_000) / size_of::<Bucket>();
-        let mut buckets = Vec::with_capacity(num_buckets);
-        buckets.resize(num_buckets, Bucket::new());
+        // Round down to a power of two, so we never use more memory than we were given
+        let max_num_buckets = usize::max((size_mbs * 1024 * 1024) / size_of::<Bucket>(), 1);
+        let num_buckets = 1 << max_num_buckets.ilog2();
+
+        let buckets = (0..num_buckets).map(|_| Bucket::new()).collect();
         Table {
             buckets,
-            age_count: 0,
+            bucket_idx_mask: num_buckets - 1,
+            generation: AtomicU8::new(0),
             size_mbs,
         }
     }
@@ -107,99 +220,200 @@ impl Table {
         self.size_mbs
     }
 
-    pub fn is_any_entry_locked(&self) -> bool {
-        for bucket in &self.buckets {
-            for entry in &bucket.entries {
-                if entry.is_set() && !entry.is_valid() {
-                    return true;
+    // Returns how full the table is in permille, for UCI's "hashfull"
+    // Only samples the first buckets, and only counts entries from the current search
+    pub fn get_hashfull(&self) -> usize {
+        const SAMPLE_BUCKETS: usize = 1000 / ENTRIES_PER_BUCKET;
+
+        let generation = self.get_generation();
+        let num_sample_buckets = usize::min(SAMPLE_BUCKETS, self.buckets.len());
+        let mut num_used_entries = 0;
+        for bucket in &self.buckets[..num_sample_buckets] {
+            for atomic_entry in &bucket.entries {
+                let entry = atomic_entry.load();
+                if entry.is_valid() && entry.generation == generation {
+                    num_used_entries += 1;
                 }
             }
         }
 
-        false
+        num_used_entries * 1000 / (num_sample_buckets * ENTRIES_PER_BUCKET)
+    }
+
+    // Should be called at the start of every search
+    pub fn new_search(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn get_generation(&self) -> u8 {
+        self.generation.load(Ordering::Relaxed) & GENERATION_MASK
     }
 
     fn get_bucket_idx(&self, hash: Hash) -> usize {
-        (hash as usize) % self.buckets.len()
+        (hash as usize) & self.bucket_idx_mask
     }
 
-    // If the entry is locked, just returns an empty entry
-    pub fn get_fast(&self, hash: Hash) -> Entry {
-        let bucket = &self.buckets[self.get_bucket_idx(hash)];
-        for i in 0..ENTRIES_PER_BUCKET {
-            if bucket.entries[i].hash == hash {
-                return bucket.entries[i];
-            }
+    // Hints the CPU to start loading the bucket for this hash into cache
+    // See: https://www.chessprogramming.org/Transposition_Table#Prefetch
+    #[inline(always)]
+    pub fn prefetch(&self, hash: Hash) {
+        #[cfg(target_arch = "x86_64")]
+        unsafe {
+            use std::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+            let bucket_ptr = &self.buckets[self.get_bucket_idx(hash)] as *const Bucket;
+            _mm_prefetch(bucket_ptr as *const i8, _MM_HINT_T0);
         }
 
-        Entry::new()
+        #[cfg(not(target_arch = "x86_64"))]
+        let _ = hash;
     }
 
-    // Waits for the entry to be unlocked
-    pub fn get_wait(&self, hash: Hash) -> Entry {
+    // If no matching entry is found (or it was torn by a concurrent write), returns an empty entry
+    pub fn get_fast(&self, hash: Hash) -> Entry {
         let bucket = &self.buckets[self.get_bucket_idx(hash)];
-        loop {
-            let mut was_locked = false;
-            for i in 0..ENTRIES_PER_BUCKET {
-                if bucket.entries[i].hash == hash {
-                    let result = bucket.entries[i];
-                    if result.is_set() && !result.is_valid() {
-                        was_locked = true;
-                        break;
-                    }
-                    return result;
-                }
-            }
-
-            if was_locked {
-                continue;
-            } else {
-                return Entry::new();
+        let hash_key = get_hash_key(hash);
+        for atomic_entry in &bucket.entries {
+            let entry = atomic_entry.load();
+            if entry.hash_key == hash_key {
+                return entry;
             }
         }
+
+        Entry::new()
     }
 
     pub fn set(
-        &mut self,
+        &self,
         hash: Hash,
         eval: Value,
-        best_move_idx: u8,
+        static_eval: Value,
+        best_move: EncodedMove,
         depth_remaining: u8,
         entry_type: EntryType,
     ) {
-        let bucket_idx = self.get_bucket_idx(hash);
-        let bucket = &mut self.buckets[bucket_idx];
-
-        // Find the oldest entry to replace
-        let mut replace_entry_idx = 0;
-        let mut oldest_entry_age = u64::max_value();
-        for i in 0..ENTRIES_PER_BUCKET {
-            let existing_entry = bucket.entries[i];
-            if existing_entry.hash == hash {
-                // We found a matching hash, just use that
-                replace_entry_idx = i;
+        let bucket = &self.buckets[self.get_bucket_idx(hash)];
+        let generation = self.get_generation();
+        let hash_key = get_hash_key(hash);
+
+        let entry = Entry {
+            hash_key,
+            eval,
+            static_eval,
+            best_move,
+            depth_remaining,
+            entry_type,
+            generation,
+        };
+
+        let mut replace_entry_idx = None;
+        for (i, atomic_entry) in bucket.entries.iter().enumerate() {
+            let existing_entry = atomic_entry.load();
+            if existing_entry.hash_key == hash_key && existing_entry.is_valid() {
+                // Same position, keep the existing entry only if it's from a much deeper search of this generation
+                let is_much_deeper =
+                    existing_entry.depth_remaining as i64 > (depth_remaining as i64) + 2;
+                if is_much_deeper
+                    && existing_entry.generation == generation
+                    && entry_type != EntryType::Exact
+                {
+                    return;
+                }
+
+                replace_entry_idx = Some(i);
                 break;
             }
+        }
 
-            if existing_entry.age_count < oldest_entry_age {
-                oldest_entry_age = existing_entry.age_count;
-                replace_entry_idx = i;
+        let replace_entry_idx = replace_entry_idx.unwrap_or_else(|| {
+            // Find the least valuable depth-preferred entry
+            let mut worst_entry_idx = 0;
+            let mut worst_entry_value = i64::MAX;
+            for (i, atomic_entry) in bucket
+                .entries
+                .iter()
+                .enumerate()
+                .take(ALWAYS_REPLACE_ENTRY_IDX)
+            {
+                let value = atomic_entry.load().get_replace_value(generation);
+                if value < worst_entry_value {
+                    worst_entry_value = value;
+                    worst_entry_idx = i;
+                }
+            }
+
+            if entry.get_replace_value(generation) >= worst_entry_value {
+                worst_entry_idx
+            } else {
+                ALWAYS_REPLACE_ENTRY_IDX
+            }
+        });
+
+        bucket.entries[replace_entry_idx].store(&entry);
+    }
+
+    // Saves the table to a file, so analysis can be continued in a later session
+    pub fn save(&self, path: &str) -> std::io::Result<()> {
+        let mut writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+
+        writer.write_all(FILE_MAGIC)?;
+        writer.write_all(&FILE_VERSION.to_le_bytes())?;
+        writer.write_all(&(self.size_mbs as u64).to_le_bytes())?;
+        writer.write_all(&(self.buckets.len() as u64).to_le_bytes())?;
+        writer.write_all(&[self.get_generation()])?;
+
+        for bucket in &self.buckets {
+            for atomic_entry in &bucket.entries {
+                writer.write_all(&atomic_entry.key.load(Ordering::Relaxed).to_le_bytes())?;
+                writer.write_all(&atomic_entry.data.load(Ordering::Relaxed).to_le_bytes())?;
             }
         }
 
-        self.age_count += 1;
+        writer.flush()
+    }
 
-        let mut entry = Entry {
-            hash,
-            eval,
-            best_move_idx,
-            depth_remaining,
-            entry_type,
-            age_count: self.age_count,
-            checksum: 0,
-        };
-        entry.update_checksum();
+    pub fn load(path: &str) -> std::io::Result<Table> {
+        let invalid_data_err =
+            |msg: &str| std::io::Error::new(std::io::ErrorKind::InvalidData, msg.to_string());
+
+        let mut reader = std::io::BufReader::new(std::fs::File::open(path)?);
+
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != FILE_MAGIC {
+            return Err(invalid_data_err("not a table file"));
+        }
+
+        let mut version_bytes = [0u8; 4];
+        reader.read_exact(&mut version_bytes)?;
+        if u32::from_le_bytes(version_bytes) != FILE_VERSION {
+            return Err(invalid_data_err("unsupported table file version"));
+        }
+
+        let size_mbs = read_u64(&mut reader)? as usize;
+        let num_buckets = read_u64(&mut reader)? as usize;
+
+        let table = Table::new(size_mbs);
+        if table.buckets.len() != num_buckets {
+            return Err(invalid_data_err(
+                "bucket count doesn't match the table size",
+            ));
+        }
+
+        let mut generation = [0u8; 1];
+        reader.read_exact(&mut generation)?;
+        table.generation.store(generation[0], Ordering::Relaxed);
+
+        for bucket in &table.buckets {
+            for atomic_entry in &bucket.entries {
+                atomic_entry
+                    .key
+                    .store(read_u64(&mut reader)?, Ordering::Relaxed);
+                atomic_entry
+                    .data
+                    .store(read_u64(&mut reader)?, Ordering::Relaxed);
+            }
+        }
 
-        bucket.entries[replace_entry_idx] = entry;
+        Ok(table)
     }
 }
@@ -0,0 +1,216 @@
+use crate::board::*;
+use crate::eval;
+use crate::eval::{Value, Weight};
+use crate::eval_params;
+use crate::eval_params::EvalParams;
+use crate::fen;
+
+// Tunes the handcrafted eval weights to predict game results, by minimizing the error between the results and the eval
+// See: https://www.chessprogramming.org/Texel%27s_Tuning_Method
+
+pub struct TuneEntry {
+    pub board: Board,
+    pub result: f64, // For white, 1.0 for a win, 0.5 for a draw, 0.0 for a loss
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct TuneSettings {
+    pub max_passes: usize,
+    pub step: Weight, // How far each weight is nudged at a time, in pawns
+    pub num_threads: usize,
+}
+
+impl TuneSettings {
+    pub fn new() -> TuneSettings {
+        TuneSettings {
+            max_passes: 100,
+            step: 0.01,
+            num_threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+        }
+    }
+}
+
+impl Default for TuneSettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn parse_result(result_str: &str) -> Option<f64> {
+    match result_str.trim().trim_matches(|c| c == '[' || c == ']') {
+        "1.0" | "1" | "1-0" => Some(1.0),
+        "0.5" | "1/2-1/2" => Some(0.5),
+        "0.0" | "0" | "0-1" => Some(0.0),
+        _ => None,
+    }
+}
+
+// Parses a line of "<fen> | <result>" or "<fen> | <score> | <result>" (the datagen format)
+pub fn parse_entry(line: &str) -> Option<TuneEntry> {
+    let parts: Vec<&str> = line.split('|').collect();
+    if parts.len() < 2 {
+        return None;
+    }
+
+    let board = fen::load_fen(parts[0].trim()).ok()?;
+    let result = parse_result(parts[parts.len() - 1])?;
+    Some(TuneEntry { board, result })
+}
+
+pub fn load_dataset(path: &str) -> std::io::Result<Vec<TuneEntry>> {
+    let data = std::fs::read_to_string(path)?;
+
+    let mut entries = Vec::new();
+    for (line_idx, line) in data.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        match parse_entry(line) {
+            Some(entry) => entries.push(entry),
+            None => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("invalid entry on line {}: \"{}\"", line_idx + 1, line),
+                ))
+            }
+        }
+    }
+
+    Ok(entries)
+}
+
+// Maps an eval to an expected result from 0-1
+fn sigmoid(eval: Value, k: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-k * (eval as f64) / 400.0))
+}
+
+// Evaluates with the current global eval params
+fn eval_entry(entry: &TuneEntry) -> Value {
+    // The board's piece-square and material scores were made with whatever params were set when it was loaded
+    let mut board = entry.board;
+    board.piece_square_scores = eval::calc_piece_square_scores(&board);
+    board.material_scores = eval::calc_material_scores(&board);
+
+    let eval = eval::eval_board(&board);
+    if board.turn_idx == 0 {
+        eval
+    } else {
+        -eval
+    }
+}
+
+// Mean squared error between the results and the eval's predictions
+pub fn calc_error(entries: &[TuneEntry], k: f64, num_threads: usize) -> f64 {
+    if entries.is_empty() {
+        return 0.0;
+    }
+
+    let chunk_size = entries.len().div_ceil(num_threads.max(1));
+    let total_error: f64 = std::thread::scope(|scope| {
+        let handles: Vec<_> = entries
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|entry| (entry.result - sigmoid(eval_entry(entry), k)).powi(2))
+                        .sum::<f64>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .sum()
+    });
+
+    total_error / (entries.len() as f64)
+}
+
+// Finds the sigmoid scaling that best fits the current eval, which stays fixed while tuning
+pub fn find_best_k(entries: &[TuneEntry], num_threads: usize) -> f64 {
+    let mut best_k = 1.0;
+    let mut best_error = calc_error(entries, best_k, num_threads);
+
+    // Narrow down the search range each round
+    let mut step = 0.5;
+    for _ in 0..10 {
+        let mut improved = true;
+        while improved {
+            improved = false;
+            for k in [best_k - step, best_k + step] {
+                if k <= 0.0 {
+                    continue;
+                }
+
+                let error = calc_error(entries, k, num_threads);
+                if error < best_error {
+                    best_k = k;
+                    best_error = error;
+                    improved = true;
+                }
+            }
+        }
+        step /= 2.0;
+    }
+
+    best_k
+}
+
+// Local search: every weight is nudged up or down, and the change is kept if it lowers the error
+// Calls on_pass with the params after each pass, so progress can be saved
+// NOTE: Changes the global eval params while running, and leaves the tuned params set
+pub fn tune(
+    entries: &[TuneEntry],
+    start_params: EvalParams,
+    settings: &TuneSettings,
+    mut on_pass: impl FnMut(usize, &EvalParams, f64),
+) -> EvalParams {
+    let mut params = start_params;
+    eval_params::set(params.clone());
+
+    let k = find_best_k(entries, settings.num_threads);
+    let mut best_error = calc_error(entries, k, settings.num_threads);
+    println!("Tuning with K={:.4}, starting error {:.8}", k, best_error);
+
+    let mut num_weights = 0;
+    params.for_each_weight(&mut |_| num_weights += 1);
+
+    for pass_idx in 0..settings.max_passes {
+        let mut improved = false;
+        for weight_idx in 0..num_weights {
+            for delta in [settings.step, -settings.step] {
+                let mut next_params = params.clone();
+                nudge_weight(&mut next_params, weight_idx, delta);
+                eval_params::set(next_params.clone());
+
+                let error = calc_error(entries, k, settings.num_threads);
+                if error < best_error {
+                    best_error = error;
+                    params = next_params;
+                    improved = true;
+                    break;
+                }
+            }
+        }
+
+        eval_params::set(params.clone());
+        on_pass(pass_idx, &params, best_error);
+        if !improved {
+            break;
+        }
+    }
+
+    params
+}
+
+fn nudge_weight(params: &mut EvalParams, target_idx: usize, delta: Weight) {
+    let mut weight_idx = 0;
+    params.for_each_weight(&mut |weight| {
+        if weight_idx == target_idx {
+            *weight += delta;
+        }
+        weight_idx += 1;
+    });
+}
@@ -1,69 +1,191 @@
-use crate::async_engine::AsyncEngine;
+use crate::async_engine::{AsyncEngine, SearchParams};
+use crate::bench;
 use crate::board::*;
+use crate::book::Book;
+use crate::debug_log;
 use crate::eval::*;
+use crate::eval_params;
+use crate::eval_params::EvalParams;
 use crate::fen;
+use crate::log_println;
 use crate::move_gen;
-use crate::search;
-use crate::search::SearchInfo;
+use crate::nnue;
+use crate::perft;
+use crate::search::{GameResult, SearchListener, SearchProgress, SearchStats};
+#[cfg(feature = "tune-options")]
+use crate::search_params;
 use crate::time_manager::TimeState;
-use crate::transpos;
 use std::cmp::PartialEq;
+use std::sync::{Arc, Mutex};
 // Refs:
 // - https://gist.github.com/DOBRO/2592c6dad754ba67e6dcaec8c90165bf
 // - https://github.com/ZealanL/BoardMouse/blob/4d3b6c608a3cb82a1299580a90dcb3c831fc02f8/src/UCI/UCI.cpp
 
-#[derive(Debug, Copy, Clone, PartialEq)]
+// Each type of option gets its own handler, which is only called with a value that's already checked and parsed
+// String and combo handlers can still refuse a value by returning an error, then the old value is kept
+#[derive(Debug, Copy, Clone)]
 enum UCIOptionType {
-    Int,
-    Bool,
-    Button,
+    Int {
+        value_min: i64,
+        value_max: i64,
+        handler: Option<fn(&mut UCIState, i64)>,
+    },
+    Bool {
+        handler: Option<fn(&mut UCIState, bool)>,
+    },
+    Button {
+        handler: fn(&mut UCIState),
+    },
+    String {
+        handler: fn(&mut UCIState, &str) -> Option<String>,
+    },
+    Combo {
+        choices: &'static [&'static str],
+        handler: Option<fn(&mut UCIState, &str) -> Option<String>>,
+    },
 }
 
-#[derive(Debug, Copy, Clone)]
+impl UCIOptionType {
+    // As written in the "option" lines of the "uci" command
+    fn get_name(&self) -> &'static str {
+        match self {
+            UCIOptionType::Int { .. } => "spin",
+            UCIOptionType::Bool { .. } => "check",
+            UCIOptionType::Button { .. } => "button",
+            UCIOptionType::String { .. } => "string",
+            UCIOptionType::Combo { .. } => "combo",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 struct UCIOption {
     option_type: UCIOptionType,
     name: &'static str,
-    value: i64,
-    value_min: i64,
-    value_max: i64,
-    change_callback: Option<fn(&mut UCIState, i64)>,
+    value: i64,        // Only for int and bool options
+    value_str: String, // Only for string and combo options
 }
 
 impl UCIOption {
-    const TYPE_NAMES: [&'static str; 3] = ["spin", "check", "button"];
-
     pub fn new_int(
         name: &'static str,
         default: i64,
         value_min: i64,
         value_max: i64,
-        change_callback: Option<fn(&mut UCIState, i64)>,
+        handler: Option<fn(&mut UCIState, i64)>,
     ) -> UCIOption {
         UCIOption {
-            option_type: UCIOptionType::Int,
+            option_type: UCIOptionType::Int {
+                value_min,
+                value_max,
+                handler,
+            },
             name,
             value: default,
-            value_min,
-            value_max,
-            change_callback,
+            value_str: String::new(),
         }
     }
 
-    pub fn new_button(name: &'static str, change_callback: fn(&mut UCIState, i64)) -> UCIOption {
+    pub fn new_bool(
+        name: &'static str,
+        default: bool,
+        handler: Option<fn(&mut UCIState, bool)>,
+    ) -> UCIOption {
         UCIOption {
-            option_type: UCIOptionType::Button,
+            option_type: UCIOptionType::Bool { handler },
+            name,
+            value: default as i64,
+            value_str: String::new(),
+        }
+    }
+
+    pub fn new_button(name: &'static str, handler: fn(&mut UCIState)) -> UCIOption {
+        UCIOption {
+            option_type: UCIOptionType::Button { handler },
             name,
             value: 0,
-            value_min: 0,
-            value_max: 0,
-            change_callback: Some(change_callback),
+            value_str: String::new(),
+        }
+    }
+
+    pub fn new_string(
+        name: &'static str,
+        default: &str,
+        handler: fn(&mut UCIState, &str) -> Option<String>,
+    ) -> UCIOption {
+        UCIOption {
+            option_type: UCIOptionType::String { handler },
+            name,
+            value: 0,
+            value_str: default.to_string(),
+        }
+    }
+
+    // The default must be one of the choices
+    pub fn new_combo(
+        name: &'static str,
+        default: &'static str,
+        choices: &'static [&'static str],
+        handler: Option<fn(&mut UCIState, &str) -> Option<String>>,
+    ) -> UCIOption {
+        debug_assert!(choices.contains(&default));
+        UCIOption {
+            option_type: UCIOptionType::Combo { choices, handler },
+            name,
+            value: 0,
+            value_str: default.to_string(),
+        }
+    }
+
+    // Prints the line the "uci" command advertises this option with
+    fn print(&self) {
+        let mut line = format!(
+            "option name {} type {}",
+            self.name,
+            self.option_type.get_name()
+        );
+
+        match self.option_type {
+            UCIOptionType::Int {
+                value_min,
+                value_max,
+                ..
+            } => {
+                line += &format!(
+                    " default {} min {} max {}",
+                    self.value, value_min, value_max
+                );
+            }
+            UCIOptionType::Bool { .. } => {
+                line += &format!(" default {}", self.value > 0);
+            }
+            UCIOptionType::Button { .. } => {}
+            UCIOptionType::String { .. } => {
+                line += &format!(" default {}", self.value_str);
+            }
+            UCIOptionType::Combo { choices, .. } => {
+                line += &format!(" default {}", self.value_str);
+                for choice in choices {
+                    line += &format!(" var {}", choice);
+                }
+            }
         }
+
+        log_println!("{}", line);
     }
 }
 
+// Value of string options that aren't set to anything
+const UCI_EMPTY_STRING: &str = "<empty>";
+
 pub struct UCIState {
     engine: AsyncEngine,
     options: Vec<UCIOption>,
+    book: Option<Book>,
+    game_ply: usize, // Moves played since the position command's start position
+    // Set when a tune option changes, they're only applied once the next search starts
+    #[cfg(feature = "tune-options")]
+    tune_options_changed: bool,
 }
 
 impl UCIState {
@@ -71,6 +193,17 @@ impl UCIState {
         const DEFAULT_TABLE_SIZE_MBS: usize = 100;
         let options = [
             UCIOption::new_int("Threads", 8, 1, 256, None),
+            UCIOption::new_int("MultiPV", 1, 1, 256, None),
+            // Only tells the GUI that we support pondering, it decides when we ponder
+            UCIOption::new_bool("Ponder", false, None),
+            // Castles are sent as the king taking its rook, see Move::to_uci()
+            UCIOption::new_bool("UCI_Chess960", false, None),
+            UCIOption::new_int("Contempt", 0, -100, 100, None), // In centipawns
+            // In milliseconds, raise this if we lose on time from lag
+            UCIOption::new_int("Move Overhead", 10, 0, 5000, None),
+            // Nodes per millisecond, where 0 is off
+            // If on, time is counted in nodes searched instead, which makes timed searches reproducible (with 1 thread)
+            UCIOption::new_int("nodestime", 0, 0, 100_000, None),
             UCIOption::new_int(
                 "Hash",
                 DEFAULT_TABLE_SIZE_MBS as i64,
@@ -80,23 +213,185 @@ impl UCIState {
                     state.engine.maybe_update_table_size(new_value as usize);
                 }),
             ),
-            UCIOption::new_button("Clear Hash", |state: &mut UCIState, new_value: i64| {
+            UCIOption::new_button("Clear Hash", |state: &mut UCIState| {
                 state.engine.reset_table();
             }),
+            // Without a network, the handcrafted eval is used
+            UCIOption::new_string(
+                "EvalFile",
+                UCI_EMPTY_STRING,
+                |state: &mut UCIState, path: &str| {
+                    if path.is_empty() || path == UCI_EMPTY_STRING {
+                        set_network(state, None);
+                        return None;
+                    }
+
+                    match nnue::Network::load(path) {
+                        Ok(network) => {
+                            set_network(state, Some(network));
+                            None
+                        }
+                        Err(err) => {
+                            Some(format!("Failed to load network from \"{}\": {}", path, err))
+                        }
+                    }
+                },
+            ),
+            // Weights for the handcrafted eval, see eval_params.rs
+            UCIOption::new_string(
+                "EvalParamsFile",
+                UCI_EMPTY_STRING,
+                |state: &mut UCIState, path: &str| {
+                    let params = if path.is_empty() || path == UCI_EMPTY_STRING {
+                        EvalParams::DEFAULT
+                    } else {
+                        match EvalParams::load(path) {
+                            Ok(params) => params,
+                            Err(err) => {
+                                return Some(format!(
+                                    "Failed to load eval params from \"{}\": {}",
+                                    path, err
+                                ))
+                            }
+                        }
+                    };
+
+                    set_eval_params(state, params);
+                    None
+                },
+            ),
+            UCIOption::new_bool("OwnBook", false, None),
+            // "Weighted" picks randomly, where moves with more weight are picked more often, "Best" always picks the heaviest
+            UCIOption::new_combo("BookMoveChoice", "Weighted", &["Weighted", "Best"], None),
+            UCIOption::new_string(
+                "BookFile",
+                UCI_EMPTY_STRING,
+                |state: &mut UCIState, path: &str| {
+                    if path.is_empty() || path == UCI_EMPTY_STRING {
+                        state.book = None;
+                        return None;
+                    }
+
+                    match Book::load(path) {
+                        Ok(book) => {
+                            log_println!("info string Loaded book with {} entries", book.len());
+                            state.book = Some(book);
+                            None
+                        }
+                        Err(err) => Some(format!("Failed to load book from \"{}\": {}", path, err)),
+                    }
+                },
+            ),
+            // Book moves are only played this many plies into the game
+            UCIOption::new_int("BookDepth", 20, 1, 1000, None),
+            // Appends all UCI traffic to this file, see debug_log.rs
+            UCIOption::new_string(
+                "Debug Log File",
+                UCI_EMPTY_STRING,
+                |_state: &mut UCIState, path: &str| {
+                    let path =
+                        Some(path).filter(|path| !path.is_empty() && *path != UCI_EMPTY_STRING);
+                    debug_log::set_path(path).err().map(|err| {
+                        format!(
+                            "Failed to open debug log file \"{}\": {}",
+                            path.unwrap(),
+                            err
+                        )
+                    })
+                },
+            ),
         ];
 
+        let mut engine = AsyncEngine::new(DEFAULT_TABLE_SIZE_MBS);
+        engine.set_listener(Arc::new(Mutex::new(UCIListener)));
+
         let mut result = UCIState {
-            engine: AsyncEngine::new(DEFAULT_TABLE_SIZE_MBS),
+            engine,
             options: Vec::new(),
+            book: None,
+            game_ply: 0,
+            #[cfg(feature = "tune-options")]
+            tune_options_changed: false,
         };
 
         for option in options.iter() {
             result.options.push(option.clone());
         }
 
+        #[cfg(feature = "tune-options")]
+        result.add_tune_options();
+
         result
     }
 
+    // Every search param and eval weight as a spin option, see search_params.rs
+    // Eval weights are in centipawns, while the params themselves are in pawns
+    #[cfg(feature = "tune-options")]
+    fn add_tune_options(&mut self) {
+        const EVAL_WEIGHT_RANGE: i64 = 10000;
+
+        // Options keep their name for the whole program, so the generated names are leaked once here
+        fn leak_name(name: &str) -> &'static str {
+            Box::leak(name.to_string().into_boxed_str())
+        }
+
+        let on_change: fn(&mut UCIState, i64) = |state, _| state.tune_options_changed = true;
+        search_params::get()
+            .clone()
+            .for_each_option(&mut |name, value, value_min, value_max| {
+                self.options.push(UCIOption::new_int(
+                    leak_name(name),
+                    *value,
+                    value_min,
+                    value_max,
+                    Some(on_change),
+                ));
+            });
+        eval_params::get()
+            .clone()
+            .for_each_named_weight(&mut |name, weight| {
+                let value = weight_to_centipawns(*weight);
+                self.options.push(UCIOption::new_int(
+                    leak_name(name),
+                    value,
+                    i64::min(value, -EVAL_WEIGHT_RANGE),
+                    i64::max(value, EVAL_WEIGHT_RANGE),
+                    Some(on_change),
+                ));
+            });
+    }
+
+    pub fn get_engine(&self) -> &AsyncEngine {
+        &self.engine
+    }
+
+    pub fn get_engine_mut(&mut self) -> &mut AsyncEngine {
+        &mut self.engine
+    }
+
+    // NOTE: Doesn't call the option's change callback
+    pub fn set_option_val(&mut self, name: &str, value: i64) {
+        for option in &mut self.options {
+            if option.name == name {
+                option.value = value;
+                return;
+            }
+        }
+
+        panic!("UCI Option {} not found", name);
+    }
+
+    pub fn set_option_str(&mut self, name: &str, value: &str) {
+        for option in &mut self.options {
+            if option.name == name {
+                option.value_str = value.to_string();
+                return;
+            }
+        }
+
+        panic!("UCI Option {} not found", name);
+    }
+
     pub fn get_option_val(&self, name: &str) -> i64 {
         for option in &self.options {
             if option.name == name {
@@ -106,50 +401,147 @@ impl UCIState {
 
         panic!("UCI Option {} not found", name);
     }
+
+    // For string and combo options
+    pub fn get_option_str(&self, name: &str) -> &str {
+        for option in &self.options {
+            if option.name == name {
+                return &option.value_str;
+            }
+        }
+
+        panic!("UCI Option {} not found", name);
+    }
+}
+
+// Prints the search's progress as info lines, and its best move
+pub struct UCIListener;
+
+impl SearchListener for UCIListener {
+    fn on_new_pv(&mut self, board: &Board, progress: &SearchProgress) {
+        print_search_results(board, progress);
+    }
+
+    fn on_current_move(&mut self, board: &Board, mv: &Move, move_number: usize, depth: u8) {
+        print_current_move(board, mv, move_number, depth);
+    }
+
+    fn on_finish(&mut self, board: &Board, best_move: Move, ponder_move: Option<Move>) {
+        print_best_move(best_move, ponder_move, board.is_chess960);
+    }
+
+    // There's no move to play, so we send the null move
+    fn on_no_legal_moves(&mut self, _board: &Board, game_result: GameResult) {
+        if game_result == GameResult::Checkmate {
+            log_println!("info depth 0 score mate 0");
+        } else {
+            log_println!("info depth 0 score cp 0");
+        }
+        log_println!("bestmove 0000");
+    }
 }
 
 //////////////////////////
 
-pub fn print_search_results(
-    board: &Board,
-    table: &transpos::Table,
-    depth: u8,
-    eval: Value,
-    search_info: &SearchInfo,
-    elapsed_time: f64,
-) {
-    let mut moves = move_gen::MoveBuffer::new();
-    move_gen::generate_moves(board, &mut moves);
-
-    let pv_moves = search::determine_pv(*board, table);
+pub fn print_search_results(board: &Board, progress: &SearchProgress) {
     let mut pv_str = String::new();
-    for i in 0..pv_moves.len() {
+    for i in 0..progress.pv.len() {
         if i > 0 {
             pv_str.push(' ');
         }
 
-        pv_str += format!("{}", &pv_moves[i]).as_str();
+        pv_str += &progress.pv[i].to_uci(board.is_chess960);
     }
 
-    let eval_str;
-    if eval.abs() >= VALUE_CHECKMATE_MIN {
-        eval_str = eval_to_str(eval).replace("#", "mate ");
+    let eval_str = if let Some(mate_moves) = get_mate_moves(progress.eval) {
+        format!("mate {}", mate_moves)
     } else {
-        eval_str = format!("cp {}", (eval * 100.0).round() as i64);
-    }
+        format!("cp {}", progress.eval)
+    };
+
+    let depth = progress.depth;
+    let sel_depth = progress.sel_depth;
+    let multipv = progress.multipv;
+    let hashfull = progress.hashfull;
+    let total_nodes = progress.total_nodes;
+    // The clock might not have ticked yet
+    let nodes_per_sec = ((total_nodes as f64) / progress.elapsed_time.max(0.001)).round() as i64;
+    let elapsed_ms = (progress.elapsed_time * 1000.0).round() as i64;
+
+    log_println!(
+        "info depth {depth} seldepth {sel_depth} multipv {multipv} score {eval_str} nodes {total_nodes} nps {nodes_per_sec} hashfull {hashfull} time {elapsed_ms} pv {pv_str}"
+    );
+}
+
+pub fn print_current_move(board: &Board, mv: &Move, move_number: usize, depth: u8) {
+    log_println!(
+        "info depth {} currmove {} currmovenumber {}",
+        depth,
+        mv.to_uci(board.is_chess960),
+        move_number
+    );
+}
+
+// Diagnostics for one iteration, printed in debug mode
+pub fn print_search_stats(depth: u8, stats: &SearchStats) {
+    let hit_percent = if stats.table_probes > 0 {
+        (stats.table_hits as f64) * 100.0 / (stats.table_probes as f64)
+    } else {
+        0.0
+    };
+    log_println!(
+        "info string Depth {} table: probes {} hits {} ({:.1}%) cutoffs {}",
+        depth,
+        stats.table_probes,
+        stats.table_hits,
+        hit_percent,
+        stats.table_cutoffs
+    );
+    log_println!(
+        "info string Depth {} pruned nodes: reverse futility {} razoring {} null move {} probcut {} multi-cut {}",
+        depth,
+        stats.reverse_futility_prunes,
+        stats.razor_prunes,
+        stats.null_move_prunes,
+        stats.probcut_prunes,
+        stats.multi_cut_prunes
+    );
+    log_println!(
+        "info string Depth {} pruned moves: futility {} late move {}",
+        depth,
+        stats.futility_prunes,
+        stats.late_move_prunes
+    );
+}
 
-    let multipv = 1;
-    let total_nodes = search_info.total_nodes;
-    let nodes_per_sec = ((search_info.total_nodes as f64) / elapsed_time).round() as i64;
-    let elapsed_ms = (elapsed_time * 1000.0).round() as i64;
+// Prints the node count after each root move (divide), then the total and how long it took
+pub fn print_perft(board: &Board, depth: u8, num_threads: usize, table_size_mbs: usize) {
+    let settings = perft::PerftSettings {
+        num_threads,
+        print: true,
+        table_size_mbs,
+    };
 
-    println!(
-        "info depth {depth} multipv {multipv} score {eval_str} nodes {total_nodes} nps {nodes_per_sec} time {elapsed_ms} pv {pv_str}"
+    let start_time = std::time::Instant::now();
+    let total_nodes = perft::perft_with_settings(board, depth, &settings);
+    let elapsed_time = start_time.elapsed().as_secs_f64();
+    log_println!(
+        "Time: {}ms ({} nps)",
+        (elapsed_time * 1000.0).round() as i64,
+        ((total_nodes as f64) / elapsed_time.max(1e-9)).round() as i64
     );
 }
 
-pub fn print_best_move(best_move: Move) {
-    println!("bestmove {}", best_move);
+pub fn print_best_move(best_move: Move, ponder_move: Option<Move>, is_chess960: bool) {
+    if let Some(ponder_move) = ponder_move {
+        log_println!(
+            "bestmove {} ponder {}",
+            best_move.to_uci(is_chess960),
+            ponder_move.to_uci(is_chess960)
+        );
+    } else {
+        log_println!("bestmove {}", best_move.to_uci(is_chess960));
+    }
 }
 
 // Just returns an Option<String> of the error
@@ -177,33 +569,14 @@ inventory::submit! {
     Command::new("uci", cmd_uci)
 }
 fn cmd_uci(_parts: &Vec<String>, state: &mut UCIState) -> Option<String> {
-    println!("id name BoardCrab v{}", env!("CARGO_PKG_VERSION"));
-    println!("id author ZealanL");
+    log_println!("id name BoardCrab v{}", env!("CARGO_PKG_VERSION"));
+    log_println!("id author ZealanL");
 
     for option in &state.options {
-        print!(
-            "option name {} type {}",
-            option.name,
-            UCIOption::TYPE_NAMES[option.option_type as usize]
-        );
-
-        match option.option_type {
-            UCIOptionType::Int => {
-                print!(
-                    " default {} min {} max {}",
-                    option.value, option.value_min, option.value_max
-                );
-            }
-            UCIOptionType::Bool => {
-                print!(" default {}", option.value > 0);
-            }
-            UCIOptionType::Button => {}
-        }
-
-        println!();
+        option.print();
     }
 
-    println!("uciok");
+    log_println!("uciok");
     None
 }
 
@@ -211,7 +584,22 @@ inventory::submit! {
     Command::new("isready", cmd_isready)
 }
 fn cmd_isready(_parts: &Vec<String>, _state: &mut UCIState) -> Option<String> {
-    println!("readyok");
+    log_println!("readyok");
+    None
+}
+
+inventory::submit! {
+    Command::new("debug", cmd_debug)
+}
+// When on, we print extra diagnostics as info strings
+fn cmd_debug(parts: &Vec<String>, state: &mut UCIState) -> Option<String> {
+    let is_debug = match parts.get(1).map(|part| part.as_str()) {
+        Some("on") => true,
+        Some("off") => false,
+        _ => return cmd_err!("Invalid syntax, format: \"debug on|off\""),
+    };
+
+    state.engine.set_debug(is_debug);
     None
 }
 
@@ -219,100 +607,115 @@ inventory::submit! {
     Command::new("setoption", cmd_setoption)
 }
 fn cmd_setoption(parts: &Vec<String>, state: &mut UCIState) -> Option<String> {
-    if parts.len() <= 3 || parts[1] != "name" {
+    if parts.len() < 3 || parts[1] != "name" {
         return cmd_err!("Invalid syntax, format: \"setoption name <name> value <value>\"");
     }
 
-    // Collect the option name
-    let mut new_value_start_idx = 3;
-    let mut option_name = String::new();
-    for i in 2..parts.len() {
-        if parts[i] != "value" {
-            if !option_name.is_empty() {
-                option_name += " ";
-            }
-            option_name += parts[i].as_str();
-            new_value_start_idx += 1;
-        } else {
-            break;
-        }
-    }
+    // Both the name and the value can have spaces
+    let value_idx = parts[2..]
+        .iter()
+        .position(|part| part == "value")
+        .map_or(parts.len(), |idx| idx + 2);
+    let option_name = parts[2..value_idx].join(" ");
+    let new_value_str = parts.get((value_idx + 1)..).unwrap_or_default().join(" ");
 
-    let mut new_value_str = String::new();
-    for i in new_value_start_idx..parts.len() {
-        if !new_value_str.is_empty() {
-            new_value_str += " ";
-        }
-        new_value_str += parts[i].as_str();
+    let Some(option) = state
+        .options
+        .iter_mut()
+        .find(|option| option.name.eq_ignore_ascii_case(&option_name))
+    else {
+        return cmd_err!("No option named \"{}\"", option_name);
+    };
+    let option_name = option.name;
+
+    if new_value_str.is_empty() && !matches!(option.option_type, UCIOptionType::Button { .. }) {
+        return cmd_err!("Value missing");
     }
 
-    for option in &mut state.options {
-        if option.name.eq_ignore_ascii_case(&option_name) {
-            let is_button = option.option_type == UCIOptionType::Button;
-            if !is_button && new_value_str.is_empty() {
-                return cmd_err!("Value missing");
+    match option.option_type {
+        UCIOptionType::Int {
+            value_min,
+            value_max,
+            handler,
+        } => {
+            let Ok(new_value) = new_value_str.parse::<i64>() else {
+                return cmd_err!("Invalid number value: \"{}\"", new_value_str);
+            };
+            if new_value < value_min || new_value > value_max {
+                return cmd_err!(
+                    "Invalid number value \"{}\", valid range is [{}-{}]",
+                    new_value,
+                    value_min,
+                    value_max
+                );
             }
 
-            let new_value: i64 = match new_value_str.to_lowercase().as_str() {
-                "false" => 0,
-                "true" => 1,
+            option.value = new_value;
+            log_println!("info string \"{}\" -> {}", option_name, new_value);
+            if let Some(handler) = handler {
+                handler(state, new_value);
+            }
+        }
+        UCIOptionType::Bool { handler } => {
+            let new_value = match new_value_str.to_lowercase().as_str() {
+                "true" | "1" => true,
+                "false" | "0" => false,
                 _ => {
-                    let parsed = new_value_str.parse::<i64>();
-                    if parsed.is_ok() {
-                        parsed.ok().unwrap()
-                    } else {
-                        if is_button {
-                            // We don't need a value
-                            0
-                        } else {
-                            return cmd_err!("Invalid number value: \"{}\"", new_value_str);
-                        }
-                    }
+                    return cmd_err!(
+                        "Invalid bool value: \"{}\", expected \"false\", \"true\", \"0\", or \"1\"",
+                        new_value_str
+                    );
                 }
             };
 
-            // Invalid value handling
-            match option.option_type {
-                UCIOptionType::Int => {
-                    if new_value < option.value_min || new_value > option.value_max {
-                        return cmd_err!(
-                            "Invalid number value \"{}\", valid range is [{}-{}]",
-                            new_value,
-                            option.value_min,
-                            option.value_max
-                        );
-                    }
-                }
-                UCIOptionType::Bool => {
-                    if new_value < 0 || new_value > 1 {
-                        return cmd_err!("Invalid bool value: \"{}\", expected \"false\", \"true\", \"0\", or \"1\"", new_value_str);
-                    }
-                }
-                UCIOptionType::Button => {
-                    // Don't care
-                }
+            option.value = new_value as i64;
+            log_println!("info string \"{}\" -> {}", option_name, new_value);
+            if let Some(handler) = handler {
+                handler(state, new_value);
             }
-
-            option.value = new_value;
-            if is_button {
-                println!("info string \"{}\" triggered", option.name);
-            } else {
-                println!("info string \"{}\" -> {}", option.name, new_value_str);
+        }
+        UCIOptionType::Button { handler } => {
+            log_println!("info string \"{}\" triggered", option_name);
+            handler(state);
+        }
+        UCIOptionType::String { handler } => {
+            if let Some(err) = handler(state, &new_value_str) {
+                return Some(err);
             }
-            if option.change_callback.is_some() {
-                option.change_callback.unwrap()(state, new_value);
+
+            state.set_option_str(option_name, &new_value_str);
+            log_println!("info string \"{}\" -> {}", option_name, new_value_str);
+        }
+        UCIOptionType::Combo { choices, handler } => {
+            // Like option names, choices aren't case sensitive
+            let Some(&choice) = choices
+                .iter()
+                .find(|choice| choice.eq_ignore_ascii_case(&new_value_str))
+            else {
+                return cmd_err!(
+                    "Invalid choice \"{}\", expected one of: {}",
+                    new_value_str,
+                    choices.join(", ")
+                );
+            };
+
+            if let Some(err) = handler.and_then(|handler| handler(state, choice)) {
+                return Some(err);
             }
-            return None;
+
+            state.set_option_str(option_name, choice);
+            log_println!("info string \"{}\" -> {}", option_name, choice);
         }
     }
 
-    cmd_err!("No option named \"{}\"", option_name)
+    None
 }
 
 inventory::submit! {
     Command::new("quit", cmd_quit)
 }
-fn cmd_quit(_parts: &Vec<String>, _state: &mut UCIState) -> Option<String> {
+fn cmd_quit(_parts: &Vec<String>, state: &mut UCIState) -> Option<String> {
+    shutdown(state);
     std::process::exit(0)
 }
 
@@ -326,6 +729,8 @@ fn cmd_position(parts: &Vec<String>, state: &mut UCIState) -> Option<String> {
 
     let mut board;
 
+    let mut game_hashes = Vec::new();
+
     let mut cur_part_idx: usize = 2;
     if parts[1] == "fen" {
         let mut fen_part_amount: usize = 0;
@@ -350,37 +755,34 @@ fn cmd_position(parts: &Vec<String>, state: &mut UCIState) -> Option<String> {
         return cmd_err!("Unknown position type \"{}\"", parts[1]);
     }
 
+    // Chess960 FENs turn this on by themselves, but the GUI can also have it on for a normal looking position
+    board.is_chess960 |= state.get_option_val("UCI_Chess960") != 0;
+
     if cur_part_idx < parts.len() {
         if parts[cur_part_idx] == "moves" {
             for i in (cur_part_idx + 1)..parts.len() {
-                let move_str = &parts[i];
-
-                let mut moves = move_gen::MoveBuffer::new();
-                move_gen::generate_moves(&board, &mut moves);
-
-                let mut move_found = false;
-                for mv in moves.iter() {
-                    if format!("{mv}").eq(move_str) {
-                        board.do_move(mv);
-                        move_found = true;
-                        break;
+                let mv = match board.parse_uci_move(&parts[i]) {
+                    Ok(mv) => mv,
+                    Err(err) => {
+                        return cmd_err!("{} for position \"{}\"", err, fen::make_fen(&board));
                     }
-                }
+                };
 
-                if !move_found {
-                    return cmd_err!(
-                        "Invalid move \"{}\" for position \"{}\"",
-                        move_str,
-                        fen::make_fen(&board)
-                    );
+                if mv.has_flag(Move::FLAG_CAPTURE) || mv.from_piece_idx == PIECE_PAWN {
+                    // Positions before this move can't ever be repeated
+                    game_hashes.clear();
+                } else {
+                    game_hashes.push(board.hash);
                 }
+                board.do_move(&mv);
             }
         } else {
             return cmd_err!("Unknown position suffix \"{}\"", parts[cur_part_idx]);
         }
     }
 
-    state.engine.set_board(&board);
+    state.engine.set_board(&board, game_hashes);
+    state.game_ply = parts.len().saturating_sub(cur_part_idx + 1);
     None
 }
 
@@ -388,7 +790,40 @@ inventory::submit! {
     Command::new("go", cmd_go)
 }
 fn cmd_go(parts: &Vec<String>, state: &mut UCIState) -> Option<String> {
+    #[cfg(feature = "tune-options")]
+    apply_tune_options(state);
+
     let board = state.engine.get_board();
+    let mut params = SearchParams::new();
+
+    // Restrict the root to only the given moves
+    if let Some(searchmoves_idx) = parts.iter().position(|part| part == "searchmoves") {
+        let mut moves = move_gen::MoveBuffer::new();
+        move_gen::generate_moves(board, &mut moves);
+
+        let mut allowed_moves = vec![false; moves.len()];
+        for move_str in parts.iter().skip(searchmoves_idx + 1) {
+            let move_idx = moves
+                .iter()
+                .position(|mv| mv.to_uci(board.is_chess960).eq(move_str));
+            if let Some(move_idx) = move_idx {
+                allowed_moves[move_idx] = true;
+            } else {
+                // End of the move list
+                break;
+            }
+        }
+
+        if !allowed_moves.contains(&true) {
+            return cmd_err!("No valid moves given to \"searchmoves\"");
+        }
+
+        for (i, &is_allowed) in allowed_moves.iter().enumerate() {
+            if !is_allowed {
+                params.root_excluded_moves.push(i as u8);
+            }
+        }
+    }
 
     let mut pairs = Vec::new();
     let mut singles = Vec::new();
@@ -406,8 +841,9 @@ fn cmd_go(parts: &Vec<String>, state: &mut UCIState) -> Option<String> {
         }
     }
 
-    let mut max_depth: u8 = u8::MAX;
     let mut time_state: TimeState = TimeState::new();
+    params.is_pondering = singles.iter().any(|single| single == "ponder");
+    params.is_infinite = singles.iter().any(|single| single == "infinite");
 
     let remaining_time_str = if board.turn_idx == 0 {
         "wtime"
@@ -418,9 +854,19 @@ fn cmd_go(parts: &Vec<String>, state: &mut UCIState) -> Option<String> {
 
     for pair in pairs {
         let first_arg = pair.0.as_str();
+        if pair.1 < 0 {
+            return cmd_err!("Negative value for \"{}\"", first_arg);
+        }
+
         match first_arg {
             "depth" => {
-                max_depth = pair.1 as u8;
+                params.limits.max_depth = Some(pair.1.clamp(1, u8::MAX as i64) as u8);
+            }
+            "nodes" => {
+                params.limits.max_nodes = Some(pair.1 as usize);
+            }
+            "mate" => {
+                params.limits.mate_moves = Some(pair.1.clamp(1, (u8::MAX / 2) as i64) as u8);
             }
             "movetime" => {
                 time_state.max_time = Some(pair.1 as f64 / 1000.0);
@@ -429,7 +875,12 @@ fn cmd_go(parts: &Vec<String>, state: &mut UCIState) -> Option<String> {
                 time_state.moves_till_time_control = Some(pair.1 as u64);
             }
             "perft" => {
-                search::perft(state.engine.get_board(), pair.1 as u8, true);
+                print_perft(
+                    state.engine.get_board(),
+                    pair.1.clamp(0, u8::MAX as i64) as u8,
+                    state.get_option_val("Threads") as usize,
+                    state.get_option_val("Hash") as usize,
+                );
                 return None;
             }
             _ => {
@@ -442,13 +893,113 @@ fn cmd_go(parts: &Vec<String>, state: &mut UCIState) -> Option<String> {
         }
     }
 
+    // Play straight from the book if we can
+    if state.get_option_val("OwnBook") != 0
+        && !params.is_infinite
+        && !params.is_pondering
+        && params.root_excluded_moves.is_empty()
+        && state.game_ply < state.get_option_val("BookDepth") as usize
+    {
+        let pick_best = state.get_option_str("BookMoveChoice") == "Best";
+        let book_move = state.book.as_ref().and_then(|book| {
+            if pick_best {
+                book.get_best_move(board)
+            } else {
+                book.pick_move(board, &mut rand::rng())
+            }
+        });
+        if let Some(book_move) = book_move {
+            let is_chess960 = board.is_chess960;
+            state.engine.stop_search();
+            log_println!("info string Book move");
+            print_best_move(book_move, None, is_chess960);
+            return None;
+        }
+    }
+
+    time_state.move_overhead = state.get_option_val("Move Overhead") as f64 / 1000.0;
+    let nodes_time = state.get_option_val("nodestime");
+    if nodes_time > 0 {
+        time_state.nodes_per_sec = Some((nodes_time * 1000) as f64);
+    }
+    if !params.is_infinite {
+        params.time_state = Some(time_state);
+    }
+
+    params.contempt = state.get_option_val("Contempt") as Value;
+
+    if state.engine.is_debug() {
+        log_println!("info string Search params: {:?}", params);
+    }
+
     state
         .engine
         .maybe_update_table_size(state.get_option_val("Hash") as usize);
     state.engine.start_search(
-        max_depth,
-        Some(time_state),
+        params,
         state.get_option_val("Threads") as usize,
+        state.get_option_val("MultiPV") as usize,
+    );
+    None
+}
+
+inventory::submit! {
+    Command::new("ponderhit", cmd_ponderhit)
+}
+fn cmd_ponderhit(_parts: &Vec<String>, state: &mut UCIState) -> Option<String> {
+    if state.engine.ponder_hit() {
+        None
+    } else {
+        cmd_err!("Not pondering")
+    }
+}
+
+inventory::submit! {
+    Command::new("ucinewgame", cmd_ucinewgame)
+}
+fn cmd_ucinewgame(_parts: &Vec<String>, state: &mut UCIState) -> Option<String> {
+    state.engine.new_game();
+    None
+}
+
+inventory::submit! {
+    Command::new("savehash", cmd_savehash)
+}
+fn cmd_savehash(parts: &Vec<String>, state: &mut UCIState) -> Option<String> {
+    if parts.len() < 2 {
+        return cmd_err!("File path missing, format: \"savehash <path>\"");
+    }
+
+    let path = parts[1..].join(" ");
+    if let Err(err) = state.engine.save_table(&path) {
+        return cmd_err!("Failed to save table to \"{}\": {}", path, err);
+    }
+
+    log_println!("info string Saved table to \"{}\"", path);
+    None
+}
+
+inventory::submit! {
+    Command::new("loadhash", cmd_loadhash)
+}
+fn cmd_loadhash(parts: &Vec<String>, state: &mut UCIState) -> Option<String> {
+    if parts.len() < 2 {
+        return cmd_err!("File path missing, format: \"loadhash <path>\"");
+    }
+
+    let path = parts[1..].join(" ");
+    if let Err(err) = state.engine.load_table(&path) {
+        return cmd_err!("Failed to load table from \"{}\": {}", path, err);
+    }
+
+    // The loaded table keeps its own size, so the next search doesn't resize (and clear) it
+    let table_size_mbs = state.engine.get_table_size_mbs();
+    state.set_option_val("Hash", table_size_mbs as i64);
+
+    log_println!(
+        "info string Loaded table from \"{}\" ({} MB)",
+        path,
+        table_size_mbs
     );
     None
 }
@@ -481,13 +1032,18 @@ fn cmd_ratemoves(_parts: &Vec<String>, state: &mut UCIState) -> Option<String> {
         moves.push(mv);
     }
     moves.sort_by(|a, b| {
-        eval_move(state.engine.get_board(), b).total_cmp(&eval_move(state.engine.get_board(), a))
+        eval_move(state.engine.get_board(), b).cmp(&eval_move(state.engine.get_board(), a))
     });
 
-    println!("Moves:");
+    log_println!("Moves:");
     for i in 0..moves.len() {
         let mv = moves[i];
-        println!("\t{}: {}", mv, eval_move(state.engine.get_board(), &mv));
+        let board = state.engine.get_board();
+        log_println!(
+            "\t{}: {}",
+            mv.to_uci(board.is_chess960),
+            eval_move(board, &mv)
+        );
     }
 
     None
@@ -496,13 +1052,115 @@ fn cmd_ratemoves(_parts: &Vec<String>, state: &mut UCIState) -> Option<String> {
 inventory::submit! {
     Command::new("d", cmd_d)
 }
+// Shows the current position, for checking what "position" commands did
 fn cmd_d(_parts: &Vec<String>, state: &mut UCIState) -> Option<String> {
-    println!("{}", state.engine.get_board());
+    let board = state.engine.get_board();
+    log_println!("{}", board);
+    log_println!("Key: {:016X}", board.hash);
+    log_println!(
+        "Static eval (side to move): {}",
+        eval_to_str(eval_board(board))
+    );
     None
 }
 
+inventory::submit! {
+    Command::new("perft", cmd_perft)
+}
+fn cmd_perft(parts: &Vec<String>, state: &mut UCIState) -> Option<String> {
+    let Some(depth) = parts
+        .get(1)
+        .and_then(|depth_str| depth_str.parse::<u8>().ok())
+    else {
+        return cmd_err!("Invalid or missing depth, format: \"perft <depth>\"");
+    };
+
+    state.engine.stop_search();
+    print_perft(
+        state.engine.get_board(),
+        depth,
+        state.get_option_val("Threads") as usize,
+        state.get_option_val("Hash") as usize,
+    );
+    None
+}
+
+inventory::submit! {
+    Command::new("bench", cmd_bench)
+}
+fn cmd_bench(parts: &Vec<String>, state: &mut UCIState) -> Option<String> {
+    let depth = match parts.get(1) {
+        Some(depth_str) => match depth_str.parse::<u8>() {
+            Ok(depth) if depth > 0 => depth,
+            _ => return cmd_err!("Invalid depth \"{}\", format: \"bench [depth]\"", depth_str),
+        },
+        None => bench::DEFAULT_DEPTH,
+    };
+
+    state.engine.stop_search();
+    let results = bench::run(depth, true);
+    log_println!(
+        "{} nodes {} nps",
+        results.total_nodes,
+        results.get_nodes_per_sec()
+    );
+    None
+}
+
+#[cfg(feature = "tune-options")]
+fn weight_to_centipawns(weight: Weight) -> i64 {
+    (weight * 100.0).round() as i64
+}
+
+// Copies the tune options into the search and eval params, if any changed since the last search
+#[cfg(feature = "tune-options")]
+fn apply_tune_options(state: &mut UCIState) {
+    if !std::mem::take(&mut state.tune_options_changed) {
+        return;
+    }
+
+    let mut new_search_params = search_params::get().clone();
+    new_search_params.for_each_option(&mut |name, value, _, _| *value = state.get_option_val(name));
+
+    // Only weights whose option changed are set, so the rest keep their precision
+    let mut new_eval_params = eval_params::get().clone();
+    new_eval_params.for_each_named_weight(&mut |name, weight| {
+        let value = state.get_option_val(name);
+        if value != weight_to_centipawns(*weight) {
+            *weight = value as Weight / 100.0;
+        }
+    });
+
+    state.engine.stop_search();
+    search_params::set(new_search_params);
+    if new_eval_params != *eval_params::get() {
+        set_eval_params(state, new_eval_params);
+    }
+}
+
+fn set_eval_params(state: &mut UCIState, params: EvalParams) {
+    // Evals in the table were made with the old params (this also stops the search)
+    state.engine.reset_table();
+    eval_params::set(params);
+    state.engine.refresh_board();
+}
+
+fn set_network(state: &mut UCIState, network: Option<nnue::Network>) {
+    // Evals in the table were made with the old evaluator (this also stops the search)
+    state.engine.reset_table();
+    nnue::set_network(network);
+    state.engine.refresh_board();
+}
+
+// Stops any search before we exit, which still prints its best move so the GUI doesn't lose the game on time
+pub fn shutdown(state: &mut UCIState) {
+    state.engine.stop_search();
+}
+
 // Returns true if the command was understood and processed correctly
 pub fn process_cmd(line_str: String, state: &mut UCIState) -> bool {
+    debug_log::write_received(&line_str);
+
     let parts: Vec<String> = line_str
         .trim()
         .split_whitespace()
@@ -516,7 +1174,7 @@ pub fn process_cmd(line_str: String, state: &mut UCIState) -> bool {
         if parts[0].eq_ignore_ascii_case(name) {
             let cmd_err = function(&parts, state);
             if cmd_err.is_some() {
-                println!("info string Error: {}", cmd_err.unwrap());
+                log_println!("info string Error: {}", cmd_err.unwrap());
                 return false;
             } else {
                 return true;
@@ -524,6 +1182,6 @@ pub fn process_cmd(line_str: String, state: &mut UCIState) -> bool {
         }
     }
 
-    println!("info string Unknown command \"{}\"", parts[0]);
+    log_println!("info string Unknown command \"{}\"", parts[0]);
     false
 }
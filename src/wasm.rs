@@ -0,0 +1,145 @@
+use crate::board::*;
+use crate::eval::*;
+use crate::fen;
+use crate::game::Game;
+use crate::search;
+use crate::transpos;
+use wasm_bindgen::prelude::*;
+
+// JavaScript bindings, for running the engine in a browser
+// There are no threads on wasm32-unknown-unknown, and std::time::Instant panics, so searches can't block until a stop time
+// Instead, searches are split into short steps, and the page gets control back after each one:
+//
+//   engine.startSearch(1000);
+//   const step = () => engine.searchStep() ? play(engine.bestMove()) : setTimeout(step, 0);
+//   step();
+//
+// The lib is only built as an rlib, since a cdylib can't be built without std, so the cdylib is asked for when packaging:
+//
+//   cargo rustc --lib --release --target wasm32-unknown-unknown --features wasm --crate-type cdylib
+//   wasm-bindgen --target web --out-dir pkg target/wasm32-unknown-unknown/release/board_crab_lib.wasm
+
+// Small enough that a step only takes a few milliseconds
+const NODES_PER_STEP: usize = 25_000;
+
+#[wasm_bindgen]
+pub fn init() {
+    crate::init();
+}
+
+#[wasm_bindgen]
+pub struct Engine {
+    game: Game,
+    table: transpos::Table,
+    stop_time: f64, // From Date.now(), in milliseconds
+    is_searching: bool,
+    result: Option<search::IterativeResult>,
+}
+
+#[wasm_bindgen]
+impl Engine {
+    #[wasm_bindgen(constructor)]
+    pub fn new(table_size_mbs: usize) -> Engine {
+        crate::init();
+        Engine {
+            game: Game::new(Board::start_pos()),
+            table: transpos::Table::new(table_size_mbs),
+            stop_time: 0.0,
+            is_searching: false,
+            result: None,
+        }
+    }
+
+    // Like the UCI "position" command, the FEN can also be "startpos", and moves are in UCI notation
+    #[wasm_bindgen(js_name = setPosition)]
+    pub fn set_position(&mut self, fen_str: &str, moves: Vec<String>) -> Result<(), JsError> {
+        let board = if fen_str == "startpos" {
+            Board::start_pos()
+        } else {
+            fen::load_fen(fen_str).map_err(|err| JsError::new(&err.to_string()))?
+        };
+
+        let mut game = Game::new(board);
+        for move_str in &moves {
+            game.push_uci(move_str)
+                .map_err(|err| JsError::new(&err.to_string()))?;
+        }
+
+        self.game = game;
+        self.is_searching = false;
+        self.result = None;
+        Ok(())
+    }
+
+    pub fn fen(&self) -> String {
+        fen::make_fen(self.game.get_board())
+    }
+
+    #[wasm_bindgen(js_name = legalMoves)]
+    pub fn legal_moves(&self) -> Vec<String> {
+        let board = self.game.get_board();
+        board
+            .legal_moves()
+            .map(|mv| mv.to_uci(board.is_chess960))
+            .collect()
+    }
+
+    #[wasm_bindgen(js_name = startSearch)]
+    pub fn start_search(&mut self, time_ms: f64) {
+        self.stop_time = js_sys::Date::now() + time_ms;
+        self.is_searching = true;
+        self.result = None;
+    }
+
+    // Searches for a bit, and returns true once the search is done
+    // Each step searches from the start again, but the table remembers the earlier steps, so it keeps getting deeper
+    #[wasm_bindgen(js_name = searchStep)]
+    pub fn search_step(&mut self) -> bool {
+        if !self.is_searching {
+            return true;
+        }
+
+        let limits = search::SearchLimits::new().nodes(NODES_PER_STEP);
+        let step_result = search::search_iterative(
+            self.game.get_board(),
+            &self.table,
+            self.game.get_hashes(),
+            &limits,
+        );
+
+        let Some(step_result) = step_result else {
+            // No legal moves
+            self.is_searching = false;
+            return true;
+        };
+        if self
+            .result
+            .is_none_or(|result| step_result.depth >= result.depth)
+        {
+            self.result = Some(step_result);
+        }
+
+        let is_done = is_mate_eval(step_result.eval)
+            || step_result.depth == u8::MAX
+            || js_sys::Date::now() >= self.stop_time;
+        self.is_searching = !is_done;
+        is_done
+    }
+
+    // The best move found so far in UCI notation, or undefined if there are no legal moves (or no search yet)
+    #[wasm_bindgen(js_name = bestMove)]
+    pub fn best_move(&self) -> Option<String> {
+        let board = self.game.get_board();
+        self.result
+            .map(|result| result.best_move.to_uci(board.is_chess960))
+    }
+
+    // In centipawns, from the perspective of the side to move
+    pub fn eval(&self) -> Option<i32> {
+        self.result.map(|result| result.eval)
+    }
+
+    pub fn depth(&self) -> u8 {
+        self.result.map_or(0, |result| result.depth)
+    }
+}
@@ -0,0 +1,553 @@
+use crate::async_engine::{AsyncEngine, SearchParams};
+use crate::board::*;
+use crate::eval::*;
+use crate::fen;
+use crate::game::Game;
+use crate::move_gen;
+use crate::search::{GameResult, SearchListener, SearchProgress};
+use crate::time_manager::TimeState;
+use std::sync::{Arc, Mutex};
+
+// XBoard/WinBoard protocol (CECP), for GUIs and tournament managers that don't speak UCI
+// Only normal chess is supported, and moves are sent and received in coordinate notation (like UCI)
+// See: https://www.gnu.org/software/xboard/engine-intf.html
+
+pub struct XBoardState {
+    engine: AsyncEngine,
+    game: Game,
+    engine_team_idx: Option<usize>, // The side we play, None in force mode
+    is_thinking: bool, // Searching for our move, which gets played on our board once the search is done
+    show_thinking: bool,
+    num_threads: usize,
+    max_depth: u8,
+
+    // Time controls, in seconds
+    moves_per_session: Option<u64>, // Moves until the clock gets the base time again, None for the whole game
+    base_time: Option<f64>,
+    time_inc: f64,
+    time_per_move: Option<f64>, // Set by "st", overrides the rest
+    remaining_time: Option<f64>,
+}
+
+impl XBoardState {
+    pub fn new() -> XBoardState {
+        const DEFAULT_TABLE_SIZE_MBS: usize = 100;
+        XBoardState {
+            engine: AsyncEngine::new(DEFAULT_TABLE_SIZE_MBS),
+            game: Game::new(Board::start_pos()),
+            engine_team_idx: Some(1),
+            is_thinking: false,
+            show_thinking: false,
+            num_threads: 1,
+            max_depth: u8::MAX,
+            moves_per_session: None,
+            base_time: None,
+            time_inc: 0.0,
+            time_per_move: None,
+            remaining_time: None,
+        }
+    }
+
+    fn get_time_state(&self) -> TimeState {
+        let mut time_state = TimeState::new();
+        if self.time_per_move.is_some() {
+            time_state.max_time = self.time_per_move;
+            return time_state;
+        }
+
+        time_state.remaining_time = self.remaining_time;
+        if self.time_inc > 0.0 {
+            time_state.time_inc = Some(self.time_inc);
+        }
+        if let Some(moves_per_session) = self.moves_per_session {
+            let moves_played = (self.game.get_board().full_move_counter as u64).saturating_sub(1);
+            time_state.moves_till_time_control =
+                Some(moves_per_session - moves_played % moves_per_session);
+        }
+        time_state
+    }
+}
+
+impl Default for XBoardState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Prints thinking output for each depth (if the GUI wants it), and the move we play
+pub struct XBoardListener {
+    pub show_thinking: bool,
+}
+
+impl SearchListener for XBoardListener {
+    fn on_depth_complete(&mut self, _board: &Board, progress: &SearchProgress) {
+        if self.show_thinking {
+            print_search_results(progress);
+        }
+    }
+
+    fn on_finish(&mut self, _board: &Board, best_move: Move, _ponder_move: Option<Move>) {
+        print_best_move(best_move);
+    }
+}
+
+//////////////////////////
+
+// Prints a line of thinking output: depth, score, time in centiseconds, nodes, and the PV
+pub fn print_search_results(progress: &SearchProgress) {
+    let pv_strs: Vec<String> = progress.pv.iter().map(|mv| mv.to_string()).collect();
+
+    // Mate scores are written as 100000 plus the moves until mate
+    let score = match get_mate_moves(progress.eval) {
+        Some(mate_moves) if mate_moves > 0 => 100_000 + mate_moves,
+        Some(mate_moves) => -100_000 + mate_moves,
+        None => progress.eval as i64,
+    };
+
+    println!(
+        "{} {} {} {} {}",
+        progress.depth,
+        score,
+        (progress.elapsed_time * 100.0).round() as i64,
+        progress.total_nodes,
+        pv_strs.join(" ")
+    );
+}
+
+pub fn print_best_move(best_move: Move) {
+    println!("move {}", best_move);
+}
+
+// Claims the result if the game is over, and returns true if it was
+fn claim_result(state: &XBoardState) -> bool {
+    let board = state.game.get_board();
+    let result_str = match state.game.result() {
+        None => return false,
+        Some(GameResult::Checkmate) if board.turn_idx == 0 => "0-1 {Black mates}",
+        Some(GameResult::Checkmate) => "1-0 {White mates}",
+        Some(GameResult::Stalemate) => "1/2-1/2 {Stalemate}",
+        Some(GameResult::FiftyMoveRule) => "1/2-1/2 {Fifty move rule}",
+        Some(GameResult::InsufficientMaterial) => "1/2-1/2 {Insufficient material}",
+        Some(GameResult::Repetition) => "1/2-1/2 {Threefold repetition}",
+        Some(GameResult::InProgress) => unreachable!(),
+    };
+    println!("{}", result_str);
+    true
+}
+
+// Starts searching for our move, unless the game is already over
+fn start_thinking(state: &mut XBoardState) {
+    if claim_result(state) {
+        return;
+    }
+
+    let mut params = SearchParams::new();
+    params.limits.max_depth = Some(state.max_depth);
+    params.time_state = Some(state.get_time_state());
+
+    state
+        .engine
+        .set_listener(Arc::new(Mutex::new(XBoardListener {
+            show_thinking: state.show_thinking,
+        })));
+    state
+        .engine
+        .set_board(state.game.get_board(), state.game.get_hashes().to_vec());
+    state.engine.start_search(params, state.num_threads, 1);
+    state.is_thinking = true;
+}
+
+// Plays the move of our last search on our board, since the GUI only tells us about its own moves
+// If stop is false, this waits until the search is done by itself
+fn finish_thinking(state: &mut XBoardState, stop: bool) {
+    if !state.is_thinking {
+        return;
+    }
+    state.is_thinking = false;
+
+    let best_move_idx = if stop {
+        state.engine.stop_search()
+    } else {
+        state.engine.wait_for_search()
+    };
+
+    if let Some(best_move_idx) = best_move_idx {
+        let mut moves = move_gen::MoveBuffer::new();
+        move_gen::generate_moves(state.game.get_board(), &mut moves);
+        state.game.push(moves[best_move_idx as usize]);
+        claim_result(state);
+    }
+}
+
+// Starts thinking if it's our turn
+fn maybe_start_thinking(state: &mut XBoardState) {
+    if state.engine_team_idx == Some(state.game.get_board().turn_idx) {
+        start_thinking(state);
+    }
+}
+
+// Parses a time like "5" (minutes) or "2:30" (minutes and seconds) into seconds
+fn parse_base_time(time_str: &str) -> Option<f64> {
+    match time_str.split_once(':') {
+        Some((minutes_str, seconds_str)) => {
+            Some(minutes_str.parse::<f64>().ok()? * 60.0 + seconds_str.parse::<f64>().ok()?)
+        }
+        None => Some(time_str.parse::<f64>().ok()? * 60.0),
+    }
+}
+
+// Just returns an Option<String> of the error
+macro_rules! cmd_err {
+    ($($x:expr),*) => {
+        Some(format!($($x),*))
+    };
+}
+
+inventory::collect!(Command);
+pub struct Command {
+    name: &'static str,
+    function: fn(&[String], &mut XBoardState) -> Option<String>,
+}
+impl Command {
+    pub const fn new(
+        name: &'static str,
+        function: fn(&[String], &mut XBoardState) -> Option<String>,
+    ) -> Self {
+        Command { name, function }
+    }
+}
+
+inventory::submit! {
+    Command::new("protover", cmd_protover)
+}
+fn cmd_protover(_parts: &[String], _state: &mut XBoardState) -> Option<String> {
+    println!(
+        "feature myname=\"BoardCrab v{}\" variants=\"normal\" ping=1 setboard=1 usermove=1 playother=1 colors=0 sigint=0 sigterm=0 reuse=1 analyze=0 memory=1 smp=1 done=1",
+        env!("CARGO_PKG_VERSION")
+    );
+    None
+}
+
+inventory::submit! {
+    Command::new("new", cmd_new)
+}
+fn cmd_new(_parts: &[String], state: &mut XBoardState) -> Option<String> {
+    finish_thinking(state, true);
+    state.engine.new_game();
+    state.game = Game::new(Board::start_pos());
+    state.engine_team_idx = Some(1);
+    state.max_depth = u8::MAX;
+    state.remaining_time = state.base_time;
+    None
+}
+
+inventory::submit! {
+    Command::new("variant", cmd_variant)
+}
+fn cmd_variant(parts: &[String], _state: &mut XBoardState) -> Option<String> {
+    match parts.get(1) {
+        Some(variant) if variant == "normal" => None,
+        _ => cmd_err!("unsupported variant"),
+    }
+}
+
+inventory::submit! {
+    Command::new("setboard", cmd_setboard)
+}
+fn cmd_setboard(parts: &[String], state: &mut XBoardState) -> Option<String> {
+    finish_thinking(state, true);
+    match fen::load_fen_from_parts(&parts[1..].to_vec()) {
+        Ok(board) => {
+            state.game = Game::new(board);
+            None
+        }
+        Err(err) => {
+            println!("tellusererror Illegal position");
+            cmd_err!("{}", err)
+        }
+    }
+}
+
+inventory::submit! {
+    Command::new("force", cmd_force)
+}
+fn cmd_force(_parts: &[String], state: &mut XBoardState) -> Option<String> {
+    finish_thinking(state, true);
+    state.engine_team_idx = None;
+    None
+}
+
+inventory::submit! {
+    Command::new("go", cmd_go)
+}
+fn cmd_go(_parts: &[String], state: &mut XBoardState) -> Option<String> {
+    finish_thinking(state, true);
+    state.engine_team_idx = Some(state.game.get_board().turn_idx);
+    start_thinking(state);
+    None
+}
+
+inventory::submit! {
+    Command::new("playother", cmd_playother)
+}
+fn cmd_playother(_parts: &[String], state: &mut XBoardState) -> Option<String> {
+    finish_thinking(state, true);
+    state.engine_team_idx = Some(1 - state.game.get_board().turn_idx);
+    None
+}
+
+inventory::submit! {
+    Command::new("usermove", cmd_usermove)
+}
+fn cmd_usermove(parts: &[String], state: &mut XBoardState) -> Option<String> {
+    let Some(move_str) = parts.get(1) else {
+        return cmd_err!("move missing");
+    };
+
+    // Our own move might still be on its way to the board
+    finish_thinking(state, false);
+
+    if state.game.push_uci(move_str).is_err() {
+        println!("Illegal move: {}", move_str);
+        return None;
+    }
+
+    maybe_start_thinking(state);
+    None
+}
+
+inventory::submit! {
+    Command::new("?", cmd_move_now)
+}
+fn cmd_move_now(_parts: &[String], state: &mut XBoardState) -> Option<String> {
+    finish_thinking(state, true);
+    None
+}
+
+inventory::submit! {
+    Command::new("undo", cmd_undo)
+}
+fn cmd_undo(_parts: &[String], state: &mut XBoardState) -> Option<String> {
+    finish_thinking(state, true);
+    if state.game.undo().is_none() {
+        return cmd_err!("no moves to undo");
+    }
+    None
+}
+
+inventory::submit! {
+    Command::new("remove", cmd_remove)
+}
+// Takes back a move of each side, so it's the user's turn again
+fn cmd_remove(_parts: &[String], state: &mut XBoardState) -> Option<String> {
+    finish_thinking(state, true);
+    if state.game.get_moves().len() < 2 {
+        return cmd_err!("no moves to remove");
+    }
+    state.game.undo();
+    state.game.undo();
+    None
+}
+
+inventory::submit! {
+    Command::new("result", cmd_result)
+}
+fn cmd_result(_parts: &[String], state: &mut XBoardState) -> Option<String> {
+    finish_thinking(state, true);
+    state.engine_team_idx = None;
+    None
+}
+
+inventory::submit! {
+    Command::new("level", cmd_level)
+}
+// Format: "level <moves per session> <base time> <increment>", moves per session is 0 for the whole game
+fn cmd_level(parts: &[String], state: &mut XBoardState) -> Option<String> {
+    if parts.len() < 4 {
+        return cmd_err!("too few arguments");
+    }
+
+    let (Ok(moves_per_session), Some(base_time), Ok(time_inc)) = (
+        parts[1].parse::<u64>(),
+        parse_base_time(&parts[2]),
+        parts[3].parse::<f64>(),
+    ) else {
+        return cmd_err!("invalid time control");
+    };
+
+    state.moves_per_session = if moves_per_session > 0 {
+        Some(moves_per_session)
+    } else {
+        None
+    };
+    state.base_time = Some(base_time);
+    state.time_inc = time_inc;
+    state.time_per_move = None;
+    state.remaining_time = Some(base_time);
+    None
+}
+
+inventory::submit! {
+    Command::new("st", cmd_st)
+}
+fn cmd_st(parts: &[String], state: &mut XBoardState) -> Option<String> {
+    match parts
+        .get(1)
+        .and_then(|time_str| time_str.parse::<f64>().ok())
+    {
+        Some(time_per_move) => {
+            state.time_per_move = Some(time_per_move);
+            None
+        }
+        None => cmd_err!("invalid time"),
+    }
+}
+
+inventory::submit! {
+    Command::new("sd", cmd_sd)
+}
+fn cmd_sd(parts: &[String], state: &mut XBoardState) -> Option<String> {
+    match parts
+        .get(1)
+        .and_then(|depth_str| depth_str.parse::<u8>().ok())
+    {
+        Some(depth) if depth > 0 => {
+            state.max_depth = depth;
+            None
+        }
+        _ => cmd_err!("invalid depth"),
+    }
+}
+
+inventory::submit! {
+    Command::new("time", cmd_time)
+}
+// Our remaining time, in centiseconds
+fn cmd_time(parts: &[String], state: &mut XBoardState) -> Option<String> {
+    match parts
+        .get(1)
+        .and_then(|time_str| time_str.parse::<i64>().ok())
+    {
+        Some(time_cs) => {
+            state.remaining_time = Some((time_cs.max(0) as f64) / 100.0);
+            None
+        }
+        None => cmd_err!("invalid time"),
+    }
+}
+
+inventory::submit! {
+    Command::new("cores", cmd_cores)
+}
+fn cmd_cores(parts: &[String], state: &mut XBoardState) -> Option<String> {
+    match parts
+        .get(1)
+        .and_then(|cores_str| cores_str.parse::<usize>().ok())
+    {
+        Some(num_threads) if (1..=256).contains(&num_threads) => {
+            state.num_threads = num_threads;
+            None
+        }
+        _ => cmd_err!("invalid number of cores"),
+    }
+}
+
+inventory::submit! {
+    Command::new("memory", cmd_memory)
+}
+// Table size in MB
+fn cmd_memory(parts: &[String], state: &mut XBoardState) -> Option<String> {
+    match parts
+        .get(1)
+        .and_then(|mbs_str| mbs_str.parse::<usize>().ok())
+    {
+        Some(table_size_mbs) if table_size_mbs > 0 => {
+            finish_thinking(state, true);
+            state.engine.maybe_update_table_size(table_size_mbs);
+            None
+        }
+        _ => cmd_err!("invalid memory size"),
+    }
+}
+
+inventory::submit! {
+    Command::new("post", cmd_post)
+}
+fn cmd_post(_parts: &[String], state: &mut XBoardState) -> Option<String> {
+    state.show_thinking = true;
+    None
+}
+
+inventory::submit! {
+    Command::new("nopost", cmd_nopost)
+}
+fn cmd_nopost(_parts: &[String], state: &mut XBoardState) -> Option<String> {
+    state.show_thinking = false;
+    None
+}
+
+inventory::submit! {
+    Command::new("ping", cmd_ping)
+}
+fn cmd_ping(parts: &[String], _state: &mut XBoardState) -> Option<String> {
+    println!("pong {}", parts.get(1).map_or("", |id| id.as_str()));
+    None
+}
+
+inventory::submit! {
+    Command::new("quit", cmd_quit)
+}
+fn cmd_quit(_parts: &[String], state: &mut XBoardState) -> Option<String> {
+    shutdown(state);
+    std::process::exit(0)
+}
+
+// Commands we understand, but don't need to do anything for
+const IGNORED_CMDS: [&str; 14] = [
+    "xboard", "accepted", "rejected", "random", "otim", "hard", "easy", "computer", "name",
+    "rating", "draw", "hint", "bk", ".",
+];
+
+// Stops any search before we exit
+pub fn shutdown(state: &mut XBoardState) {
+    state.engine.stop_search();
+}
+
+// Returns true if the command was understood and processed correctly
+pub fn process_cmd(line_str: String, state: &mut XBoardState) -> bool {
+    let parts: Vec<String> = line_str
+        .split_whitespace()
+        .map(|part| part.to_string())
+        .collect();
+    if parts.is_empty() {
+        return false;
+    }
+
+    if IGNORED_CMDS.contains(&parts[0].as_str()) {
+        return true;
+    }
+
+    for Command { name, function } in inventory::iter::<Command> {
+        if parts[0] == *name {
+            if let Some(err) = function(&parts, state) {
+                println!("Error ({}): {}", err, line_str.trim());
+                return false;
+            }
+            return true;
+        }
+    }
+
+    // Without the usermove feature, moves are sent by themselves
+    let is_move = !matches!(
+        state.game.get_board().parse_uci_move(&parts[0]),
+        Err(MoveParseError::InvalidFormat(_))
+    );
+    if is_move {
+        let mut usermove_parts = vec!["usermove".to_string()];
+        usermove_parts.extend(parts);
+        return cmd_usermove(&usermove_parts, state).is_none();
+    }
+
+    println!("Error (unknown command): {}", parts[0]);
+    false
+}
@@ -1,12 +1,12 @@
 use crate::bitmask::*;
 use crate::board::*;
-use rand::Rng;
+use rand::{Rng, SeedableRng};
 extern crate rand;
 
 pub type Hash = u64;
 
 static mut LT_HASH_PIECE: [[[Hash; 64]; NUM_PIECES]; 2] = [[[0; 64]; NUM_PIECES]; 2];
-static mut LT_HASH_CASTLE_RIGHTS: [[Hash; 2]; 2] = [[0; 2]; 2];
+static mut LT_HASH_CASTLE_RIGHTS: [[Hash; 8]; 2] = [[0; 8]; 2]; // By the column of the castling rook
 static mut LT_HASH_EN_PASSANT: [Hash; 64] = [0; 64];
 static mut LT_HASH_TURN: Hash = 0;
 
@@ -14,12 +14,17 @@ pub fn hash_piece(team_idx: usize, piece_idx: usize, pos_idx: usize) -> Hash {
     unsafe { LT_HASH_PIECE[team_idx][piece_idx][pos_idx] }
 }
 
-pub fn hash_castle_rights(castle_rights: [[bool; 2]; 2]) -> Hash {
+// Rights are hashed by which rook they belong to, since in Chess960 the same pieces can have rights to different rooks
+pub fn hash_castle_rights(
+    castle_rights: [[bool; 2]; 2],
+    castle_rook_masks: [[BitMask; 2]; 2],
+) -> Hash {
     let mut result = 0;
     for i in 0..2 {
         for j in 0..2 {
             if castle_rights[i][j] {
-                result ^= unsafe { LT_HASH_CASTLE_RIGHTS[i][j] };
+                let column = bm_to_idx(castle_rook_masks[i][j]) % 8;
+                result ^= unsafe { LT_HASH_CASTLE_RIGHTS[i][column] };
             }
         }
     }
@@ -39,8 +44,12 @@ pub fn hash_turn() -> Hash {
     unsafe { LT_HASH_TURN }
 }
 
+// Fixed seed, so hashes are the same every run
+// Keeps bench node counts deterministic, and lets tables saved with "savehash" be used in a later session
+const HASH_SEED: u64 = 0xB0A7_DC7A_B5EE_D000;
+
 pub fn init() {
-    let mut rng = rand::rng();
+    let mut rng = rand::rngs::StdRng::seed_from_u64(HASH_SEED);
     unsafe {
         for i in 0..2 {
             for j in 0..NUM_PIECES {
@@ -49,7 +58,7 @@ pub fn init() {
                 }
             }
 
-            for j in 0..2 {
+            for j in 0..8 {
                 LT_HASH_CASTLE_RIGHTS[i][j] = rng.random::<Hash>();
             }
         }
@@ -0,0 +1,11 @@
+use board_crab_lib::bench;
+
+#[test]
+fn bench_deterministic_test() {
+    board_crab_lib::init();
+
+    // Node counts are used as a fingerprint of the search, so they must be the same every run
+    let results = bench::run(4, false);
+    assert!(results.total_nodes > 0);
+    assert_eq!(bench::run(4, false).total_nodes, results.total_nodes);
+}
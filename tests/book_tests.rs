@@ -0,0 +1,228 @@
+use board_crab_lib::board::*;
+use board_crab_lib::book;
+use board_crab_lib::book::{Book, BookEntry};
+use board_crab_lib::fen;
+use board_crab_lib::pgn;
+
+// Packs a move the way book files store them
+fn make_book_move(move_str: &str) -> u16 {
+    let square_idx = |coord: &str| {
+        let bytes = coord.as_bytes();
+        ((bytes[0] - b'a') as u16) + ((bytes[1] - b'1') as u16) * 8
+    };
+    square_idx(&move_str[2..4]) | (square_idx(&move_str[0..2]) << 6)
+}
+
+// Plays moves like "e2e4" from the start position
+fn play_moves(move_strs: &[&str]) -> Board {
+    let mut board = Board::start_pos();
+    for move_str in move_strs {
+        let mv = board.parse_uci_move(move_str).unwrap();
+        board.do_move(&mv);
+    }
+    board
+}
+
+// Keys from the Polyglot format description (see book.rs)
+#[test]
+fn book_key_test() {
+    board_crab_lib::init();
+
+    let key_tests: [(&[&str], book::BookKey); 9] = [
+        (&[], 0x463b96181691fc9c),
+        (&["e2e4"], 0x823c9b50fd114196),
+        (&["e2e4", "d7d5"], 0x0756b94461c50fb0),
+        (&["e2e4", "d7d5", "e4e5"], 0x662fafb965db29d4),
+        (&["e2e4", "d7d5", "e4e5", "f7f5"], 0x22a48b5a8e47ff78), // En passant is possible
+        (
+            &["e2e4", "d7d5", "e4e5", "f7f5", "e1e2"],
+            0x652a607ca3f242c1,
+        ),
+        (
+            &["e2e4", "d7d5", "e4e5", "f7f5", "e1e2", "e8f7"],
+            0x00fdd303c946bdd9,
+        ),
+        (
+            &["a2a4", "b7b5", "h2h4", "b5b4", "c2c4"],
+            0x3c8123ea7b067637,
+        ),
+        (
+            &["a2a4", "b7b5", "h2h4", "b5b4", "c2c4", "b4c3", "a1a3"],
+            0x5c3f9b829b279560,
+        ),
+    ];
+    for (move_strs, key) in key_tests {
+        assert_eq!(
+            book::get_key(&play_moves(move_strs)),
+            key,
+            "{:?}",
+            move_strs
+        );
+    }
+
+    // FENs give the same keys
+    let fen_board =
+        fen::load_fen("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3").unwrap();
+    assert_eq!(book::get_key(&fen_board), 0x22a48b5a8e47ff78);
+
+    // En passant only counts when it can actually be played
+    let no_capture_board =
+        fen::load_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1").unwrap();
+    assert_eq!(book::get_key(&no_capture_board), 0x823c9b50fd114196);
+}
+
+#[test]
+fn book_probe_test() {
+    board_crab_lib::init();
+
+    // Hand-written entries: e2e4 and d2d4 from the start position, then c7c5 after e2e4
+    #[rustfmt::skip]
+    let bytes: [u8; BookEntry::SIZE * 3] = [
+        0x46, 0x3b, 0x96, 0x18, 0x16, 0x91, 0xfc, 0x9c, 0x03, 0x1c, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00,
+        0x46, 0x3b, 0x96, 0x18, 0x16, 0x91, 0xfc, 0x9c, 0x02, 0xdb, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00,
+        0x82, 0x3c, 0x9b, 0x50, 0xfd, 0x11, 0x41, 0x96, 0x0c, 0xa2, 0x00, 0x05, 0x00, 0x00, 0x00, 0x07,
+    ];
+    let path =
+        std::env::temp_dir().join(format!("board_crab_book_test_{}.bin", std::process::id()));
+    let path_str = path.to_str().unwrap();
+    std::fs::write(&path, bytes).unwrap();
+    let book = Book::load(path_str).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(book.len(), 3);
+
+    let e4_entries = book.get_entries(0x823c9b50fd114196);
+    assert_eq!(
+        e4_entries,
+        &[BookEntry {
+            key: 0x823c9b50fd114196,
+            mv: make_book_move("c7c5"),
+            weight: 5,
+            learn: 7,
+        }]
+    );
+
+    let start_board = Board::start_pos();
+    let start_moves: Vec<(String, u16)> = book
+        .get_moves(&start_board)
+        .iter()
+        .map(|(mv, weight)| (mv.to_string(), *weight))
+        .collect();
+    assert_eq!(start_moves.len(), 2);
+    assert!(start_moves.contains(&("e2e4".to_string(), 3)));
+    assert!(start_moves.contains(&("d2d4".to_string(), 1)));
+    assert_eq!(
+        book.get_best_move(&start_board).unwrap().to_string(),
+        "e2e4"
+    );
+
+    let e4_board = play_moves(&["e2e4"]);
+    let mut rng = rand::rng();
+    assert_eq!(
+        book.pick_move(&e4_board, &mut rng).unwrap().to_string(),
+        "c7c5"
+    );
+
+    // Not in the book
+    let d4_board = play_moves(&["d2d4"]);
+    assert!(book.pick_move(&d4_board, &mut rng).is_none());
+    assert!(book.get_best_move(&d4_board).is_none());
+
+    // Illegal moves are skipped, and castling is stored as the king taking the rook
+    let castle_board = fen::load_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+    let castle_key = book::get_key(&castle_board);
+    let make_entry = |move_str: &str, weight: u16| BookEntry {
+        key: castle_key,
+        mv: make_book_move(move_str),
+        weight,
+        learn: 0,
+    };
+    let castle_book = Book::new(vec![
+        make_entry("e1h1", 1),
+        make_entry("e1e3", 10), // Not a legal move
+        make_entry("a1a8", 0),  // Never picked
+    ]);
+    for _ in 0..20 {
+        let castle_move = castle_book.pick_move(&castle_board, &mut rng).unwrap();
+        assert_eq!(castle_move.to_string(), "e1g1");
+        assert!(castle_move.has_flag(Move::FLAG_CASTLE));
+    }
+    assert_eq!(
+        castle_book
+            .get_best_move(&castle_board)
+            .unwrap()
+            .to_string(),
+        "e1g1"
+    );
+}
+
+#[test]
+fn book_build_test() {
+    board_crab_lib::init();
+
+    let pgn_str = r#"
+[Event "Test 1"]
+[Result "1-0"]
+
+1. e4 {A comment} e5 (1... c5 2. Nf3) 2. Nf3 $1 Nc6 3. Bb5 a6 1-0
+
+[Event "Test 2"]
+[Result "1/2-1/2"]
+
+1.e4 e5 2.Nf3 Nf6 1/2-1/2
+
+[Event "Test 3"]
+[Result "0-1"]
+
+1. d4 d5 ; Another comment
+2. c4 0-1
+
+[Event "Test 4"]
+[Result "*"]
+
+1. c4 *
+"#;
+
+    let games = pgn::parse_games(pgn_str).unwrap();
+    assert_eq!(games.len(), 4);
+    assert_eq!(games[0].get_tag("Event"), Some("Test 1"));
+    assert_eq!(games[0].moves.len(), 6);
+    assert_eq!(games[1].result, Some(0.5));
+    assert_eq!(games[3].result, None);
+
+    let mut settings = book::BookBuildSettings::new();
+    settings.max_plies = 3;
+    let mut builder = book::BookBuilder::new(settings);
+    for game in &games {
+        if let Some(result) = game.result {
+            builder.add_game(&game.start_board, &game.moves, result);
+        }
+    }
+    let book = builder.build();
+
+    // e4 won once and drew once (2 + 1), d4 lost
+    let start_moves: Vec<(String, u16)> = book
+        .get_moves(&Board::start_pos())
+        .iter()
+        .map(|(mv, weight)| (mv.to_string(), *weight))
+        .collect();
+    assert_eq!(start_moves, vec![("e2e4".to_string(), 3)]);
+
+    // White's c4 lost, and Nc6 is past the max plies
+    for (game, num_moves) in [(&games[2], 2), (&games[0], 3)] {
+        let mut board = Board::start_pos();
+        for mv in &game.moves[..num_moves] {
+            board.do_move(mv);
+        }
+        assert!(book.get_moves(&board).is_empty());
+    }
+
+    // Black's d5 won
+    let mut board = Board::start_pos();
+    board.do_move(&games[2].moves[0]);
+    let d4_moves: Vec<(String, u16)> = book
+        .get_moves(&board)
+        .iter()
+        .map(|(mv, weight)| (mv.to_string(), *weight))
+        .collect();
+    assert_eq!(d4_moves, vec![("d7d5".to_string(), 2)]);
+}
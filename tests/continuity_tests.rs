@@ -1,6 +1,7 @@
 use board_crab_lib::board::*;
+use board_crab_lib::fen;
 use board_crab_lib::move_gen;
-use board_crab_lib::search;
+use board_crab_lib::perft;
 use rand::Rng;
 extern crate rand;
 
@@ -29,6 +30,26 @@ fn continuity_test_1() {
             let mut board_clone = board.clone();
             board_clone.full_update();
 
+            if board.hash != board_clone.hash {
+                panic!("Continuity error (bad hash)");
+            }
+
+            if board.pawn_hash != board_clone.pawn_hash {
+                panic!("Continuity error (bad pawn hash)");
+            }
+
+            if board.piece_square_scores != board_clone.piece_square_scores {
+                panic!("Continuity error (bad piece-square scores)");
+            }
+
+            if board.material_scores != board_clone.material_scores {
+                panic!("Continuity error (bad material scores)");
+            }
+
+            if board.phase_weight != board_clone.phase_weight {
+                panic!("Continuity error (bad phase weight)");
+            }
+
             let mut clone_moves = move_gen::MoveBuffer::new();
             move_gen::generate_moves(&board_clone, &mut clone_moves);
 
@@ -57,7 +78,7 @@ fn continuity_test_2() {
     for _i in 0..NUM_GAMES {
         let board = Board::start_pos();
         for _j in 0..MAX_MOVES_PER_GAME {
-            let outer_perft = search::perft(&board, 2, false);
+            let outer_perft = perft::perft(&board, 2, false);
 
             let mut moves = move_gen::MoveBuffer::new();
             move_gen::generate_moves(&board, &mut moves);
@@ -67,7 +88,7 @@ fn continuity_test_2() {
                 next_board.do_move(mv);
                 next_board.full_update();
 
-                inner_perft_total += search::perft(&next_board, 1, false);
+                inner_perft_total += perft::perft(&next_board, 1, false);
             }
 
             if inner_perft_total != outer_perft {
@@ -76,3 +97,65 @@ fn continuity_test_2() {
         }
     }
 }
+
+// Plays a bunch of random games, and makes sure undoing every move gives back the same board
+#[test]
+fn continuity_test_undo() {
+    board_crab_lib::init();
+
+    let mut rng = rand::rng();
+
+    // Also covers Chess960 castling, where the king or rook may not move
+    let start_fens = [
+        fen::FEN_START_POS,
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "1r2k1rr/pppppppp/8/8/8/8/PPPPPPPP/1R2K1RR w GBgb - 0 1",
+        "2r1k2r/8/8/8/8/8/8/2R1K2R w KCkc - 0 1",
+    ];
+
+    const NUM_GAMES: usize = 20;
+    const MAX_MOVES_PER_GAME: usize = 60;
+    for start_fen in start_fens {
+        for _i in 0..NUM_GAMES {
+            let mut board = fen::load_fen(start_fen).unwrap();
+            for _j in 0..MAX_MOVES_PER_GAME {
+                let mut moves = move_gen::MoveBuffer::new();
+                move_gen::generate_moves(&board, &mut moves);
+                if moves.is_empty() {
+                    break;
+                }
+
+                let board_fen = fen::make_fen(&board);
+                let mut undo_board = board;
+                for mv in moves.iter() {
+                    let mut next_board = board;
+                    next_board.do_move(mv);
+
+                    let undo = undo_board.make_move(mv);
+                    assert_eq!(undo_board.hash, next_board.hash);
+                    undo_board.undo_move(mv, &undo);
+
+                    assert_eq!(fen::make_fen(&undo_board), board_fen, "Undo of {}", mv);
+                    assert_eq!(undo_board.hash, board.hash);
+                    assert_eq!(undo_board.pawn_hash, board.pawn_hash);
+                    assert_eq!(undo_board.pieces, board.pieces);
+                    assert_eq!(undo_board.occupancy, board.occupancy);
+                    assert_eq!(undo_board.attacks, board.attacks);
+                    assert_eq!(undo_board.checkers, board.checkers);
+                    assert_eq!(undo_board.pinned, board.pinned);
+                    assert_eq!(undo_board.phase_weight, board.phase_weight);
+                }
+
+                if board.checkers == 0 {
+                    let undo = undo_board.make_null_move();
+                    undo_board.undo_null_move(&undo);
+                    assert_eq!(undo_board.hash, board.hash);
+                    assert_eq!(fen::make_fen(&undo_board), board_fen);
+                }
+
+                let move_idx = rng.random_range(0..moves.len());
+                board.do_move(&moves[move_idx]);
+            }
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use board_crab_lib::datagen;
+use board_crab_lib::fen;
+use board_crab_lib::pgn;
+
+#[test]
+fn datagen_output_test() {
+    board_crab_lib::init();
+
+    let mut settings = datagen::DatagenSettings::new();
+    settings.num_games = 2;
+    settings.nodes_per_move = 500;
+    settings.max_game_plies = 20;
+    settings.table_size_mbs = 1;
+
+    let path = std::env::temp_dir().join("board_crab_datagen_output_test.txt");
+    let path_str = path.to_str().unwrap();
+    let _ = std::fs::remove_file(&path);
+    let total_positions = datagen::run(&settings, path_str).unwrap();
+    let output = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(total_positions > 0);
+    assert_eq!(output.lines().count(), total_positions);
+    for line in output.lines() {
+        let parts: Vec<&str> = line.split(" | ").collect();
+        assert_eq!(parts.len(), 3, "bad line: {}", line);
+
+        // Positions in check are never written
+        let board = fen::load_fen(parts[0]).unwrap();
+        assert_eq!(board.checkers, 0);
+
+        parts[1].parse::<i64>().unwrap();
+        assert!(["1.0", "0.5", "0.0"].contains(&parts[2]));
+    }
+}
+
+#[test]
+fn datagen_pgn_test() {
+    board_crab_lib::init();
+
+    let mut settings = datagen::DatagenSettings::new();
+    settings.num_games = 1;
+    settings.nodes_per_move = 500;
+    settings.max_game_plies = 20;
+    settings.table_size_mbs = 1;
+    settings.write_pgn = true;
+
+    let path = std::env::temp_dir().join("board_crab_datagen_pgn_test.txt");
+    let path_str = path.to_str().unwrap();
+    let pgn_path = format!("{}.pgn", path_str);
+    let _ = std::fs::remove_file(&path);
+    let _ = std::fs::remove_file(&pgn_path);
+    datagen::run(&settings, path_str).unwrap();
+    let output = std::fs::read_to_string(&path).unwrap();
+    let games = pgn::load(&pgn_path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    std::fs::remove_file(&pgn_path).unwrap();
+
+    // The game's result matches the positions', and searched moves have evals
+    assert_eq!(games.len(), 1);
+    let game = &games[0];
+    let result = output.lines().next().unwrap().split(" | ").nth(2).unwrap();
+    assert_eq!(format!("{:.1}", game.result.unwrap()), result);
+    assert!(game.moves.len() > settings.random_plies);
+    for annotation in &game.annotations[settings.random_plies..] {
+        assert!(annotation.comment.as_ref().unwrap().starts_with("[%eval "));
+    }
+}
@@ -0,0 +1,34 @@
+use board_crab_lib::debug_log;
+use board_crab_lib::log_println;
+
+#[test]
+fn debug_log_test() {
+    let path =
+        std::env::temp_dir().join(format!("board_crab_debug_log_{}.txt", std::process::id()));
+    let path_str = path.to_str().unwrap();
+    let _ = std::fs::remove_file(&path);
+
+    // Nothing is written until there's a file
+    log_println!("before");
+    debug_log::set_path(Some(path_str)).unwrap();
+    debug_log::write_received("isready\n");
+    log_println!("readyok");
+    debug_log::set_path(None).unwrap();
+    log_println!("after");
+
+    let log = std::fs::read_to_string(&path).unwrap();
+    let lines: Vec<&str> = log.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].ends_with(" << isready"));
+    assert!(lines[1].ends_with(" >> readyok"));
+    assert!(lines[0].split(' ').next().unwrap().parse::<f64>().is_ok());
+
+    // Files are appended to
+    debug_log::set_path(Some(path_str)).unwrap();
+    log_println!("uciok");
+    debug_log::set_path(None).unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 3);
+
+    assert!(debug_log::set_path(Some("/no/such/dir/log.txt")).is_err());
+    std::fs::remove_file(&path).unwrap();
+}
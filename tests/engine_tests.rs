@@ -0,0 +1,83 @@
+use board_crab_lib::board::Board;
+use board_crab_lib::engine::{Engine, EngineError, SearchOutcome};
+use board_crab_lib::fen;
+use board_crab_lib::search::{GameResult, SearchLimits};
+
+#[test]
+fn engine_search_handle_test() {
+    let mut engine = Engine::new(1);
+
+    // Finishes by itself
+    let handle = engine.start_search(SearchLimits::new().depth(4));
+    match handle.join() {
+        SearchOutcome::Finished(result) => assert_eq!(result.depth, 4),
+        outcome => panic!("Unexpected outcome: {:?}", outcome),
+    }
+
+    // Only finishes once stopped
+    let handle = engine.start_search(SearchLimits::new());
+    std::thread::sleep(std::time::Duration::from_millis(50));
+    assert!(handle.is_running());
+    handle.stop();
+    match handle.join() {
+        SearchOutcome::Finished(result) => assert!(result.depth >= 1),
+        outcome => panic!("Unexpected outcome: {:?}", outcome),
+    }
+
+    let board = fen::load_fen("R5k1/5ppp/8/8/8/8/8/6K1 b - - 1 1").unwrap();
+    engine.set_board(&board, Vec::new());
+    let outcome = engine.start_search(SearchLimits::new().depth(4)).join();
+    assert!(matches!(
+        outcome,
+        SearchOutcome::NoLegalMoves(GameResult::Checkmate)
+    ));
+    assert!(outcome.get_best_move().is_none());
+}
+
+#[test]
+fn engine_position_and_options_test() {
+    let mut engine = Engine::new(1);
+
+    engine.set_position("startpos", &["e2e4", "e7e5"]).unwrap();
+    let fen_str = fen::make_fen(engine.get_board());
+    assert_eq!(
+        fen_str,
+        "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2"
+    );
+
+    // Errors keep the current position
+    assert!(matches!(
+        engine.set_position("startpos", &["e2e4", "e2e4"]),
+        Err(EngineError::InvalidMove(_))
+    ));
+    assert!(matches!(
+        engine.set_position("not a fen", &[]),
+        Err(EngineError::InvalidFen(_))
+    ));
+    assert_eq!(fen::make_fen(engine.get_board()), fen_str);
+
+    engine.set_option("Hash", "2").unwrap();
+    engine.set_option("clear hash", "").unwrap();
+    assert!(matches!(
+        engine.set_option("Hash", "lots"),
+        Err(EngineError::InvalidOptionValue(..))
+    ));
+    assert!(matches!(
+        engine.set_option("Hash", "0"),
+        Err(EngineError::InvalidOptionValue(..))
+    ));
+    assert!(matches!(
+        engine.set_option("Unknown", "1"),
+        Err(EngineError::UnknownOption(_))
+    ));
+
+    // Mate in one
+    engine
+        .set_position("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", &[])
+        .unwrap();
+    let best_move = engine.go(SearchLimits::new().depth(3)).get_best_move();
+    assert_eq!(best_move.map(|mv| mv.to_string()), Some("a1a8".to_string()));
+
+    engine.new_game();
+    assert_eq!(engine.get_board().hash, Board::start_pos().hash);
+}
@@ -0,0 +1,52 @@
+use board_crab_lib::epd_suite;
+use board_crab_lib::fen;
+
+#[test]
+fn epd_solved_test() {
+    board_crab_lib::init();
+
+    let record = fen::load_epd(
+        "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - bm Bb5 Bc4; am Ng5;",
+    )
+    .unwrap();
+    let get_solved =
+        |san: &str| epd_suite::is_solved(&record, &record.board.parse_san(san).unwrap());
+    assert_eq!(get_solved("Bb5"), Some(true));
+    assert_eq!(get_solved("Bc4"), Some(true));
+    assert_eq!(get_solved("Ng5"), Some(false));
+    assert_eq!(get_solved("d4"), Some(false));
+
+    // Only moves to avoid
+    let record =
+        fen::load_epd("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - am Ng5;")
+            .unwrap();
+    let d4 = record.board.parse_san("d4").unwrap();
+    assert_eq!(epd_suite::is_solved(&record, &d4), Some(true));
+
+    let record = fen::load_epd("4k3/8/8/8/8/8/8/4K3 w - - id \"nothing\";").unwrap();
+    let kd1 = record.board.parse_san("Kd1").unwrap();
+    assert_eq!(epd_suite::is_solved(&record, &kd1), None);
+}
+
+#[test]
+fn epd_suite_run_test() {
+    board_crab_lib::init();
+
+    let records = [
+        "6k1/5ppp/8/8/8/8/8/R5K1 w - - bm Ra8#; id \"back rank\";",
+        "4k3/8/8/8/8/8/8/4K3 w - - id \"nothing\";",
+        "7k/5Q2/6K1/8/8/8/8/8 b - - bm Kh7; id \"stalemate\";",
+    ]
+    .map(|epd| fen::load_epd(epd).unwrap());
+
+    let mut settings = epd_suite::SuiteSettings::new();
+    settings.time_per_position = None;
+    settings.nodes_per_position = Some(20_000);
+    settings.table_size_mbs = 1;
+
+    // The mate is found easily, the other positions can't be checked
+    let results = epd_suite::run(&records, &settings);
+    assert_eq!(results.num_solved, 1);
+    assert_eq!(results.num_checked, 1);
+    assert!(results.total_nodes > 0);
+}
@@ -0,0 +1,416 @@
+use board_crab_lib::bitmask::*;
+use board_crab_lib::board::*;
+use board_crab_lib::eval;
+use board_crab_lib::fen;
+
+const TEST_FENS: [&str; 4] = [
+    fen::FEN_START_POS,
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    "6k1/5pp1/7p/8/3P4/8/5PPP/6K1 b - - 0 40",
+];
+
+// Swaps the colors of a position (board flipped vertically, pieces and turn swapped)
+fn mirror_fen(position_fen: &str) -> String {
+    let parts: Vec<&str> = position_fen.split(' ').collect();
+    let swap_case = |c: char| {
+        if c.is_ascii_uppercase() {
+            c.to_ascii_lowercase()
+        } else {
+            c.to_ascii_uppercase()
+        }
+    };
+
+    let rows: Vec<String> = parts[0]
+        .split('/')
+        .rev()
+        .map(|row| row.chars().map(swap_case).collect())
+        .collect();
+    let turn = if parts[1] == "w" { "b" } else { "w" };
+    let castling: String = if parts[2] == "-" {
+        "-".to_string()
+    } else {
+        let mut castling: Vec<char> = parts[2].chars().map(swap_case).collect();
+        castling.sort_by_key(|c| c.is_ascii_lowercase());
+        castling.into_iter().collect()
+    };
+    let en_passant = if parts[3] == "-" {
+        "-".to_string()
+    } else {
+        let mut chars = parts[3].chars();
+        let file = chars.next().unwrap();
+        let rank = chars.next().unwrap();
+        format!("{}{}", file, if rank == '3' { '6' } else { '3' })
+    };
+
+    format!(
+        "{} {} {} {} {} {}",
+        rows.join("/"),
+        turn,
+        castling,
+        en_passant,
+        parts[4],
+        parts[5]
+    )
+}
+
+#[test]
+fn eval_symmetry_test() {
+    board_crab_lib::init();
+
+    for position_fen in TEST_FENS {
+        let board = fen::load_fen(position_fen).unwrap();
+        let mirrored_fen = mirror_fen(position_fen);
+        let mirrored_board = fen::load_fen(&mirrored_fen).unwrap();
+
+        let eval = eval::eval_board(&board);
+        let mirrored_eval = eval::eval_board(&mirrored_board);
+        if eval != mirrored_eval {
+            panic!(
+                "Asymmetric eval for \"{}\" (got: {}, mirrored \"{}\": {})",
+                position_fen, eval, mirrored_fen, mirrored_eval
+            );
+        }
+    }
+}
+
+#[test]
+fn eval_phase_test() {
+    board_crab_lib::init();
+
+    let start_board = fen::load_fen(fen::FEN_START_POS).unwrap();
+    assert_eq!(eval::calc_phase(&start_board), 1.0);
+
+    let pawn_endgame_board = fen::load_fen("4k3/pppp4/8/8/8/8/4PPPP/4K3 w - - 0 1").unwrap();
+    assert_eq!(eval::calc_phase(&pawn_endgame_board), 0.0);
+
+    let rook_endgame_board = fen::load_fen("4k3/r7/8/8/8/8/R7/4K3 w - - 0 1").unwrap();
+    let rook_endgame_phase = eval::calc_phase(&rook_endgame_board);
+    assert!(rook_endgame_phase > 0.0 && rook_endgame_phase < 0.5);
+
+    // The tapered score moves from the middlegame value to the endgame value
+    let score = eval::Score::new(1.0, 3.0);
+    assert_eq!(score.blend(1.0), 1.0);
+    assert_eq!(score.blend(0.0), 3.0);
+    assert_eq!(score.blend(0.5), 2.0);
+}
+
+#[test]
+fn pawn_structure_test() {
+    board_crab_lib::init();
+
+    // White: doubled and isolated g-pawns, backward d-pawn (d4 is attacked by e5), passed c-pawn
+    // Black: connected e/f phalanx, isolated and passed a-pawn
+    let board = fen::load_fen("4k3/8/8/p3pp2/2P1P3/3P2P1/6P1/4K3 w - - 0 1").unwrap();
+    let to_coords = |mask: BitMask| {
+        let mut coords: Vec<String> = bm_iter_bits(mask).map(bm_to_coord).collect();
+        coords.sort();
+        coords.join(" ")
+    };
+
+    assert_eq!(to_coords(eval::get_doubled_pawns(&board, 0)), "g2");
+    assert_eq!(to_coords(eval::get_isolated_pawns(&board, 0)), "g2 g3");
+    assert_eq!(to_coords(eval::get_backward_pawns(&board, 0)), "d3");
+    assert_eq!(to_coords(eval::get_connected_pawns(&board, 0)), "c4 e4");
+    assert_eq!(to_coords(eval::get_passed_pawns(&board, 0)), "c4");
+
+    assert_eq!(to_coords(eval::get_doubled_pawns(&board, 1)), "");
+    assert_eq!(to_coords(eval::get_isolated_pawns(&board, 1)), "a5");
+    assert_eq!(to_coords(eval::get_backward_pawns(&board, 1)), "");
+    assert_eq!(to_coords(eval::get_connected_pawns(&board, 1)), "e5 f5");
+    assert_eq!(to_coords(eval::get_passed_pawns(&board, 1)), "a5");
+}
+
+#[test]
+fn pawn_structure_cache_test() {
+    board_crab_lib::init();
+
+    // Evaluate twice, so the second time comes from the cache
+    for _ in 0..2 {
+        for position_fen in TEST_FENS {
+            let board = fen::load_fen(position_fen).unwrap();
+            let cached_scores = eval::eval_pawn_structures_cached(&board);
+            for team_idx in 0..2 {
+                assert_eq!(
+                    cached_scores[team_idx],
+                    eval::eval_pawn_structure(&board, team_idx)
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn king_safety_test() {
+    board_crab_lib::init();
+
+    // Queen and knight both hitting the squares around white's king
+    let attacked_board = fen::load_fen("r5k1/5ppp/8/8/6n1/7q/5PPP/5RK1 w - - 0 1").unwrap();
+    assert!(eval::eval_king_attackers(&attacked_board, 0).mg < 0.0);
+    assert_eq!(
+        eval::eval_king_attackers(&attacked_board, 1),
+        eval::Score::ZERO
+    );
+
+    // A lone attacker isn't counted
+    let single_attacker_board = fen::load_fen("r5k1/5ppp/8/8/8/7q/5PPP/5RK1 w - - 0 1").unwrap();
+    assert_eq!(
+        eval::eval_king_attackers(&single_attacker_board, 0),
+        eval::Score::ZERO
+    );
+
+    // Missing the pawns in front of the king should hurt
+    let sheltered_board = fen::load_fen("6k1/5ppp/8/8/8/8/5PPP/3Q2K1 b - - 0 1").unwrap();
+    let exposed_board = fen::load_fen("6k1/5p1p/8/8/8/8/5PPP/3Q2K1 b - - 0 1").unwrap();
+    assert!(
+        eval::eval_king_safety(&exposed_board, 1).mg
+            < eval::eval_king_safety(&sheltered_board, 1).mg
+    );
+}
+
+#[test]
+fn mobility_test() {
+    board_crab_lib::init();
+
+    let center_knight_board = fen::load_fen("4k3/8/8/8/3N4/8/8/4K3 w - - 0 1").unwrap();
+    let corner_knight_board = fen::load_fen("4k3/8/8/8/8/8/8/N3K3 w - - 0 1").unwrap();
+    assert!(
+        eval::eval_mobility(&center_knight_board, 0).mg
+            > eval::eval_mobility(&corner_knight_board, 0).mg
+    );
+
+    // Squares covered by enemy pawns aren't safe to move to
+    let pawn_covered_board = fen::load_fen("4k3/8/2p1p3/8/3N4/8/8/4K3 w - - 0 1").unwrap();
+    assert!(
+        eval::eval_mobility(&pawn_covered_board, 0).mg
+            < eval::eval_mobility(&center_knight_board, 0).mg
+    );
+}
+
+#[test]
+fn piece_bonus_test() {
+    board_crab_lib::init();
+
+    let eval_pieces = |position_fen: &str, piece_idx: usize| {
+        let board = fen::load_fen(position_fen).unwrap();
+        eval::eval_piece_type(&board, 0, piece_idx, board.pieces[0][piece_idx]).mg
+    };
+
+    // Knight outpost on d5, defended by the e4 pawn
+    let outpost = eval_pieces("4k3/8/8/3N4/4P3/8/8/4K3 w - - 0 1", PIECE_KNIGHT);
+    let no_support = eval_pieces("4k3/8/8/3N4/8/8/4P3/4K3 w - - 0 1", PIECE_KNIGHT);
+    let can_be_chased = eval_pieces("4k3/2p5/8/3N4/4P3/8/8/4K3 w - - 0 1", PIECE_KNIGHT);
+    assert!(outpost > no_support);
+    assert!(outpost > can_be_chased);
+
+    let bishop_pair = eval_pieces("4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1", PIECE_BISHOP);
+    let single_bishop = eval_pieces("4k3/8/8/8/8/8/8/2B1K3 w - - 0 1", PIECE_BISHOP);
+    assert!(bishop_pair > single_bishop);
+
+    let open_rook = eval_pieces("4k3/8/8/8/8/8/1P6/R3K3 w - - 0 1", PIECE_ROOK);
+    let semi_open_rook = eval_pieces("4k3/p7/8/8/8/8/1P6/R3K3 w - - 0 1", PIECE_ROOK);
+    let closed_rook = eval_pieces("4k3/p7/8/8/8/8/P7/R3K3 w - - 0 1", PIECE_ROOK);
+    assert!(open_rook > semi_open_rook);
+    assert!(semi_open_rook > closed_rook);
+}
+
+#[test]
+fn threats_test() {
+    board_crab_lib::init();
+
+    let eval_threats = |position_fen: &str| {
+        let board = fen::load_fen(position_fen).unwrap();
+        eval::eval_threats(&board, 0).mg
+    };
+
+    // Nothing of black's is attacked
+    assert_eq!(eval_threats("4k3/8/8/8/8/8/8/4K3 w - - 0 1"), 0.0);
+
+    // Pawn attacking a knight defended by a pawn
+    let pawn_threat = eval_threats("4k3/8/4p3/3n4/4P3/8/8/4K3 w - - 0 1");
+    assert!(pawn_threat > 0.0);
+
+    // The same knight without a defender is also hanging
+    let hanging_threat = eval_threats("4k3/8/8/3n4/4P3/8/8/4K3 w - - 0 1");
+    assert!(hanging_threat > pawn_threat);
+
+    // Knight attacking a defended rook
+    let minor_threat = eval_threats("4k3/8/4p3/3r4/8/4N3/8/4K3 w - - 0 1");
+    assert!(minor_threat > 0.0);
+
+    // Knight attacking a defended pawn
+    assert_eq!(eval_threats("4k3/8/4p3/3p4/8/4N3/8/4K3 w - - 0 1"), 0.0);
+}
+
+#[test]
+fn imbalance_test() {
+    board_crab_lib::init();
+
+    let eval_imbalance = |position_fen: &str| {
+        let board = fen::load_fen(position_fen).unwrap();
+        eval::eval_imbalance(&board, 0).mg
+    };
+
+    // Knights get better with more pawns on the board, rooks get worse
+    let closed_knight = eval_imbalance("4k3/8/8/8/8/8/PPPPPPPP/1N2K3 w - - 0 1");
+    let open_knight = eval_imbalance("4k3/8/8/8/8/8/PP6/1N2K3 w - - 0 1");
+    assert!(closed_knight > open_knight);
+
+    let closed_rook = eval_imbalance("4k3/8/8/8/8/8/PPPPPPPP/R3K3 w - - 0 1");
+    let open_rook = eval_imbalance("4k3/8/8/8/8/8/PP6/R3K3 w - - 0 1");
+    assert!(closed_rook < open_rook);
+
+    // Pieces don't change value with the base amount of pawns
+    assert_eq!(
+        eval_imbalance("4k3/8/8/8/8/8/PPPPP3/RN2KB1B w - - 0 1"),
+        0.0
+    );
+}
+
+#[test]
+fn tempo_test() {
+    board_crab_lib::init();
+
+    // Everything but the tempo bonus is the same for both sides to move,
+    // so the side-relative evals only add up to zero without it
+    let get_tempo = |position_fen: &str| {
+        let white_board = fen::load_fen(position_fen).unwrap();
+        let black_board = fen::load_fen(&position_fen.replace(" w ", " b ")).unwrap();
+        (eval::eval_board(&white_board) + eval::eval_board(&black_board)) / 2
+    };
+
+    assert!(get_tempo(fen::FEN_START_POS) > 0);
+    assert!(get_tempo("4k3/r7/8/8/8/8/R7/4K3 w - - 0 1") > 0);
+
+    // Pawn endgames are all about zugzwang, so moving isn't an advantage there
+    assert_eq!(get_tempo("4k3/pppp4/8/8/8/8/4PPPP/4K3 w - - 0 1"), 0);
+}
+
+#[test]
+fn passed_pawn_test() {
+    board_crab_lib::init();
+
+    let eval_passed_pawns = |position_fen: &str| {
+        let board = fen::load_fen(position_fen).unwrap();
+        eval::eval_passed_pawns(&board, 0).eg
+    };
+
+    // Black's king is too far away to catch the pawn
+    assert!(eval_passed_pawns("8/8/8/8/P7/6k1/8/7K b - - 0 1") > 3.0);
+    assert!(eval_passed_pawns("8/8/8/8/P7/4k3/8/7K b - - 0 1") < 3.0);
+
+    // Moving first lets the pawn get away from a king that would otherwise catch it
+    assert!(eval_passed_pawns("8/8/8/8/P4k2/8/8/7K w - - 0 1") > 3.0);
+    assert!(eval_passed_pawns("8/8/8/8/P3k3/8/8/7K w - - 0 1") < 3.0);
+
+    // The square rule doesn't work if black has pieces that can stop the pawn
+    assert!(eval_passed_pawns("8/8/8/8/P7/6k1/8/6nK b - - 0 1") < 3.0);
+
+    // Pawn supported by another pawn, and blocked by a knight
+    let free_pawn = eval_passed_pawns("8/8/3k4/8/3P4/8/8/3K3n w - - 0 1");
+    let supported_pawn = eval_passed_pawns("8/8/3k4/8/3P4/2P5/8/3K3n w - - 0 1");
+    let blocked_pawn = eval_passed_pawns("8/8/3k4/3n4/3P4/8/8/3K4 w - - 0 1");
+    assert!(supported_pawn > free_pawn);
+    assert!(blocked_pawn < free_pawn);
+}
+
+#[test]
+fn endgame_scale_test() {
+    board_crab_lib::init();
+
+    let get_scale = |position_fen: &str| {
+        let board = fen::load_fen(position_fen).unwrap();
+        eval::get_endgame_scale(&board, 0)
+    };
+
+    assert_eq!(get_scale(fen::FEN_START_POS), 1.0);
+
+    // Opposite-colored bishops, but not same-colored ones
+    assert!(get_scale("4k3/2b2p2/8/8/8/8/2B2PPP/4K3 w - - 0 1") < 1.0);
+    assert_eq!(get_scale("4k3/5p2/2b5/8/8/8/2B2PPP/4K3 w - - 0 1"), 1.0);
+
+    // Rook ending a pawn up, but not two
+    assert!(get_scale("4k3/r4pp1/8/8/8/8/R4PPP/4K3 w - - 0 1") < 1.0);
+    assert_eq!(get_scale("4k3/r5p1/8/8/8/8/R4PPP/4K3 w - - 0 1"), 1.0);
+
+    // Rook against a minor piece without pawns, but not a queen
+    assert!(get_scale("4k3/8/3b4/8/8/8/8/R3K3 w - - 0 1") < 1.0);
+    assert_eq!(get_scale("4k3/8/3b4/8/8/8/8/Q3K3 w - - 0 1"), 1.0);
+
+    // Scaling applies to the real eval
+    let rook_ending_board = fen::load_fen("4k3/r4pp1/8/8/8/8/R4PPP/4K3 w - - 0 1").unwrap();
+    let rook_ending_eval = eval::eval_board(&rook_ending_board);
+    let win_board = fen::load_fen("4k3/r5p1/8/8/8/8/R4PPP/4K3 w - - 0 1").unwrap();
+    assert!(rook_ending_eval > 0 && rook_ending_eval < eval::eval_board(&win_board));
+}
+
+#[test]
+fn mop_up_test() {
+    board_crab_lib::init();
+
+    let eval_mop_up = |position_fen: &str| {
+        let board = fen::load_fen(position_fen).unwrap();
+        eval::eval_mop_up(&board, 0).eg
+    };
+
+    // Only used against a lone king
+    assert_eq!(eval_mop_up("7k/7p/8/8/8/8/8/R3K3 w - - 0 1"), 0.0);
+
+    // Enemy king on the edge, and our king close by
+    let center_king = eval_mop_up("8/8/8/3k4/8/8/8/R3K3 w - - 0 1");
+    let edge_king = eval_mop_up("3k4/8/8/8/8/8/8/R3K3 w - - 0 1");
+    let close_kings = eval_mop_up("3k4/8/3K4/8/8/8/8/R7 w - - 0 1");
+    assert!(edge_king > center_king);
+    assert!(close_kings > edge_king);
+
+    // With a light-squared bishop, the king has to go to a8 or h1
+    let right_corner = eval_mop_up("k7/8/8/8/8/8/8/4KBN1 w - - 0 1");
+    let wrong_corner = eval_mop_up("7k/8/8/8/8/8/8/4KBN1 w - - 0 1");
+    assert!(right_corner > wrong_corner);
+}
+
+#[test]
+fn explain_test() {
+    board_crab_lib::init();
+
+    for position_fen in TEST_FENS {
+        let board = fen::load_fen(position_fen).unwrap();
+        let trace = eval::explain(&board);
+
+        // The terms should add up to the totals
+        for team_idx in 0..2 {
+            let term_sum = trace
+                .terms
+                .iter()
+                .fold(eval::Score::ZERO, |sum, term| sum + term.scores[team_idx]);
+            let total = trace.team_totals[team_idx];
+            assert!((term_sum.mg - total.mg).abs() < 0.001, "{}", position_fen);
+            assert!((term_sum.eg - total.eg).abs() < 0.001, "{}", position_fen);
+        }
+
+        assert_eq!(trace.eval, eval::eval_board(&board));
+        assert!(!trace.is_nnue);
+    }
+}
+
+#[test]
+fn lazy_eval_test() {
+    board_crab_lib::init();
+
+    for position_fen in TEST_FENS {
+        let board = fen::load_fen(position_fen).unwrap();
+        let eval = eval::eval_board(&board);
+
+        // A window around the real eval should always get the full eval
+        let (window_eval, is_exact) = eval::eval_board_in_window(&board, eval - 1, eval + 1);
+        assert!(is_exact, "{}", position_fen);
+        assert_eq!(window_eval, eval, "{}", position_fen);
+    }
+
+    // Up a queen, so a window around zero doesn't need the full eval
+    let board = fen::load_fen("4k3/pppppppp/8/8/8/8/PPPPPPPP/3QK3 w - - 0 1").unwrap();
+    let (lazy_eval, is_exact) = eval::eval_board_in_window(&board, -1, 1);
+    assert!(!is_exact);
+    assert!(lazy_eval > 500);
+    assert!((lazy_eval - eval::eval_board(&board)).abs() < 300);
+}
@@ -0,0 +1,151 @@
+use board_crab_lib::board::*;
+use board_crab_lib::fen;
+
+#[test]
+fn fen_round_trip_test() {
+    board_crab_lib::init();
+
+    for position_fen in [
+        fen::FEN_START_POS,
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 13 57",
+        "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 3 20",
+        "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w Kq e6 0 2",
+    ] {
+        let board = fen::load_fen(position_fen).unwrap();
+        assert_eq!(board.to_fen(), position_fen);
+    }
+
+    // Missing counters default to the start of the game
+    let board = fen::load_fen("4k3/8/8/8/8/8/8/4K3 b -").unwrap();
+    assert_eq!(board.to_fen(), "4k3/8/8/8/8/8/8/4K3 b - - 0 1");
+
+    assert!(fen::load_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 0").is_err());
+    assert!(fen::load_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 x").is_err());
+}
+
+#[test]
+fn fen_chess960_test() {
+    board_crab_lib::init();
+
+    // Shredder-FEN is written back as X-FEN, which only names the rook's column when it isn't the outermost one
+    for (position_fen, target_fen) in [
+        (
+            "qnbnr1kr/ppp1b1pp/4p3/3p1p2/8/2NPP3/PPP1BPPP/QNB1R1KR w HEhe - 1 9",
+            "qnbnr1kr/ppp1b1pp/4p3/3p1p2/8/2NPP3/PPP1BPPP/QNB1R1KR w KQkq - 1 9",
+        ),
+        (
+            "1r2k1rr/8/8/8/8/8/8/1R2K1RR w GBgb - 0 1",
+            "1r2k1rr/8/8/8/8/8/8/1R2K1RR w GQgq - 0 1",
+        ),
+        (
+            "1r2k1rr/8/8/8/8/8/8/1R2K1RR w GQgq - 0 1",
+            "1r2k1rr/8/8/8/8/8/8/1R2K1RR w GQgq - 0 1",
+        ),
+    ] {
+        let board = fen::load_fen(position_fen).unwrap();
+        assert!(board.is_chess960);
+        assert_eq!(board.to_fen(), target_fen);
+        assert_eq!(fen::load_fen(target_fen).unwrap().hash, board.hash);
+    }
+
+    // Normal positions in Shredder-FEN are still normal
+    let board = fen::load_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w HAha - 0 1").unwrap();
+    assert!(!board.is_chess960);
+    assert_eq!(board.hash, Board::start_pos().hash);
+
+    // Castle rights to different rooks are different positions
+    assert_ne!(
+        fen::load_fen("4k3/8/8/8/8/8/8/1R2K1RR w G - 0 1")
+            .unwrap()
+            .hash,
+        fen::load_fen("4k3/8/8/8/8/8/8/1R2K1RR w H - 0 1")
+            .unwrap()
+            .hash
+    );
+
+    // Rights need a rook, and the king on its back row
+    assert!(fen::load_fen("4k3/8/8/8/8/8/8/4K3 w K - 0 1").is_err());
+    assert!(fen::load_fen("4k3/8/8/8/8/8/8/1R2K1RR w C - 0 1").is_err());
+    assert!(fen::load_fen("4k3/8/8/8/8/8/4K3/R6R w KQ - 0 1").is_err());
+}
+
+#[test]
+fn fen_counters_test() {
+    board_crab_lib::init();
+
+    let mut board = Board::start_pos();
+    for (san, target_fen) in [
+        (
+            "Nf3",
+            "rnbqkbnr/pppppppp/8/8/8/5N2/PPPPPPPP/RNBQKB1R b KQkq - 1 1",
+        ),
+        (
+            "Nf6",
+            "rnbqkb1r/pppppppp/5n2/8/8/5N2/PPPPPPPP/RNBQKB1R w KQkq - 2 2",
+        ),
+        (
+            "e4",
+            "rnbqkb1r/pppppppp/5n2/8/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq e3 0 2",
+        ),
+        (
+            "Nxe4",
+            "rnbqkb1r/pppppppp/8/8/4n3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 0 3",
+        ),
+    ] {
+        let mv = board.parse_san(san).unwrap();
+        board.do_move(&mv);
+        assert_eq!(board.to_fen(), target_fen);
+    }
+}
+
+#[test]
+fn epd_test() {
+    board_crab_lib::init();
+
+    let record = fen::load_epd(
+        r#"2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id "WAC.001; mate"; ce +32750; pv Qg6 fxg6;"#,
+    )
+    .unwrap();
+    assert_eq!(
+        record.board.to_fen(),
+        "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - 0 1"
+    );
+    assert_eq!(record.get_id(), Some("WAC.001; mate"));
+    assert_eq!(record.get_centipawn_eval(), Some(32750));
+    assert_eq!(record.opcodes.len(), 4);
+    assert_eq!(record.get_opcode("am"), None);
+
+    let best_moves = record.get_moves("bm").unwrap();
+    assert_eq!(best_moves.len(), 1);
+    assert_eq!(best_moves[0].to_string(), "g3g6");
+
+    let pv: Vec<String> = record
+        .get_pv()
+        .unwrap()
+        .iter()
+        .map(|mv| mv.to_string())
+        .collect();
+    assert_eq!(pv, ["g3g6", "f7g6"]);
+
+    // Multiple operands, counters as opcodes, and no final semicolon
+    let record = fen::load_epd(
+        "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - am Nc3 d4; hmvc 2; fmvn 3",
+    )
+    .unwrap();
+    assert_eq!(record.get_moves("am").unwrap().len(), 2);
+    assert_eq!(record.board.half_move_counter, 2);
+    assert_eq!(record.board.full_move_counter, 3);
+
+    // FEN counters before the opcodes
+    let record = fen::load_epd("4k3/8/8/8/8/8/8/4K3 b - - 5 40 bm Kd7;").unwrap();
+    assert_eq!(record.board.to_fen(), "4k3/8/8/8/8/8/8/4K3 b - - 5 40");
+    assert_eq!(record.get_opcode("bm").unwrap(), ["Kd7"]);
+
+    // Moves that aren't legal
+    let record = fen::load_epd("4k3/8/8/8/8/8/8/4K3 w - - bm Qh5;").unwrap();
+    assert!(record.get_moves("bm").is_none());
+
+    assert!(fen::load_epd("4k3/8/8/8/8/8/8/4K3 w").is_err());
+    assert!(fen::load_epd("4k3/8/8/8/8/8/8/4K3 w - - id \"unclosed;").is_err());
+}
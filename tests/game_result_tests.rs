@@ -0,0 +1,82 @@
+use board_crab_lib::board::*;
+use board_crab_lib::fen;
+use board_crab_lib::move_gen;
+use board_crab_lib::search;
+use board_crab_lib::search::GameResult;
+
+fn do_move_str(board: &mut Board, move_str: &str) {
+    let mut moves = move_gen::MoveBuffer::new();
+    move_gen::generate_moves(board, &mut moves);
+    let mv = *moves
+        .iter()
+        .find(|mv| format!("{mv}") == move_str)
+        .unwrap_or_else(|| panic!("Move \"{}\" not found", move_str));
+    board.do_move(&mv);
+}
+
+#[test]
+fn game_result_test() {
+    board_crab_lib::init();
+
+    let test_entries = [
+        ("start position", fen::FEN_START_POS, GameResult::InProgress),
+        (
+            "back rank mate",
+            "R5k1/5ppp/8/8/8/8/8/6K1 b - - 1 1",
+            GameResult::Checkmate,
+        ),
+        (
+            "stalemate",
+            "7k/5Q2/6K1/8/8/8/8/8 b - - 0 1",
+            GameResult::Stalemate,
+        ),
+        (
+            "fifty-move rule",
+            "4k3/8/8/8/8/8/8/R3K3 w - - 100 80",
+            GameResult::FiftyMoveRule,
+        ),
+        (
+            "checkmate beats fifty-move rule",
+            "R5k1/5ppp/8/8/8/8/8/6K1 b - - 100 80",
+            GameResult::Checkmate,
+        ),
+        (
+            "insufficient material",
+            "4k3/8/8/8/8/8/8/2B1K3 w - - 0 1",
+            GameResult::InsufficientMaterial,
+        ),
+    ];
+
+    for (name, position_fen, target_result) in test_entries {
+        let board = fen::load_fen(position_fen).unwrap();
+        let result = search::get_game_result(&board);
+        if result != target_result {
+            panic!(
+                "Failed position \"{}\" (got: {:?}, target: {:?}), fen: \"{}\"",
+                name, result, target_result, position_fen
+            );
+        }
+    }
+}
+
+#[test]
+fn half_move_counter_test() {
+    board_crab_lib::init();
+
+    let mut board = fen::load_fen("4k3/8/8/3p4/8/8/8/R2QK3 w - - 98 80").unwrap();
+
+    do_move_str(&mut board, "a1a2");
+    assert_eq!(board.half_move_counter, 99);
+
+    // Pawn moves and captures reset the counter
+    do_move_str(&mut board, "d5d4");
+    assert_eq!(board.half_move_counter, 0);
+    do_move_str(&mut board, "a2a3");
+    assert_eq!(board.half_move_counter, 1);
+    do_move_str(&mut board, "e8e7");
+    do_move_str(&mut board, "d1d4");
+    assert_eq!(board.half_move_counter, 0);
+
+    do_move_str(&mut board, "e7e6");
+    assert_eq!(board.half_move_counter, 1);
+}
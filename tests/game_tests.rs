@@ -0,0 +1,90 @@
+use board_crab_lib::fen;
+use board_crab_lib::game::Game;
+use board_crab_lib::search::GameResult;
+
+#[test]
+fn game_undo_test() {
+    board_crab_lib::init();
+
+    let start_board = fen::load_fen(fen::FEN_START_POS).unwrap();
+    let mut game = Game::new(start_board);
+    for move_str in [
+        "e2e4", "d7d5", "e4d5", "g8f6", "f1b5", "c7c6", "g1f3", "c6b5", "e1g1",
+    ] {
+        game.push_uci(move_str).unwrap();
+    }
+    assert_eq!(game.get_moves().len(), 9);
+    assert_eq!(game.get_hashes().len(), 9);
+    assert_eq!(
+        fen::make_fen(game.get_board()),
+        "rnbqkb1r/pp2pppp/5n2/1p1P4/8/5N2/PPPP1PPP/RNBQ1RK1 b kq - 1 5"
+    );
+
+    assert!(game.push_uci("e1g1").is_err());
+    assert_eq!(game.get_moves().len(), 9);
+
+    while game.undo().is_some() {}
+    assert!(game.get_moves().is_empty());
+    assert!(game.get_hashes().is_empty());
+    assert_eq!(fen::make_fen(game.get_board()), fen::FEN_START_POS);
+    assert_eq!(game.get_board().hash, game.get_start_board().hash);
+}
+
+#[test]
+fn game_result_repetition_test() {
+    board_crab_lib::init();
+
+    let mut game = Game::new(fen::load_fen(fen::FEN_START_POS).unwrap());
+    assert_eq!(game.result(), None);
+
+    // The start position occurs for the third time after the knights go back and forth twice
+    let shuffle = ["g1f3", "g8f6", "f3g1", "f6g8"];
+    for move_str in shuffle {
+        game.push_uci(move_str).unwrap();
+    }
+    assert_eq!(game.result(), None);
+    for move_str in shuffle {
+        game.push_uci(move_str).unwrap();
+    }
+    assert!(game.is_threefold_repetition());
+    assert_eq!(game.result(), Some(GameResult::Repetition));
+
+    game.undo();
+    assert_eq!(game.result(), None);
+
+    // Positions from before a pawn move don't count
+    let mut game = Game::new(fen::load_fen(fen::FEN_START_POS).unwrap());
+    for move_str in shuffle {
+        game.push_uci(move_str).unwrap();
+    }
+    game.push_uci("e2e4").unwrap();
+    game.push_uci("e7e5").unwrap();
+    for move_str in shuffle {
+        game.push_uci(move_str).unwrap();
+    }
+    assert_eq!(game.result(), None);
+}
+
+#[test]
+fn game_result_end_test() {
+    board_crab_lib::init();
+
+    let mut game = Game::new(fen::load_fen(fen::FEN_START_POS).unwrap());
+    for move_str in ["f2f3", "e7e5", "g2g4", "d8h4"] {
+        game.push_uci(move_str).unwrap();
+    }
+    assert_eq!(game.result(), Some(GameResult::Checkmate));
+
+    let mut game = Game::new(fen::load_fen("4k3/8/8/8/8/8/8/R3K3 w - - 99 80").unwrap());
+    assert_eq!(game.result(), None);
+    game.push_uci("a1a2").unwrap();
+    assert_eq!(game.result(), Some(GameResult::FiftyMoveRule));
+
+    let mut game = Game::new(fen::load_fen("4k3/8/8/8/8/8/1r6/2B1K3 w - - 0 1").unwrap());
+    assert_eq!(game.result(), None);
+    game.push_uci("c1b2").unwrap();
+    assert_eq!(game.result(), Some(GameResult::InsufficientMaterial));
+
+    let game = Game::new(fen::load_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap());
+    assert_eq!(game.result(), Some(GameResult::Stalemate));
+}
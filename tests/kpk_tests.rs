@@ -0,0 +1,77 @@
+use board_crab_lib::eval;
+use board_crab_lib::fen;
+use board_crab_lib::kpk;
+
+#[test]
+fn kpk_probe_test() {
+    board_crab_lib::init();
+
+    let test_entries = [
+        (
+            "king in front on the 6th row",
+            "4k3/8/4K3/4P3/8/8/8/8 b - - 0 1",
+            true,
+        ),
+        (
+            "defender has the opposition",
+            "4k3/8/4P3/4K3/8/8/8/8 w - - 0 1",
+            false,
+        ),
+        (
+            "king on a key square",
+            "8/4k3/8/8/4K3/8/4P3/8 b - - 0 1",
+            true,
+        ),
+        ("rook pawn", "k7/8/8/8/8/8/P7/K7 w - - 0 1", false),
+        (
+            "undefended pawn is taken",
+            "8/8/8/8/8/3k4/4P3/7K b - - 0 1",
+            false,
+        ),
+        ("outside the square", "8/8/8/8/P7/8/6k1/K7 w - - 0 1", true),
+        // Same positions with black's pawn, on the other side of the board
+        (
+            "black king in front",
+            "8/8/8/8/3p4/3k4/8/3K4 w - - 0 1",
+            true,
+        ),
+        (
+            "white has the opposition",
+            "8/8/8/8/3k4/3p4/8/3K4 b - - 0 1",
+            false,
+        ),
+        (
+            "black king on a key square",
+            "8/3p4/8/3k4/8/8/3K4/8 w - - 0 1",
+            true,
+        ),
+        ("black rook pawn", "7k/7p/8/8/8/8/8/7K b - - 0 1", false),
+    ];
+
+    for (name, position_fen, target_is_win) in test_entries {
+        let board = fen::load_fen(position_fen).unwrap();
+        let is_win = kpk::probe(&board);
+        if is_win != Some(target_is_win) {
+            panic!(
+                "Failed position \"{}\" (got: {:?}, target: {}), fen: \"{}\"",
+                name, is_win, target_is_win, position_fen
+            );
+        }
+    }
+
+    // Only king and pawn vs king positions can be probed
+    let start_board = fen::load_fen(fen::FEN_START_POS).unwrap();
+    assert_eq!(kpk::probe(&start_board), None);
+}
+
+#[test]
+fn kpk_eval_test() {
+    board_crab_lib::init();
+
+    let draw_board = fen::load_fen("4k3/8/4P3/4K3/8/8/8/8 w - - 0 1").unwrap();
+    assert_eq!(eval::eval_board(&draw_board), 0);
+
+    // The side to move is losing here
+    let win_board = fen::load_fen("8/4k3/8/8/4K3/8/4P3/8 b - - 0 1").unwrap();
+    assert!(eval::eval_board(&win_board) < -300);
+}
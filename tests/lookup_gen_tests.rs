@@ -0,0 +1,38 @@
+use board_crab_lib::board::*;
+use board_crab_lib::lookup_gen;
+#[cfg(debug_assertions)]
+use board_crab_lib::lookup_gen_magic;
+use rand::Rng;
+
+// Makes sure the fast slider lookups (magic or PEXT) match walking the rays
+#[test]
+fn slider_tos_test() {
+    board_crab_lib::init();
+
+    // Debug builds only use the slow lookups, so the fast tables aren't made on init
+    #[cfg(debug_assertions)]
+    lookup_gen_magic::init();
+
+    let mut rng = rand::rng();
+    for pos_idx in 0..64 {
+        for _ in 0..200 {
+            // Sparse and dense boards
+            let occupy = if rng.random_bool(0.5) {
+                rng.random::<u64>() & rng.random::<u64>()
+            } else {
+                rng.random::<u64>()
+            };
+
+            for piece_idx in [PIECE_BISHOP, PIECE_ROOK, PIECE_QUEEN] {
+                assert_eq!(
+                    lookup_gen::get_slider_tos_fast(piece_idx, pos_idx, occupy),
+                    lookup_gen::get_slider_tos_slow(piece_idx, pos_idx, occupy),
+                    "{} on {} with occupancy {:016X}",
+                    PIECE_NAMES[piece_idx],
+                    pos_idx,
+                    occupy
+                );
+            }
+        }
+    }
+}
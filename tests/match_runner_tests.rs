@@ -0,0 +1,131 @@
+use board_crab_lib::fen;
+use board_crab_lib::match_runner::*;
+
+#[test]
+fn match_results_elo_test() {
+    let even = MatchResults {
+        wins: 10,
+        draws: 5,
+        losses: 10,
+        ..Default::default()
+    };
+    let (elo, elo_margin) = even.get_elo().unwrap();
+    assert_eq!(elo, 0.0);
+    assert!(elo_margin > 0.0);
+
+    // 75% is about +191 Elo
+    let winning = MatchResults {
+        wins: 30,
+        draws: 0,
+        losses: 10,
+        ..Default::default()
+    };
+    let (elo, _) = winning.get_elo().unwrap();
+    assert!((elo - 190.85).abs() < 0.01);
+
+    // More games make the estimate more certain
+    let more_games = MatchResults {
+        wins: 300,
+        draws: 0,
+        losses: 100,
+        ..Default::default()
+    };
+    assert!(more_games.get_elo().unwrap().1 < winning.get_elo().unwrap().1);
+
+    let few_games = MatchResults {
+        wins: 2,
+        losses: 1,
+        ..Default::default()
+    };
+    assert_eq!(few_games.get_elo().unwrap().1, f64::INFINITY);
+
+    assert!(MatchResults::default().get_elo().is_none());
+    let all_wins = MatchResults {
+        wins: 3,
+        draws: 0,
+        losses: 0,
+        ..Default::default()
+    };
+    assert!(all_wins.get_elo().is_none());
+}
+
+#[test]
+fn sprt_test() {
+    let sprt = SprtSettings::new();
+    let (lower_bound, upper_bound) = sprt.get_bounds();
+    assert!((upper_bound - 2.944).abs() < 0.001);
+    assert_eq!(lower_bound, -upper_bound);
+
+    // Without variance there's nothing to go on yet
+    let all_draws = MatchResults {
+        draws: 10,
+        ..Default::default()
+    };
+    assert_eq!(sprt.get_llr(&all_draws), 0.0);
+
+    // Results well above elo1 pass, and well below elo0 fail
+    let winning = MatchResults {
+        wins: 600,
+        draws: 800,
+        losses: 400,
+        ..Default::default()
+    };
+    let llr = sprt.get_llr(&winning);
+    assert_eq!(sprt.get_status(llr), SprtStatus::AcceptH1);
+
+    let losing = MatchResults {
+        wins: 400,
+        draws: 800,
+        losses: 600,
+        ..Default::default()
+    };
+    let llr = sprt.get_llr(&losing);
+    assert_eq!(sprt.get_status(llr), SprtStatus::AcceptH0);
+
+    // Between the hypotheses it takes more games
+    let close = MatchResults {
+        wins: 31,
+        draws: 40,
+        losses: 30,
+        ..Default::default()
+    };
+    let llr = sprt.get_llr(&close);
+    assert_eq!(sprt.get_status(llr), SprtStatus::Continue);
+}
+
+#[test]
+fn match_runner_test() {
+    board_crab_lib::init();
+
+    let mut config = EngineConfig::new(env!("CARGO_BIN_EXE_board_crab_bin"));
+    config
+        .options
+        .push(("Threads".to_string(), "1".to_string()));
+    config.options.push(("Hash".to_string(), "1".to_string()));
+
+    let mut settings = MatchSettings::new();
+    settings.num_games = 2;
+    settings.nodes_per_move = Some(300);
+    settings.max_game_plies = 40;
+
+    // White is about to mate, so each engine wins once
+    let openings = [fen::load_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap()];
+    let results = run(&[config.clone(), config.clone()], &openings, &settings).unwrap();
+    assert_eq!(results.wins, 1);
+    assert_eq!(results.losses, 1);
+
+    let missing = EngineConfig::new("./no-such-engine");
+    assert!(run(&[missing.clone(), missing], &openings, &settings).is_err());
+
+    // SPRT stops once there's a result, and wide bounds only need a few games
+    settings.num_games = 10;
+    settings.sprt = Some(SprtSettings {
+        elo0: -1000.0,
+        elo1: 1000.0,
+        alpha: 0.4,
+        beta: 0.4,
+    });
+    let results = run(&[config.clone(), config], &openings, &settings).unwrap();
+    assert!(results.get_num_games() < 10);
+    assert_eq!(results.llrs.len(), results.get_num_games());
+}
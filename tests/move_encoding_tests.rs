@@ -0,0 +1,162 @@
+use board_crab_lib::board::*;
+use board_crab_lib::fen;
+use board_crab_lib::move_gen;
+
+const TEST_FENS: [&str; 5] = [
+    fen::FEN_START_POS,
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1",
+    "1r2k1rr/pppppppp/8/8/8/8/PPPPPPPP/1R2K1RR w GBgb - 0 1",
+];
+
+#[test]
+fn move_encode_decode_test() {
+    board_crab_lib::init();
+
+    for position_fen in TEST_FENS {
+        let board = fen::load_fen(position_fen).unwrap();
+        let mut moves = move_gen::MoveBuffer::new();
+        move_gen::generate_moves(&board, &mut moves);
+
+        for mv in moves.iter() {
+            let decoded_move = board
+                .decode_move(mv.encode())
+                .unwrap_or_else(|| panic!("Failed to decode {} in \"{}\"", mv, position_fen));
+
+            assert_eq!(decoded_move.encode(), mv.encode());
+            assert_eq!(format!("{decoded_move}"), format!("{mv}"));
+            assert!(
+                board.is_pseudo_legal(&decoded_move),
+                "Move {} should be pseudo-legal in \"{}\"",
+                mv,
+                position_fen
+            );
+        }
+    }
+}
+
+#[test]
+fn move_pseudo_legal_test() {
+    board_crab_lib::init();
+
+    // Moves from other positions are mostly not playable here
+    for position_fen in TEST_FENS {
+        let board = fen::load_fen(position_fen).unwrap();
+        let mut moves = move_gen::MoveBuffer::new();
+        move_gen::generate_moves(&board, &mut moves);
+
+        for other_fen in TEST_FENS {
+            let other_board = fen::load_fen(other_fen).unwrap();
+            let mut other_moves = move_gen::MoveBuffer::new();
+            move_gen::generate_moves(&other_board, &mut other_moves);
+
+            for other_move in other_moves.iter() {
+                let is_generated = moves.iter().any(|mv| mv.encode() == other_move.encode());
+                let is_pseudo_legal = board
+                    .decode_move(other_move.encode())
+                    .is_some_and(|mv| board.is_pseudo_legal(&mv));
+
+                let context = format!(
+                    "Move {} from \"{}\" in \"{}\"",
+                    other_move, other_fen, position_fen
+                );
+                if is_generated {
+                    assert!(is_pseudo_legal, "{}", context);
+                } else if is_pseudo_legal {
+                    // Only allowed if it's illegal because it leaves our king in check
+                    let mut next_board = board;
+                    next_board.do_move(&board.decode_move(other_move.encode()).unwrap());
+                    let king = next_board.pieces[board.turn_idx][PIECE_KING];
+                    assert!(
+                        (next_board.attacks[next_board.turn_idx] & king) != 0,
+                        "{}",
+                        context
+                    );
+                }
+            }
+        }
+    }
+
+    let board = fen::load_fen(fen::FEN_START_POS).unwrap();
+    assert!(board.decode_move(ENCODED_MOVE_NONE).is_none());
+}
+
+#[test]
+fn move_uci_test() {
+    board_crab_lib::init();
+
+    let find_castle = |board: &Board, is_right: bool| {
+        let mut moves = move_gen::MoveBuffer::new();
+        move_gen::generate_moves(board, &mut moves);
+        *moves
+            .iter()
+            .find(|mv| mv.has_flag(Move::FLAG_CASTLE) && (mv.to > mv.from) == is_right)
+            .unwrap()
+    };
+
+    // Castles are the king's move normally, and the king taking its rook in Chess960
+    let board = fen::load_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+    let castle_move = find_castle(&board, true);
+    assert_eq!(castle_move.to_string(), "e1g1");
+    assert_eq!(castle_move.to_uci(false), "e1g1");
+    assert_eq!(castle_move.to_uci(true), "e1h1");
+
+    // The king can already be where it castles to
+    let board = fen::load_fen("2k5/8/8/8/8/8/8/1RK3R1 w GB - 0 1").unwrap();
+    assert!(board.is_chess960);
+    assert_eq!(find_castle(&board, false).to_uci(true), "c1b1");
+    assert_eq!(find_castle(&board, true).to_uci(true), "c1g1");
+
+    let mut next_board = board;
+    next_board.do_move(&find_castle(&board, false));
+    assert_eq!(next_board.to_fen(), "2k5/8/8/8/8/8/8/2KR2R1 b - - 1 1");
+}
+
+#[test]
+fn parse_uci_move_test() {
+    board_crab_lib::init();
+
+    // Every legal move reads back from its UCI string
+    for position_fen in TEST_FENS {
+        let board = fen::load_fen(position_fen).unwrap();
+        let mut moves = move_gen::MoveBuffer::new();
+        move_gen::generate_moves(&board, &mut moves);
+
+        for mv in moves.iter() {
+            let move_str = mv.to_uci(board.is_chess960);
+            let parsed_move = board.parse_uci_move(&move_str).unwrap();
+            assert_eq!(parsed_move.encode(), mv.encode(), "Move: {}", move_str);
+            assert_eq!(parsed_move.flags, mv.flags, "Move: {}", move_str);
+        }
+    }
+
+    let board = Board::start_pos();
+    assert_eq!(board.parse_uci_move("g1f3").unwrap().to_string(), "g1f3");
+    for move_str in ["", "e2", "e2e4x", "e2e4q ", "i2i4", "e0e4", "E2E4", "e2-e4"] {
+        assert_eq!(
+            board.parse_uci_move(move_str).unwrap_err(),
+            MoveParseError::InvalidFormat(move_str.to_string())
+        );
+    }
+    for move_str in ["e2e5", "e7e5", "e2e4q", "e1g1"] {
+        assert_eq!(
+            board.parse_uci_move(move_str).unwrap_err(),
+            MoveParseError::IllegalMove(move_str.to_string())
+        );
+    }
+
+    // Castles depend on whether we're playing Chess960
+    let mut board = fen::load_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+    assert!(board
+        .parse_uci_move("e1g1")
+        .unwrap()
+        .has_flag(Move::FLAG_CASTLE));
+    assert!(board.parse_uci_move("e1h1").is_err());
+    board.is_chess960 = true;
+    assert!(board
+        .parse_uci_move("e1h1")
+        .unwrap()
+        .has_flag(Move::FLAG_CASTLE));
+    assert!(board.parse_uci_move("e1g1").is_err());
+}
@@ -0,0 +1,127 @@
+use board_crab_lib::board::*;
+use board_crab_lib::fen;
+use board_crab_lib::move_gen;
+use board_crab_lib::move_picker::MovePicker;
+use board_crab_lib::search::SearchInfo;
+
+const TEST_FENS: [&str; 5] = [
+    fen::FEN_START_POS,
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1",
+    "8/6k1/6p1/4NpPp/3PK2P/1r2P3/1br5/4RR2 w - f6 0 33",
+    "1rk3r1/8/8/8/8/8/q7/1RK3R1 w GBgb - 0 1",
+];
+
+fn get_moves(board: &Board) -> Vec<Move> {
+    let mut moves = move_gen::MoveBuffer::new();
+    move_gen::generate_moves(board, &mut moves);
+    moves.iter().copied().collect()
+}
+
+fn get_sorted_encoded(moves: &[Move]) -> Vec<EncodedMove> {
+    let mut encoded: Vec<EncodedMove> = moves.iter().map(|mv| mv.encode()).collect();
+    encoded.sort();
+    encoded
+}
+
+#[test]
+fn move_picker_test() {
+    board_crab_lib::init();
+
+    let search_info = SearchInfo::new();
+    for position_fen in TEST_FENS {
+        let board = fen::load_fen(position_fen).unwrap();
+        let moves = get_moves(&board);
+
+        // Killers don't have to be legal, or even quiet
+        let quiet_move = moves.iter().find(|mv| mv.is_quiet()).copied();
+        let other_board = fen::load_fen(TEST_FENS[1]).unwrap();
+        let killer_options = [
+            [ENCODED_MOVE_NONE; 2],
+            [
+                quiet_move.map_or(ENCODED_MOVE_NONE, |mv| mv.encode()),
+                moves[0].encode(),
+            ],
+            [
+                get_moves(&other_board)[5].encode(),
+                quiet_move.map_or(ENCODED_MOVE_NONE, |mv| mv.encode()),
+            ],
+        ];
+
+        for table_move in [None, Some(moves[0]), moves.last().copied(), quiet_move] {
+            for killers in killer_options {
+                let mut picker = MovePicker::new(table_move, killers);
+
+                // Starting over after a few moves shouldn't change anything
+                for _ in 0..3 {
+                    picker.next(&board, &search_info);
+                }
+                picker.reset();
+
+                let mut picked_moves = Vec::new();
+                while let Some(mv) = picker.next(&board, &search_info) {
+                    picked_moves.push(mv);
+                }
+
+                // Every legal move, each only once, with the table move first
+                assert_eq!(
+                    get_sorted_encoded(&picked_moves),
+                    get_sorted_encoded(&moves),
+                    "Picked moves differ in \"{}\"",
+                    position_fen
+                );
+                if let Some(table_move) = table_move {
+                    assert_eq!(picked_moves[0].encode(), table_move.encode());
+                }
+
+                // Picked moves should be the same as the generated ones, flags included
+                for mv in &picked_moves {
+                    let generated = moves.iter().find(|other| other.encode() == mv.encode());
+                    assert_eq!(generated.unwrap().flags, mv.flags);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn move_gen_kinds_test() {
+    board_crab_lib::init();
+
+    for position_fen in TEST_FENS {
+        let board = fen::load_fen(position_fen).unwrap();
+        let moves = get_moves(&board);
+
+        let mut captures = move_gen::MoveBuffer::new();
+        move_gen::generate_captures(&board, &mut captures);
+        assert!(captures.iter().all(|mv| !mv.is_quiet()));
+
+        let mut quiets = move_gen::MoveBuffer::new();
+        move_gen::generate_quiets(&board, &mut quiets);
+        assert!(quiets.iter().all(|mv| mv.is_quiet()));
+
+        let mut both: Vec<Move> = captures.iter().copied().collect();
+        both.extend(quiets.iter().copied());
+        assert_eq!(get_sorted_encoded(&both), get_sorted_encoded(&moves));
+
+        // Every legal move is legal, and moves from other positions usually aren't
+        assert!(moves.iter().all(|mv| move_gen::is_legal(&board, mv)));
+        for other_fen in TEST_FENS {
+            let other_board = fen::load_fen(other_fen).unwrap();
+            for mv in get_moves(&other_board) {
+                let Some(decoded) = board.decode_move(mv.encode()) else {
+                    continue;
+                };
+                if board.is_pseudo_legal(&decoded) {
+                    assert_eq!(
+                        move_gen::is_legal(&board, &decoded),
+                        moves.iter().any(|other| other.encode() == decoded.encode()),
+                        "Legality of {} in \"{}\"",
+                        decoded,
+                        position_fen
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use board_crab_lib::eval;
+use board_crab_lib::fen;
+use board_crab_lib::move_gen;
+use board_crab_lib::nnue;
+
+const NETWORK_NUM_VALUES: usize =
+    nnue::NUM_FEATURES * nnue::HIDDEN_SIZE + 3 * nnue::HIDDEN_SIZE + 1;
+
+// Writes a network with pseudo-random weights, so we don't need a real network file for testing
+fn write_test_network(path: &std::path::Path, num_values: usize) {
+    let mut rng_state: u64 = 0x2545F4914F6CDD1D;
+    let mut bytes = Vec::with_capacity(num_values * 2);
+    for _ in 0..num_values {
+        rng_state ^= rng_state << 13;
+        rng_state ^= rng_state >> 7;
+        rng_state ^= rng_state << 17;
+        let value = ((rng_state % 101) as i16) - 50;
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    std::fs::write(path, bytes).unwrap();
+}
+
+static LOAD_ONCE: std::sync::Once = std::sync::Once::new();
+
+fn load_test_network() {
+    board_crab_lib::init();
+    LOAD_ONCE.call_once(|| {
+        let path = std::env::temp_dir().join("board_crab_nnue_test_network.bin");
+        write_test_network(&path, NETWORK_NUM_VALUES);
+        let network = nnue::Network::load(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        nnue::set_network(Some(network));
+    });
+}
+
+#[test]
+fn nnue_load_invalid_test() {
+    let path = std::env::temp_dir().join("board_crab_nnue_invalid_test_network.bin");
+    write_test_network(&path, NETWORK_NUM_VALUES / 2);
+    let result = nnue::Network::load(path.to_str().unwrap());
+    std::fs::remove_file(&path).unwrap();
+    assert!(result.is_err());
+
+    assert!(nnue::Network::load("this/file/does/not/exist.bin").is_err());
+    assert!(nnue::Network::from_bytes(&[0; 64]).is_err());
+}
+
+#[test]
+fn nnue_accumulator_continuity_test() {
+    load_test_network();
+    let network = nnue::get_network().unwrap();
+
+    // Covers castling, en passant and promotions
+    let test_fens = [
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    ];
+    for position_fen in test_fens {
+        let board = fen::load_fen(position_fen).unwrap();
+        let mut moves = move_gen::MoveBuffer::new();
+        move_gen::generate_moves(&board, &mut moves);
+
+        for mv in moves.iter() {
+            let mut next_board = board;
+            next_board.do_move(mv);
+
+            let mut refreshed_accumulator = nnue::Accumulator::new();
+            refreshed_accumulator.refresh(network, &next_board);
+            assert_eq!(
+                next_board.accumulator.values, refreshed_accumulator.values,
+                "Accumulator mismatch after {} in \"{}\"",
+                mv, position_fen
+            );
+
+            // Undoing the move should give back the old values
+            let mut undo_board = board;
+            let undo = undo_board.make_move(mv);
+            undo_board.undo_move(mv, &undo);
+            assert_eq!(
+                undo_board.accumulator.values, board.accumulator.values,
+                "Accumulator mismatch after undoing {} in \"{}\"",
+                mv, position_fen
+            );
+        }
+    }
+}
+
+#[test]
+fn nnue_eval_symmetry_test() {
+    load_test_network();
+
+    // Same position with the colors swapped
+    let board =
+        fen::load_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3").unwrap();
+    let mirrored_board =
+        fen::load_fen("rnbqkb1r/pppp1ppp/5n2/4p3/4P3/2N5/PPPP1PPP/R1BQKBNR b KQkq - 2 3").unwrap();
+    assert_eq!(eval::eval_board(&board), eval::eval_board(&mirrored_board));
+}
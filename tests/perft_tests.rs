@@ -1,10 +1,12 @@
+use board_crab_lib::board::*;
 use board_crab_lib::fen;
-use board_crab_lib::search;
+use board_crab_lib::move_gen;
+use board_crab_lib::perft;
 
 fn do_test(name: &str, position_fen: &str, depth: usize, target_node_count: usize) {
     board_crab_lib::init();
     let board = fen::load_fen(position_fen).unwrap();
-    let perft_count = search::perft(&board, depth as u8, false);
+    let perft_count = perft::perft(&board, depth as u8, false);
 
     if perft_count != target_node_count {
         // Test failed
@@ -142,3 +144,203 @@ fn super_perft_test() {
         }
     }
 }
+
+// See: https://www.chessprogramming.org/Chess960_Perft_Results
+#[test]
+fn chess960_perft_test() {
+    let test_entries = [
+        (
+            "Chess960 1",
+            "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+            vec![21, 528, 12189, 326672],
+        ),
+        (
+            "Chess960 2",
+            "2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9",
+            vec![21, 807, 18002, 667366],
+        ),
+        (
+            "Chess960 3",
+            "b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9",
+            vec![20, 479, 10471, 273318],
+        ),
+        (
+            "Chess960 4",
+            "qbbnnrkr/2pp2pp/p7/1p2pp2/8/P3PP2/1PPP1KPP/QBBNNR1R w hf - 0 9",
+            vec![22, 593, 13440, 382958],
+        ),
+        (
+            // Castling would uncover an attack from the rook on a1
+            "Rook-shielded castle",
+            "4k3/8/8/8/8/8/8/rRK5 w B - 0 1",
+            vec![5, 57, 821, 12489],
+        ),
+    ];
+
+    for (name, fen_str, target_perft_results) in test_entries {
+        for i in 0..target_perft_results.len() {
+            do_test(name, fen_str, i + 1, target_perft_results[i]);
+        }
+    }
+}
+
+#[test]
+fn threaded_perft_test() {
+    board_crab_lib::init();
+
+    let board =
+        fen::load_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1")
+            .unwrap();
+
+    // More threads than root moves too
+    for num_threads in [2, 4, 64] {
+        let mut settings = perft::PerftSettings::new();
+        settings.num_threads = num_threads;
+        assert_eq!(perft::perft_with_settings(&board, 3, &settings), 97862);
+        assert_eq!(perft::perft_with_settings(&board, 0, &settings), 1);
+    }
+}
+
+#[test]
+fn hashed_perft_test() {
+    board_crab_lib::init();
+
+    // A tiny table, so entries get replaced a lot
+    let mut settings = perft::PerftSettings::new();
+    settings.num_threads = 2;
+    settings.table_size_mbs = 1;
+
+    for (position_fen, depth, target_node_count) in [
+        (fen::FEN_START_POS, 5, 4865609),
+        (
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            4,
+            4085603,
+        ),
+        ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 6, 11030083),
+    ] {
+        let board = fen::load_fen(position_fen).unwrap();
+        assert_eq!(
+            perft::perft_with_settings(&board, depth, &settings),
+            target_node_count,
+            "fen: \"{}\"",
+            position_fen
+        );
+    }
+}
+
+// Positions with castling, promotions, en passant and checks
+const MOVE_GEN_TEST_FENS: [&str; 7] = [
+    fen::FEN_START_POS,
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+    "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1",
+    "8/6k1/6p1/4NpPp/3PK2P/1r2P3/1br5/4RR2 w - f6 0 33",
+    "1rk3r1/8/8/8/8/8/q7/1RK3R1 w GBgb - 0 1",
+    "k3r3/8/8/8/8/3n4/8/R3K2R w KQ - 0 1", // Double check
+];
+
+#[test]
+fn count_moves_test() {
+    board_crab_lib::init();
+
+    for position_fen in MOVE_GEN_TEST_FENS {
+        let board = fen::load_fen(position_fen).unwrap();
+        let mut moves = move_gen::MoveBuffer::new();
+        move_gen::generate_moves(&board, &mut moves);
+
+        for mv in moves.iter() {
+            let mut next_board = board;
+            next_board.do_move(mv);
+
+            let mut next_moves = move_gen::MoveBuffer::new();
+            move_gen::generate_moves(&next_board, &mut next_moves);
+            assert_eq!(move_gen::count_moves(&next_board), next_moves.len());
+        }
+        assert_eq!(move_gen::count_moves(&board), moves.len());
+    }
+}
+
+#[test]
+fn legal_moves_test() {
+    board_crab_lib::init();
+
+    for position_fen in MOVE_GEN_TEST_FENS {
+        let board = fen::load_fen(position_fen).unwrap();
+        let mut moves = move_gen::MoveBuffer::new();
+        move_gen::generate_moves(&board, &mut moves);
+
+        let mut boards = vec![board];
+        for mv in moves.iter() {
+            let mut next_board = board;
+            next_board.do_move(mv);
+            boards.push(next_board);
+        }
+
+        // Should be the same moves in the same order, with the same flags
+        for board in boards {
+            let mut moves = move_gen::MoveBuffer::new();
+            move_gen::generate_moves(&board, &mut moves);
+            let legal_moves: Vec<(EncodedMove, u8)> = board
+                .legal_moves()
+                .map(|mv| (mv.encode(), mv.flags))
+                .collect();
+            let buffer_moves: Vec<(EncodedMove, u8)> =
+                moves.iter().map(|mv| (mv.encode(), mv.flags)).collect();
+            assert_eq!(
+                legal_moves,
+                buffer_moves,
+                "Moves differ in \"{}\"",
+                fen::make_fen(&board)
+            );
+        }
+    }
+}
+
+#[test]
+fn evasions_test() {
+    board_crab_lib::init();
+
+    let mut num_checked = 0;
+    for position_fen in MOVE_GEN_TEST_FENS {
+        let board = fen::load_fen(position_fen).unwrap();
+        let mut moves = move_gen::MoveBuffer::new();
+        move_gen::generate_moves(&board, &mut moves);
+
+        // Look two moves in too, to find more checks
+        let mut boards = vec![board];
+        for mv in moves.iter() {
+            let mut next_board = board;
+            next_board.do_move(mv);
+            boards.push(next_board);
+
+            let mut next_moves = move_gen::MoveBuffer::new();
+            move_gen::generate_moves(&next_board, &mut next_moves);
+            for next_move in next_moves.iter() {
+                let mut next_next_board = next_board;
+                next_next_board.do_move(next_move);
+                boards.push(next_next_board);
+            }
+        }
+
+        // Should be exactly the same as the normal moves
+        for board in boards.into_iter().filter(|board| board.checkers != 0) {
+            let mut moves = move_gen::MoveBuffer::new();
+            move_gen::generate_moves(&board, &mut moves);
+            let mut evasions = move_gen::MoveBuffer::new();
+            move_gen::generate_evasions(&board, &mut evasions);
+
+            let get_moves = |moves: &move_gen::MoveBuffer| -> Vec<(EncodedMove, u8)> {
+                moves.iter().map(|mv| (mv.encode(), mv.flags)).collect()
+            };
+            assert_eq!(
+                get_moves(&evasions),
+                get_moves(&moves),
+                "Evasions differ in \"{}\"",
+                fen::make_fen(&board)
+            );
+            num_checked += 1;
+        }
+    }
+    assert!(num_checked > 100);
+}
@@ -0,0 +1,156 @@
+use board_crab_lib::fen;
+use board_crab_lib::pgn;
+use board_crab_lib::pgn::{MoveAnnotation, PgnGame};
+
+fn get_move_strs(game: &PgnGame) -> Vec<String> {
+    game.moves.iter().map(|mv| mv.to_string()).collect()
+}
+
+#[test]
+fn pgn_import_test() {
+    board_crab_lib::init();
+
+    let pgn_str = r#"
+[Event "Annotated"]
+[White "Someone \"Quoted\""]
+[Result "1-0"]
+
+{Opening comment} 1. e4 $1 e5 {Solid} {reply} 2. Nf3!? (2. Bc4 {Skipped} Nf6) Nc6
+; Line comment
+3. Bb5 a6?? 1-0
+
+[Event "From a position"]
+[FEN "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1"]
+[Result "1/2-1/2"]
+
+1. e4 Kd7 2. Kd2 Ke6 1/2-1/2
+"#;
+
+    let games = pgn::parse_games(pgn_str).unwrap();
+    assert_eq!(games.len(), 2);
+
+    let game = &games[0];
+    assert_eq!(game.get_tag("White"), Some("Someone \"Quoted\""));
+    assert_eq!(game.get_tag("Round"), None);
+    assert_eq!(game.result, Some(1.0));
+    assert_eq!(game.comment.as_deref(), Some("Opening comment"));
+
+    assert_eq!(
+        get_move_strs(game),
+        ["e2e4", "e7e5", "g1f3", "b8c6", "f1b5", "a7a6"]
+    );
+
+    assert_eq!(game.annotations.len(), game.moves.len());
+    assert_eq!(
+        game.annotations[0],
+        MoveAnnotation {
+            nags: vec![1],
+            comment: None
+        }
+    );
+    assert_eq!(game.annotations[1].comment.as_deref(), Some("Solid reply"));
+    assert_eq!(game.annotations[2].nags, vec![5]);
+    assert_eq!(game.annotations[3].comment.as_deref(), Some("Line comment"));
+    assert_eq!(game.annotations[5].nags, vec![4]);
+
+    // Positions follow the moves
+    let boards = game.get_boards();
+    assert_eq!(boards.len(), game.moves.len() + 1);
+    assert_eq!(
+        fen::make_fen(&boards[2]),
+        "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2"
+    );
+    assert_eq!(boards.last().unwrap().hash, game.get_final_board().hash);
+
+    // FEN tags set the start position
+    let game = &games[1];
+    assert_eq!(
+        game.start_board.hash,
+        fen::load_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1")
+            .unwrap()
+            .hash
+    );
+    assert_eq!(game.moves.len(), 4);
+    assert_eq!(game.result, Some(0.5));
+}
+
+#[test]
+fn pgn_invalid_test() {
+    board_crab_lib::init();
+
+    assert!(pgn::parse_games("1. e5 *").is_err());
+    assert!(pgn::parse_games("[Event]\n1. e4 *").is_err());
+    assert!(pgn::parse_games("1. e4 $x *").is_err());
+    assert!(pgn::parse_games("").unwrap().is_empty());
+}
+
+#[test]
+fn pgn_export_test() {
+    board_crab_lib::init();
+
+    let pgn_str = r#"
+[Event "Export \"test\""]
+[Annotator "Someone"]
+[Result "0-1"]
+
+{Start} 1. f3 e5 {[%eval 0.50]} 2. g4 $2 Qh4# 0-1
+"#;
+
+    let game = &pgn::parse_games(pgn_str).unwrap()[0];
+    let exported = game.to_pgn();
+    assert_eq!(
+        exported,
+        r#"[Event "Export \"test\""]
+[Site "?"]
+[Date "????.??.??"]
+[Round "?"]
+[White "?"]
+[Black "?"]
+[Result "0-1"]
+[Annotator "Someone"]
+
+{Start} 1. f3 e5 {[%eval 0.50]} 2. g4 $2 Qh4# 0-1
+"#
+    );
+
+    // Exported games read back the same
+    let reimported = &pgn::parse_games(&exported).unwrap()[0];
+    assert_eq!(reimported.tags.len(), 8);
+    assert_eq!(reimported.get_tag("Event"), Some("Export \"test\""));
+    assert_eq!(get_move_strs(reimported), get_move_strs(game));
+    assert_eq!(reimported.annotations, game.annotations);
+    assert_eq!(reimported.comment, game.comment);
+    assert_eq!(reimported.result, game.result);
+
+    // Games from other positions get a FEN tag, and black's first move needs its own number
+    let mut game = PgnGame::new(fen::load_fen("4k3/8/8/8/8/8/4P3/4K3 b - - 0 30").unwrap());
+    let mv = game.start_board.parse_san("Kd7").unwrap();
+    game.moves.push(mv);
+    game.annotations.push(MoveAnnotation::default());
+    let exported = game.to_pgn();
+    assert!(exported.contains("[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/4P3/4K3 b - - 0 30\"]\n"));
+    assert!(exported.ends_with("\n30... Kd7 *\n"));
+    assert_eq!(
+        pgn::parse_games(&exported).unwrap()[0].start_board.hash,
+        game.start_board.hash
+    );
+
+    // Long games are wrapped
+    let mut game = PgnGame::new(fen::load_fen(fen::FEN_START_POS).unwrap());
+    for _ in 0..10 {
+        for san in ["Nf3", "Nf6", "Ng1", "Ng8"] {
+            let mv = game.get_final_board().parse_san(san).unwrap();
+            game.moves.push(mv);
+            game.annotations.push(MoveAnnotation::default());
+        }
+    }
+    let exported = game.to_pgn();
+    assert!(exported.lines().all(|line| line.len() <= 80));
+    assert_eq!(
+        get_move_strs(&pgn::parse_games(&exported).unwrap()[0]),
+        get_move_strs(&game)
+    );
+
+    assert_eq!(pgn::make_eval_comment(35, Some(12)), "[%eval 0.35,12]");
+    assert_eq!(pgn::make_eval_comment(-29_995, None), "[%eval #-3]");
+}
@@ -0,0 +1,128 @@
+use board_crab_lib::board::*;
+use board_crab_lib::fen;
+use board_crab_lib::move_gen;
+
+const TEST_FENS: [&str; 4] = [
+    fen::FEN_START_POS,
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1",
+];
+
+fn get_moves(board: &Board) -> Vec<Move> {
+    let mut moves = move_gen::MoveBuffer::new();
+    move_gen::generate_moves(board, &mut moves);
+    moves.iter().copied().collect()
+}
+
+// Formats the move given in UCI notation as SAN
+fn get_san(position_fen: &str, move_str: &str) -> String {
+    let board = fen::load_fen(position_fen).unwrap();
+    let mv = get_moves(&board)
+        .into_iter()
+        .find(|mv| format!("{mv}") == move_str)
+        .unwrap_or_else(|| panic!("No move {} in \"{}\"", move_str, position_fen));
+    mv.to_san(&board)
+}
+
+#[test]
+fn san_round_trip_test() {
+    board_crab_lib::init();
+
+    for position_fen in TEST_FENS {
+        let board = fen::load_fen(position_fen).unwrap();
+
+        // Also check every position one move in, to cover more disambiguation and checks
+        let mut boards = vec![board];
+        for mv in get_moves(&board) {
+            let mut next_board = board;
+            next_board.do_move(&mv);
+            boards.push(next_board);
+        }
+
+        for board in boards {
+            let moves = get_moves(&board);
+            let sans: Vec<String> = moves.iter().map(|mv| mv.to_san(&board)).collect();
+
+            for (mv, san) in moves.iter().zip(&sans) {
+                let parsed_move = board.parse_san(san).unwrap_or_else(|| {
+                    panic!("Failed to parse {} in \"{}\"", san, fen::make_fen(&board))
+                });
+                assert_eq!(parsed_move.encode(), mv.encode(), "SAN: {}", san);
+
+                // SAN should be unique within a position
+                assert_eq!(sans.iter().filter(|other| *other == san).count(), 1);
+            }
+        }
+    }
+}
+
+#[test]
+fn san_format_test() {
+    board_crab_lib::init();
+
+    assert_eq!(get_san(fen::FEN_START_POS, "e2e4"), "e4");
+    assert_eq!(get_san(fen::FEN_START_POS, "g1f3"), "Nf3");
+
+    // Castling
+    let castle_fen = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+    assert_eq!(get_san(castle_fen, "e1g1"), "O-O");
+    assert_eq!(get_san(castle_fen, "e1c1"), "O-O-O");
+
+    // Disambiguation by column, row, and both
+    assert_eq!(get_san("4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1", "b1d2"), "Nbd2");
+    assert_eq!(get_san("4k3/R7/8/8/8/8/8/R3K3 w - - 0 1", "a1a4"), "R1a4");
+    assert_eq!(
+        get_san("k7/8/8/8/8/2Q1Q3/7K/4Q3 w - - 0 1", "e3c1"),
+        "Qe3c1"
+    );
+
+    // Captures, promotions, and checks
+    assert_eq!(get_san("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6"), "exd6");
+    assert_eq!(get_san("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1", "b7b8q"), "b8=Q+");
+    assert_eq!(
+        get_san("2r1k3/1P6/8/8/8/8/8/4K3 w - - 0 1", "b7c8n"),
+        "bxc8=N"
+    );
+    assert_eq!(
+        get_san(
+            "rnbqkbnr/pppp1ppp/8/4p3/6P1/5P2/PPPPP2P/RNBQKBNR b KQkq g3 0 2",
+            "d8h4"
+        ),
+        "Qh4#"
+    );
+}
+
+#[test]
+fn san_parse_test() {
+    board_crab_lib::init();
+
+    let castle_board = fen::load_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+    assert_eq!(
+        format!("{}", castle_board.parse_san("0-0").unwrap()),
+        "e1g1"
+    );
+    assert_eq!(
+        format!("{}", castle_board.parse_san("O-O-O+").unwrap()),
+        "e1c1"
+    );
+
+    let promotion_board = fen::load_fen("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+    assert_eq!(
+        format!("{}", promotion_board.parse_san("b8Q").unwrap()),
+        "b7b8q"
+    );
+    assert_eq!(
+        format!("{}", promotion_board.parse_san("b8=r").unwrap()),
+        "b7b8r"
+    );
+    assert!(promotion_board.parse_san("b8").is_none());
+
+    // Ambiguous and impossible moves
+    let knights_board = fen::load_fen("4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1").unwrap();
+    assert!(knights_board.parse_san("Nd2").is_none());
+    assert!(knights_board.parse_san("Nbd2!?").is_some());
+    assert!(knights_board.parse_san("Nc4").is_none());
+    assert!(knights_board.parse_san("").is_none());
+    assert!(knights_board.parse_san("Zd2").is_none());
+}
@@ -1,4 +1,4 @@
-use board_crab_lib::eval::Value;
+use board_crab_lib::eval;
 use board_crab_lib::fen;
 use board_crab_lib::search;
 use board_crab_lib::thread_flag::ThreadFlag;
@@ -17,6 +17,7 @@ fn search_consistency_test() {
         .collect::<Vec<&str>>();
 
     let mut table = transpos::Table::new(4); // Small for low depth
+    let limits = search::SearchLimits::new();
 
     let mut total_move_matches: usize = 0;
     let mut total_positions: usize = 0;
@@ -27,10 +28,10 @@ fn search_consistency_test() {
         }
 
         let board = fen::load_fen(cur_fen).unwrap();
-        let best_move_a = search::search(&board, &mut table, MAX_DEPTH - 1, None, None, None)
+        let best_move_a = search::search(&board, &mut table, MAX_DEPTH - 1, None, &limits)
             .1
             .root_best_move_idx;
-        let best_move_b = search::search(&board, &mut table, MAX_DEPTH, None, None, None)
+        let best_move_b = search::search(&board, &mut table, MAX_DEPTH, None, &limits)
             .1
             .root_best_move_idx;
 
@@ -40,7 +41,7 @@ fn search_consistency_test() {
         total_positions += 1;
     }
 
-    let consistent_frac = (total_move_matches as Value) / (total_positions as Value);
+    let consistent_frac = (total_move_matches as f32) / (total_positions as f32);
     println!("Search consistency: {}%", consistent_frac * 100.0);
 
     if consistent_frac < 0.3 {
@@ -50,3 +51,146 @@ fn search_consistency_test() {
         );
     }
 }
+
+// Excluding the best root move should give a different move that's no better
+#[test]
+fn search_excluding_test() {
+    board_crab_lib::init();
+
+    const DEPTH: u8 = 3;
+
+    let board = fen::load_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+    let table = transpos::Table::new(4);
+    let limits = search::SearchLimits::new();
+
+    let (best_eval, best_info) = search::search(&board, &table, DEPTH, None, &limits);
+    let mut search_info = search::SearchInfo::new();
+    search_info.root_excluded_moves = vec![best_info.root_best_move_idx];
+    let (second_eval, second_info) =
+        search::search_with_info(&board, &table, DEPTH, None, search_info, &limits);
+
+    assert_ne!(best_info.root_best_move_idx, second_info.root_best_move_idx);
+    assert!(second_eval <= best_eval);
+}
+
+// Mates should be reported as the exact amount of moves until mate
+#[test]
+fn search_mate_distance_test() {
+    board_crab_lib::init();
+
+    let board =
+        fen::load_fen("r2qkb1r/pp2nppp/3p4/2pNN1B1/2BnP3/3P4/PPP2PPP/R2bK2R w KQkq - 1 1").unwrap();
+    let table = transpos::Table::new(4);
+    let limits = search::SearchLimits::new();
+
+    // Search a few depths so the mate is found again through the table
+    for depth in 5..=7 {
+        let (eval, _) = search::search(&board, &table, depth, None, &limits);
+        assert_eq!(eval::get_mate_moves(eval), Some(2));
+    }
+}
+
+// Every root move should be searched, and the best one should have the root's eval
+#[test]
+fn search_root_moves_test() {
+    board_crab_lib::init();
+
+    let board = fen::load_fen(fen::FEN_START_POS).unwrap();
+    let table = transpos::Table::new(4);
+    let limits = search::SearchLimits::new();
+
+    let mut search_info = search::SearchInfo::new();
+    for depth in 1..=4 {
+        let root_moves = std::mem::take(&mut search_info.root_moves);
+        search_info = search::SearchInfo::new();
+        search_info.root_moves = root_moves;
+
+        let eval;
+        (eval, search_info) =
+            search::search_with_info(&board, &table, depth, None, search_info, &limits);
+
+        assert_eq!(search_info.root_moves.len(), 20);
+        let best_move = board
+            .legal_moves()
+            .nth(search_info.root_best_move_idx as usize)
+            .unwrap();
+        let best_root_move = search_info
+            .root_moves
+            .iter()
+            .find(|root_move| root_move.mv.encode() == best_move.encode())
+            .unwrap();
+        assert_eq!(best_root_move.eval, eval);
+        assert!(search_info
+            .root_moves
+            .iter()
+            .all(|root_move| root_move.eval <= eval));
+
+        let root_move_nodes: usize = search_info
+            .root_moves
+            .iter()
+            .map(|root_move| root_move.nodes)
+            .sum();
+        assert!(root_move_nodes < search_info.total_nodes);
+    }
+}
+
+// The PV comes from the search itself, so it should lead all the way to the mate
+#[test]
+fn search_pv_test() {
+    board_crab_lib::init();
+
+    let board =
+        fen::load_fen("r2qkb1r/pp2nppp/3p4/2pNN1B1/2BnP3/3P4/PPP2PPP/R2bK2R w KQkq - 1 1").unwrap();
+    let table = transpos::Table::new(4);
+    let limits = search::SearchLimits::new();
+
+    let (_, search_info) = search::search(&board, &table, 5, None, &limits);
+    let pv = search_info.get_pv();
+    assert_eq!(pv.len(), 3);
+
+    let mut board = board;
+    for mv in pv {
+        assert!(board
+            .legal_moves()
+            .any(|other| other.encode() == mv.encode()));
+        board.do_move(mv);
+    }
+    assert_eq!(
+        search::get_game_result(&board),
+        search::GameResult::Checkmate
+    );
+}
+
+// A bad table entry (like from a hash collision) shouldn't crash anything
+#[test]
+fn determine_pv_test() {
+    board_crab_lib::init();
+
+    let board = fen::load_fen(fen::FEN_START_POS).unwrap();
+    let table = transpos::Table::new(1);
+    assert_eq!(
+        search::determine_pv(board, &table).unwrap_err(),
+        search::PvError::NotInTable
+    );
+
+    // A rook move that can't be played at the start
+    let other_board = fen::load_fen("k7/8/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+    let illegal_move = other_board.parse_uci_move("a1a8").unwrap();
+    table.set(
+        board.hash,
+        0,
+        0,
+        illegal_move.encode(),
+        1,
+        transpos::EntryType::Exact,
+    );
+    assert_eq!(
+        search::determine_pv(board, &table).unwrap_err(),
+        search::PvError::IllegalMove
+    );
+
+    let limits = search::SearchLimits::new();
+    let (_, search_info) = search::search(&board, &table, 4, None, &limits);
+    let pv = search::determine_pv(board, &table).unwrap();
+    assert_eq!(pv[0].encode(), search_info.get_pv()[0].encode());
+}
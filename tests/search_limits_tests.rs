@@ -0,0 +1,42 @@
+use board_crab_lib::board::*;
+use board_crab_lib::eval;
+use board_crab_lib::search;
+use board_crab_lib::search::SearchLimits;
+use board_crab_lib::transpos;
+use std::time::{Duration, Instant};
+
+#[test]
+fn search_limits_test() {
+    board_crab_lib::init();
+
+    let board = Board::start_pos();
+    let table = transpos::Table::new(1);
+
+    let result = search::search_iterative(&board, &table, &[], &SearchLimits::new().depth(3));
+    assert_eq!(result.unwrap().depth, 3);
+
+    let result = search::search_iterative(&board, &table, &[], &SearchLimits::new().nodes(5000));
+    assert!(result.unwrap().total_nodes <= 5000);
+
+    // The move time starts counting once the search does
+    let limits = SearchLimits::new().move_time(Duration::from_millis(100));
+    std::thread::sleep(Duration::from_millis(200));
+    let start_time = Instant::now();
+    let result = search::search_iterative(&board, &table, &[], &limits).unwrap();
+    assert!(result.depth > 1);
+    assert!(start_time.elapsed() < Duration::from_secs(1));
+
+    // The earlier of the move time and stop time wins
+    let start_time = Instant::now();
+    let limits = SearchLimits::new()
+        .move_time(Duration::from_secs(5))
+        .stop_time(start_time + Duration::from_secs(1))
+        .start(start_time);
+    assert_eq!(limits.stop_time, Some(start_time + Duration::from_secs(1)));
+    assert_eq!(limits.move_time, None);
+
+    let limits = SearchLimits::new().mate(2);
+    assert!(limits.is_mate_found(eval::VALUE_CHECKMATE - 3));
+    assert!(!limits.is_mate_found(eval::VALUE_CHECKMATE - 5));
+    assert!(!SearchLimits::new().is_mate_found(eval::VALUE_CHECKMATE - 1));
+}
@@ -0,0 +1,85 @@
+use board_crab_lib::board::*;
+use board_crab_lib::fen;
+use board_crab_lib::search;
+use board_crab_lib::search::{SearchListener, SearchProgress};
+use board_crab_lib::transpos;
+
+#[derive(Default)]
+struct RecordingListener {
+    pv_depths: Vec<u8>,
+    completed_depths: Vec<u8>,
+    best_move: Option<Move>,
+    game_result: Option<search::GameResult>,
+}
+
+impl SearchListener for RecordingListener {
+    fn on_new_pv(&mut self, board: &Board, progress: &SearchProgress) {
+        // Every PV should be playable from the root
+        let mut board = *board;
+        for mv in &progress.pv {
+            assert!(board
+                .legal_moves()
+                .any(|other| other.encode() == mv.encode()));
+            board.do_move(mv);
+        }
+
+        self.pv_depths.push(progress.depth);
+    }
+
+    fn on_depth_complete(&mut self, _board: &Board, progress: &SearchProgress) {
+        self.completed_depths.push(progress.depth);
+    }
+
+    fn on_finish(&mut self, _board: &Board, best_move: Move, _ponder_move: Option<Move>) {
+        assert!(self.best_move.is_none());
+        self.best_move = Some(best_move);
+    }
+
+    fn on_no_legal_moves(&mut self, _board: &Board, game_result: search::GameResult) {
+        self.game_result = Some(game_result);
+    }
+}
+
+#[test]
+fn search_listener_test() {
+    board_crab_lib::init();
+
+    let board = Board::start_pos();
+    let table = transpos::Table::new(1);
+    let mut listener = RecordingListener::default();
+    let result = search::search_iterative_with_listener(
+        &board,
+        &table,
+        &[],
+        &search::SearchLimits::new().depth(4),
+        &mut listener,
+    )
+    .unwrap();
+
+    assert_eq!(listener.pv_depths, [1, 2, 3, 4]);
+    assert_eq!(listener.completed_depths, [1, 2, 3, 4]);
+    assert_eq!(
+        listener.best_move.map(|mv| mv.encode()),
+        Some(result.best_move.encode())
+    );
+}
+
+#[test]
+fn search_listener_no_legal_moves_test() {
+    board_crab_lib::init();
+
+    let board = fen::load_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+    let table = transpos::Table::new(1);
+    let mut listener = RecordingListener::default();
+    let result = search::search_iterative_with_listener(
+        &board,
+        &table,
+        &[],
+        &search::SearchLimits::new().depth(4),
+        &mut listener,
+    );
+
+    assert!(result.is_none());
+    assert!(listener.best_move.is_none());
+    assert_eq!(listener.game_result, Some(search::GameResult::Stalemate));
+}
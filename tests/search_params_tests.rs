@@ -0,0 +1,40 @@
+use board_crab_lib::eval_params::EvalParams;
+use board_crab_lib::search_params::SearchTuneParams;
+use std::collections::HashSet;
+
+#[test]
+fn search_params_options_test() {
+    let mut params = SearchTuneParams::DEFAULT;
+    let mut names = HashSet::new();
+    params.for_each_option(&mut |name, value, value_min, value_max| {
+        assert!(names.insert(name.to_string()), "Duplicate option {}", name);
+        assert!(
+            *value >= value_min && *value <= value_max,
+            "{} out of range",
+            name
+        );
+    });
+
+    // Arrays get an option per value
+    assert!(names.contains("reverse_futility_margin"));
+    assert!(names.contains("futility_margins_2"));
+    assert!(!names.contains("futility_margins"));
+
+    // Options set the values they visit
+    params.for_each_option(&mut |name, value, _, _| {
+        if name == "futility_margins_1" {
+            *value = 123;
+        }
+    });
+    assert_eq!(params.futility_margins, [0, 123, 250]);
+
+    // Eval weight names don't clash with the search params
+    let mut eval_params = EvalParams::DEFAULT;
+    eval_params.for_each_named_weight(&mut |name, weight| {
+        assert!(names.insert(name.to_string()), "Duplicate option {}", name);
+        if name == "bishop_pair_1" {
+            *weight = 1.5;
+        }
+    });
+    assert_eq!(eval_params.bishop_pair[1], 1.5);
+}
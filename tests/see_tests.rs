@@ -0,0 +1,66 @@
+use board_crab_lib::board;
+use board_crab_lib::eval::Value;
+use board_crab_lib::fen;
+use board_crab_lib::move_gen;
+
+fn do_test(name: &str, position_fen: &str, move_str: &str, target_see: Value) {
+    board_crab_lib::init();
+    let board = fen::load_fen(position_fen).unwrap();
+
+    let mut moves = move_gen::MoveBuffer::new();
+    move_gen::generate_moves(&board, &mut moves);
+    let mv = moves
+        .iter()
+        .find(|mv| format!("{mv}") == move_str)
+        .unwrap_or_else(|| panic!("Move \"{}\" not found in position \"{}\"", move_str, name));
+
+    let see = board::see(&board, mv);
+    if see != target_see {
+        // Test failed
+        panic!(
+            "Failed position \"{}\" with move {} (got: {}, target: {}), fen: \"{}\"",
+            name, move_str, see, target_see, position_fen
+        );
+    }
+}
+
+#[test]
+fn see_test() {
+    let test_entries = [
+        (
+            "free pawn",
+            "1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1",
+            "e1e5",
+            100,
+        ),
+        (
+            "x-ray exchange",
+            "1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1",
+            "d3e5",
+            -200,
+        ),
+        (
+            "queen takes defended pawn",
+            "4k3/8/3p4/4p3/8/8/8/4QK2 w - - 0 1",
+            "e1e5",
+            -800,
+        ),
+        (
+            "pawn takes defended knight",
+            "4k3/8/3p4/4n3/3P4/8/8/4K3 w - - 0 1",
+            "d4e5",
+            200,
+        ),
+        ("en passant", "7k/8/8/3pP3/8/8/8/K7 w - d6 0 1", "e5d6", 100),
+        (
+            "king can't recapture into defense",
+            "4k3/8/8/8/b7/8/3r4/3RK3 b - - 0 1",
+            "d2d1",
+            500,
+        ),
+    ];
+
+    for entry in test_entries {
+        do_test(entry.0, entry.1, entry.2, entry.3);
+    }
+}
@@ -0,0 +1,54 @@
+#![cfg(feature = "serde")]
+
+use board_crab_lib::board::*;
+use board_crab_lib::eval;
+use board_crab_lib::fen;
+use board_crab_lib::search;
+use board_crab_lib::transpos;
+
+#[test]
+fn serde_board_test() {
+    board_crab_lib::init();
+
+    let fen_str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+    let board = fen::load_fen(fen_str).unwrap();
+    let json = serde_json::to_string(&board).unwrap();
+    assert_eq!(json, format!("\"{}\"", fen_str));
+
+    let loaded: Board = serde_json::from_str(&json).unwrap();
+    assert_eq!(loaded.hash, board.hash);
+    assert_eq!(eval::eval_board(&loaded), eval::eval_board(&board));
+
+    assert!(serde_json::from_str::<Board>("\"not a fen\"").is_err());
+    assert!(serde_json::from_str::<Board>("5").is_err());
+}
+
+#[test]
+fn serde_results_test() {
+    board_crab_lib::init();
+
+    let board = fen::load_fen(fen::FEN_START_POS).unwrap();
+    let mv = board.parse_uci_move("e2e4").unwrap();
+    assert_eq!(serde_json::to_string(&mv).unwrap(), "\"e2e4\"");
+
+    let table = transpos::Table::new(1);
+    let limits = search::SearchLimits::new().depth(2);
+    let result = search::search_iterative(&board, &table, &[], &limits).unwrap();
+    let json = serde_json::to_value(result).unwrap();
+    assert_eq!(json["best_move"], result.best_move.to_string());
+    assert_eq!(json["depth"], 2);
+
+    let game_result = search::GameResult::Stalemate;
+    let json = serde_json::to_string(&game_result).unwrap();
+    assert_eq!(
+        serde_json::from_str::<search::GameResult>(&json).unwrap(),
+        game_result
+    );
+
+    let trace = eval::explain(&board);
+    let json = serde_json::to_string(&trace).unwrap();
+    let loaded: eval::EvalTrace = serde_json::from_str(&json).unwrap();
+    assert_eq!(loaded.eval, trace.eval);
+    assert_eq!(loaded.terms.len(), trace.terms.len());
+    assert_eq!(loaded.team_totals, trace.team_totals);
+}
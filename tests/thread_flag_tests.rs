@@ -0,0 +1,35 @@
+use board_crab_lib::board::*;
+use board_crab_lib::eval;
+use board_crab_lib::search;
+use board_crab_lib::search::{SearchLimits, ThreadFlag};
+use board_crab_lib::transpos;
+
+#[test]
+fn cancel_search_test() {
+    board_crab_lib::init();
+
+    let board = Board::start_pos();
+    let table = transpos::Table::new(1);
+
+    // Clones share the flag
+    let stop_flag = ThreadFlag::new();
+    let limits = SearchLimits::new().stop_flag(stop_flag.clone());
+
+    let canceller = std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        stop_flag.trigger();
+    });
+    let (eval, _) = search::search(&board, &table, u8::MAX, None, &limits);
+    canceller.join().unwrap();
+    assert!(eval::is_inf_eval(eval));
+
+    // Stays triggered until reset
+    let stop_flag = limits.stop_flag.as_ref().unwrap();
+    assert!(stop_flag.get());
+    let (eval, _) = search::search(&board, &table, 20, None, &limits);
+    assert!(eval::is_inf_eval(eval));
+
+    stop_flag.reset();
+    let (eval, _) = search::search(&board, &table, 3, None, &limits);
+    assert!(!eval::is_inf_eval(eval));
+}
@@ -0,0 +1,34 @@
+use board_crab_lib::board::*;
+use board_crab_lib::time_manager;
+use board_crab_lib::time_manager::TimeState;
+
+#[test]
+fn move_overhead_test() {
+    board_crab_lib::init();
+
+    let board = Board::start_pos();
+
+    let mut time_state = TimeState::new();
+    time_state.remaining_time = Some(60.0);
+    time_state.time_inc = Some(1.0);
+    let base_time = time_manager::get_max_time_to_use(&board, time_state).unwrap();
+
+    time_state.move_overhead = 0.1;
+    let time_with_overhead = time_manager::get_max_time_to_use(&board, time_state).unwrap();
+    assert!((base_time - time_with_overhead - 0.1).abs() < 1e-9);
+
+    // Fixed move times lose the overhead too, but never go negative
+    let mut time_state = TimeState::new();
+    time_state.max_time = Some(0.5);
+    time_state.move_overhead = 0.1;
+    assert_eq!(
+        time_manager::get_max_time_to_use(&board, time_state),
+        Some(0.4)
+    );
+
+    time_state.move_overhead = 1.0;
+    assert_eq!(
+        time_manager::get_max_time_to_use(&board, time_state),
+        Some(0.0)
+    );
+}
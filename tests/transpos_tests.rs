@@ -0,0 +1,119 @@
+use board_crab_lib::transpos;
+use board_crab_lib::transpos::EntryType;
+
+// Hashes that only differ in their high bits all land in the same bucket
+fn make_colliding_hash(base_hash: u64, idx: u64) -> u64 {
+    base_hash ^ (idx << 48)
+}
+
+#[test]
+fn transpos_deep_entry_kept_test() {
+    let table = transpos::Table::new(1);
+
+    let deep_hash = 0x1234;
+    table.set(deep_hash, 100, 50, 3, 20, EntryType::Exact);
+
+    // Lots of shallow entries in the same bucket shouldn't push out the deep one
+    for i in 1..20 {
+        table.set(
+            make_colliding_hash(deep_hash, i),
+            0,
+            0,
+            0,
+            1,
+            EntryType::FailLow,
+        );
+    }
+
+    let entry = table.get_fast(deep_hash);
+    assert!(entry.is_valid());
+    assert_eq!(entry.depth_remaining, 20);
+    assert_eq!(entry.static_eval, 50);
+    assert_eq!(entry.best_move, 3);
+
+    // The most recent shallow entry should still be stored too
+    assert!(table
+        .get_fast(make_colliding_hash(deep_hash, 19))
+        .is_valid());
+}
+
+#[test]
+fn transpos_old_entry_replaced_test() {
+    let table = transpos::Table::new(1);
+
+    let old_hash = 0x5678;
+    table.set(old_hash, 100, 50, 0, 10, EntryType::Exact);
+
+    // Entries from many searches ago aren't worth keeping
+    for _ in 0..4 {
+        table.new_search();
+    }
+
+    for i in 1..4 {
+        table.set(
+            make_colliding_hash(old_hash, i),
+            0,
+            0,
+            0,
+            1,
+            EntryType::FailLow,
+        );
+    }
+
+    assert!(!table.get_fast(old_hash).is_valid());
+}
+
+#[test]
+fn transpos_hashfull_test() {
+    let table = transpos::Table::new(1);
+    assert_eq!(table.get_hashfull(), 0);
+
+    for i in 0..100_000u64 {
+        table.set(
+            i.wrapping_mul(0x9E3779B97F4A7C15),
+            0,
+            0,
+            0,
+            1,
+            EntryType::Exact,
+        );
+    }
+    assert!(table.get_hashfull() > 500);
+
+    // Entries from previous searches don't count
+    table.new_search();
+    assert_eq!(table.get_hashfull(), 0);
+}
+
+#[test]
+fn transpos_save_load_test() {
+    let table = transpos::Table::new(1);
+    for i in 0..1000u64 {
+        table.set(
+            i.wrapping_mul(0x9E3779B97F4A7C15),
+            i as i32,
+            -(i as i32),
+            (i % 4000) as u16,
+            (i % 30) as u8,
+            EntryType::FailHigh,
+        );
+    }
+
+    let path = std::env::temp_dir().join("board_crab_transpos_save_load_test.bin");
+    let path_str = path.to_str().unwrap();
+    table.save(path_str).unwrap();
+    let loaded_table = transpos::Table::load(path_str).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(loaded_table.get_size_mbs(), table.get_size_mbs());
+    for i in 0..1000u64 {
+        let hash = i.wrapping_mul(0x9E3779B97F4A7C15);
+        let entry = table.get_fast(hash);
+        let loaded_entry = loaded_table.get_fast(hash);
+        assert_eq!(entry.is_valid(), loaded_entry.is_valid());
+        assert_eq!(entry.eval, loaded_entry.eval);
+        assert_eq!(entry.static_eval, loaded_entry.static_eval);
+        assert_eq!(entry.best_move, loaded_entry.best_move);
+        assert_eq!(entry.depth_remaining, loaded_entry.depth_remaining);
+    }
+}
@@ -0,0 +1,98 @@
+use board_crab_lib::eval;
+use board_crab_lib::eval_params;
+use board_crab_lib::eval_params::EvalParams;
+use board_crab_lib::fen;
+use board_crab_lib::tune;
+
+// Tests here change the global eval params, so they must not run at the same time
+static PARAMS_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+#[test]
+fn tune_parse_entry_test() {
+    board_crab_lib::init();
+
+    let entry =
+        tune::parse_entry("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1 | 35 | 1.0")
+            .unwrap();
+    assert_eq!(entry.result, 1.0);
+    assert_eq!(entry.board.turn_idx, 1);
+
+    let entry = tune::parse_entry("8/8/4k3/8/8/4K3/4P3/8 w - - 0 1 | 0.5").unwrap();
+    assert_eq!(entry.result, 0.5);
+
+    assert!(tune::parse_entry("8/8/4k3/8/8/4K3/4P3/8 w - - 0 1").is_none());
+    assert!(tune::parse_entry("8/8/4k3/8/8/4K3/4P3/8 w - - 0 1 | 2.0").is_none());
+}
+
+#[test]
+fn tune_params_used_test() {
+    board_crab_lib::init();
+    let _lock = PARAMS_LOCK.lock().unwrap();
+
+    let fen_str = "4k3/pppp4/8/8/8/8/PPPPP3/4K3 w - - 0 1";
+    let start_eval = eval::eval_board(&fen::load_fen(fen_str).unwrap());
+
+    // Pawns are worth a lot more, and white has an extra one
+    let mut params = EvalParams::DEFAULT;
+    params.piece_base_value[0] = [5.0, 5.0];
+    eval_params::set(params);
+    let new_eval = eval::eval_board(&fen::load_fen(fen_str).unwrap());
+    eval_params::set(EvalParams::DEFAULT);
+
+    assert!(new_eval > start_eval + 300);
+}
+
+#[test]
+fn tune_error_decreases_test() {
+    board_crab_lib::init();
+    let _lock = PARAMS_LOCK.lock().unwrap();
+
+    // White is a knight up in both endgames, but somehow never wins
+    let entries: Vec<tune::TuneEntry> = [
+        "4k3/pppp4/8/8/8/5N2/PPPP4/4K3 w - - 0 1 | 0.0",
+        "4k3/pppp4/8/8/8/2N5/PPPP4/4K3 b - - 0 1 | 0.5",
+    ]
+    .iter()
+    .map(|line| tune::parse_entry(line).unwrap())
+    .collect();
+
+    let mut settings = tune::TuneSettings::new();
+    settings.max_passes = 1;
+    settings.step = 0.1;
+
+    let mut errors = Vec::new();
+    let start_error = tune::calc_error(&entries, 1.0, 1);
+    let tuned_params = tune::tune(&entries, EvalParams::DEFAULT, &settings, |_, _, error| {
+        errors.push(error)
+    });
+    let tuned_error = tune::calc_error(&entries, 1.0, 1);
+    eval_params::set(EvalParams::DEFAULT);
+
+    assert_eq!(errors.len(), 1);
+    assert!(tuned_error < start_error);
+    assert!(tuned_params.piece_base_value[1][1] < EvalParams::DEFAULT.piece_base_value[1][1]);
+}
+
+#[test]
+fn eval_params_toml_test() {
+    let mut params = EvalParams::DEFAULT;
+    params.bishop_pair = [0.123, -4.5];
+    params.piece_tb[3][17] = [1.0, 0.0078125];
+
+    let loaded_params = EvalParams::from_toml(&params.to_toml()).unwrap();
+    assert_eq!(loaded_params, params);
+
+    // Missing weights keep their defaults, and arrays can span lines
+    let partial_params = EvalParams::from_toml(
+        "# Comment\nopen_rooks = [\n  0.5, # Middlegame\n  0.25\n]\nmobility_weights = [[0, 0], [1, 1], [2, 2], [3, 3], [4, 4], [5, 5]]\n",
+    )
+    .unwrap();
+    assert_eq!(partial_params.open_rooks, [0.5, 0.25]);
+    assert_eq!(partial_params.mobility_weights[4], [4.0, 4.0]);
+    assert_eq!(partial_params.bishop_pair, EvalParams::DEFAULT.bishop_pair);
+
+    assert!(EvalParams::from_toml("not_a_weight = [1, 2]").is_err());
+    assert!(EvalParams::from_toml("open_rooks = [1, 2, 3]").is_err());
+    assert!(EvalParams::from_toml("open_rooks = [1, x]").is_err());
+    assert!(EvalParams::from_toml("open_rooks = [1, 2").is_err());
+}
@@ -0,0 +1,66 @@
+use board_crab_lib::nnue;
+use board_crab_lib::uci;
+
+const NETWORK_NUM_VALUES: usize =
+    nnue::NUM_FEATURES * nnue::HIDDEN_SIZE + 3 * nnue::HIDDEN_SIZE + 1;
+
+// Writes a network with pseudo-random weights, so we don't need a real network file for testing
+fn write_test_network(path: &std::path::Path) {
+    let mut rng_state: u64 = 0x2545F4914F6CDD1D;
+    let mut bytes = Vec::with_capacity(NETWORK_NUM_VALUES * 2);
+    for _ in 0..NETWORK_NUM_VALUES {
+        rng_state ^= rng_state << 13;
+        rng_state ^= rng_state >> 7;
+        rng_state ^= rng_state << 17;
+        let value = ((rng_state % 101) as i16) - 50;
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    std::fs::write(path, bytes).unwrap();
+}
+
+fn cmd(state: &mut uci::UCIState, line: &str) {
+    assert!(
+        uci::process_cmd(line.to_string(), state),
+        "\"{}\" failed",
+        line
+    );
+}
+
+#[test]
+fn uci_switch_eval_file_test() {
+    board_crab_lib::init();
+
+    let path = std::env::temp_dir().join("board_crab_uci_test_network.bin");
+    write_test_network(&path);
+    let path_str = path.to_str().unwrap();
+
+    let mut state = uci::UCIState::new();
+    cmd(&mut state, "setoption name Threads value 1");
+    cmd(&mut state, "setoption name Hash value 1");
+    cmd(&mut state, "position startpos moves e2e4 e7e5");
+    cmd(&mut state, "go depth 6");
+    state.get_engine_mut().wait_for_search();
+    assert!(state.get_engine().get_hashfull() > 0);
+
+    // Switching during a search stops it, and nothing from the handcrafted eval is kept
+    cmd(&mut state, "go infinite");
+    cmd(
+        &mut state,
+        &format!("setoption name EvalFile value {}", path_str),
+    );
+    std::fs::remove_file(&path).unwrap();
+    let network = nnue::get_network().unwrap();
+    assert_eq!(state.get_engine().get_hashfull(), 0);
+    assert!(state
+        .get_engine()
+        .get_board()
+        .accumulator
+        .is_valid_for(network));
+
+    cmd(&mut state, "go depth 4");
+    assert!(state.get_engine_mut().wait_for_search().is_some());
+
+    cmd(&mut state, "setoption name EvalFile value <empty>");
+    assert!(nnue::get_network().is_none());
+    assert_eq!(state.get_engine().get_hashfull(), 0);
+}